@@ -0,0 +1,342 @@
+//! Client-to-server clock synchronization.
+//!
+//! Stamping inputs with `SystemTime::now()` drifts between machines and
+//! jumps with NTP corrections, making server-side timestamp comparisons
+//! unreliable. `ClockSync` instead estimates the offset between our local
+//! monotonic clock and the server's clock from round-trip echoes (the
+//! server acking one of our input sequences alongside its own time), and
+//! smooths that estimate with an EWMA so a single noisy sample can't yank
+//! it around.
+//!
+//! When the server also echoes back when it received the acked input (its
+//! "T2"), `on_server_ack` upgrades to the classic NTP four-timestamp
+//! algorithm instead of its two-timestamp approximation: T1 is our send
+//! time, T2/T3 are the server's receive/reply times, T4 is our receive
+//! time. `offset = ((T2-T1)+(T3-T4))/2` and `round_trip_delay =
+//! (T4-T1)-(T3-T2)` separate network delay from server-side processing
+//! time, which a simple "halve the RTT" one-way estimate can't do. Recent
+//! samples are kept in a small "minimum-delay filter": the sample with the
+//! smallest round-trip delay had the least queuing distortion on either
+//! side, so its offset is the one folded into the EWMA.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// Default EWMA smoothing factor: weight given to each new sample.
+const DEFAULT_ALPHA: f64 = 0.1;
+/// Echoes with a round-trip time above this are discarded as unreliable.
+const DEFAULT_MAX_RTT_MS: u64 = 500;
+/// Largest the offset estimate is allowed to move in a single update, even
+/// if the EWMA would move it further.
+const DEFAULT_MAX_STEP_MS: i64 = 50;
+/// How many in-flight sends we track before dropping the oldest; bounds
+/// memory if acks stop arriving entirely (e.g. a dead connection).
+const MAX_PENDING_SENDS: usize = 64;
+/// How many recent four-timestamp samples the minimum-delay filter keeps.
+const MIN_DELAY_FILTER_SIZE: usize = 8;
+
+/// Tracks the estimated offset between our monotonic clock and the
+/// server's clock, so inputs can be stamped with an approximation of
+/// server time instead of raw (and drifty) wall-clock time.
+pub struct ClockSync {
+    epoch: Instant,
+    pending_sends: VecDeque<(u32, u64)>,
+    /// Recent four-timestamp samples as `(offset_ms, round_trip_delay_ms)`,
+    /// newest last; the minimum-delay filter picks among these.
+    samples: VecDeque<(i64, u64)>,
+    offset_ms: Option<i64>,
+    last_rtt_ms: Option<u64>,
+    alpha: f64,
+    max_rtt_ms: u64,
+    max_step_ms: i64,
+}
+
+impl ClockSync {
+    pub fn new() -> Self {
+        Self::with_params(DEFAULT_ALPHA, DEFAULT_MAX_RTT_MS, DEFAULT_MAX_STEP_MS)
+    }
+
+    pub fn with_params(alpha: f64, max_rtt_ms: u64, max_step_ms: i64) -> Self {
+        Self {
+            epoch: Instant::now(),
+            pending_sends: VecDeque::new(),
+            samples: VecDeque::new(),
+            offset_ms: None,
+            last_rtt_ms: None,
+            alpha,
+            max_rtt_ms,
+            max_step_ms,
+        }
+    }
+
+    /// Our local monotonic clock, in milliseconds since this `ClockSync` was
+    /// created.
+    pub fn local_now_ms(&self) -> u64 {
+        self.epoch.elapsed().as_millis() as u64
+    }
+
+    /// Our best estimate of the server's current clock: the local monotonic
+    /// clock shifted by the current offset estimate. Used to stamp outgoing
+    /// inputs instead of wall-clock time.
+    pub fn server_now_ms(&self) -> u64 {
+        (self.local_now_ms() as i64 + self.offset_ms()) as u64
+    }
+
+    pub fn offset_ms(&self) -> i64 {
+        self.offset_ms.unwrap_or(0)
+    }
+
+    pub fn last_rtt_ms(&self) -> Option<u64> {
+        self.last_rtt_ms
+    }
+
+    /// Records that `sequence` was just sent, so a later `on_server_ack` for
+    /// that sequence can compute how long the round trip took.
+    pub fn record_send(&mut self, sequence: u32) {
+        let now = self.local_now_ms();
+        self.pending_sends.push_back((sequence, now));
+        while self.pending_sends.len() > MAX_PENDING_SENDS {
+            self.pending_sends.pop_front();
+        }
+    }
+
+    /// Call when the server reports `acked_sequence` as its last-processed
+    /// input alongside its own `server_send_time_ms` (its "T3"). Folds a new
+    /// offset sample in if we have a matching recorded send (our "T1").
+    ///
+    /// `server_receive_time_ms`, when present, is the server's receive time
+    /// for that input (its "T2"), enabling the full four-timestamp
+    /// estimate; otherwise falls back to the two-timestamp approximation.
+    pub fn on_server_ack(
+        &mut self,
+        acked_sequence: u32,
+        server_send_time_ms: u64,
+        server_receive_time_ms: Option<u64>,
+    ) {
+        let Some(pos) = self
+            .pending_sends
+            .iter()
+            .position(|&(seq, _)| seq == acked_sequence)
+        else {
+            return;
+        };
+        let (_, send_time_ms) = self.pending_sends[pos];
+        // Sequences are monotonic, so acking one also confirms everything
+        // sent before it; drop them all rather than leaking forever.
+        self.pending_sends.drain(..=pos);
+
+        let local_now_ms = self.local_now_ms();
+        match server_receive_time_ms {
+            Some(receive_time_ms) => {
+                self.on_four_timestamp_echo(send_time_ms, receive_time_ms, server_send_time_ms, local_now_ms)
+            }
+            None => self.on_echo(send_time_ms, server_send_time_ms, local_now_ms),
+        }
+    }
+
+    /// Two-timestamp estimator (our send/receive times bracketing a single
+    /// server timestamp), split out from `on_server_ack` so it can be driven
+    /// with fully synthetic times in tests without mocking the clock.
+    pub fn on_echo(&mut self, send_time_ms: u64, server_time_ms: u64, local_now_ms: u64) {
+        let rtt_ms = local_now_ms.saturating_sub(send_time_ms);
+        if rtt_ms > self.max_rtt_ms {
+            return;
+        }
+        self.last_rtt_ms = Some(rtt_ms);
+
+        let one_way_ms = (rtt_ms / 2) as i64;
+        let sample_offset = server_time_ms as i64 + one_way_ms - local_now_ms as i64;
+        self.apply_offset_sample(sample_offset);
+    }
+
+    /// Full four-timestamp NTP estimator: `t1_send_ms`/`t4_now_ms` are ours
+    /// (our clock's arbitrary epoch), `t2_receive_ms`/`t3_send_ms` are the
+    /// server's (its wall clock). Computes `offset = ((T2-T1)+(T3-T4))/2`
+    /// and `round_trip_delay = (T4-T1)-(T3-T2)`, then folds the sample into
+    /// the minimum-delay filter: out of the last `MIN_DELAY_FILTER_SIZE`
+    /// samples, the one with the smallest round-trip delay is taken as
+    /// authoritative, since queuing delay on either end only ever inflates
+    /// the delay, never shrinks it.
+    pub fn on_four_timestamp_echo(&mut self, t1_send_ms: u64, t2_receive_ms: u64, t3_send_ms: u64, t4_now_ms: u64) {
+        let round_trip_delay_ms = (t4_now_ms as i64 - t1_send_ms as i64) - (t3_send_ms as i64 - t2_receive_ms as i64);
+        if round_trip_delay_ms < 0 {
+            return;
+        }
+        let round_trip_delay_ms = round_trip_delay_ms as u64;
+        if round_trip_delay_ms > self.max_rtt_ms {
+            return;
+        }
+
+        let offset_sample = ((t2_receive_ms as i64 - t1_send_ms as i64) + (t3_send_ms as i64 - t4_now_ms as i64)) / 2;
+
+        self.samples.push_back((offset_sample, round_trip_delay_ms));
+        while self.samples.len() > MIN_DELAY_FILTER_SIZE {
+            self.samples.pop_front();
+        }
+
+        let &(best_offset, best_delay) =
+            self.samples.iter().min_by_key(|&&(_, delay)| delay).expect("just pushed a sample");
+        self.last_rtt_ms = Some(best_delay);
+        self.apply_offset_sample(best_offset);
+    }
+
+    fn apply_offset_sample(&mut self, sample_offset: i64) {
+        self.offset_ms = Some(match self.offset_ms {
+            None => sample_offset,
+            Some(prev) => {
+                let ewma = self.alpha * sample_offset as f64 + (1.0 - self.alpha) * prev as f64;
+                let delta = (ewma - prev as f64).clamp(-(self.max_step_ms as f64), self.max_step_ms as f64);
+                prev + delta.round() as i64
+            }
+        });
+    }
+}
+
+impl Default for ClockSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_is_zero_before_any_echo() {
+        let clock_sync = ClockSync::new();
+        assert_eq!(clock_sync.offset_ms(), 0);
+        assert_eq!(clock_sync.last_rtt_ms(), None);
+    }
+
+    #[test]
+    fn test_first_sample_seeds_the_offset_directly() {
+        let mut clock_sync = ClockSync::new();
+        // Sent at local t=0, server says its time was 1000 when it saw it,
+        // we observe the echo at local t=100 (rtt=100, one-way=50).
+        clock_sync.on_echo(0, 1000, 100);
+        assert_eq!(clock_sync.offset_ms(), 1000 + 50 - 100);
+        assert_eq!(clock_sync.last_rtt_ms(), Some(100));
+    }
+
+    #[test]
+    fn test_offset_converges_toward_a_consistent_signal() {
+        let mut clock_sync = ClockSync::with_params(0.2, 500, 1000);
+        let true_offset = 5000i64;
+        let mut local_now = 0u64;
+
+        for _ in 0..200 {
+            let send_time = local_now;
+            let rtt = 40u64;
+            let observed_at = local_now + rtt;
+            let server_time = (observed_at as i64 - rtt as i64 / 2 + true_offset) as u64;
+            clock_sync.on_echo(send_time, server_time, observed_at);
+            local_now = observed_at + 16;
+        }
+
+        assert!(
+            (clock_sync.offset_ms() - true_offset).abs() < 50,
+            "offset {} did not converge to {}",
+            clock_sync.offset_ms(),
+            true_offset
+        );
+    }
+
+    #[test]
+    fn test_offset_never_moves_more_than_the_clamp_in_one_update() {
+        let mut clock_sync = ClockSync::with_params(1.0, 10_000, 50);
+        clock_sync.on_echo(0, 0, 0);
+        assert_eq!(clock_sync.offset_ms(), 0);
+
+        // A huge, single jump in server time should still only move the
+        // estimate by at most max_step_ms.
+        clock_sync.on_echo(100, 1_000_000, 100);
+        assert!(clock_sync.offset_ms().abs() <= 50);
+    }
+
+    #[test]
+    fn test_echo_with_excessive_rtt_is_discarded() {
+        let mut clock_sync = ClockSync::with_params(0.1, 200, 50);
+        clock_sync.on_echo(0, 1000, 600); // rtt=600 > max_rtt_ms=200
+        assert_eq!(clock_sync.offset_ms(), 0);
+        assert_eq!(clock_sync.last_rtt_ms(), None);
+    }
+
+    #[test]
+    fn test_record_send_and_server_ack_round_trip() {
+        let mut clock_sync = ClockSync::new();
+        clock_sync.record_send(1);
+        clock_sync.record_send(2);
+        clock_sync.on_server_ack(2, 5000, None);
+
+        assert!(clock_sync.last_rtt_ms().is_some());
+        let offset_before_stale_ack = clock_sync.offset_ms();
+        // Acking sequence 2 should also have dropped the pending entry for 1.
+        clock_sync.on_server_ack(1, 6000, None);
+        // No matching pending send remains, so this ack is silently ignored
+        // rather than producing a second sample (which would pull the offset
+        // toward the later server time).
+        assert_eq!(clock_sync.offset_ms(), offset_before_stale_ack);
+    }
+
+    #[test]
+    fn test_unknown_acked_sequence_is_ignored() {
+        let mut clock_sync = ClockSync::new();
+        clock_sync.record_send(1);
+        clock_sync.on_server_ack(999, 5000, None);
+        assert_eq!(clock_sync.offset_ms(), 0);
+        assert_eq!(clock_sync.last_rtt_ms(), None);
+    }
+
+    #[test]
+    fn test_local_now_ms_is_monotonic() {
+        let clock_sync = ClockSync::new();
+        let first = clock_sync.local_now_ms();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        let second = clock_sync.local_now_ms();
+        assert!(second >= first);
+    }
+
+    #[test]
+    fn test_four_timestamp_echo_recovers_offset_with_asymmetric_delay() {
+        // max_rtt raised so the 1030ms round trip below isn't discarded.
+        let mut clock_sync = ClockSync::with_params(DEFAULT_ALPHA, 10_000, DEFAULT_MAX_STEP_MS);
+        // We send at our t=0; server receives at its t=1000 and replies at
+        // its t=1010 (10ms of server-side processing in between); we
+        // receive the reply at our t=1040.
+        // offset = ((T2-T1)+(T3-T4))/2 = ((1000-0)+(1010-1040))/2 = 485
+        // round_trip_delay = (T4-T1)-(T3-T2) = (1040-0)-(1010-1000) = 1030
+        clock_sync.on_four_timestamp_echo(0, 1000, 1010, 1040);
+
+        assert_eq!(clock_sync.offset_ms(), 485);
+        assert_eq!(clock_sync.last_rtt_ms(), Some(1030));
+    }
+
+    #[test]
+    fn test_four_timestamp_echo_with_negative_delay_is_discarded() {
+        let mut clock_sync = ClockSync::new();
+        // (T4-T1) - (T3-T2) is negative: an impossible sample (the server
+        // claims to have spent longer processing than the whole round trip
+        // took), so it must be ignored rather than corrupting the offset.
+        clock_sync.on_four_timestamp_echo(0, 0, 10_000, 10);
+        assert_eq!(clock_sync.offset_ms(), 0);
+        assert_eq!(clock_sync.last_rtt_ms(), None);
+    }
+
+    #[test]
+    fn test_minimum_delay_filter_prefers_the_least_delayed_sample() {
+        let mut clock_sync = ClockSync::with_params(1.0, 10_000, 10_000);
+
+        // A noisy, heavily-delayed sample with a distorted offset...
+        clock_sync.on_four_timestamp_echo(0, 1000, 1000, 500);
+        let noisy_offset = clock_sync.offset_ms();
+
+        // ...followed by a clean, low-delay sample with the true offset.
+        clock_sync.on_four_timestamp_echo(10_000, 10_980, 10_980, 10_020);
+
+        // The filter should have picked the low-delay sample as
+        // authoritative rather than blending in the noisy one.
+        assert_ne!(clock_sync.offset_ms(), noisy_offset);
+        assert_eq!(clock_sync.last_rtt_ms(), Some(20));
+    }
+}
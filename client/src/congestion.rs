@@ -0,0 +1,234 @@
+//! Pluggable congestion controller pacing the client's outgoing input
+//! packets.
+//!
+//! Inputs are currently queued with a fixed artificial delay and flushed
+//! the moment their simulated release time passes (see `impairment`), with
+//! no notion of a send budget — a client just keeps sending every tick
+//! regardless of how the link is coping. `CongestionController` tracks a
+//! window in bytes and grows or shrinks it from ack/loss feedback, the way
+//! `server::congestion::CongestionController` paces outgoing snapshots in
+//! the other direction. Two algorithms are selectable: classic NewReno
+//! (additive-increase slow start then per-RTT `MSS^2/cwnd` congestion
+//! avoidance, multiplicative decrease on loss) and RFC 8312 CUBIC (a cubic
+//! window function centered on the pre-loss window). `send_input` consults
+//! `can_send` before releasing a packet, so a congested link throttles the
+//! client's own send rate instead of bursting inputs it can't get acked.
+
+/// Approximate input-packet size, used only to seed a sane initial window.
+const MSS_BYTES: f64 = 64.0;
+const INITIAL_CWND_BYTES: f64 = MSS_BYTES * 4.0;
+/// The window never shrinks below one packet's worth of budget.
+const MIN_CWND_BYTES: f64 = MSS_BYTES;
+const INITIAL_SSTHRESH_BYTES: f64 = MSS_BYTES * 64.0;
+
+/// RFC 8312's recommended CUBIC scaling constant.
+const CUBIC_C: f64 = 0.4;
+/// CUBIC's multiplicative-decrease factor, applied to `cwnd` on loss.
+const CUBIC_BETA: f64 = 0.7;
+/// NewReno's multiplicative-decrease factor, applied to `cwnd` on loss.
+const NEWRENO_BETA: f64 = 0.5;
+
+/// Which window-growth/backoff model paces the outgoing queue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// RFC 5681/6582-style additive-increase-multiplicative-decrease.
+    NewReno,
+    /// RFC 8312 CUBIC.
+    Cubic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    SlowStart,
+    CongestionAvoidance,
+}
+
+/// Tracks the outgoing input queue's congestion window in bytes.
+pub struct CongestionController {
+    algorithm: Algorithm,
+    phase: Phase,
+    cwnd_bytes: f64,
+    ssthresh_bytes: f64,
+    /// Window at the last loss; CUBIC's window function is centered on this.
+    w_max_bytes: f64,
+    /// Seconds elapsed since the last loss (or construction), used as `t`
+    /// in CUBIC's window function. Tracked as an accumulated duration
+    /// rather than an `Instant` so this module has no wall-clock
+    /// dependency and stays trivially testable.
+    time_since_loss_secs: f64,
+}
+
+impl CongestionController {
+    pub fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            phase: Phase::SlowStart,
+            cwnd_bytes: INITIAL_CWND_BYTES,
+            ssthresh_bytes: INITIAL_SSTHRESH_BYTES,
+            w_max_bytes: INITIAL_CWND_BYTES,
+            time_since_loss_secs: 0.0,
+        }
+    }
+
+    pub fn cwnd_bytes(&self) -> f64 {
+        self.cwnd_bytes
+    }
+
+    pub fn is_in_slow_start(&self) -> bool {
+        self.phase == Phase::SlowStart
+    }
+
+    /// Whether another packet of `bytes` can be sent without exceeding the
+    /// current window, given `bytes_in_flight` already unacknowledged.
+    pub fn can_send(&self, bytes_in_flight: usize, bytes: usize) -> bool {
+        (bytes_in_flight + bytes) as f64 <= self.cwnd_bytes
+    }
+
+    /// Advances the controller's internal clock by `dt_secs` of real time,
+    /// so CUBIC's window function (which is a function of time since the
+    /// last loss, not of acks) keeps growing between acks.
+    pub fn advance_time(&mut self, dt_secs: f64) {
+        self.time_since_loss_secs += dt_secs;
+    }
+
+    /// Feeds one acked input's size into the controller.
+    pub fn on_ack(&mut self, acked_bytes: usize) {
+        match self.phase {
+            Phase::SlowStart => {
+                self.cwnd_bytes += acked_bytes as f64;
+                if self.cwnd_bytes >= self.ssthresh_bytes {
+                    self.phase = Phase::CongestionAvoidance;
+                    if self.algorithm == Algorithm::Cubic {
+                        self.w_max_bytes = self.cwnd_bytes;
+                        self.time_since_loss_secs = 0.0;
+                    }
+                }
+            }
+            Phase::CongestionAvoidance => match self.algorithm {
+                // Classic Reno congestion avoidance: cwnd += MSS * MSS / cwnd per ack.
+                Algorithm::NewReno => {
+                    self.cwnd_bytes += MSS_BYTES * MSS_BYTES / self.cwnd_bytes;
+                }
+                Algorithm::Cubic => {
+                    self.cwnd_bytes = self.cubic_window().max(MIN_CWND_BYTES);
+                }
+            },
+        }
+    }
+
+    /// RFC 8312's CUBIC window function: `W(t) = C*(t - K)^3 + W_max` where
+    /// `K = cbrt(W_max * (1 - beta) / C)`.
+    fn cubic_window(&self) -> f64 {
+        let t = self.time_since_loss_secs;
+        let k = (self.w_max_bytes * (1.0 - CUBIC_BETA) / CUBIC_C).cbrt();
+        CUBIC_C * (t - k).powi(3) + self.w_max_bytes
+    }
+
+    /// Signals a detected loss — e.g. a gap in the server's acked input
+    /// sequence numbers. Sets `ssthresh = cwnd/2` and cuts `cwnd` by the
+    /// algorithm's multiplicative-decrease factor (`NEWRENO_BETA` for
+    /// NewReno, `CUBIC_BETA` for CUBIC), never below one packet.
+    pub fn on_loss(&mut self) {
+        self.ssthresh_bytes = (self.cwnd_bytes / 2.0).max(MIN_CWND_BYTES);
+        self.w_max_bytes = self.cwnd_bytes;
+        self.time_since_loss_secs = 0.0;
+
+        self.cwnd_bytes = match self.algorithm {
+            Algorithm::NewReno => (self.cwnd_bytes * NEWRENO_BETA).max(MIN_CWND_BYTES),
+            Algorithm::Cubic => (self.cwnd_bytes * CUBIC_BETA).max(MIN_CWND_BYTES),
+        };
+        self.phase = Phase::CongestionAvoidance;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_starts_in_slow_start_with_initial_window() {
+        let controller = CongestionController::new(Algorithm::NewReno);
+        assert!(controller.is_in_slow_start());
+        assert_eq!(controller.cwnd_bytes(), INITIAL_CWND_BYTES);
+    }
+
+    #[test]
+    fn test_slow_start_grows_cwnd_by_exactly_the_acked_bytes() {
+        let mut controller = CongestionController::new(Algorithm::NewReno);
+        let before = controller.cwnd_bytes();
+
+        controller.on_ack(64);
+
+        assert_eq!(controller.cwnd_bytes(), before + 64.0);
+        assert!(controller.is_in_slow_start());
+    }
+
+    #[test]
+    fn test_slow_start_exits_once_cwnd_reaches_ssthresh() {
+        let mut controller = CongestionController::new(Algorithm::NewReno);
+        controller.ssthresh_bytes = 300.0;
+        controller.cwnd_bytes = 280.0;
+
+        controller.on_ack(64);
+
+        assert!(!controller.is_in_slow_start());
+    }
+
+    #[test]
+    fn test_newreno_congestion_avoidance_grows_slower_than_slow_start() {
+        let mut controller = CongestionController::new(Algorithm::NewReno);
+        controller.phase = Phase::CongestionAvoidance;
+        controller.cwnd_bytes = 1000.0;
+
+        controller.on_ack(64);
+
+        let expected = 1000.0 + MSS_BYTES * MSS_BYTES / 1000.0;
+        assert!((controller.cwnd_bytes() - expected).abs() < 1e-9);
+        assert!(controller.cwnd_bytes() - 1000.0 < 64.0);
+    }
+
+    #[test]
+    fn test_on_loss_halves_ssthresh_and_applies_newreno_beta() {
+        let mut controller = CongestionController::new(Algorithm::NewReno);
+        controller.cwnd_bytes = 10_000.0;
+
+        controller.on_loss();
+
+        assert_eq!(controller.ssthresh_bytes, 5_000.0);
+        assert!((controller.cwnd_bytes() - 5_000.0).abs() < 1e-6);
+        assert!(!controller.is_in_slow_start());
+    }
+
+    #[test]
+    fn test_on_loss_never_cuts_cwnd_below_the_floor() {
+        let mut controller = CongestionController::new(Algorithm::NewReno);
+        controller.cwnd_bytes = MIN_CWND_BYTES * 1.1;
+
+        controller.on_loss();
+
+        assert!(controller.cwnd_bytes() >= MIN_CWND_BYTES);
+    }
+
+    #[test]
+    fn test_cubic_window_grows_back_over_time_after_loss() {
+        let mut controller = CongestionController::new(Algorithm::Cubic);
+        controller.cwnd_bytes = 100_000.0;
+        controller.on_loss();
+        let reduced = controller.cwnd_bytes();
+
+        controller.advance_time(0.05);
+        controller.on_ack(0);
+
+        assert!(controller.cwnd_bytes() >= reduced);
+    }
+
+    #[test]
+    fn test_can_send_respects_the_current_window() {
+        let controller = CongestionController::new(Algorithm::NewReno);
+        let cwnd = controller.cwnd_bytes() as usize;
+
+        assert!(controller.can_send(0, cwnd));
+        assert!(!controller.can_send(0, cwnd + 1));
+        assert!(!controller.can_send(cwnd, 1));
+    }
+}
@@ -0,0 +1,244 @@
+//! Transient visual effects: dust puffs on landing, a trail when a player
+//! reverses horizontal direction, and a flash when two cubes' AABBs start
+//! overlapping. Each effect is a short-lived struct with a position,
+//! velocity, and spawn timestamp; `EffectSystem::update` advances and culls
+//! them by elapsed time, and `Renderer` draws the survivors with fading
+//! alpha.
+
+use shared::{Player, FLOOR_Y, PLAYER_SIZE};
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+/// What triggered an effect, used to pick its draw style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectKind {
+    Dust,
+    Trail,
+    Flash,
+}
+
+/// A single transient visual. Position advances by `vel_x`/`vel_y` each
+/// update like any other simulated particle, and it's culled once its age
+/// (derived from `spawned_at`) exceeds `lifetime`.
+#[derive(Debug, Clone)]
+pub struct Effect {
+    pub kind: EffectKind,
+    pub x: f32,
+    pub y: f32,
+    vel_x: f32,
+    vel_y: f32,
+    spawned_at: Instant,
+    lifetime: f32,
+}
+
+impl Effect {
+    /// Fraction of `lifetime` remaining, used to fade the effect's alpha out
+    /// smoothly as it ages. Clamped to `[0, 1]` so a slightly-stale frame
+    /// (age already past lifetime but not yet culled) doesn't draw negative.
+    pub fn alpha(&self) -> f32 {
+        let age = self.spawned_at.elapsed().as_secs_f32();
+        (1.0 - age / self.lifetime).clamp(0.0, 1.0)
+    }
+}
+
+/// Tracks the live set of transient effects, plus the per-player velocity
+/// history needed to detect the gameplay events that spawn them.
+#[derive(Debug, Default)]
+pub struct EffectSystem {
+    effects: Vec<Effect>,
+    last_vel_x: HashMap<u32, f32>,
+    last_vel_y: HashMap<u32, f32>,
+    colliding_pairs: HashSet<(u32, u32)>,
+}
+
+impl EffectSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spawns an effect at `(x, y)` drifting by `(vel_x, vel_y)`, alive for
+    /// `lifetime` seconds.
+    pub fn spawn_effect(&mut self, kind: EffectKind, x: f32, y: f32, vel_x: f32, vel_y: f32, lifetime: f32) {
+        self.effects.push(Effect {
+            kind,
+            x,
+            y,
+            vel_x,
+            vel_y,
+            spawned_at: Instant::now(),
+            lifetime,
+        });
+    }
+
+    /// Observes one player's velocity this frame against what it was last
+    /// frame, spawning a dust puff when vertical velocity crosses from
+    /// falling to resting at `FLOOR_Y` (a landing) or a trail when
+    /// horizontal velocity's sign flips (a direction change). A no-op the
+    /// first time a given player id is observed, since there's no prior
+    /// sample to compare against yet.
+    pub fn observe_player(&mut self, player: &Player) {
+        let prev_vel_y = self.last_vel_y.insert(player.id, player.vel_y);
+        let prev_vel_x = self.last_vel_x.insert(player.id, player.vel_x);
+
+        let feet_y = player.y + PLAYER_SIZE;
+        if let Some(prev_y) = prev_vel_y {
+            if prev_y > 0.0 && player.vel_y <= 0.0 && (feet_y - FLOOR_Y).abs() < 1.0 {
+                self.spawn_dust(player.x + PLAYER_SIZE / 2.0, feet_y);
+            }
+        }
+
+        if let Some(prev_x) = prev_vel_x {
+            if prev_x.signum() != player.vel_x.signum() && prev_x != 0.0 && player.vel_x != 0.0 {
+                self.spawn_trail(player.x + PLAYER_SIZE / 2.0, player.y + PLAYER_SIZE / 2.0);
+            }
+        }
+    }
+
+    fn spawn_dust(&mut self, x: f32, y: f32) {
+        self.spawn_effect(EffectKind::Dust, x, y, 0.0, -20.0, 0.4);
+    }
+
+    fn spawn_trail(&mut self, x: f32, y: f32) {
+        self.spawn_effect(EffectKind::Trail, x, y, 0.0, 0.0, 0.25);
+    }
+
+    /// Spawns a flash at the midpoint between two colliding cubes.
+    pub fn spawn_collision_flash(&mut self, x: f32, y: f32) {
+        self.spawn_effect(EffectKind::Flash, x, y, 0.0, 0.0, 0.2);
+    }
+
+    /// Observes whether `a` and `b` overlap this frame, spawning a flash at
+    /// their midpoint only on the transition into overlap — so two cubes
+    /// resting against each other for several frames flash once, not every
+    /// frame they stay in contact.
+    pub fn observe_collision(&mut self, a: &Player, b: &Player) {
+        let pair = if a.id < b.id { (a.id, b.id) } else { (b.id, a.id) };
+
+        let (a_left, a_top, a_right, a_bottom) = a.get_bounds();
+        let (b_left, b_top, b_right, b_bottom) = b.get_bounds();
+        let overlapping = a_left < b_right && a_right > b_left && a_top < b_bottom && a_bottom > b_top;
+
+        if overlapping {
+            if self.colliding_pairs.insert(pair) {
+                self.spawn_collision_flash((a.x + b.x) / 2.0 + PLAYER_SIZE / 2.0, (a.y + b.y) / 2.0 + PLAYER_SIZE / 2.0);
+            }
+        } else {
+            self.colliding_pairs.remove(&pair);
+        }
+    }
+
+    /// Advances every live effect's position by `dt` and drops any that have
+    /// outlived their lifetime.
+    pub fn update(&mut self, dt: f32) {
+        for effect in &mut self.effects {
+            effect.x += effect.vel_x * dt;
+            effect.y += effect.vel_y * dt;
+        }
+        self.effects.retain(|e| e.spawned_at.elapsed().as_secs_f32() < e.lifetime);
+    }
+
+    pub fn effects(&self) -> &[Effect] {
+        &self.effects
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    fn falling_player() -> Player {
+        let mut p = Player::new(1, 100.0, FLOOR_Y - PLAYER_SIZE - 10.0);
+        p.vel_y = 200.0;
+        p
+    }
+
+    #[test]
+    fn test_observe_player_spawns_dust_on_landing() {
+        let mut system = EffectSystem::new();
+        let mut player = falling_player();
+        system.observe_player(&player);
+        assert!(system.effects().is_empty());
+
+        player.y = FLOOR_Y - PLAYER_SIZE;
+        player.vel_y = 0.0;
+        system.observe_player(&player);
+
+        assert_eq!(system.effects().len(), 1);
+        assert_eq!(system.effects()[0].kind, EffectKind::Dust);
+    }
+
+    #[test]
+    fn test_observe_player_spawns_trail_on_direction_change() {
+        let mut system = EffectSystem::new();
+        let mut player = Player::new(1, 100.0, 0.0);
+        player.vel_x = 150.0;
+        system.observe_player(&player);
+
+        player.vel_x = -150.0;
+        system.observe_player(&player);
+
+        assert_eq!(system.effects().len(), 1);
+        assert_eq!(system.effects()[0].kind, EffectKind::Trail);
+    }
+
+    #[test]
+    fn test_first_observation_never_spawns() {
+        let mut system = EffectSystem::new();
+        system.observe_player(&falling_player());
+        assert!(system.effects().is_empty());
+    }
+
+    #[test]
+    fn test_update_culls_expired_effects() {
+        let mut system = EffectSystem::new();
+        system.spawn_effect(EffectKind::Flash, 0.0, 0.0, 0.0, 0.0, 0.01);
+        sleep(Duration::from_millis(20));
+        system.update(0.016);
+        assert!(system.effects().is_empty());
+    }
+
+    #[test]
+    fn test_update_advances_position() {
+        let mut system = EffectSystem::new();
+        system.spawn_effect(EffectKind::Dust, 0.0, 0.0, 10.0, -10.0, 1.0);
+        system.update(0.5);
+        assert_eq!(system.effects()[0].x, 5.0);
+        assert_eq!(system.effects()[0].y, -5.0);
+    }
+
+    #[test]
+    fn test_observe_collision_flashes_once_per_overlap() {
+        let mut system = EffectSystem::new();
+        let a = Player::new(1, 100.0, 100.0);
+        let b = Player::new(2, 100.0 + PLAYER_SIZE / 2.0, 100.0);
+
+        system.observe_collision(&a, &b);
+        assert_eq!(system.effects().len(), 1);
+
+        // Still overlapping next frame: no second flash.
+        system.observe_collision(&a, &b);
+        assert_eq!(system.effects().len(), 1);
+    }
+
+    #[test]
+    fn test_observe_collision_ignores_non_overlapping_players() {
+        let mut system = EffectSystem::new();
+        let a = Player::new(1, 0.0, 0.0);
+        let b = Player::new(2, 1000.0, 1000.0);
+
+        system.observe_collision(&a, &b);
+        assert!(system.effects().is_empty());
+    }
+
+    #[test]
+    fn test_alpha_fades_toward_zero_as_effect_ages() {
+        let mut system = EffectSystem::new();
+        system.spawn_effect(EffectKind::Flash, 0.0, 0.0, 0.0, 0.0, 0.02);
+        let fresh_alpha = system.effects()[0].alpha();
+        sleep(Duration::from_millis(15));
+        let aged_alpha = system.effects()[0].alpha();
+        assert!(aged_alpha < fresh_alpha);
+    }
+}
@@ -1,11 +1,11 @@
 //! Client-side game state management with prediction and reconciliation
 
-use log::debug;
+use log::{debug, error};
 use shared::{
-    resolve_collision, InputState, Player, FLOOR_Y, GRAVITY, JUMP_VELOCITY, PLAYER_SIZE,
+    resolve_collision, Gamemode, InputState, Player, FLOOR_Y, GRAVITY, JUMP_VELOCITY, PLAYER_SIZE,
     PLAYER_SPEED, WORLD_WIDTH,
 };
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
 
 /// Configuration for server state processing
 #[derive(Debug, Clone)]
@@ -13,6 +13,11 @@ pub struct ServerStateConfig {
     pub client_id: Option<u32>,
     pub reconciliation_enabled: bool,
     pub interpolation_enabled: bool,
+    /// When the interpolation buffer underruns (no packet has arrived past
+    /// `render_time` yet), whether remote players should be extrapolated
+    /// forward from their last known velocity (smoother, can overshoot) or
+    /// frozen at their last confirmed position (accurate, can hitch).
+    pub extrapolation_enabled: bool,
 }
 
 /// Basic game state containing all players and simulation tick
@@ -52,7 +57,7 @@ impl GameState {
     /// Updates physics for all players
     pub fn update_physics(&mut self, dt: f32) {
         for player in self.players.values_mut() {
-            if !player.on_ground {
+            if !player.on_ground && !player.flying {
                 player.vel_y += GRAVITY * dt;
             }
 
@@ -61,15 +66,17 @@ impl GameState {
 
             player.x = player.x.clamp(0.0, WORLD_WIDTH - PLAYER_SIZE);
 
-            if player.y + PLAYER_SIZE >= FLOOR_Y {
-                player.y = FLOOR_Y - PLAYER_SIZE;
-                player.vel_y = 0.0;
-                player.on_ground = true;
-            }
+            if !player.flying {
+                if player.y + PLAYER_SIZE >= FLOOR_Y {
+                    player.y = FLOOR_Y - PLAYER_SIZE;
+                    player.vel_y = 0.0;
+                    player.on_ground = true;
+                }
 
-            if player.y <= 0.0 {
-                player.y = 0.0;
-                player.vel_y = 0.0;
+                if player.y <= 0.0 {
+                    player.y = 0.0;
+                    player.vel_y = 0.0;
+                }
             }
         }
 
@@ -88,6 +95,10 @@ impl GameState {
                     self.players.get(&id1).cloned(),
                     self.players.get(&id2).cloned(),
                 ) {
+                    if p1.gamemode == Gamemode::Spectator || p2.gamemode == Gamemode::Spectator {
+                        continue;
+                    }
+
                     let mut player1 = p1;
                     let mut player2 = p2;
 
@@ -105,6 +116,43 @@ impl GameState {
         self.update_physics(dt);
         self.tick += 1;
     }
+
+    /// Deterministic checksum over this tick's full state: every player's
+    /// `id, x, y, vel_x, vel_y, on_ground` in sorted-id order, plus `tick`
+    /// itself. Floats are quantized to fixed-point first so two runs that
+    /// agree mathematically but differ in the last bit of rounding don't
+    /// spuriously disagree — same technique as `shared::compute_checksum`,
+    /// widened to `u64` and covering `tick` for `ClientGameState`'s
+    /// sync-test guard.
+    pub fn checksum(&self) -> u64 {
+        fn quantize(value: f32) -> i64 {
+            (value * 1000.0) as i64
+        }
+
+        fn fold(hash: &mut u64, bytes: &[u8]) {
+            const FNV_PRIME: u64 = 0x0000_0100_0000_01B3;
+            for byte in bytes {
+                *hash ^= *byte as u64;
+                *hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        }
+
+        let mut sorted: Vec<&Player> = self.players.values().collect();
+        sorted.sort_by_key(|player| player.id);
+
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut hash = FNV_OFFSET_BASIS;
+        fold(&mut hash, &self.tick.to_le_bytes());
+        for player in sorted {
+            fold(&mut hash, &player.id.to_le_bytes());
+            fold(&mut hash, &quantize(player.x).to_le_bytes());
+            fold(&mut hash, &quantize(player.y).to_le_bytes());
+            fold(&mut hash, &quantize(player.vel_x).to_le_bytes());
+            fold(&mut hash, &quantize(player.vel_y).to_le_bytes());
+            fold(&mut hash, &[player.on_ground as u8]);
+        }
+        hash
+    }
 }
 
 impl Default for GameState {
@@ -113,15 +161,292 @@ impl Default for GameState {
     }
 }
 
+/// Advances a single remote player forward by `dt` seconds using its last
+/// known velocity, for use when the interpolation buffer underruns (the
+/// newest buffered state is still recent enough to trust, but no newer one
+/// has arrived yet). Applies the same gravity/floor/world-bounds clamps as
+/// `GameState::update_physics`, but skips the collision pass — this is a
+/// display-only guess about one player in isolation, not an authoritative
+/// simulation step.
+fn extrapolate_player(player: &Player, dt: f32) -> Player {
+    let mut player = player.clone();
+
+    if !player.on_ground && !player.flying {
+        player.vel_y += GRAVITY * dt;
+    }
+
+    player.x += player.vel_x * dt;
+    player.y += player.vel_y * dt;
+
+    player.x = player.x.clamp(0.0, WORLD_WIDTH - PLAYER_SIZE);
+
+    if !player.flying {
+        if player.y + PLAYER_SIZE >= FLOOR_Y {
+            player.y = FLOOR_Y - PLAYER_SIZE;
+            player.vel_y = 0.0;
+            player.on_ground = true;
+        }
+
+        if player.y <= 0.0 {
+            player.y = 0.0;
+            player.vel_y = 0.0;
+        }
+    }
+
+    player
+}
+
+/// Default number of out-of-order server states `ServerStateBuffer` will
+/// hold while waiting for a gap to fill in.
+const DEFAULT_SERVER_STATE_BUFFER_DEPTH: usize = 32;
+
+/// Reorders and de-duplicates incoming `Packet::GameState` snapshots by
+/// tick before they reach `confirmed_state`, so a late or duplicate UDP
+/// delivery can't regress `last_confirmed_tick` and corrupt reconciliation.
+/// Same reorder/dedupe idea as `shared::JitterBuffer` (itself borrowed from
+/// rtpbin2's jitterbuffer), applied to server snapshots instead of inputs.
+pub struct ServerStateBuffer {
+    buffer_depth: usize,
+    buffered: BTreeMap<u32, (u64, Vec<Player>)>,
+    last_confirmed_tick: u32,
+    /// Whether a first tick has ever been promoted. Until then any tick is
+    /// accepted as the baseline (e.g. a client joining mid-game sees ticks
+    /// starting well above 0); afterwards promotion requires strict
+    /// `last_confirmed_tick + 1` contiguity.
+    bootstrapped: bool,
+    dropped_count: u32,
+    reordered_count: u32,
+}
+
+impl ServerStateBuffer {
+    pub fn new(buffer_depth: usize) -> Self {
+        Self {
+            buffer_depth,
+            buffered: BTreeMap::new(),
+            last_confirmed_tick: 0,
+            bootstrapped: false,
+            dropped_count: 0,
+            reordered_count: 0,
+        }
+    }
+
+    /// Feeds a newly-arrived state. Drops it outright (counted in
+    /// `dropped_count`) if a baseline tick has been established and this
+    /// one is at or before it — a stale or duplicate delivery. Otherwise
+    /// buffers it, counting it as reordered if a higher tick is already
+    /// buffered, and evicts the oldest buffered entry once `buffer_depth`
+    /// is exceeded.
+    pub fn insert(&mut self, tick: u32, timestamp: u64, players: Vec<Player>) {
+        if self.bootstrapped && tick <= self.last_confirmed_tick {
+            self.dropped_count += 1;
+            return;
+        }
+
+        if let Some((&highest, _)) = self.buffered.iter().next_back() {
+            if tick < highest {
+                self.reordered_count += 1;
+            }
+        }
+        self.buffered.insert(tick, (timestamp, players));
+
+        while self.buffered.len() > self.buffer_depth {
+            if let Some(&oldest) = self.buffered.keys().next() {
+                self.buffered.remove(&oldest);
+                self.dropped_count += 1;
+            }
+        }
+    }
+
+    /// Removes and returns the contiguous run of states now available, in
+    /// ascending tick order, advancing `last_confirmed_tick` to the highest
+    /// tick returned. Before any tick has been promoted, the lowest
+    /// buffered tick is taken as the baseline regardless of its value;
+    /// after that, only `last_confirmed_tick + 1`, `+ 2`, ... are promoted,
+    /// so a tick with a gap before it stays buffered until the gap fills in.
+    pub fn drain_contiguous(&mut self) -> Vec<(u32, u64, Vec<Player>)> {
+        let mut drained = Vec::new();
+
+        if !self.bootstrapped {
+            let Some((&first_tick, _)) = self.buffered.iter().next() else {
+                return drained;
+            };
+            let (timestamp, players) = self.buffered.remove(&first_tick).unwrap();
+            self.last_confirmed_tick = first_tick;
+            self.bootstrapped = true;
+            drained.push((first_tick, timestamp, players));
+        }
+
+        loop {
+            let next = self.last_confirmed_tick + 1;
+            match self.buffered.remove(&next) {
+                Some((timestamp, players)) => {
+                    self.last_confirmed_tick = next;
+                    drained.push((next, timestamp, players));
+                }
+                None => break,
+            }
+        }
+
+        drained
+    }
+
+    /// Number of states currently buffered, waiting on a gap to fill.
+    pub fn depth(&self) -> usize {
+        self.buffered.len()
+    }
+
+    /// Count of states dropped as stale/duplicate or evicted for exceeding
+    /// `buffer_depth`.
+    pub fn dropped_count(&self) -> u32 {
+        self.dropped_count
+    }
+
+    /// Count of states that arrived while a higher tick was already
+    /// buffered.
+    pub fn reordered_count(&self) -> u32 {
+        self.reordered_count
+    }
+
+    /// Highest tick promoted to `confirmed_state` so far.
+    pub fn last_confirmed_tick(&self) -> u32 {
+        self.last_confirmed_tick
+    }
+}
+
+/// Sparse, sequence-indexed queue of local inputs, modeled on backroll's
+/// `InputQueue`. Unlike a flat, compacted `Vec<InputState>`, this can answer
+/// "what input applies at sequence N" even when N was never recorded (e.g. a
+/// dropped input packet): `get_or_predict` fills the gap by repeating the
+/// most recent known input, the standard GGPO prediction assumption.
+pub struct InputQueue {
+    inputs: BTreeMap<u32, InputState>,
+    last_known: Option<InputState>,
+}
+
+impl InputQueue {
+    pub fn new() -> Self {
+        Self {
+            inputs: BTreeMap::new(),
+            last_known: None,
+        }
+    }
+
+    /// Records a real input at its own sequence number.
+    pub fn add(&mut self, input: InputState) {
+        self.last_known = Some(input.clone());
+        self.inputs.insert(input.sequence, input);
+    }
+
+    /// Drops every input at or before `up_to_seq` — they've been
+    /// acknowledged by the server and no longer need to be replayed.
+    pub fn confirm(&mut self, up_to_seq: u32) {
+        self.inputs.retain(|&seq, _| seq > up_to_seq);
+    }
+
+    /// Returns the real input recorded at `seq`, or — if none was ever
+    /// recorded there — a predicted input repeating the most recent real
+    /// input at or before `seq`, falling back to the most recent input ever
+    /// added if `seq` predates everything still buffered (e.g. it was
+    /// already `confirm`ed away). Returns `None` only if no input has ever
+    /// been added.
+    pub fn get_or_predict(&self, seq: u32) -> Option<InputState> {
+        if let Some(input) = self.inputs.get(&seq) {
+            return Some(input.clone());
+        }
+
+        self.inputs
+            .range(..=seq)
+            .next_back()
+            .map(|(_, input)| input.clone())
+            .or_else(|| self.last_known.clone())
+    }
+
+    /// Highest sequence number still buffered, if any.
+    pub fn max_sequence(&self) -> Option<u32> {
+        self.inputs.keys().next_back().copied()
+    }
+
+    /// Number of real (non-predicted) inputs still buffered.
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+
+    /// Evicts the `count` oldest buffered inputs, to bound memory growth if
+    /// `confirm` somehow falls behind (e.g. a stalled connection).
+    pub fn prune_oldest(&mut self, count: usize) {
+        let stale: Vec<u32> = self.inputs.keys().take(count).copied().collect();
+        for seq in stale {
+            self.inputs.remove(&seq);
+        }
+    }
+}
+
+impl Default for InputQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Client-side game state manager handling prediction, reconciliation, and interpolation
 pub struct ClientGameState {
-    pub confirmed_state: GameState,     // Last confirmed state from server
-    pub predicted_state: GameState,     // Client's predicted current state
-    pub input_history: Vec<InputState>, // Unconfirmed inputs for rollback
+    pub confirmed_state: GameState, // Last confirmed state from server
+    pub predicted_state: GameState, // Client's predicted current state
+    pub input_queue: InputQueue,    // Unconfirmed inputs for rollback, indexed by sequence
     pub last_confirmed_tick: u32,
     pub interpolation_buffer: Vec<(u64, Vec<Player>)>, // Timestamped states for interpolation
     pub physics_accumulator: f32,
     pub fixed_timestep: f32, // Fixed timestep for deterministic simulation (60 FPS)
+    /// Hard cap on how far `predicted_state` may run ahead of
+    /// `last_confirmed_tick`. `apply_prediction` stalls once this many ticks
+    /// of unconfirmed prediction have piled up, which in turn bounds
+    /// `perform_reconciliation`'s replay to at most this many inputs
+    /// (mirrors GGRS's `with_max_prediction_window`).
+    pub max_prediction_frames: u32,
+    /// Ticks a freshly submitted input sits in `pending_inputs` before it is
+    /// applied to `predicted_state`, so the same local input lands on the
+    /// same relative simulation tick across clients (GGRS's `with_input_delay`).
+    pub input_delay: u32,
+    /// Inputs submitted to `apply_prediction` but not yet released into
+    /// `predicted_state`/`input_queue`, either because they're still
+    /// waiting out `input_delay` or because prediction is stalled.
+    pending_inputs: VecDeque<InputState>,
+    /// When true, every tick `apply_prediction` advances is independently
+    /// re-simulated from its pre-tick snapshot and the two checksums are
+    /// compared, catching non-determinism (e.g. a `HashMap` iteration order
+    /// dependency in `handle_collisions`) that would otherwise silently
+    /// corrupt rollback+replay. Mirrors GGRS's `SyncTestSession`.
+    pub sync_test_enabled: bool,
+    /// Reorders/de-duplicates incoming server states by tick before they're
+    /// folded into `confirmed_state`.
+    pub server_state_buffer: ServerStateBuffer,
+    /// Render-only offset for the local player, captured by
+    /// `capture_render_offset` at the moment of a reconciliation correction
+    /// and decayed to zero over `render_smoothing_time_constant` so the
+    /// displayed position glides to the corrected one instead of popping.
+    render_offset: (f32, f32),
+    render_offset_captured_at: Option<std::time::Instant>,
+    /// Time constant (seconds) for `render_offset`'s exponential decay —
+    /// CrystalOrb-style display-state smoothing. ~100-150ms hides the pop
+    /// without making correction visibly sluggish.
+    pub render_smoothing_time_constant: f32,
+    /// Corrections larger than this are treated as a teleport (e.g. a
+    /// respawn) rather than an ordinary reconciliation nudge, and snap
+    /// instantly instead of being smoothed.
+    pub render_snap_threshold: f32,
+    /// Cached from the most recently received `ServerStateConfig`. When the
+    /// interpolation buffer underruns, controls whether remote players are
+    /// extrapolated forward from their last known velocity or frozen in
+    /// place; see `get_interpolated_players`.
+    pub extrapolation_enabled: bool,
+    /// Maximum gap, in milliseconds, past the newest buffered state that
+    /// `get_interpolated_players` will extrapolate forward before giving up
+    /// and freezing instead (a long-enough gap means the extrapolated guess
+    /// is more likely wrong than helpful).
+    pub max_extrapolation_ms: u64,
 }
 
 impl ClientGameState {
@@ -129,23 +454,79 @@ impl ClientGameState {
         Self {
             confirmed_state: GameState::new(),
             predicted_state: GameState::new(),
-            input_history: Vec::new(),
+            input_queue: InputQueue::new(),
             last_confirmed_tick: 0,
             interpolation_buffer: Vec::new(),
             physics_accumulator: 0.0,
             fixed_timestep: 1.0 / 60.0,
+            max_prediction_frames: 8,
+            input_delay: 0,
+            pending_inputs: VecDeque::new(),
+            sync_test_enabled: false,
+            server_state_buffer: ServerStateBuffer::new(DEFAULT_SERVER_STATE_BUFFER_DEPTH),
+            render_offset: (0.0, 0.0),
+            render_offset_captured_at: None,
+            render_smoothing_time_constant: 0.12,
+            render_snap_threshold: PLAYER_SIZE * 5.0,
+            extrapolation_enabled: true,
+            max_extrapolation_ms: 250,
         }
     }
 
-    /// Processes authoritative server state update
+    /// Processes authoritative server state update. Buffers the incoming
+    /// state by tick and only folds in the contiguous run that's now
+    /// available, so an out-of-order or duplicate packet can't regress
+    /// `confirmed_state`.
     pub fn apply_server_state(
         &mut self,
         tick: u32,
         timestamp: u64,
         players: Vec<Player>,
         last_processed_input: HashMap<u32, u32>,
+        checksum: u32,
         config: ServerStateConfig,
     ) {
+        self.server_state_buffer.insert(tick, timestamp, players);
+
+        for (promoted_tick, promoted_timestamp, promoted_players) in
+            self.server_state_buffer.drain_contiguous()
+        {
+            // Only the entry matching this call's own `tick` carries an
+            // acked-input map and desync checksum to reconcile against —
+            // older entries promoted alongside it were buffered waiting on
+            // this gap and just fold into `confirmed_state` as-is.
+            let reconcile_with = (promoted_tick == tick).then_some((&last_processed_input, checksum));
+            self.fold_confirmed_state(promoted_tick, promoted_timestamp, promoted_players, reconcile_with, &config);
+        }
+    }
+
+    /// Applies one promoted server tick to `confirmed_state`/`predicted_state`
+    /// and the interpolation buffer. When `reconcile_with` carries this
+    /// tick's acked-input map and checksum, also runs the desync check and
+    /// either reconciles or syncs `predicted_state`, per `config`.
+    fn fold_confirmed_state(
+        &mut self,
+        tick: u32,
+        timestamp: u64,
+        players: Vec<Player>,
+        reconcile_with: Option<(&HashMap<u32, u32>, u32)>,
+        config: &ServerStateConfig,
+    ) {
+        // Compare this tick's predicted state (before it's overwritten below)
+        // against the server's authoritative checksum. A mismatch means
+        // client prediction has desynced from server physics somewhere
+        // upstream of reconciliation noticing any single player's drift.
+        if let Some((_, checksum)) = reconcile_with {
+            let predicted_players: Vec<Player> = self.predicted_state.players.values().cloned().collect();
+            let predicted_checksum = shared::compute_checksum(&predicted_players);
+            if predicted_checksum != checksum {
+                debug!(
+                    "Desync detected at tick {}: predicted checksum {:#010x} != server checksum {:#010x}",
+                    tick, predicted_checksum, checksum
+                );
+            }
+        }
+
         // Update confirmed state
         self.confirmed_state.players.clear();
         for player in &players {
@@ -166,6 +547,8 @@ impl ClientGameState {
             }
         }
 
+        self.extrapolation_enabled = config.extrapolation_enabled;
+
         // Add to interpolation buffer
         if config.interpolation_enabled {
             self.interpolation_buffer.push((timestamp, players));
@@ -173,17 +556,20 @@ impl ClientGameState {
             self.interpolation_buffer.retain(|(ts, _)| *ts > cutoff);
         }
 
-        // Perform reconciliation
-        if config.reconciliation_enabled {
-            if let Some(client_id) = config.client_id {
-                self.perform_reconciliation(client_id, last_processed_input);
-            }
-        } else if let Some(client_id) = config.client_id {
-            // Without reconciliation, just sync to confirmed state
-            if let Some(confirmed_player) = self.confirmed_state.players.get(&client_id) {
-                self.predicted_state
-                    .players
-                    .insert(client_id, confirmed_player.clone());
+        if let Some(client_id) = config.client_id {
+            match reconcile_with {
+                Some((last_processed_input, _)) if config.reconciliation_enabled => {
+                    self.perform_reconciliation(client_id, last_processed_input.clone());
+                }
+                _ => {
+                    // Without reconciliation data for this tick (or with it
+                    // disabled), just sync to confirmed state.
+                    if let Some(confirmed_player) = self.confirmed_state.players.get(&client_id) {
+                        self.predicted_state
+                            .players
+                            .insert(client_id, confirmed_player.clone());
+                    }
+                }
             }
         }
 
@@ -193,14 +579,13 @@ impl ClientGameState {
     /// Performs client-side reconciliation using rollback and replay
     fn perform_reconciliation(&mut self, client_id: u32, last_processed_input: HashMap<u32, u32>) {
         if let Some(&last_processed_seq) = last_processed_input.get(&client_id) {
-            // Remove processed inputs
-            let initial_history_len = self.input_history.len();
-            self.input_history
-                .retain(|input| input.sequence > last_processed_seq);
+            // Drop acknowledged inputs from the queue
+            let before = self.input_queue.len();
+            self.input_queue.confirm(last_processed_seq);
 
             debug!(
-                "Removed {} processed inputs from history",
-                initial_history_len - self.input_history.len()
+                "Removed {} processed inputs from queue",
+                before - self.input_queue.len()
             );
 
             let confirmed_player = self.confirmed_state.players.get(&client_id);
@@ -214,36 +599,150 @@ impl ClientGameState {
 
                 if distance > 1.0 {
                     debug!("Rollback needed! Distance: {:.2}", distance);
+                    let pre_correction_pos = (predicted.x, predicted.y);
 
                     // Rollback: Reset to confirmed state
                     self.predicted_state = self.confirmed_state.clone();
                     self.predicted_state.tick = self.confirmed_state.tick;
 
-                    // Replay: Re-apply unacknowledged inputs
-                    for input in &self.input_history {
-                        self.predicted_state
-                            .apply_input(client_id, input, self.fixed_timestep);
-                        self.predicted_state.step(self.fixed_timestep);
+                    // Replay: re-apply unacknowledged inputs by sequence,
+                    // capped at `max_prediction_frames` so a client that fell
+                    // far behind doesn't re-simulate an unbounded number of
+                    // ticks on every server packet. A sequence with no real
+                    // input recorded (e.g. a dropped input packet) is filled
+                    // in by `get_or_predict`, repeating the last known input
+                    // rather than leaving a hole in the replay.
+                    if let Some(replay_end) = self.input_queue.max_sequence() {
+                        let window = self.max_prediction_frames.saturating_sub(1);
+                        let replay_start =
+                            replay_end.saturating_sub(window).max(last_processed_seq + 1);
+
+                        for seq in replay_start..=replay_end {
+                            if let Some(input) = self.input_queue.get_or_predict(seq) {
+                                self.predicted_state
+                                    .apply_input(client_id, &input, self.fixed_timestep);
+                                self.predicted_state.step(self.fixed_timestep);
+                            }
+                        }
                     }
+
+                    self.capture_render_offset(client_id, pre_correction_pos);
                 }
             }
         }
     }
 
+    /// Captures how far the local player just snapped during rollback, so
+    /// `get_render_players` can glide the displayed position back from
+    /// `pre_correction_pos` instead of popping. Skips smoothing (snapping
+    /// instantly) when the jump exceeds `render_snap_threshold` — that's a
+    /// teleport (e.g. a respawn), not an ordinary reconciliation nudge.
+    fn capture_render_offset(&mut self, client_id: u32, pre_correction_pos: (f32, f32)) {
+        let Some(new_predicted) = self.predicted_state.players.get(&client_id) else {
+            return;
+        };
+
+        let dx = pre_correction_pos.0 - new_predicted.x;
+        let dy = pre_correction_pos.1 - new_predicted.y;
+        let snap_distance = (dx * dx + dy * dy).sqrt();
+
+        if snap_distance <= self.render_snap_threshold {
+            self.render_offset = (dx, dy);
+            self.render_offset_captured_at = Some(std::time::Instant::now());
+        } else {
+            self.render_offset = (0.0, 0.0);
+            self.render_offset_captured_at = None;
+        }
+    }
+
+    /// Current render-only offset for the local player, decayed
+    /// exponentially from the value `capture_render_offset` last set, with
+    /// `render_smoothing_time_constant` as the decay's time constant
+    /// (CrystalOrb-style display-state smoothing).
+    fn current_render_offset(&self) -> (f32, f32) {
+        let Some(captured_at) = self.render_offset_captured_at else {
+            return (0.0, 0.0);
+        };
+
+        let elapsed = captured_at.elapsed().as_secs_f32();
+        let decay = (-elapsed / self.render_smoothing_time_constant).exp();
+        (self.render_offset.0 * decay, self.render_offset.1 * decay)
+    }
+
+    /// Clones the local player out of `predicted_state` with the current
+    /// render-smoothing offset applied — for display only, `predicted_state`
+    /// itself is never touched by this.
+    fn local_render_player(&self, client_id: u32) -> Option<Player> {
+        let mut player = self.predicted_state.players.get(&client_id)?.clone();
+        let (offset_x, offset_y) = self.current_render_offset();
+        player.x += offset_x;
+        player.y += offset_y;
+        Some(player)
+    }
+
     /// Applies client-side prediction for immediate input response
     pub fn apply_prediction(&mut self, client_id: u32, input: &InputState) {
+        // Buffer the input; it only takes effect once it has waited out
+        // `input_delay` ticks in the queue.
+        self.pending_inputs.push_back(input.clone());
+        if (self.pending_inputs.len() as u32) <= self.input_delay {
+            return;
+        }
+
+        // Stall if predicted_state has already run `max_prediction_frames`
+        // ticks ahead of the last confirmed tick — leave the input queued
+        // rather than simulate further, capping worst-case replay cost.
+        let frames_ahead = self
+            .predicted_state
+            .tick
+            .saturating_sub(self.last_confirmed_tick);
+        if frames_ahead >= self.max_prediction_frames {
+            debug!(
+                "Stalling prediction: {} frames ahead of last confirmed tick {}",
+                frames_ahead, self.last_confirmed_tick
+            );
+            return;
+        }
+
+        let input = self.pending_inputs.pop_front().unwrap();
+
         // Store input for potential rollback
-        self.input_history.push(input.clone());
+        self.input_queue.add(input.clone());
 
-        // Prevent unbounded memory growth
-        if self.input_history.len() > 1000 {
-            self.input_history.drain(0..100);
+        // Prevent unbounded memory growth (e.g. if `confirm` falls behind
+        // because reconciliation has been disabled)
+        if self.input_queue.len() > 1000 {
+            self.input_queue.prune_oldest(100);
         }
 
+        let pre_tick_state = self.sync_test_enabled.then(|| self.predicted_state.clone());
+
         // Apply input immediately to predicted state
         self.predicted_state
-            .apply_input(client_id, input, self.fixed_timestep);
+            .apply_input(client_id, &input, self.fixed_timestep);
         self.predicted_state.step(self.fixed_timestep);
+
+        if let Some(before) = pre_tick_state {
+            self.run_sync_test_check(client_id, &input, &before);
+        }
+    }
+
+    /// Re-simulates `before` with the same `input` that was just applied to
+    /// `predicted_state` and compares checksums, logging the diverging tick
+    /// and both checksums on mismatch. Only called when `sync_test_enabled`.
+    fn run_sync_test_check(&self, client_id: u32, input: &InputState, before: &GameState) {
+        let mut replay = before.clone();
+        replay.apply_input(client_id, input, self.fixed_timestep);
+        replay.step(self.fixed_timestep);
+
+        let actual = self.predicted_state.checksum();
+        let replayed = replay.checksum();
+        if actual != replayed {
+            error!(
+                "Sync test mismatch at tick {}: checksum {:#018x} != replayed checksum {:#018x}",
+                self.predicted_state.tick, actual, replayed
+            );
+        }
     }
 
     /// Updates physics accumulator for fixed timestep simulation
@@ -261,17 +760,18 @@ impl ClientGameState {
         client_id: Option<u32>,
         prediction_enabled: bool,
         interpolation_enabled: bool,
+        now_ms: u64,
     ) -> Vec<Player> {
         if interpolation_enabled {
-            self.get_interpolated_players(client_id)
+            self.get_interpolated_players(client_id, now_ms)
         } else {
             let mut players = Vec::new();
 
             if let Some(client_id) = client_id {
                 // Local player: use predicted or confirmed state
                 if prediction_enabled {
-                    if let Some(our_player) = self.predicted_state.players.get(&client_id) {
-                        players.push(our_player.clone());
+                    if let Some(our_player) = self.local_render_player(client_id) {
+                        players.push(our_player);
                     }
                 } else if let Some(our_player) = self.confirmed_state.players.get(&client_id) {
                     players.push(our_player.clone());
@@ -291,20 +791,27 @@ impl ClientGameState {
         }
     }
 
-    /// Performs temporal interpolation between buffered server states
-    fn get_interpolated_players(&self, client_id: Option<u32>) -> Vec<Player> {
+    /// The last server-confirmed snapshot of `client_id`, ignoring prediction
+    /// entirely — used by the reconciliation-debug "ghost" overlay, which
+    /// needs the raw authoritative position rather than anything smoothed or
+    /// interpolated for normal rendering.
+    pub fn confirmed_local_player(&self, client_id: u32) -> Option<&Player> {
+        self.confirmed_state.players.get(&client_id)
+    }
+
+    /// Performs temporal interpolation between buffered server states.
+    /// `now_ms` is the caller's best estimate of the *server's* current
+    /// clock (e.g. `InputManager::server_now_ms`, NTP-offset-corrected)
+    /// rather than raw local wall-clock time, so interpolation still lines
+    /// up with buffered server timestamps even when the client's own system
+    /// clock has drifted from the server's.
+    fn get_interpolated_players(&self, client_id: Option<u32>, now_ms: u64) -> Vec<Player> {
         if self.interpolation_buffer.len() < 2 {
-            return self.get_render_players(client_id, false, false);
+            return self.get_render_players(client_id, false, false, now_ms);
         }
 
         // Calculate render time with 150ms delay for smooth interpolation
-        let now = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or(std::time::Duration::from_secs(0))
-            .as_millis();
-        let now_safe = (now.min(u64::MAX as u128)) as u64;
-
-        let render_time = now_safe.saturating_sub(150);
+        let render_time = now_ms.saturating_sub(150);
 
         // Find the two states to interpolate between
         let mut before = None;
@@ -336,8 +843,8 @@ impl ClientGameState {
                 for p1 in players1 {
                     // Local player uses prediction, not interpolation
                     if Some(p1.id) == client_id {
-                        if let Some(our_player) = self.predicted_state.players.get(&p1.id) {
-                            result.push(our_player.clone());
+                        if let Some(our_player) = self.local_render_player(p1.id) {
+                            result.push(our_player);
                         }
                         continue;
                     }
@@ -351,6 +858,12 @@ impl ClientGameState {
                             vel_x: p1.vel_x + (p2.vel_x - p1.vel_x) * alpha,
                             vel_y: p1.vel_y + (p2.vel_y - p1.vel_y) * alpha,
                             on_ground: p2.on_ground,
+                            layer: p2.layer,
+                            mask: p2.mask,
+                            gamemode: p2.gamemode,
+                            flying: p2.flying,
+                            collider: p2.collider,
+                            username: p2.username.clone(),
                         };
                         result.push(interpolated);
                     }
@@ -358,19 +871,29 @@ impl ClientGameState {
                 result
             }
             (Some(before_idx), None) => {
-                let (_, players) = &self.interpolation_buffer[before_idx];
-                let mut result = players.clone();
+                let (timestamp, players) = &self.interpolation_buffer[before_idx];
+                let gap_ms = render_time.saturating_sub(*timestamp);
+
+                let mut result = if self.extrapolation_enabled && gap_ms <= self.max_extrapolation_ms {
+                    players
+                        .iter()
+                        .map(|p| extrapolate_player(p, gap_ms as f32 / 1000.0))
+                        .collect()
+                } else {
+                    players.clone()
+                };
+
                 // Still use prediction for local player
                 if let Some(client_id) = client_id {
-                    if let Some(our_player) = self.predicted_state.players.get(&client_id) {
+                    if let Some(our_player) = self.local_render_player(client_id) {
                         if let Some(pos) = result.iter().position(|p| p.id == client_id) {
-                            result[pos] = our_player.clone();
+                            result[pos] = our_player;
                         }
                     }
                 }
                 result
             }
-            _ => self.get_render_players(client_id, false, false),
+            _ => self.get_render_players(client_id, false, false, now_ms),
         }
     }
 }
@@ -385,6 +908,7 @@ impl Default for ClientGameState {
 mod tests {
     use super::*;
     use shared::{InputState, Player};
+    use std::{thread, time::Duration};
 
     #[test]
     fn test_game_state_creation() {
@@ -431,11 +955,15 @@ mod tests {
         let client_state = ClientGameState::new();
         assert_eq!(client_state.confirmed_state.tick, 0);
         assert_eq!(client_state.predicted_state.tick, 0);
-        assert!(client_state.input_history.is_empty());
+        assert!(client_state.input_queue.is_empty());
         assert!(client_state.interpolation_buffer.is_empty());
         assert_eq!(client_state.last_confirmed_tick, 0);
         assert_eq!(client_state.physics_accumulator, 0.0);
         assert_eq!(client_state.fixed_timestep, 1.0 / 60.0);
+        assert_eq!(client_state.max_prediction_frames, 8);
+        assert_eq!(client_state.input_delay, 0);
+        assert!(!client_state.sync_test_enabled);
+        assert_eq!(client_state.server_state_buffer.depth(), 0);
     }
 
     #[test]
@@ -497,7 +1025,7 @@ mod tests {
 
         let player = &client_state.predicted_state.players[&1];
         assert_eq!(player.vel_x, PLAYER_SPEED);
-        assert_eq!(client_state.input_history.len(), 1);
+        assert_eq!(client_state.input_queue.len(), 1);
     }
 
     #[test]
@@ -509,9 +1037,10 @@ mod tests {
             client_id: Some(1),
             reconciliation_enabled: false,
             interpolation_enabled: false,
+            extrapolation_enabled: true,
         };
 
-        client_state.apply_server_state(5, 2000, players, HashMap::new(), config);
+        client_state.apply_server_state(5, 2000, players, HashMap::new(), 0, config);
 
         assert_eq!(client_state.confirmed_state.tick, 5);
         assert_eq!(client_state.confirmed_state.players[&1].x, 150.0);
@@ -562,7 +1091,7 @@ mod tests {
                 right: true,
                 jump: false,
             };
-            client_state.input_history.push(input);
+            client_state.input_queue.add(input);
         }
 
         // Server state with significantly different position
@@ -575,12 +1104,13 @@ mod tests {
             client_id: Some(1),
             reconciliation_enabled: true,
             interpolation_enabled: false,
+            extrapolation_enabled: true,
         };
 
-        client_state.apply_server_state(10, 5000, players, last_processed, config);
+        client_state.apply_server_state(10, 5000, players, last_processed, 0, config);
 
         // Should have performed rollback and replay
-        assert_eq!(client_state.input_history.len(), 1); // Only unprocessed input remains
+        assert_eq!(client_state.input_queue.len(), 1); // Only unprocessed input remains
         let final_player = &client_state.predicted_state.players[&1];
         // Position should be closer to server state after reconciliation
         assert!(final_player.x < 200.0);
@@ -595,9 +1125,10 @@ mod tests {
             client_id: Some(1),
             reconciliation_enabled: false,
             interpolation_enabled: true,
+            extrapolation_enabled: true,
         };
 
-        client_state.apply_server_state(1, 1000, players, HashMap::new(), config);
+        client_state.apply_server_state(1, 1000, players, HashMap::new(), 0, config);
 
         assert_eq!(client_state.interpolation_buffer.len(), 1);
         assert_eq!(client_state.interpolation_buffer[0].0, 1000); // timestamp
@@ -612,7 +1143,7 @@ mod tests {
             .players
             .insert(1, Player::new(1, 100.0, 100.0));
 
-        let players = client_state.get_render_players(Some(1), false, false);
+        let players = client_state.get_render_players(Some(1), false, false, now_ms());
         assert_eq!(players.len(), 1);
         assert_eq!(players[0].x, 100.0);
     }
@@ -629,7 +1160,7 @@ mod tests {
             .players
             .insert(1, Player::new(1, 150.0, 100.0));
 
-        let players = client_state.get_render_players(Some(1), true, false);
+        let players = client_state.get_render_players(Some(1), true, false, now_ms());
         assert_eq!(players.len(), 1);
         assert_eq!(players[0].x, 150.0); // Should use predicted state
     }
@@ -663,20 +1194,103 @@ mod tests {
                 client_id: Some(1),
                 reconciliation_enabled: false,
                 interpolation_enabled: true,
+                extrapolation_enabled: true,
             };
 
             // Use timestamps within the retention window (1000ms)
             let base_time = 15000u64; // Recent timestamp
             let timestamp = base_time + (i as u64) * 100; // 100ms apart
-            client_state.apply_server_state(i, timestamp, players, HashMap::new(), config);
+            client_state.apply_server_state(i, timestamp, players, HashMap::new(), 0, config);
         }
 
         // Should have all 5 states (all within retention window)
         assert_eq!(client_state.interpolation_buffer.len(), 5);
     }
 
+    fn now_ms() -> u64 {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as u64
+    }
+
+    #[test]
+    fn test_extrapolate_player_advances_by_velocity_and_applies_gravity() {
+        let mut player = Player::new(1, 100.0, 100.0);
+        player.vel_x = 50.0;
+        player.vel_y = 20.0;
+        player.on_ground = false;
+
+        let extrapolated = extrapolate_player(&player, 0.1);
+
+        assert_eq!(extrapolated.x, 105.0);
+        assert!(extrapolated.y > 102.0); // advanced by vel_y, plus a touch of gravity
+        assert!(extrapolated.vel_y > player.vel_y); // gravity accumulated
+    }
+
+    #[test]
+    fn test_get_interpolated_players_extrapolates_remote_player_on_buffer_underrun() {
+        let mut client_state = ClientGameState::new();
+
+        let mut remote = Player::new(2, 100.0, 100.0);
+        remote.vel_x = 100.0;
+        remote.on_ground = true; // keep gravity out of the way for a simple assertion
+
+        // Buffer entries 250ms and 234ms old: with the 150ms render delay the
+        // newer one is 84ms behind render time, within the 250ms default
+        // extrapolation window, so the underrun branch should extrapolate
+        // rather than freeze.
+        let timestamp = now_ms().saturating_sub(250);
+        client_state.interpolation_buffer =
+            vec![(timestamp, vec![remote.clone()]), (timestamp + 16, vec![remote.clone()])];
+
+        let players = client_state.get_interpolated_players(Some(1), now_ms());
+
+        let rendered = players.iter().find(|p| p.id == 2).unwrap();
+        assert!(rendered.x > remote.x);
+    }
+
+    #[test]
+    fn test_get_interpolated_players_freezes_when_extrapolation_disabled() {
+        let mut client_state = ClientGameState::new();
+        client_state.extrapolation_enabled = false;
+
+        let mut remote = Player::new(2, 100.0, 100.0);
+        remote.vel_x = 100.0;
+        remote.on_ground = true;
+
+        let timestamp = now_ms().saturating_sub(250);
+        client_state.interpolation_buffer =
+            vec![(timestamp, vec![remote.clone()]), (timestamp + 16, vec![remote.clone()])];
+
+        let players = client_state.get_interpolated_players(Some(1), now_ms());
+
+        let rendered = players.iter().find(|p| p.id == 2).unwrap();
+        assert_eq!(rendered.x, remote.x);
+    }
+
     #[test]
-    fn test_input_history_overflow_protection() {
+    fn test_get_interpolated_players_freezes_beyond_max_extrapolation_window() {
+        let mut client_state = ClientGameState::new();
+        client_state.max_extrapolation_ms = 250;
+
+        let mut remote = Player::new(2, 100.0, 100.0);
+        remote.vel_x = 100.0;
+        remote.on_ground = true;
+
+        // 450ms old: past the 250ms window, so this should fall back to freezing.
+        let timestamp = now_ms().saturating_sub(450);
+        client_state.interpolation_buffer =
+            vec![(timestamp, vec![remote.clone()]), (timestamp + 16, vec![remote.clone()])];
+
+        let players = client_state.get_interpolated_players(Some(1), now_ms());
+
+        let rendered = players.iter().find(|p| p.id == 2).unwrap();
+        assert_eq!(rendered.x, remote.x);
+    }
+
+    #[test]
+    fn test_input_queue_overflow_protection() {
         let mut client_state = ClientGameState::new();
 
         // Add many inputs through apply_prediction to trigger overflow protection
@@ -692,6 +1306,399 @@ mod tests {
         }
 
         // Should be managed by overflow protection in apply_prediction
-        assert!(client_state.input_history.len() <= 1000);
+        assert!(client_state.input_queue.len() <= 1000);
+    }
+
+    #[test]
+    fn test_apply_prediction_stalls_beyond_max_prediction_window() {
+        let mut client_state = ClientGameState::new();
+        client_state.max_prediction_frames = 4;
+        client_state
+            .predicted_state
+            .players
+            .insert(1, Player::new(1, 100.0, 100.0));
+
+        // Drive predicted_state well past the window while last_confirmed_tick
+        // stays at 0, as happens when a client falls behind the server.
+        for i in 0..10 {
+            let input = InputState {
+                sequence: i,
+                timestamp: i as u64 * 16,
+                left: false,
+                right: true,
+                jump: false,
+            };
+            client_state.apply_prediction(1, &input);
+        }
+
+        // Prediction should have stalled exactly at the window boundary
+        // instead of running ahead indefinitely.
+        assert_eq!(client_state.predicted_state.tick, 4);
+        assert_eq!(client_state.input_queue.len(), 4);
+
+        // Confirming up to tick 4 should let prediction resume and drain
+        // the inputs that were queued up while stalled.
+        let config = ServerStateConfig {
+            client_id: Some(1),
+            reconciliation_enabled: false,
+            interpolation_enabled: false,
+            extrapolation_enabled: true,
+        };
+        client_state.apply_server_state(4, 1000, vec![Player::new(1, 100.0, 100.0)], HashMap::new(), 0, config);
+
+        for i in 10..16 {
+            let input = InputState {
+                sequence: i,
+                timestamp: i as u64 * 16,
+                left: false,
+                right: true,
+                jump: false,
+            };
+            client_state.apply_prediction(1, &input);
+        }
+
+        assert_eq!(client_state.predicted_state.tick, 8);
+    }
+
+    #[test]
+    fn test_apply_prediction_with_input_delay_holds_input() {
+        let mut client_state = ClientGameState::new();
+        client_state.input_delay = 2;
+        client_state
+            .predicted_state
+            .players
+            .insert(1, Player::new(1, 100.0, 100.0));
+
+        let input = InputState {
+            sequence: 1,
+            timestamp: 1000,
+            left: false,
+            right: true,
+            jump: false,
+        };
+
+        // First two submissions are only buffered, not yet applied.
+        client_state.apply_prediction(1, &input);
+        assert_eq!(client_state.input_queue.len(), 0);
+        assert_eq!(client_state.predicted_state.tick, 0);
+
+        client_state.apply_prediction(1, &input);
+        assert_eq!(client_state.input_queue.len(), 0);
+        assert_eq!(client_state.predicted_state.tick, 0);
+
+        // The third submission releases the first buffered input.
+        client_state.apply_prediction(1, &input);
+        assert_eq!(client_state.input_queue.len(), 1);
+        assert_eq!(client_state.predicted_state.tick, 1);
+    }
+
+    #[test]
+    fn test_game_state_checksum_matches_for_identical_states() {
+        let mut state1 = GameState::new();
+        let mut state2 = GameState::new();
+        state1.players.insert(1, Player::new(1, 100.0, 100.0));
+        state2.players.insert(1, Player::new(1, 100.0, 100.0));
+
+        assert_eq!(state1.checksum(), state2.checksum());
+    }
+
+    #[test]
+    fn test_game_state_checksum_differs_on_position_or_tick() {
+        let mut state1 = GameState::new();
+        let mut state2 = GameState::new();
+        state1.players.insert(1, Player::new(1, 100.0, 100.0));
+        state2.players.insert(1, Player::new(1, 105.0, 100.0));
+
+        assert_ne!(state1.checksum(), state2.checksum());
+
+        let mut state3 = GameState::new();
+        state3.players.insert(1, Player::new(1, 100.0, 100.0));
+        state3.tick = 1;
+        assert_ne!(state1.checksum(), state3.checksum());
+    }
+
+    #[test]
+    fn test_sync_test_mode_agrees_with_deterministic_simulation() {
+        let mut client_state = ClientGameState::new();
+        client_state.sync_test_enabled = true;
+        client_state
+            .predicted_state
+            .players
+            .insert(1, Player::new(1, 100.0, 100.0));
+
+        // A single deterministic player re-simulates identically from its
+        // pre-tick snapshot, so no mismatch should be logged and the tick
+        // should still advance normally.
+        for i in 0..5 {
+            let input = InputState {
+                sequence: i,
+                timestamp: i as u64 * 16,
+                left: false,
+                right: true,
+                jump: false,
+            };
+            client_state.apply_prediction(1, &input);
+        }
+
+        assert_eq!(client_state.predicted_state.tick, 5);
+    }
+
+    #[test]
+    fn test_server_state_buffer_bootstraps_on_first_tick_regardless_of_value() {
+        let mut buffer = ServerStateBuffer::new(32);
+        buffer.insert(500, 1000, vec![Player::new(1, 0.0, 0.0)]);
+
+        let drained = buffer.drain_contiguous();
+        assert_eq!(drained.len(), 1);
+        assert_eq!(drained[0].0, 500);
+        assert_eq!(buffer.last_confirmed_tick(), 500);
+    }
+
+    #[test]
+    fn test_server_state_buffer_drops_stale_or_duplicate_tick() {
+        let mut buffer = ServerStateBuffer::new(32);
+        buffer.insert(5, 1000, vec![]);
+        buffer.drain_contiguous();
+
+        buffer.insert(5, 1001, vec![]); // duplicate
+        buffer.insert(3, 1002, vec![]); // stale
+
+        assert_eq!(buffer.dropped_count(), 2);
+        assert_eq!(buffer.depth(), 0);
+    }
+
+    #[test]
+    fn test_server_state_buffer_holds_out_of_order_tick_until_gap_fills() {
+        let mut buffer = ServerStateBuffer::new(32);
+        buffer.insert(1, 1000, vec![]);
+        buffer.drain_contiguous();
+
+        // Tick 3 arrives before tick 2 — it must not be promoted yet.
+        buffer.insert(3, 1002, vec![]);
+        assert_eq!(buffer.reordered_count(), 0); // nothing higher was buffered yet
+        assert!(buffer.drain_contiguous().is_empty());
+        assert_eq!(buffer.last_confirmed_tick(), 1);
+
+        // Tick 2 fills the gap — both 2 and 3 promote together, in order.
+        buffer.insert(2, 1001, vec![]);
+        let drained = buffer.drain_contiguous();
+        let ticks: Vec<u32> = drained.iter().map(|(tick, _, _)| *tick).collect();
+        assert_eq!(ticks, vec![2, 3]);
+        assert_eq!(buffer.last_confirmed_tick(), 3);
+    }
+
+    #[test]
+    fn test_server_state_buffer_counts_reordered_arrivals() {
+        let mut buffer = ServerStateBuffer::new(32);
+        buffer.insert(1, 1000, vec![]);
+        buffer.drain_contiguous();
+
+        buffer.insert(3, 1002, vec![]); // buffered, waiting on tick 2
+        buffer.insert(2, 1001, vec![]); // arrives after a higher tick is already buffered
+
+        assert_eq!(buffer.reordered_count(), 1);
+    }
+
+    #[test]
+    fn test_server_state_buffer_evicts_oldest_past_buffer_depth() {
+        let mut buffer = ServerStateBuffer::new(2);
+        buffer.insert(10, 1000, vec![]); // bootstrap baseline, immediately promotable
+        buffer.drain_contiguous();
+
+        // Ticks 12..=14 all wait on the still-missing tick 11, so the
+        // buffer fills past its depth and evicts the oldest of them.
+        buffer.insert(12, 1001, vec![]);
+        buffer.insert(13, 1002, vec![]);
+        buffer.insert(14, 1003, vec![]);
+
+        assert_eq!(buffer.depth(), 2);
+        assert!(buffer.dropped_count() >= 1);
+    }
+
+    #[test]
+    fn test_apply_server_state_out_of_order_does_not_regress_confirmed_tick() {
+        let mut client_state = ClientGameState::new();
+        let config = ServerStateConfig {
+            client_id: Some(1),
+            reconciliation_enabled: false,
+            interpolation_enabled: false,
+            extrapolation_enabled: true,
+        };
+
+        client_state.apply_server_state(5, 1000, vec![Player::new(1, 100.0, 100.0)], HashMap::new(), 0, config.clone());
+        assert_eq!(client_state.confirmed_state.tick, 5);
+
+        // A late, out-of-order packet for an older tick must not clobber
+        // the already-confirmed newer tick.
+        client_state.apply_server_state(2, 900, vec![Player::new(1, 999.0, 999.0)], HashMap::new(), 0, config);
+        assert_eq!(client_state.confirmed_state.tick, 5);
+        assert_eq!(client_state.server_state_buffer.dropped_count(), 1);
+    }
+
+    #[test]
+    fn test_render_offset_shrinks_monotonically_after_correction() {
+        let mut client_state = ClientGameState::new();
+        let client_id = 1;
+
+        client_state
+            .confirmed_state
+            .players
+            .insert(client_id, Player::new(client_id, 100.0, 100.0));
+        client_state
+            .predicted_state
+            .players
+            .insert(client_id, Player::new(client_id, 150.0, 100.0));
+
+        let mut last_processed_input = HashMap::new();
+        last_processed_input.insert(client_id, 0);
+        client_state.perform_reconciliation(client_id, last_processed_input);
+        assert!(client_state.render_offset_captured_at.is_some());
+
+        let base_x = client_state.predicted_state.players[&client_id].x;
+
+        let first = client_state.get_render_players(Some(client_id), true, false, now_ms());
+        let first_offset = (first[0].x - base_x).abs();
+        assert!(first_offset > 0.0);
+
+        thread::sleep(Duration::from_millis(20));
+        let second = client_state.get_render_players(Some(client_id), true, false, now_ms());
+        let second_offset = (second[0].x - base_x).abs();
+
+        thread::sleep(Duration::from_millis(20));
+        let third = client_state.get_render_players(Some(client_id), true, false, now_ms());
+        let third_offset = (third[0].x - base_x).abs();
+
+        assert!(second_offset < first_offset);
+        assert!(third_offset < second_offset);
+    }
+
+    #[test]
+    fn test_render_offset_skips_smoothing_past_snap_threshold() {
+        let mut client_state = ClientGameState::new();
+        let client_id = 1;
+
+        client_state
+            .confirmed_state
+            .players
+            .insert(client_id, Player::new(client_id, 100.0, 100.0));
+        // Far enough away to be a teleport, not a reconciliation nudge.
+        client_state.predicted_state.players.insert(
+            client_id,
+            Player::new(client_id, 100.0 + client_state.render_snap_threshold * 2.0, 100.0),
+        );
+
+        let mut last_processed_input = HashMap::new();
+        last_processed_input.insert(client_id, 0);
+        client_state.perform_reconciliation(client_id, last_processed_input);
+
+        assert!(client_state.render_offset_captured_at.is_none());
+        let rendered = client_state.get_render_players(Some(client_id), true, false, now_ms());
+        assert_eq!(rendered[0].x, client_state.predicted_state.players[&client_id].x);
+    }
+
+    fn test_input(sequence: u32, right: bool) -> InputState {
+        InputState {
+            sequence,
+            timestamp: sequence as u64 * 16,
+            left: false,
+            right,
+            jump: false,
+        }
+    }
+
+    #[test]
+    fn test_input_queue_returns_real_input_when_present() {
+        let mut queue = InputQueue::new();
+        queue.add(test_input(1, true));
+        queue.add(test_input(2, false));
+
+        assert_eq!(queue.get_or_predict(1), Some(test_input(1, true)));
+        assert_eq!(queue.get_or_predict(2), Some(test_input(2, false)));
+    }
+
+    #[test]
+    fn test_input_queue_predicts_gap_by_repeating_last_known_input() {
+        let mut queue = InputQueue::new();
+        queue.add(test_input(1, true));
+        queue.add(test_input(4, false));
+
+        // Sequences 2 and 3 were never recorded (e.g. dropped input
+        // packets) — each should predict the most recent real input
+        // *before* it, not the one that comes after.
+        assert_eq!(queue.get_or_predict(2), Some(test_input(1, true)));
+        assert_eq!(queue.get_or_predict(3), Some(test_input(1, true)));
+        assert_eq!(queue.get_or_predict(4), Some(test_input(4, false)));
+    }
+
+    #[test]
+    fn test_input_queue_predicts_from_last_known_once_everything_before_is_confirmed() {
+        let mut queue = InputQueue::new();
+        queue.add(test_input(1, true));
+        queue.confirm(1); // sequence 1 is now gone from `inputs`...
+
+        // ...but `get_or_predict` still falls back to it for a later gap.
+        assert_eq!(queue.get_or_predict(2), Some(test_input(1, true)));
+    }
+
+    #[test]
+    fn test_input_queue_get_or_predict_empty_queue_returns_none() {
+        let queue = InputQueue::new();
+        assert_eq!(queue.get_or_predict(0), None);
+    }
+
+    #[test]
+    fn test_input_queue_confirm_drops_acknowledged_inputs() {
+        let mut queue = InputQueue::new();
+        for seq in 1..=5 {
+            queue.add(test_input(seq, true));
+        }
+
+        queue.confirm(3);
+
+        assert_eq!(queue.len(), 2);
+        assert_eq!(queue.max_sequence(), Some(5));
+    }
+
+    #[test]
+    fn test_input_queue_prune_oldest_evicts_lowest_sequences() {
+        let mut queue = InputQueue::new();
+        for seq in 1..=5 {
+            queue.add(test_input(seq, true));
+        }
+
+        queue.prune_oldest(2);
+
+        assert_eq!(queue.len(), 3);
+        assert_eq!(queue.get_or_predict(1), Some(test_input(5, true))); // 1 and 2 predate everything buffered, so fall back to the last input added
+        assert_eq!(queue.max_sequence(), Some(5));
+    }
+
+    #[test]
+    fn test_reconciliation_replay_predicts_dropped_input_mid_window() {
+        let mut client_state = ClientGameState::new();
+        client_state
+            .predicted_state
+            .players
+            .insert(1, Player::new(1, 200.0, 100.0));
+
+        // Sequence 2 is deliberately missing, simulating a dropped input
+        // packet; replay must still cover it via prediction.
+        client_state.input_queue.add(test_input(1, true));
+        client_state.input_queue.add(test_input(3, true));
+
+        // Far enough from the predicted position to force a rollback.
+        client_state
+            .confirmed_state
+            .players
+            .insert(1, Player::new(1, 50.0, 100.0));
+        let mut last_processed = HashMap::new();
+        last_processed.insert(1u32, 0u32);
+        client_state.perform_reconciliation(1, last_processed);
+
+        // Replay ran for sequences 1..=3 (1 real, 2 predicted from 1, 3 real)
+        // without panicking on the gap, and the player ended up displaced
+        // from the rolled-back confirmed position by the replayed inputs.
+        let final_player = &client_state.predicted_state.players[&1];
+        assert!(final_player.x > 50.0);
     }
 }
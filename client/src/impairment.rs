@@ -0,0 +1,292 @@
+//! Configurable packet-loss/jitter/reorder impairment queue for the
+//! artificial-latency layer.
+//!
+//! `fake_ping_ms` alone only models a fixed one-way delay: nothing gets
+//! lost, and nothing arrives early or late relative to its neighbors, which
+//! makes it a poor stand-in for a real network when stress-testing
+//! interpolation or reconciliation. `ImpairmentQueue` adds the four things a
+//! fixed delay can't: `loss_probability` randomly drops a fraction of
+//! submissions outright, `jitter_stddev_ms` delays each survivor's
+//! scheduled release time past the caller-supplied base delay by a Gaussian
+//! draw clamped at zero (so never earlier than the base delay),
+//! `reorder_window` adds a further independent random offset so
+//! two packets submitted close together can be released out of order, and
+//! `duplication_probability` occasionally schedules a second release of the
+//! same item (its own independent jitter/reorder roll, so the duplicate
+//! doesn't always arrive back-to-back with the original). Mirrors
+//! `server::impairment::ImpairmentStage`'s `Delayed`/`BinaryHeap` shape,
+//! generalized over the held item type so one implementation drains both
+//! the outgoing `Vec<u8>` queue and the incoming `(Packet, Instant)` queue —
+//! jittered release times can pass each other, so both need earliest-first
+//! draining rather than a strict FIFO front-check.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+/// Tunable impairment parameters. `loss_probability` is clamped to `[0, 1]`
+/// at construction so a misconfigured value can't invert its own meaning.
+#[derive(Debug, Clone, Copy)]
+pub struct ImpairmentConfig {
+    pub loss_probability: f64,
+    pub jitter_stddev_ms: f64,
+    /// Width of an additional, independent random delay applied to every
+    /// surviving item on top of its base delay and jitter, so items
+    /// submitted close together can still be released out of their
+    /// original order.
+    pub reorder_window: Duration,
+    /// Probability that a surviving item is scheduled for release twice
+    /// instead of once, each copy getting its own independent jitter/reorder
+    /// roll — modeling a retransmitting router or a duplicate UDP delivery.
+    pub duplication_probability: f64,
+}
+
+impl ImpairmentConfig {
+    pub fn new(loss_probability: f64, jitter_stddev_ms: f64, reorder_window: Duration, duplication_probability: f64) -> Self {
+        Self {
+            loss_probability: loss_probability.clamp(0.0, 1.0),
+            jitter_stddev_ms,
+            reorder_window,
+            duplication_probability: duplication_probability.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Default for ImpairmentConfig {
+    /// No loss, no jitter, no reordering, no duplication — equivalent to the
+    /// queue just holding items for their plain base delay.
+    fn default() -> Self {
+        Self::new(0.0, 0.0, Duration::ZERO, 0.0)
+    }
+}
+
+/// One item held until its simulated release time.
+struct Delayed<T> {
+    release_at: Instant,
+    item: T,
+}
+
+impl<T> PartialEq for Delayed<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.release_at == other.release_at
+    }
+}
+impl<T> Eq for Delayed<T> {}
+
+impl<T> PartialOrd for Delayed<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for Delayed<T> {
+    // Reversed so `BinaryHeap` (a max-heap) pops the earliest release time first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.release_at.cmp(&self.release_at)
+    }
+}
+
+/// Applies loss/jitter/reorder verdicts to items passing through the
+/// artificial-latency layer, holding survivors until their simulated release
+/// time.
+pub struct ImpairmentQueue<T> {
+    config: ImpairmentConfig,
+    rng: StdRng,
+    pending: BinaryHeap<Delayed<T>>,
+}
+
+impl<T> ImpairmentQueue<T> {
+    /// `seed` makes every loss/jitter/reorder roll reproducible: the same
+    /// seed and the same sequence of `submit` calls always produce the same
+    /// verdicts.
+    pub fn new(config: ImpairmentConfig, seed: u64) -> Self {
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+            pending: BinaryHeap::new(),
+        }
+    }
+
+    pub fn config(&self) -> ImpairmentConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: ImpairmentConfig) {
+        self.config = config;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Drops every item still awaiting release without delivering it, e.g.
+    /// on reconnect so a stale session's in-flight packets don't bleed into
+    /// the new one.
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Rolls a loss verdict for `item`; if it survives, schedules it for
+    /// release at `now + base_delay`, perturbed by jitter and a reorder
+    /// offset, then independently rolls duplication and — if it hits —
+    /// schedules a second release with its own jitter/reorder roll. Returns
+    /// `true` if the item was dropped outright.
+    pub fn submit(&mut self, item: T, now: Instant, base_delay: Duration) -> bool
+    where
+        T: Clone,
+    {
+        if self.rng.gen_bool(self.config.loss_probability) {
+            return true;
+        }
+
+        let release_at = now + base_delay + self.jitter() + self.reorder_offset();
+        self.pending.push(Delayed {
+            release_at,
+            item: item.clone(),
+        });
+
+        if self.rng.gen_bool(self.config.duplication_probability) {
+            let dup_release_at = now + base_delay + self.jitter() + self.reorder_offset();
+            self.pending.push(Delayed {
+                release_at: dup_release_at,
+                item,
+            });
+        }
+
+        false
+    }
+
+    fn jitter(&mut self) -> Duration {
+        if self.config.jitter_stddev_ms <= 0.0 {
+            return Duration::ZERO;
+        }
+        // Box-Muller, matching `server::impairment::ImpairmentStage::jitter`'s
+        // approach to turning a uniform RNG into a normal one without an
+        // extra dependency.
+        let u1: f64 = self.rng.gen_range(f64::EPSILON..1.0);
+        let u2: f64 = self.rng.gen_range(0.0..1.0);
+        let standard_normal = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        let jitter_ms = (standard_normal * self.config.jitter_stddev_ms).max(0.0);
+        Duration::from_secs_f64(jitter_ms / 1000.0)
+    }
+
+    fn reorder_offset(&mut self) -> Duration {
+        if self.config.reorder_window.is_zero() {
+            return Duration::ZERO;
+        }
+        self.rng.gen_range(Duration::ZERO..self.config.reorder_window)
+    }
+
+    /// Releases every held item whose simulated release time has passed,
+    /// earliest first.
+    pub fn drain_ready(&mut self, now: Instant) -> Vec<T> {
+        let mut ready = Vec::new();
+        while let Some(next) = self.pending.peek() {
+            if next.release_at > now {
+                break;
+            }
+            ready.push(self.pending.pop().unwrap().item);
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn never_lose_delivers_exactly_one_copy() {
+        let mut queue = ImpairmentQueue::new(ImpairmentConfig::default(), 1);
+        let now = Instant::now();
+
+        let dropped = queue.submit(1u8, now, Duration::ZERO);
+        assert!(!dropped);
+        assert_eq!(queue.drain_ready(now), vec![1u8]);
+    }
+
+    #[test]
+    fn always_lose_drops_everything() {
+        let config = ImpairmentConfig::new(1.0, 0.0, Duration::ZERO, 0.0);
+        let mut queue = ImpairmentQueue::new(config, 2);
+        let now = Instant::now();
+
+        let dropped = queue.submit(1u8, now, Duration::ZERO);
+        assert!(dropped);
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn delayed_items_are_not_ready_until_their_release_time() {
+        let config = ImpairmentConfig::new(0.0, 0.0, Duration::ZERO, 0.0);
+        let mut queue = ImpairmentQueue::new(config, 3);
+        let now = Instant::now();
+
+        queue.submit(1u8, now, Duration::from_millis(50));
+        assert!(queue.drain_ready(now).is_empty());
+        assert_eq!(queue.drain_ready(now + Duration::from_millis(60)), vec![1u8]);
+    }
+
+    #[test]
+    fn reorder_window_can_release_a_later_submission_before_an_earlier_one() {
+        let config = ImpairmentConfig::new(0.0, 0.0, Duration::from_millis(100), 0.0);
+        let mut queue = ImpairmentQueue::new(config, 11);
+        let now = Instant::now();
+
+        for i in 0..20u8 {
+            queue.submit(i, now, Duration::from_millis(50));
+        }
+
+        let drained = queue.drain_ready(now + Duration::from_secs(1));
+        assert_eq!(drained.len(), 20);
+        assert_ne!(drained, (0..20u8).collect::<Vec<_>>(), "reorder window should shuffle at least one pair");
+    }
+
+    #[test]
+    fn clear_discards_everything_still_pending() {
+        let mut queue = ImpairmentQueue::new(ImpairmentConfig::default(), 5);
+        let now = Instant::now();
+        queue.submit(1u8, now, Duration::from_secs(10));
+        assert!(!queue.is_empty());
+
+        queue.clear();
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn always_duplicate_schedules_two_releases_for_one_submission() {
+        let config = ImpairmentConfig::new(0.0, 0.0, Duration::ZERO, 1.0);
+        let mut queue = ImpairmentQueue::new(config, 7);
+        let now = Instant::now();
+
+        queue.submit(1u8, now, Duration::ZERO);
+        assert_eq!(queue.drain_ready(now), vec![1u8, 1u8]);
+    }
+
+    #[test]
+    fn never_duplicate_schedules_exactly_one_release() {
+        let config = ImpairmentConfig::new(0.0, 0.0, Duration::ZERO, 0.0);
+        let mut queue = ImpairmentQueue::new(config, 8);
+        let now = Instant::now();
+
+        queue.submit(1u8, now, Duration::ZERO);
+        assert_eq!(queue.drain_ready(now), vec![1u8]);
+    }
+
+    #[test]
+    fn same_seed_produces_the_same_sequence_of_verdicts() {
+        let config = ImpairmentConfig::new(0.3, 5.0, Duration::from_millis(5), 0.2);
+        let now = Instant::now();
+
+        let run = |seed: u64| {
+            let mut queue = ImpairmentQueue::new(config, seed);
+            (0..50)
+                .map(|i| queue.submit(i, now, Duration::from_millis(10)))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(42), run(42));
+    }
+}
@@ -1,25 +1,85 @@
 //! Client input management with sequencing and change detection
 
-use macroquad::prelude::*;
+use crate::clock_sync::ClockSync;
+use crate::input_map::{Action, ActionState, InputMap};
+use crate::input_replay::{InputPlayer, InputRecorder};
+use log::{error, info};
 use shared::InputState;
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// How many of our own sent-but-unacknowledged inputs ride along as
+/// redundant copies in each outgoing packet — enough to survive a short
+/// burst of loss without leaning on retransmission.
+const UNACKED_BUFFER_CAPACITY: usize = 4;
+
+/// Whether `a` is newer than `b` in a wrapping `u32` sequence space,
+/// correctly handling the wraparound at `u32::MAX` (e.g. `0` is newer than
+/// `u32::MAX`). Assumes the two sequences are never more than half the
+/// space apart, which always holds here given how small `next_sequence`'s
+/// per-input increment is relative to `u32::MAX`.
+fn sequence_is_newer(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) > 0
+}
+
+/// Whether input this frame comes from the live keyboard or a loaded demo.
+/// Recording runs alongside `Live` rather than being a mode of its own,
+/// since a player can be recording exactly the live session they're playing.
+#[derive(Debug, PartialEq, Eq)]
+enum Mode {
+    Live,
+    Playback,
+}
 
 /// Manages user input collection and transformation into networked game inputs
 pub struct InputManager {
     next_sequence: u32,
     current_input: InputState,
     last_input_sent: Instant,
-
-    // Previous frame key states for edge detection
-    prev_key_1: bool,
-    prev_key_2: bool,
-    prev_key_3: bool,
-    prev_key_r: bool,
-    prev_key_g: bool,
+    last_update: Instant,
+
+    input_map: InputMap,
+    action_state: ActionState,
+    clock_sync: ClockSync,
+    /// Highest timestamp emitted so far, guarded against ever regressing
+    /// (see `next_timestamp`) even if the clock-sync offset or the
+    /// underlying clock itself steps backward.
+    last_timestamp: u64,
+    /// Counts consecutive calls where the raw candidate didn't advance past
+    /// `last_timestamp`, purely for diagnostics; reset once real time moves
+    /// past it again. Named after the UUIDv1 clock sequence this mirrors.
+    clock_seq: u32,
+
+    /// Ring buffer of our own sent-but-not-yet-acknowledged inputs, oldest
+    /// first, capped at `UNACKED_BUFFER_CAPACITY`. Bundled into each
+    /// outgoing packet as redundant copies — borrowing the reorder/dedup
+    /// idea from the rtpbin2 jitterbuffer, just applied sender-side — so a
+    /// single dropped datagram doesn't lose an input until the next change.
+    /// Pruned as the server acks sequences via `on_server_ack`.
+    unacked: VecDeque<InputState>,
+    /// Total inputs sent via `update`, and how many of those sends carried
+    /// at least one redundant copy. Exposed as `resend_rate` for the debug
+    /// graph.
+    sends_total: u64,
+    sends_with_redundancy: u64,
+
+    mode: Mode,
+    recorder: Option<InputRecorder>,
+    player: Option<InputPlayer>,
+    /// Set the frame playback finishes, cleared the next time it's read via
+    /// `take_playback_finished`, so callers can surface end-of-playback once.
+    playback_finished: bool,
 }
 
 impl InputManager {
     pub fn new() -> Self {
+        Self::with_input_map(InputMap::default_bindings())
+    }
+
+    /// Same as `new`, but with a caller-supplied `InputMap` — e.g. one
+    /// loaded from a player's rebind config via `InputMap::load`.
+    pub fn with_input_map(input_map: InputMap) -> Self {
         Self {
             next_sequence: 1,
             current_input: InputState {
@@ -30,54 +90,95 @@ impl InputManager {
                 jump: false,
             },
             last_input_sent: Instant::now(),
-            prev_key_1: false,
-            prev_key_2: false,
-            prev_key_3: false,
-            prev_key_r: false,
-            prev_key_g: false,
+            last_update: Instant::now(),
+            input_map,
+            action_state: ActionState::new(),
+            clock_sync: ClockSync::new(),
+            last_timestamp: 0,
+            clock_seq: 0,
+            unacked: VecDeque::new(),
+            sends_total: 0,
+            sends_with_redundancy: 0,
+            mode: Mode::Live,
+            recorder: None,
+            player: None,
+            playback_finished: false,
         }
     }
 
-    /// Updates input state and returns control events and optional network input
-    /// Returns: ((prediction_toggle, reconciliation_toggle, interpolation_toggle, reconnect, graph_toggle), input_to_send)
-    pub fn update(&mut self) -> ((bool, bool, bool, bool, bool), Option<InputState>) {
-        // Sample movement keys (support both WASD and arrow keys)
-        let left = is_key_down(KeyCode::A) || is_key_down(KeyCode::Left);
-        let right = is_key_down(KeyCode::D) || is_key_down(KeyCode::Right);
-        let jump = is_key_down(KeyCode::Space);
-
-        // Sample debug/control keys
-        let key_1 = is_key_down(KeyCode::Key1);
-        let key_2 = is_key_down(KeyCode::Key2);
-        let key_3 = is_key_down(KeyCode::Key3);
-        let key_r = is_key_down(KeyCode::R);
-        let key_g = is_key_down(KeyCode::G);
-
-        let mut toggles = (false, false, false, false, false);
-
-        // Detect key press events (current && !previous)
-        if key_1 && !self.prev_key_1 {
-            toggles.0 = true; // Toggle prediction
-        }
-        if key_2 && !self.prev_key_2 {
-            toggles.1 = true; // Toggle reconciliation
-        }
-        if key_3 && !self.prev_key_3 {
-            toggles.2 = true; // Toggle interpolation
+    /// Arms recording of every input sent from here on, until
+    /// `stop_recording_and_save` is called. A no-op if already recording.
+    pub fn start_recording(&mut self) {
+        if self.recorder.is_none() {
+            info!("Input recording armed");
+            self.recorder = Some(InputRecorder::new());
         }
-        if key_r && !self.prev_key_r {
-            toggles.3 = true; // Reconnect
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    /// Stops recording (if armed) and writes the captured session to `path`.
+    pub fn stop_recording_and_save(&mut self, path: &Path) -> std::io::Result<()> {
+        if let Some(recorder) = self.recorder.take() {
+            recorder.save(path)?;
         }
-        if key_g && !self.prev_key_g {
-            toggles.4 = true; // Toggle network graph
+        Ok(())
+    }
+
+    /// Switches to replaying `path` instead of sampling the live keyboard.
+    /// Playback inputs bypass `InputMap`/`ActionState` entirely, so toggles
+    /// don't fire from a demo — only the recorded movement does.
+    pub fn load_playback(&mut self, path: &Path) -> std::io::Result<()> {
+        self.player = Some(InputPlayer::load(path)?);
+        self.mode = Mode::Playback;
+        self.playback_finished = false;
+        Ok(())
+    }
+
+    pub fn is_playback(&self) -> bool {
+        self.mode == Mode::Playback
+    }
+
+    /// Returns `true` exactly once, the first call after playback finishes.
+    pub fn take_playback_finished(&mut self) -> bool {
+        std::mem::take(&mut self.playback_finished)
+    }
+
+    /// Updates input state and returns control events and optional network input
+    /// Returns: ((prediction_toggle, reconciliation_toggle, interpolation_toggle, reconnect, graph_toggle, recording_toggle, axis_scaling_toggle, fly_toggle, nametag_toggle, reconciliation_debug_toggle, impairment_toggle), input_to_send)
+    pub fn update(&mut self) -> ((bool, bool, bool, bool, bool, bool, bool, bool, bool, bool, bool), Option<InputState>) {
+        let dt = self.last_update.elapsed();
+        self.last_update = Instant::now();
+
+        if self.mode == Mode::Playback {
+            return (
+                (false, false, false, false, false, false, false, false, false, false, false),
+                self.update_playback(dt),
+            );
         }
 
-        // Update previous key states
-        self.prev_key_1 = key_1;
-        self.prev_key_2 = key_2;
-        self.prev_key_3 = key_3;
-        self.prev_key_r = key_r;
-        self.prev_key_g = key_g;
+        let currently_pressed = self.input_map.sample();
+        self.action_state.update(&currently_pressed);
+
+        let left = self.action_state.pressed(Action::MoveLeft);
+        let right = self.action_state.pressed(Action::MoveRight);
+        let jump = self.action_state.pressed(Action::Jump);
+
+        let toggles = (
+            self.action_state.just_pressed(Action::TogglePrediction),
+            self.action_state.just_pressed(Action::ToggleReconciliation),
+            self.action_state.just_pressed(Action::ToggleInterpolation),
+            self.action_state.just_pressed(Action::Reconnect),
+            self.action_state.just_pressed(Action::ToggleGraph),
+            self.action_state.just_pressed(Action::ToggleRecording),
+            self.action_state.just_pressed(Action::ToggleAxisScaling),
+            self.action_state.just_pressed(Action::ToggleFly),
+            self.action_state.just_pressed(Action::ToggleNametag),
+            self.action_state.just_pressed(Action::ToggleReconciliationDebug),
+            self.action_state.just_pressed(Action::ToggleImpairment),
+        );
 
         // Check if input state changed
         let input_changed = left != self.current_input.left
@@ -92,31 +193,164 @@ impl InputManager {
         if should_send {
             self.current_input = InputState {
                 sequence: self.next_sequence,
-                timestamp: Self::get_timestamp(),
+                timestamp: self.next_timestamp(),
                 left,
                 right,
                 jump,
             };
 
             input_to_send = Some(self.current_input.clone());
+            self.clock_sync.record_send(self.next_sequence);
             self.next_sequence += 1;
             self.last_input_sent = Instant::now();
+            self.push_unacked(self.current_input.clone());
+
+            if let Some(recorder) = self.recorder.as_mut() {
+                recorder.advance(dt);
+                recorder.record(self.current_input.clone());
+            }
         }
 
         (toggles, input_to_send)
     }
 
+    /// Pushes `input` onto the unacked ring buffer, evicting the oldest
+    /// entry once at `UNACKED_BUFFER_CAPACITY` — those are the least likely
+    /// to still help (either already delivered, or lost long enough ago
+    /// that resending them barely moves the odds).
+    fn push_unacked(&mut self, input: InputState) {
+        if self.unacked.len() >= UNACKED_BUFFER_CAPACITY {
+            self.unacked.pop_front();
+        }
+        self.unacked.push_back(input);
+
+        self.sends_total += 1;
+        if self.unacked.len() > 1 {
+            self.sends_with_redundancy += 1;
+        }
+    }
+
+    /// Previously-sent, still-unacked inputs to bundle alongside the one
+    /// `update` just returned, oldest first — everything in the ring buffer
+    /// except the input that was just pushed onto its back.
+    pub fn redundant_inputs(&self) -> Vec<InputState> {
+        let len = self.unacked.len();
+        if len <= 1 {
+            return Vec::new();
+        }
+        self.unacked.iter().take(len - 1).cloned().collect()
+    }
+
+    /// How many of our own sends are currently unacknowledged, for the
+    /// debug graph.
+    pub fn unacked_count(&self) -> usize {
+        self.unacked.len()
+    }
+
+    /// Fraction of sends so far that carried at least one redundant copy,
+    /// for the debug graph.
+    pub fn resend_rate(&self) -> f64 {
+        if self.sends_total == 0 {
+            0.0
+        } else {
+            self.sends_with_redundancy as f64 / self.sends_total as f64
+        }
+    }
+
+    /// Feeds a server echo of `acked_sequence` (its last-processed input)
+    /// alongside the server's own clock reading into the clock-sync
+    /// estimator, refining the offset used to stamp future inputs. Also
+    /// prunes the unacked ring buffer of anything at or before it.
+    ///
+    /// `server_receive_time_ms`, when present, is the server's wall-clock
+    /// receive time for `acked_sequence` (the NTP "T2"), letting
+    /// `ClockSync` run the full four-timestamp estimate instead of its
+    /// two-timestamp approximation.
+    pub fn on_server_ack(&mut self, acked_sequence: u32, server_time_ms: u64, server_receive_time_ms: Option<u64>) {
+        self.clock_sync
+            .on_server_ack(acked_sequence, server_time_ms, server_receive_time_ms);
+        self.unacked
+            .retain(|input| sequence_is_newer(input.sequence, acked_sequence));
+    }
+
+    /// Current estimated clock offset (ms) between us and the server, for
+    /// display on the debug network graph.
+    pub fn clock_offset_ms(&self) -> i64 {
+        self.clock_sync.offset_ms()
+    }
+
+    /// Most recent round-trip time (ms) a clock-sync echo was measured
+    /// over, if any echo has been accepted yet.
+    pub fn clock_rtt_ms(&self) -> Option<u64> {
+        self.clock_sync.last_rtt_ms()
+    }
+
+    /// Our best NTP-corrected estimate of the server's current clock (ms),
+    /// for comparing against buffered server tick timestamps during
+    /// interpolation without trusting the client's own system clock to
+    /// agree with the server's.
+    pub fn server_now_ms(&self) -> u64 {
+        self.clock_sync.server_now_ms()
+    }
+
+    /// Returns the next timestamp to stamp an outgoing input with, guarded
+    /// to never regress even if the underlying clock (or the clock-sync
+    /// offset riding on top of it) steps backward — an NTP slew, a
+    /// suspend/resume, or just the offset estimate correcting downward
+    /// faster than real time advances. The server sorts and reconciles
+    /// inputs by this value, so a regression would reorder them.
+    ///
+    /// Mirrors a UUIDv1 clock sequence: when the raw reading doesn't clear
+    /// `last_timestamp`, we hold steady and bump by one instead, counting
+    /// how many times in a row that's happened in `clock_seq` purely for
+    /// diagnostics. `clock_seq` resets once real time moves past
+    /// `last_timestamp` again.
+    fn next_timestamp(&mut self) -> u64 {
+        let candidate = self.clock_sync.server_now_ms();
+
+        if candidate > self.last_timestamp {
+            self.clock_seq = 0;
+            self.last_timestamp = candidate;
+        } else {
+            self.clock_seq += 1;
+            self.last_timestamp += 1;
+        }
+
+        self.last_timestamp
+    }
+
+    /// Pulls the next due input from the loaded demo, if any, re-stamping it
+    /// with our own sequence counter so downstream code can't tell it apart
+    /// from a live send. Falls back to `Live` once the demo runs out.
+    fn update_playback(&mut self, dt: Duration) -> Option<InputState> {
+        let input = match self.player.as_mut() {
+            Some(player) => player.poll(dt, self.next_sequence),
+            None => {
+                error!("update() called in Playback mode with no player loaded");
+                self.mode = Mode::Live;
+                return None;
+            }
+        };
+
+        if let Some(input) = &input {
+            self.current_input = input.clone();
+            self.next_sequence += 1;
+        }
+
+        if self.player.as_ref().is_some_and(InputPlayer::is_finished) {
+            self.mode = Mode::Live;
+            self.player = None;
+            self.playback_finished = true;
+            info!("Playback finished, returning to live input");
+        }
+
+        input
+    }
+
     /// Returns the current input state
     pub fn get_current_input(&self) -> &InputState {
         &self.current_input
     }
-
-    fn get_timestamp() -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or(Duration::from_secs(0))
-            .as_millis() as u64
-    }
 }
 
 impl Default for InputManager {
@@ -131,6 +365,12 @@ mod tests {
     use std::thread;
     use std::time::Duration;
 
+    /// A throwaway timestamp for tests that just need some `u64` to put in
+    /// a manually-constructed `InputState` fixture.
+    fn fixture_timestamp() -> u64 {
+        ClockSync::new().server_now_ms()
+    }
+
     #[test]
     fn test_input_manager_creation() {
         let input_manager = InputManager::new();
@@ -139,27 +379,27 @@ mod tests {
         assert!(!input_manager.current_input.left);
         assert!(!input_manager.current_input.right);
         assert!(!input_manager.current_input.jump);
-        assert!(!input_manager.prev_key_1);
-        assert!(!input_manager.prev_key_2);
-        assert!(!input_manager.prev_key_3);
-        assert!(!input_manager.prev_key_r);
-        assert!(!input_manager.prev_key_g);
+        for &action in &Action::ALL {
+            assert!(!input_manager.action_state.pressed(action));
+        }
     }
 
     #[test]
-    fn test_get_timestamp() {
-        let timestamp1 = InputManager::get_timestamp();
+    fn test_clock_sync_local_now_is_monotonic_across_a_sleep() {
+        let clock_sync = ClockSync::new();
+        let timestamp1 = clock_sync.local_now_ms();
         thread::sleep(Duration::from_millis(2));
-        let timestamp2 = InputManager::get_timestamp();
+        let timestamp2 = clock_sync.local_now_ms();
         assert!(timestamp2 > timestamp1);
         assert!(timestamp2 - timestamp1 >= 1); // At least 1ms difference
     }
 
     #[test]
-    fn test_timestamp_monotonic() {
+    fn test_clock_sync_local_now_is_monotonic_over_several_samples() {
+        let clock_sync = ClockSync::new();
         let timestamps: Vec<u64> = (0..10)
             .map(|_| {
-                let ts = InputManager::get_timestamp();
+                let ts = clock_sync.local_now_ms();
                 thread::sleep(Duration::from_millis(1));
                 ts
             })
@@ -193,7 +433,7 @@ mod tests {
         // Manually create an input to test sequence behavior
         input_manager.current_input = InputState {
             sequence: input_manager.next_sequence,
-            timestamp: InputManager::get_timestamp(),
+            timestamp: fixture_timestamp(),
             left: true,
             right: false,
             jump: false,
@@ -226,7 +466,7 @@ mod tests {
         // Simulate sequence increment
         input_manager.current_input = InputState {
             sequence: input_manager.next_sequence,
-            timestamp: InputManager::get_timestamp(),
+            timestamp: fixture_timestamp(),
             left: false,
             right: false,
             jump: false,
@@ -239,7 +479,7 @@ mod tests {
         // Test overflow
         input_manager.current_input = InputState {
             sequence: input_manager.next_sequence,
-            timestamp: InputManager::get_timestamp(),
+            timestamp: fixture_timestamp(),
             left: false,
             right: false,
             jump: false,
@@ -251,16 +491,157 @@ mod tests {
     }
 
     #[test]
-    fn test_timestamp_validity() {
-        let timestamp = InputManager::get_timestamp();
+    fn test_clock_offset_and_rtt_are_unset_before_any_server_ack() {
+        let input_manager = InputManager::new();
+        assert_eq!(input_manager.clock_offset_ms(), 0);
+        assert_eq!(input_manager.clock_rtt_ms(), None);
+    }
+
+    #[test]
+    fn test_on_server_ack_with_unknown_sequence_leaves_offset_unset() {
+        let mut input_manager = InputManager::new();
+        input_manager.on_server_ack(12345, fixture_timestamp(), None);
+        assert_eq!(input_manager.clock_offset_ms(), 0);
+        assert_eq!(input_manager.clock_rtt_ms(), None);
+    }
+
+    #[test]
+    fn test_next_timestamp_never_regresses_when_clock_steps_backward() {
+        let mut input_manager = InputManager::new();
+        let first = input_manager.next_timestamp();
+
+        // Simulate the clock (or the clock-sync offset riding on top of it)
+        // stepping backward by pretending we'd already emitted a timestamp
+        // far in the future.
+        input_manager.last_timestamp = first + 10_000;
+        // The first call may itself have been guarded (the clock can still
+        // read 0ms right after construction), so count from here.
+        let clock_seq_before = input_manager.clock_seq;
+
+        let second = input_manager.next_timestamp();
+        assert!(second > first + 10_000);
+        assert_eq!(second, first + 10_001);
+        assert_eq!(input_manager.clock_seq, clock_seq_before + 1);
+    }
+
+    #[test]
+    fn test_next_timestamp_resets_clock_seq_once_real_time_catches_up() {
+        let mut input_manager = InputManager::new();
+        input_manager.last_timestamp = u64::MAX - 5;
+
+        // Real clock reads far below this, so every call is guarded.
+        input_manager.next_timestamp();
+        input_manager.next_timestamp();
+        assert!(input_manager.clock_seq >= 2);
+
+        // Real time (eventually) moves past last_timestamp again; sleep so
+        // the clock reads strictly past 0ms.
+        input_manager.last_timestamp = 0;
+        thread::sleep(Duration::from_millis(2));
+        input_manager.next_timestamp();
+        assert_eq!(input_manager.clock_seq, 0);
+    }
 
-        // Should be a reasonable timestamp (after 2020)
-        let year_2020_ms = 1577836800000u64; // Jan 1, 2020
-        assert!(timestamp > year_2020_ms);
+    #[test]
+    fn test_next_timestamp_is_strictly_increasing_across_many_calls() {
+        let mut input_manager = InputManager::new();
+        let mut previous = input_manager.next_timestamp();
+        for _ in 0..50 {
+            let next = input_manager.next_timestamp();
+            assert!(next > previous, "{} should be > {}", next, previous);
+            previous = next;
+        }
+    }
 
-        // Should be before year 2100
-        let year_2100_ms = 4102444800000u64; // Jan 1, 2100
-        assert!(timestamp < year_2100_ms);
+    #[test]
+    fn test_sequence_is_newer_handles_wraparound() {
+        assert!(sequence_is_newer(2, 1));
+        assert!(!sequence_is_newer(1, 2));
+        assert!(!sequence_is_newer(5, 5));
+        assert!(sequence_is_newer(0, u32::MAX));
+        assert!(!sequence_is_newer(u32::MAX, 0));
+    }
+
+    #[test]
+    fn test_push_unacked_evicts_oldest_past_capacity() {
+        let mut input_manager = InputManager::new();
+        for sequence in 1..=(UNACKED_BUFFER_CAPACITY as u32 + 2) {
+            input_manager.push_unacked(InputState {
+                sequence,
+                timestamp: fixture_timestamp(),
+                left: false,
+                right: false,
+                jump: false,
+            });
+        }
+
+        assert_eq!(input_manager.unacked_count(), UNACKED_BUFFER_CAPACITY);
+        assert_eq!(input_manager.unacked.front().unwrap().sequence, 3);
+        assert_eq!(input_manager.unacked.back().unwrap().sequence, 6);
+    }
+
+    #[test]
+    fn test_redundant_inputs_excludes_just_pushed_entry() {
+        let mut input_manager = InputManager::new();
+        assert!(input_manager.redundant_inputs().is_empty());
+
+        input_manager.push_unacked(InputState {
+            sequence: 1,
+            timestamp: fixture_timestamp(),
+            left: false,
+            right: false,
+            jump: false,
+        });
+        assert!(input_manager.redundant_inputs().is_empty());
+
+        input_manager.push_unacked(InputState {
+            sequence: 2,
+            timestamp: fixture_timestamp(),
+            left: false,
+            right: false,
+            jump: false,
+        });
+        let redundant = input_manager.redundant_inputs();
+        assert_eq!(redundant.len(), 1);
+        assert_eq!(redundant[0].sequence, 1);
+    }
+
+    #[test]
+    fn test_resend_rate_tracks_fraction_of_sends_with_redundancy() {
+        let mut input_manager = InputManager::new();
+        assert_eq!(input_manager.resend_rate(), 0.0);
+
+        for sequence in 1..=4 {
+            input_manager.push_unacked(InputState {
+                sequence,
+                timestamp: fixture_timestamp(),
+                left: false,
+                right: false,
+                jump: false,
+            });
+        }
+
+        // First send had nothing to bundle; the other three did.
+        assert_eq!(input_manager.resend_rate(), 3.0 / 4.0);
+    }
+
+    #[test]
+    fn test_on_server_ack_prunes_unacked_up_to_and_including_acked_sequence() {
+        let mut input_manager = InputManager::new();
+        for sequence in 1..=4 {
+            input_manager.push_unacked(InputState {
+                sequence,
+                timestamp: fixture_timestamp(),
+                left: false,
+                right: false,
+                jump: false,
+            });
+        }
+
+        input_manager.on_server_ack(2, fixture_timestamp(), None);
+
+        let remaining: Vec<u32> = input_manager.unacked.iter().map(|i| i.sequence).collect();
+        assert_eq!(remaining, vec![3, 4]);
     }
 
     #[test]
@@ -268,19 +649,18 @@ mod tests {
         let mut input_manager = InputManager::new();
 
         // Test initial state
-        assert!(!input_manager.prev_key_1);
-        assert!(!input_manager.prev_key_2);
-        assert!(!input_manager.prev_key_3);
-        assert!(!input_manager.prev_key_r);
-        assert!(!input_manager.prev_key_g);
+        for &action in &Action::ALL {
+            assert!(!input_manager.action_state.pressed(action));
+        }
 
-        // Test state persistence after manual update
-        input_manager.prev_key_1 = true;
-        input_manager.prev_key_2 = true;
+        // Test state persistence after a manual frame update
+        input_manager
+            .action_state
+            .update(&[Action::TogglePrediction, Action::ToggleReconciliation].into_iter().collect());
 
-        assert!(input_manager.prev_key_1);
-        assert!(input_manager.prev_key_2);
-        assert!(!input_manager.prev_key_3);
+        assert!(input_manager.action_state.pressed(Action::TogglePrediction));
+        assert!(input_manager.action_state.pressed(Action::ToggleReconciliation));
+        assert!(!input_manager.action_state.pressed(Action::ToggleInterpolation));
     }
 
     #[test]
@@ -322,7 +702,7 @@ mod tests {
         // Set initial state
         input_manager.current_input = InputState {
             sequence: 1,
-            timestamp: InputManager::get_timestamp(),
+            timestamp: fixture_timestamp(),
             left: false,
             right: false,
             jump: false,
@@ -377,7 +757,7 @@ mod tests {
         for (left, right, jump) in combinations.iter() {
             let input_state = InputState {
                 sequence: 1,
-                timestamp: InputManager::get_timestamp(),
+                timestamp: fixture_timestamp(),
                 left: *left,
                 right: *right,
                 jump: *jump,
@@ -394,23 +774,79 @@ mod tests {
     fn test_toggle_state_representation() {
         // Test that toggle states can represent all possible combinations
         let toggle_combinations = [
-            (false, false, false, false, false),
-            (true, false, false, false, false),
-            (false, true, false, false, false),
-            (false, false, true, false, false),
-            (false, false, false, true, false),
-            (false, false, false, false, true),
-            (true, true, true, true, true),
+            (false, false, false, false, false, false, false, false, false, false, false),
+            (true, false, false, false, false, false, false, false, false, false, false),
+            (false, true, false, false, false, false, false, false, false, false, false),
+            (false, false, true, false, false, false, false, false, false, false, false),
+            (false, false, false, true, false, false, false, false, false, false, false),
+            (false, false, false, false, true, false, false, false, false, false, false),
+            (false, false, false, false, false, true, false, false, false, false, false),
+            (false, false, false, false, false, false, true, false, false, false, false),
+            (false, false, false, false, false, false, false, true, false, false, false),
+            (false, false, false, false, false, false, false, false, true, false, false),
+            (false, false, false, false, false, false, false, false, false, true, false),
+            (false, false, false, false, false, false, false, false, false, false, true),
+            (true, true, true, true, true, true, true, true, true, true, true),
         ];
 
-        for (pred, recon, interp, reconnect, graph) in toggle_combinations.iter() {
-            let toggles = (*pred, *recon, *interp, *reconnect, *graph);
+        for (pred, recon, interp, reconnect, graph, recording, axis_scaling, fly, nametag, recon_debug, impairment) in
+            toggle_combinations.iter()
+        {
+            let toggles = (
+                *pred,
+                *recon,
+                *interp,
+                *reconnect,
+                *graph,
+                *recording,
+                *axis_scaling,
+                *fly,
+                *nametag,
+                *recon_debug,
+                *impairment,
+            );
 
             assert_eq!(toggles.0, *pred);
             assert_eq!(toggles.1, *recon);
             assert_eq!(toggles.2, *interp);
             assert_eq!(toggles.3, *reconnect);
             assert_eq!(toggles.4, *graph);
+            assert_eq!(toggles.5, *recording);
+            assert_eq!(toggles.6, *axis_scaling);
+            assert_eq!(toggles.7, *fly);
+            assert_eq!(toggles.8, *nametag);
+            assert_eq!(toggles.9, *recon_debug);
+            assert_eq!(toggles.10, *impairment);
         }
     }
+
+    #[test]
+    fn test_start_recording_is_idempotent() {
+        let mut input_manager = InputManager::new();
+        assert!(!input_manager.is_recording());
+        input_manager.start_recording();
+        assert!(input_manager.is_recording());
+        input_manager.start_recording();
+        assert!(input_manager.is_recording());
+    }
+
+    #[test]
+    fn test_load_playback_switches_mode_and_finishes_when_demo_is_empty() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("input_manager_test_empty_{}.demo", std::process::id()));
+        let empty_events: Vec<(Duration, InputState)> = Vec::new();
+        std::fs::write(&path, bincode::serialize(&empty_events).unwrap()).unwrap();
+
+        let mut input_manager = InputManager::new();
+        input_manager.load_playback(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert!(input_manager.is_playback());
+        let (_, input) = input_manager.update();
+        assert!(input.is_none());
+        assert!(!input_manager.is_playback());
+        assert!(input_manager.take_playback_finished());
+        // Only reported once.
+        assert!(!input_manager.take_playback_finished());
+    }
 }
@@ -0,0 +1,393 @@
+//! Data-driven action-binding layer.
+//!
+//! Maps abstract `Action`s to physical key bindings via `InputMap`, and
+//! tracks pressed/just-pressed/just-released edges per action via
+//! `ActionState`. Replaces the old hardcoded `KeyCode` checks and
+//! `prev_key_*` fields in `InputManager` with something rebindable and
+//! testable without mocking macroquad's keyboard.
+
+use log::warn;
+use macroquad::prelude::{is_key_down, KeyCode};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// An abstract input action, independent of whatever physical key or
+/// button currently triggers it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    TogglePrediction,
+    ToggleReconciliation,
+    ToggleInterpolation,
+    Reconnect,
+    ToggleGraph,
+    ToggleRecording,
+    ToggleAxisScaling,
+    ToggleFly,
+    ToggleNametag,
+    ToggleReconciliationDebug,
+    ToggleImpairment,
+}
+
+impl Action {
+    pub const ALL: [Action; 14] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Jump,
+        Action::TogglePrediction,
+        Action::ToggleReconciliation,
+        Action::ToggleInterpolation,
+        Action::Reconnect,
+        Action::ToggleGraph,
+        Action::ToggleRecording,
+        Action::ToggleAxisScaling,
+        Action::ToggleFly,
+        Action::ToggleNametag,
+        Action::ToggleReconciliationDebug,
+        Action::ToggleImpairment,
+    ];
+
+    fn config_name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::Jump => "jump",
+            Action::TogglePrediction => "toggle_prediction",
+            Action::ToggleReconciliation => "toggle_reconciliation",
+            Action::ToggleInterpolation => "toggle_interpolation",
+            Action::Reconnect => "reconnect",
+            Action::ToggleGraph => "toggle_graph",
+            Action::ToggleRecording => "toggle_recording",
+            Action::ToggleAxisScaling => "toggle_axis_scaling",
+            Action::ToggleFly => "toggle_fly",
+            Action::ToggleNametag => "toggle_nametag",
+            Action::ToggleReconciliationDebug => "toggle_reconciliation_debug",
+            Action::ToggleImpairment => "toggle_impairment",
+        }
+    }
+
+    fn from_config_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.config_name() == name)
+    }
+}
+
+/// A physical input this action can be bound to. Only keyboard bindings
+/// exist today; gamepad buttons/axes can join this enum later without
+/// touching `InputMap`'s or `ActionState`'s public surface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Binding {
+    Key(KeyCode),
+}
+
+impl Binding {
+    fn is_down(self) -> bool {
+        match self {
+            Binding::Key(key) => is_key_down(key),
+        }
+    }
+
+    fn config_name(self) -> &'static str {
+        match self {
+            Binding::Key(key) => keycode_name(key),
+        }
+    }
+}
+
+/// Names recognized in a rebind config file, spelled like
+/// `macroquad::prelude::KeyCode`'s variant names so the file stays
+/// human-readable. Only the keys listed here (and in `keycode_from_name`)
+/// have a name; any other key is written as "Unknown".
+fn keycode_name(key: KeyCode) -> &'static str {
+    match key {
+        KeyCode::A => "A",
+        KeyCode::D => "D",
+        KeyCode::R => "R",
+        KeyCode::G => "G",
+        KeyCode::L => "L",
+        KeyCode::Left => "Left",
+        KeyCode::Right => "Right",
+        KeyCode::Space => "Space",
+        KeyCode::Key1 => "Key1",
+        KeyCode::Key2 => "Key2",
+        KeyCode::Key3 => "Key3",
+        KeyCode::Key4 => "Key4",
+        KeyCode::Key5 => "Key5",
+        KeyCode::P => "P",
+        KeyCode::F => "F",
+        KeyCode::N => "N",
+        _ => "Unknown",
+    }
+}
+
+fn keycode_from_name(name: &str) -> Option<KeyCode> {
+    match name {
+        "A" => Some(KeyCode::A),
+        "D" => Some(KeyCode::D),
+        "R" => Some(KeyCode::R),
+        "G" => Some(KeyCode::G),
+        "L" => Some(KeyCode::L),
+        "Left" => Some(KeyCode::Left),
+        "Right" => Some(KeyCode::Right),
+        "Space" => Some(KeyCode::Space),
+        "Key1" => Some(KeyCode::Key1),
+        "Key2" => Some(KeyCode::Key2),
+        "Key3" => Some(KeyCode::Key3),
+        "Key4" => Some(KeyCode::Key4),
+        "Key5" => Some(KeyCode::Key5),
+        "P" => Some(KeyCode::P),
+        "F" => Some(KeyCode::F),
+        "N" => Some(KeyCode::N),
+        _ => None,
+    }
+}
+
+/// Maps each `Action` to one or more physical bindings, any of which
+/// activates it. Rebindable at runtime via `bind`, or loaded wholesale from
+/// a config file via `load`.
+#[derive(Debug, Clone)]
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl InputMap {
+    /// The bindings `InputManager` hardcoded before this module existed.
+    pub fn default_bindings() -> Self {
+        let mut bindings = HashMap::new();
+        bindings.insert(Action::MoveLeft, vec![Binding::Key(KeyCode::A), Binding::Key(KeyCode::Left)]);
+        bindings.insert(Action::MoveRight, vec![Binding::Key(KeyCode::D), Binding::Key(KeyCode::Right)]);
+        bindings.insert(Action::Jump, vec![Binding::Key(KeyCode::Space)]);
+        bindings.insert(Action::TogglePrediction, vec![Binding::Key(KeyCode::Key1)]);
+        bindings.insert(Action::ToggleReconciliation, vec![Binding::Key(KeyCode::Key2)]);
+        bindings.insert(Action::ToggleInterpolation, vec![Binding::Key(KeyCode::Key3)]);
+        bindings.insert(Action::Reconnect, vec![Binding::Key(KeyCode::R)]);
+        bindings.insert(Action::ToggleGraph, vec![Binding::Key(KeyCode::G)]);
+        bindings.insert(Action::ToggleRecording, vec![Binding::Key(KeyCode::P)]);
+        bindings.insert(Action::ToggleAxisScaling, vec![Binding::Key(KeyCode::L)]);
+        bindings.insert(Action::ToggleFly, vec![Binding::Key(KeyCode::F)]);
+        bindings.insert(Action::ToggleNametag, vec![Binding::Key(KeyCode::N)]);
+        bindings.insert(Action::ToggleReconciliationDebug, vec![Binding::Key(KeyCode::Key4)]);
+        bindings.insert(Action::ToggleImpairment, vec![Binding::Key(KeyCode::Key5)]);
+        Self { bindings }
+    }
+
+    pub fn bindings_for(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub fn bind(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+
+    /// Samples the real keyboard and returns every action with at least one
+    /// bound key currently held down.
+    pub fn sample(&self) -> HashSet<Action> {
+        Action::ALL
+            .into_iter()
+            .filter(|&action| self.bindings_for(action).iter().any(|binding| binding.is_down()))
+            .collect()
+    }
+
+    /// Starts from `default_bindings` and applies rebinds from a simple
+    /// `action=KEY1,KEY2` per-line config file; actions the file doesn't
+    /// mention keep their defaults. Malformed lines, unknown actions, and
+    /// unknown key names are logged and skipped rather than failing the
+    /// whole load, since a typo in a hand-edited rebind file shouldn't lock
+    /// a player out of the game.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut map = Self::default_bindings();
+        let contents = fs::read_to_string(path)?;
+
+        for line in contents.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let Some((name, keys)) = line.split_once('=') else {
+                warn!("Skipping malformed input-binding line: {}", line);
+                continue;
+            };
+
+            let Some(action) = Action::from_config_name(name.trim()) else {
+                warn!("Unknown action in input-binding file: {}", name.trim());
+                continue;
+            };
+
+            let bindings: Vec<Binding> = keys
+                .split(',')
+                .filter_map(|key_name| keycode_from_name(key_name.trim()))
+                .map(Binding::Key)
+                .collect();
+
+            if bindings.is_empty() {
+                warn!("No valid key bindings for action {} in input-binding file", name.trim());
+                continue;
+            }
+
+            map.bind(action, bindings);
+        }
+
+        Ok(map)
+    }
+
+    /// Serializes the current bindings in the same `action=KEY1,KEY2` format
+    /// `load` reads, so a rebind UI can round-trip a player's layout.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut contents = String::new();
+        for &action in &Action::ALL {
+            let names: Vec<&str> = self.bindings_for(action).iter().map(|b| b.config_name()).collect();
+            contents.push_str(&format!("{}={}\n", action.config_name(), names.join(",")));
+        }
+        fs::write(path, contents)
+    }
+}
+
+impl Default for InputMap {
+    fn default() -> Self {
+        Self::default_bindings()
+    }
+}
+
+/// Tracks pressed/just-pressed/just-released edges per `Action`, derived
+/// entirely from each frame's snapshot of currently-active actions rather
+/// than polling raw keys directly. Any action missing from that snapshot is
+/// treated as released this frame even if it was held last frame — without
+/// this, an action whose only bound key got released while a differently
+/// *named* key happened to still read as down elsewhere could stay stuck
+/// "pressed".
+#[derive(Debug, Default)]
+pub struct ActionState {
+    current: HashSet<Action>,
+    previous: HashSet<Action>,
+}
+
+impl ActionState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, currently_pressed: &HashSet<Action>) {
+        self.previous = std::mem::replace(&mut self.current, currently_pressed.clone());
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.current.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.current.contains(&action) && !self.previous.contains(&action)
+    }
+
+    pub fn just_released(&self, action: Action) -> bool {
+        !self.current.contains(&action) && self.previous.contains(&action)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pressed(actions: &[Action]) -> HashSet<Action> {
+        actions.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_action_state_starts_with_nothing_pressed() {
+        let state = ActionState::new();
+        for &action in &Action::ALL {
+            assert!(!state.pressed(action));
+            assert!(!state.just_pressed(action));
+            assert!(!state.just_released(action));
+        }
+    }
+
+    #[test]
+    fn test_action_state_reports_just_pressed_on_first_frame_held() {
+        let mut state = ActionState::new();
+        state.update(&pressed(&[Action::Jump]));
+        assert!(state.pressed(Action::Jump));
+        assert!(state.just_pressed(Action::Jump));
+        assert!(!state.just_released(Action::Jump));
+    }
+
+    #[test]
+    fn test_action_state_does_not_repeat_just_pressed_while_held() {
+        let mut state = ActionState::new();
+        state.update(&pressed(&[Action::Jump]));
+        state.update(&pressed(&[Action::Jump]));
+        assert!(state.pressed(Action::Jump));
+        assert!(!state.just_pressed(Action::Jump));
+    }
+
+    #[test]
+    fn test_action_state_releases_an_action_absent_from_the_current_frame() {
+        let mut state = ActionState::new();
+        state.update(&pressed(&[Action::Jump]));
+        state.update(&pressed(&[]));
+        assert!(!state.pressed(Action::Jump));
+        assert!(state.just_released(Action::Jump));
+    }
+
+    #[test]
+    fn test_default_bindings_cover_every_action() {
+        let map = InputMap::default_bindings();
+        for &action in &Action::ALL {
+            assert!(!map.bindings_for(action).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_rebind_replaces_only_the_named_action() {
+        let mut map = InputMap::default_bindings();
+        map.bind(Action::Jump, vec![Binding::Key(KeyCode::W)]);
+        assert_eq!(map.bindings_for(Action::Jump), &[Binding::Key(KeyCode::W)]);
+        assert_eq!(map.bindings_for(Action::MoveLeft), InputMap::default_bindings().bindings_for(Action::MoveLeft));
+    }
+
+    #[test]
+    fn test_load_applies_rebind_and_keeps_other_defaults() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("input_map_test_{}.cfg", std::process::id()));
+        fs::write(&path, "jump=Key1\n# a comment\n\nmove_left=D\n").unwrap();
+
+        let map = InputMap::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(map.bindings_for(Action::Jump), &[Binding::Key(KeyCode::Key1)]);
+        assert_eq!(map.bindings_for(Action::MoveLeft), &[Binding::Key(KeyCode::D)]);
+        // Untouched action keeps its default.
+        assert_eq!(map.bindings_for(Action::Reconnect), &[Binding::Key(KeyCode::R)]);
+    }
+
+    #[test]
+    fn test_load_skips_unknown_action_and_key_names() {
+        let mut path = std::env::temp_dir();
+        path.push(format!("input_map_test_unknown_{}.cfg", std::process::id()));
+        fs::write(&path, "not_a_real_action=A\njump=NotAKey\n").unwrap();
+
+        let map = InputMap::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        // Both lines are skipped, so jump keeps its default binding.
+        assert_eq!(map.bindings_for(Action::Jump), &[Binding::Key(KeyCode::Space)]);
+    }
+
+    #[test]
+    fn test_save_and_load_round_trips_a_rebind() {
+        let mut map = InputMap::default_bindings();
+        map.bind(Action::Jump, vec![Binding::Key(KeyCode::Key1)]);
+
+        let mut path = std::env::temp_dir();
+        path.push(format!("input_map_test_roundtrip_{}.cfg", std::process::id()));
+        map.save(&path).unwrap();
+        let loaded = InputMap::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(loaded.bindings_for(Action::Jump), &[Binding::Key(KeyCode::Key1)]);
+        assert_eq!(loaded.bindings_for(Action::MoveRight), map.bindings_for(Action::MoveRight));
+    }
+}
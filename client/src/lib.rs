@@ -1,7 +1,16 @@
 //! Game client library for networked multiplayer
 
+pub mod clock_sync;
+pub mod congestion;
+pub mod effects;
 pub mod game;
+pub mod impairment;
 pub mod input;
+pub mod input_map;
+pub mod input_replay;
+pub mod nat_traversal;
 pub mod network;
 pub mod network_graph;
+pub mod ping_tracker;
+pub mod render_replay;
 pub mod rendering;
\ No newline at end of file
@@ -1,14 +1,24 @@
 //! Client application entry point
 
+mod clock_sync;
+mod congestion;
+mod effects;
 mod game;
+mod impairment;
 mod input;
+mod input_map;
+mod input_replay;
+mod nat_traversal;
 mod network;
 mod network_graph;
+mod ping_tracker;
+mod render_replay;
 mod rendering;
 
 use clap::Parser;
 use log::info;
 use macroquad::prelude::*;
+use network_graph::AxisScaling;
 
 /// Command-line arguments for client configuration
 #[derive(Parser, Debug)]
@@ -21,6 +31,55 @@ struct Args {
     /// Artificial latency in milliseconds for netcode testing
     #[arg(short = 'l', long, default_value = "0")]
     fake_ping: u64,
+
+    /// Path to a `.demo` file to replay instead of sampling live input
+    #[arg(long)]
+    demo: Option<std::path::PathBuf>,
+
+    /// Path to a `.frames` recording to scrub through instead of rendering
+    /// live network state. Space toggles play/pause, `.` steps one frame.
+    #[arg(long)]
+    render_replay: Option<std::path::PathBuf>,
+
+    /// Value-axis scaling for the ping and bandwidth graphs
+    #[arg(long, value_enum, default_value = "linear")]
+    axis_scaling: AxisScaling,
+
+    /// Probability (0.0-1.0) that a queued artificial-latency packet is
+    /// dropped instead of delivered, for stress-testing netcode under loss
+    #[arg(long, default_value = "0.0")]
+    packet_loss: f64,
+
+    /// Standard deviation (ms) of Gaussian jitter applied on top of
+    /// `fake_ping`'s base delay
+    #[arg(long, default_value = "0.0")]
+    jitter_ms: f64,
+
+    /// Width (ms) of an additional random delay applied to every surviving
+    /// packet, wide enough to let jittered packets arrive out of order
+    #[arg(long, default_value = "0")]
+    reorder_window_ms: u64,
+
+    /// Probability (0.0-1.0) that a surviving artificial-latency packet is
+    /// scheduled for release twice instead of once, for stress-testing
+    /// netcode against duplicate UDP delivery
+    #[arg(long, default_value = "0.0")]
+    duplication: f64,
+
+    /// Outbound silence (ms) tolerated before a tiny heartbeat is sent to
+    /// keep a NAT's UDP mapping from expiring. Unset falls back to a third of
+    /// the idle timeout negotiated at handshake (the full timeout on a
+    /// loopback server); with no negotiated timeout either, no keepalive is
+    /// sent.
+    #[arg(long)]
+    keep_alive_ms: Option<u64>,
+
+    /// Inbound silence (seconds) tolerated before forcing a full reconnect.
+    /// Unset falls back to the idle timeout negotiated at handshake; with no
+    /// negotiated timeout either, reconnection is left to the manual `R`
+    /// toggle.
+    #[arg(long)]
+    session_timeout_secs: Option<u64>,
 }
 
 /// Configures the game window
@@ -50,12 +109,51 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     if args.fake_ping > 0 {
         info!("Simulating {}ms latency", args.fake_ping);
     }
+    if args.packet_loss > 0.0 || args.jitter_ms > 0.0 || args.reorder_window_ms > 0 || args.duplication > 0.0 {
+        info!(
+            "Simulating impairment: {:.0}% loss, {:.1}ms jitter stddev, {}ms reorder window, {:.0}% duplication",
+            args.packet_loss * 100.0,
+            args.jitter_ms,
+            args.reorder_window_ms,
+            args.duplication * 100.0
+        );
+    }
+    if let Some(keep_alive_ms) = args.keep_alive_ms {
+        info!("NAT keepalive: heartbeat after {}ms of outbound silence", keep_alive_ms);
+    }
+    if let Some(session_timeout_secs) = args.session_timeout_secs {
+        info!("Session timeout: forcing reconnect after {}s of inbound silence", session_timeout_secs);
+    }
     info!("Controls: A/D to move, Space to jump");
     info!("Press 1/2/3 to toggle Prediction/Reconciliation/Interpolation");
     info!("Press G to toggle Network Graph");
+    info!("Press L to toggle linear/log axis scaling");
     info!("Press R to reconnect to server");
+    info!("Press 5 to toggle packet-loss/jitter/reorder impairment");
+
+    let mut client = network::Client::new(
+        &args.server,
+        args.fake_ping,
+        args.axis_scaling,
+        args.packet_loss,
+        args.jitter_ms,
+        args.reorder_window_ms,
+        args.duplication,
+        args.keep_alive_ms.map(std::time::Duration::from_millis),
+        args.session_timeout_secs.map(std::time::Duration::from_secs),
+    )
+    .await?;
+
+    if let Some(demo_path) = &args.demo {
+        info!("Replaying demo: {}", demo_path.display());
+        client.load_demo(demo_path)?;
+    }
+
+    if let Some(render_replay_path) = &args.render_replay {
+        info!("Scrubbing render replay: {}", render_replay_path.display());
+        client.load_render_replay(render_replay_path)?;
+    }
 
-    let mut client = network::Client::new(&args.server, args.fake_ping).await?;
     client.run().await?;
 
     Ok(())
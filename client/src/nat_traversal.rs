@@ -0,0 +1,228 @@
+//! Peer rendezvous and hole-punching, plus the adaptive keepalive that keeps
+//! the resulting NAT mapping open.
+//!
+//! `Client::new` resolves a server address and connects to it directly,
+//! which works fine against the authoritative server (always reachable on a
+//! public port) but not for a direct client-to-client path where both sides
+//! sit behind NATs. This tracks that handshake: both sides learn each
+//! other's candidate addresses via `Packet::EndpointReport`/`PeerEndpoints`
+//! relayed through a rendezvous point (the game server), send simultaneous
+//! probes to every candidate, and keep whichever one replies first. The
+//! mutually reported NAT mapping timeouts also drive an adaptive keepalive
+//! so the punched mapping doesn't expire once established.
+
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Keepalive interval used before any NAT mapping timeout has been reported
+/// by either side — most home routers hold a UDP mapping open far longer
+/// than this, so it's a conservative default rather than a measured value.
+const DEFAULT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(5 * 60);
+/// Interval used once a short mapping timeout has been detected, close to
+/// the ~30-60s timeouts typical of consumer NAT tables.
+const SHORT_NAT_KEEPALIVE_INTERVAL: Duration = Duration::from_secs(25);
+/// A reported NAT timeout below this is treated as "detected" for the
+/// purposes of switching to the shorter keepalive interval.
+const SHORT_NAT_THRESHOLD: Duration = Duration::from_secs(120);
+/// How many missed keepalive intervals without any traffic before a peer is
+/// declared dead.
+const DEAD_PEER_INTERVAL_MULTIPLE: u32 = 4;
+
+/// Computes the keepalive interval from each side's self-reported NAT
+/// mapping timeout: half the smaller of the two, collapsing to
+/// `SHORT_NAT_KEEPALIVE_INTERVAL` once either side reports a timeout under
+/// `SHORT_NAT_THRESHOLD`, otherwise the conservative default.
+pub fn adaptive_keepalive_interval(
+    local_nat_timeout: Option<Duration>,
+    peer_nat_timeout: Option<Duration>,
+) -> Duration {
+    let smaller = match (local_nat_timeout, peer_nat_timeout) {
+        (Some(a), Some(b)) => a.min(b),
+        (Some(a), None) | (None, Some(a)) => a,
+        (None, None) => return DEFAULT_KEEPALIVE_INTERVAL,
+    };
+
+    if smaller < SHORT_NAT_THRESHOLD {
+        SHORT_NAT_KEEPALIVE_INTERVAL
+    } else {
+        (smaller / 2).max(SHORT_NAT_KEEPALIVE_INTERVAL)
+    }
+}
+
+/// State of one simultaneous hole-punch attempt against the candidate
+/// addresses a rendezvous point relayed for a peer.
+pub struct HolePunch {
+    candidates: Vec<SocketAddr>,
+    confirmed: Option<SocketAddr>,
+}
+
+impl HolePunch {
+    pub fn new(candidates: Vec<SocketAddr>) -> Self {
+        Self {
+            candidates,
+            confirmed: None,
+        }
+    }
+
+    /// Candidates a probe should still be sent to. Empty once an address is
+    /// confirmed, since there's nothing left to punch toward.
+    pub fn probe_targets(&self) -> &[SocketAddr] {
+        if self.confirmed.is_some() {
+            &[]
+        } else {
+            &self.candidates
+        }
+    }
+
+    /// Records a probe reply from `addr`. The first reply wins; later ones
+    /// (e.g. a slower duplicate path arriving after the fastest) are
+    /// ignored, and a reply from an address that was never a candidate is
+    /// ignored too.
+    pub fn note_reply_from(&mut self, addr: SocketAddr) {
+        if self.confirmed.is_none() && self.candidates.contains(&addr) {
+            self.confirmed = Some(addr);
+        }
+    }
+
+    pub fn confirmed_addr(&self) -> Option<SocketAddr> {
+        self.confirmed
+    }
+}
+
+/// Tracks a punched connection's liveness: when the next keepalive probe is
+/// due, and whether the peer should be considered dead from silence.
+pub struct KeepaliveMonitor {
+    interval: Duration,
+    last_sent: Instant,
+    last_received: Instant,
+}
+
+impl KeepaliveMonitor {
+    pub fn new(interval: Duration) -> Self {
+        let now = Instant::now();
+        Self {
+            interval,
+            last_sent: now,
+            last_received: now,
+        }
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Whether it's time to send another keepalive probe.
+    pub fn due(&self) -> bool {
+        self.last_sent.elapsed() >= self.interval
+    }
+
+    pub fn record_sent(&mut self) {
+        self.last_sent = Instant::now();
+    }
+
+    pub fn record_received(&mut self) {
+        self.last_received = Instant::now();
+    }
+
+    /// True once `DEAD_PEER_INTERVAL_MULTIPLE` keepalive intervals have
+    /// passed with no traffic at all from the peer.
+    pub fn is_dead(&self) -> bool {
+        self.last_received.elapsed() >= self.interval * DEAD_PEER_INTERVAL_MULTIPLE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn addr(port: u16) -> SocketAddr {
+        format!("127.0.0.1:{}", port).parse().unwrap()
+    }
+
+    #[test]
+    fn test_adaptive_keepalive_defaults_conservatively_with_no_reports() {
+        assert_eq!(
+            adaptive_keepalive_interval(None, None),
+            DEFAULT_KEEPALIVE_INTERVAL
+        );
+    }
+
+    #[test]
+    fn test_adaptive_keepalive_collapses_to_short_interval_when_nat_detected() {
+        let interval = adaptive_keepalive_interval(
+            Some(Duration::from_secs(60)),
+            Some(Duration::from_secs(300)),
+        );
+        assert_eq!(interval, SHORT_NAT_KEEPALIVE_INTERVAL);
+    }
+
+    #[test]
+    fn test_adaptive_keepalive_uses_half_the_smaller_reported_timeout() {
+        let interval = adaptive_keepalive_interval(
+            Some(Duration::from_secs(200)),
+            Some(Duration::from_secs(400)),
+        );
+        assert_eq!(interval, Duration::from_secs(100));
+    }
+
+    #[test]
+    fn test_adaptive_keepalive_uses_whichever_side_reported_a_timeout() {
+        let interval = adaptive_keepalive_interval(None, Some(Duration::from_secs(40)));
+        assert_eq!(interval, SHORT_NAT_KEEPALIVE_INTERVAL);
+    }
+
+    #[test]
+    fn test_hole_punch_confirms_first_replying_candidate() {
+        let mut punch = HolePunch::new(vec![addr(1001), addr(1002)]);
+        assert_eq!(punch.probe_targets().len(), 2);
+
+        punch.note_reply_from(addr(1002));
+        assert_eq!(punch.confirmed_addr(), Some(addr(1002)));
+        assert!(punch.probe_targets().is_empty());
+    }
+
+    #[test]
+    fn test_hole_punch_ignores_a_later_reply_once_confirmed() {
+        let mut punch = HolePunch::new(vec![addr(1001), addr(1002)]);
+        punch.note_reply_from(addr(1001));
+        punch.note_reply_from(addr(1002));
+        assert_eq!(punch.confirmed_addr(), Some(addr(1001)));
+    }
+
+    #[test]
+    fn test_hole_punch_ignores_a_reply_from_an_address_not_in_the_candidate_list() {
+        let mut punch = HolePunch::new(vec![addr(1001)]);
+        punch.note_reply_from(addr(9999));
+        assert!(punch.confirmed_addr().is_none());
+    }
+
+    #[test]
+    fn test_keepalive_monitor_is_due_once_interval_elapses() {
+        let monitor = KeepaliveMonitor::new(Duration::from_millis(10));
+        thread::sleep(Duration::from_millis(15));
+        assert!(monitor.due());
+    }
+
+    #[test]
+    fn test_keepalive_monitor_not_due_right_after_sending() {
+        let mut monitor = KeepaliveMonitor::new(Duration::from_millis(50));
+        monitor.record_sent();
+        assert!(!monitor.due());
+    }
+
+    #[test]
+    fn test_keepalive_monitor_declares_peer_dead_after_missed_intervals() {
+        let mut monitor = KeepaliveMonitor::new(Duration::from_millis(10));
+        monitor.record_received();
+        thread::sleep(Duration::from_millis(50));
+        assert!(monitor.is_dead());
+    }
+
+    #[test]
+    fn test_keepalive_monitor_not_dead_right_after_receiving_traffic() {
+        let mut monitor = KeepaliveMonitor::new(Duration::from_secs(60));
+        monitor.record_received();
+        assert!(!monitor.is_dead());
+    }
+}
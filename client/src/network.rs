@@ -1,23 +1,47 @@
 //! Client-side network implementation with artificial latency simulation
 
+use crate::congestion::{Algorithm, CongestionController};
 use crate::game::{ClientGameState, ServerStateConfig};
+use crate::impairment::{ImpairmentConfig, ImpairmentQueue};
 use crate::input::InputManager;
-use crate::network_graph::NetworkGraph;
+use crate::network_graph::{AxisScaling, NetworkGraph};
+use crate::render_replay::{FramePlayer, FrameRecorder};
 use crate::rendering::{RenderConfig, Renderer};
 use bincode::{deserialize, serialize};
 use log::{error, info, warn};
 use macroquad::prelude::*;
 use shared::{InputState, Packet};
-use std::collections::VecDeque;
+use std::collections::{HashMap, VecDeque};
 use std::net::{SocketAddr, UdpSocket};
+use std::path::Path;
 use std::time::{Duration, Instant};
 
+/// Range of wire-protocol versions this client can speak. `Connect` advertises
+/// this range and the server replies with the single version it negotiated.
+const CLIENT_MIN_SUPPORTED_VERSION: u32 = 1;
+const CLIENT_MAX_SUPPORTED_VERSION: u32 = 1;
+/// How long (seconds) we ask the server to wait before considering us dead.
+/// Higher than the server's bare-minimum default so a brief connection hiccup
+/// doesn't drop us, while staying well under the server's negotiated ceiling.
+const CLIENT_REQUESTED_TIMEOUT_SECS: u32 = 15;
+/// Number of recent RTT samples `min_rtt_ms` latches its minimum over.
+const RTT_WINDOW_SIZE: usize = 10;
+
 /// Main client managing network communication and game state
 pub struct Client {
     // Network components
     socket: UdpSocket,
     server_addr: SocketAddr,
     client_id: Option<u32>,
+    /// Token from the last `Connected` response. Presenting it on a future
+    /// `Connect` lets the server rebind this session instead of starting fresh
+    /// if a reconnect lands within the server's grace period.
+    resume_token: Option<u64>,
+    /// Sealed `ConnectionToken` from the last `Connected` response, present
+    /// iff the server has connect-token enforcement enabled. Echoed back
+    /// alongside `resume_token` on a reconnect; see
+    /// `shared::Packet::Connect::connect_token`.
+    connect_token: Option<Vec<u8>>,
     connected: bool,
 
     // Game systems
@@ -32,21 +56,109 @@ pub struct Client {
     ping_ms: u64,
     ping_history: VecDeque<u64>,
     last_packet_received: Instant,
-    connection_timeout: Duration,
-
-    // Clock synchronization for remote servers
-    clock_offset_samples: VecDeque<i64>, // Track clock offset between client and server
-    last_server_timestamp: Option<u64>,
-    packet_send_times: VecDeque<(u64, Instant)>, // Track when we sent packets for RTT calculation
-
-    // Packet queuing for artificial latency simulation
-    outgoing_packets: VecDeque<(Vec<u8>, Instant)>,
-    incoming_packets: VecDeque<(Packet, Instant, Instant)>,
+    /// Smoothed RTT and RTT variance (milliseconds), RFC 6298/QUIC-style,
+    /// fed by each valid ping sample. Drives `pto()` instead of a fixed
+    /// connection timeout, so detection adapts to the link instead of being
+    /// too loose on a good connection or too tight on a bad one.
+    srtt_ms: Option<f32>,
+    rttvar_ms: f32,
+    /// Latched minimum RTT (milliseconds) over the last `RTT_WINDOW_SIZE`
+    /// samples, kept separately from `srtt_ms`: `srtt` tracks the *typical*
+    /// RTT and absorbs queueing delay, while this tracks the *best-case*
+    /// link RTT an EWMA would never settle back down to after a congestion
+    /// spike passes out of the window.
+    rtt_window: VecDeque<f32>,
+    /// Whether a proactive keepalive `Ping` has already been sent for the
+    /// current silence since `last_packet_received`, so it's sent at most
+    /// once per `pto()` window instead of every frame.
+    keepalive_sent: bool,
+    /// When we last sent anything at all. Drives `maintain_nat_keepalive`,
+    /// which is about refreshing the NAT's UDP mapping rather than probing
+    /// the server's liveness, so it tracks our own outbound silence instead
+    /// of `last_packet_received`.
+    last_packet_sent: Instant,
+    /// How long outbound silence is tolerated before a tiny heartbeat
+    /// `Ping` is sent to keep the NAT's mapping from expiring (e.g. during
+    /// a paused game, when `input_manager` stops producing inputs to send).
+    /// An explicit CLI value always wins; `None` falls back to a fraction of
+    /// `negotiated_timeout` (see `effective_keep_alive`) rather than
+    /// disabling the keepalive outright.
+    keep_alive: Option<Duration>,
+    /// Total inbound silence tolerated before forcing a full `reconnect()`,
+    /// independent of and typically longer than `check_connection_health`'s
+    /// adaptive `pto()`-based liveness probe (which only flags the
+    /// connection dead; it doesn't act on it). An explicit CLI value always
+    /// wins; `None` falls back to `negotiated_timeout` itself (see
+    /// `effective_session_timeout`).
+    session_timeout: Option<Duration>,
+    /// The idle timeout this session actually negotiated with the server
+    /// (`Packet::Connected::negotiated_timeout_secs`): the minimum of our
+    /// `Connect::requested_timeout_secs` and the server's own preference.
+    /// `None` until the handshake completes. Used as the fallback basis for
+    /// `keep_alive`/`session_timeout` when the user didn't override them on
+    /// the CLI.
+    negotiated_timeout: Option<Duration>,
+
+    /// Paces the outgoing input queue: `send_input` only releases a packet
+    /// while `bytes_in_flight` stays under the controller's current window.
+    congestion: CongestionController,
+    /// Bytes sent but not yet acked by the server's `last_processed_input`.
+    bytes_in_flight: usize,
+    /// Sent-but-unacked input packets awaiting an ack, oldest first, so
+    /// `record_congestion_ack` can pop every entry an ack covers and sum
+    /// their bytes in one pass.
+    inflight_inputs: VecDeque<(u32, usize)>,
+    /// The last input sequence the server acked, so the next ack's gap (if
+    /// any) can be read off as a loss signal for `congestion`.
+    last_acked_input_sequence: Option<u32>,
+
+    /// The tick of the most recent `GameState`/`GameStateDelta` folded into
+    /// `game_state`, echoed back on every `Packet::Input` as
+    /// `acked_snapshot_tick` so the server knows which snapshot it can
+    /// safely diff a future `GameStateDelta` against.
+    last_applied_snapshot_tick: u32,
+
+    // Packet queuing for artificial latency simulation, with configurable
+    // loss/jitter/reordering layered on top (see `impairment`).
+    outgoing_impairment: ImpairmentQueue<Vec<u8>>,
+    incoming_impairment: ImpairmentQueue<(Packet, Instant)>,
+    /// The loss/jitter/reorder parameters currently armed, kept alongside
+    /// the queues so `handle_toggles` can swap back to a no-op config
+    /// without losing what the user configured at startup.
+    impairment_config: ImpairmentConfig,
+    /// Whether `impairment_config` is actively applied, or the queues are
+    /// temporarily running with a no-op config (see `Action::ToggleImpairment`).
+    impairment_enabled: bool,
 
     // Netcode feature toggles
     prediction_enabled: bool,
     reconciliation_enabled: bool,
     interpolation_enabled: bool,
+    /// Whether remote players should be extrapolated forward from their last
+    /// known velocity when the interpolation buffer underruns, rather than
+    /// frozen at their last confirmed position. See `ClientGameState`'s
+    /// `get_interpolated_players`.
+    extrapolation_enabled: bool,
+    /// Whether the local player's own nametag is drawn above its cube. See
+    /// `RenderConfig::show_own_nametag`.
+    show_own_nametag: bool,
+    /// Whether the server-confirmed "ghost" overlay and reconciliation-error
+    /// readout are drawn for the local player. See
+    /// `RenderConfig::show_reconciliation_debug`.
+    show_reconciliation_debug: bool,
+
+    /// Whether the server last granted this client fly capability, per its
+    /// most recent `Packet::SetGamemode`. Gates whether `Action::ToggleFly`
+    /// bothers sending `Packet::ToggleFly` at all.
+    can_fly: bool,
+
+    /// Armed alongside `input_manager`'s recording (see `toggle_recording`):
+    /// captures every rendered frame's `players` slice and `RenderConfig`
+    /// into a `.frames` timeline for later scrubbable playback.
+    frame_recorder: Option<FrameRecorder>,
+    /// When set, the render loop draws from this recorded timeline instead
+    /// of live game state. See `load_render_replay`.
+    render_replay_player: Option<FramePlayer>,
 }
 
 impl Client {
@@ -54,6 +166,13 @@ impl Client {
     pub async fn new(
         server_addr: &str,
         fake_ping_ms: u64,
+        axis_scaling: AxisScaling,
+        packet_loss: f64,
+        jitter_ms: f64,
+        reorder_window_ms: u64,
+        duplication: f64,
+        keep_alive: Option<Duration>,
+        session_timeout: Option<Duration>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
         let socket = UdpSocket::bind("0.0.0.0:0")?;
         socket.set_nonblocking(true)?;
@@ -61,32 +180,73 @@ impl Client {
         let server_addr = Self::resolve_address(server_addr)?;
         let renderer = Renderer::new()?;
 
+        let mut network_graph = NetworkGraph::new();
+        network_graph.set_axis_scaling(axis_scaling);
+
+        let impairment_config =
+            ImpairmentConfig::new(packet_loss, jitter_ms, Duration::from_millis(reorder_window_ms), duplication);
+        // Seeded from wall-clock time rather than a fixed constant: unlike
+        // the server's deterministic-replay impairment stage, a dev running
+        // the client repeatedly wants a different loss/jitter sequence each
+        // session, not the same one replayed every launch.
+        let impairment_seed = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+
         Ok(Client {
             socket,
             server_addr,
             client_id: None,
+            resume_token: None,
+            connect_token: None,
             connected: false,
             game_state: ClientGameState::new(),
             input_manager: InputManager::new(),
             renderer,
-            network_graph: NetworkGraph::new(), // Initialize network graph
+            network_graph,
             real_ping_ms: 0,
             fake_ping_ms,
             ping_ms: 0,
             ping_history: VecDeque::new(),
             last_packet_received: Instant::now(),
-            connection_timeout: Duration::from_secs(5),
-            clock_offset_samples: VecDeque::new(),
-            last_server_timestamp: None,
-            packet_send_times: VecDeque::new(),
-            outgoing_packets: VecDeque::new(),
-            incoming_packets: VecDeque::new(),
+            last_packet_sent: Instant::now(),
+            keep_alive,
+            session_timeout,
+            negotiated_timeout: None,
+            srtt_ms: None,
+            rttvar_ms: 0.0,
+            rtt_window: VecDeque::new(),
+            keepalive_sent: false,
+            congestion: CongestionController::new(Algorithm::NewReno),
+            bytes_in_flight: 0,
+            inflight_inputs: VecDeque::new(),
+            last_acked_input_sequence: None,
+            last_applied_snapshot_tick: 0,
+            outgoing_impairment: ImpairmentQueue::new(impairment_config, impairment_seed),
+            incoming_impairment: ImpairmentQueue::new(impairment_config, impairment_seed.wrapping_add(1)),
+            impairment_config,
+            impairment_enabled: true,
             prediction_enabled: true,
             reconciliation_enabled: true,
             interpolation_enabled: true,
+            extrapolation_enabled: true,
+            show_own_nametag: true,
+            show_reconciliation_debug: false,
+            can_fly: false,
+            frame_recorder: None,
+            render_replay_player: None,
         })
     }
 
+    /// Switches to drawing from a `.frames` recording (see `FrameRecorder`)
+    /// instead of live network/game state. The render loop then drives the
+    /// loaded `FramePlayer`'s scrub cursor directly from the keyboard.
+    pub fn load_render_replay(&mut self, path: &Path) -> std::io::Result<()> {
+        self.render_replay_player = Some(FramePlayer::load(path)?);
+        Ok(())
+    }
+
     /// Resolves server address supporting both IP addresses and domain names
     fn resolve_address(addr_str: &str) -> Result<SocketAddr, Box<dyn std::error::Error>> {
         // Try parsing as direct SocketAddr first
@@ -107,7 +267,20 @@ impl Client {
 
     async fn connect(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         info!("Connecting to server...");
-        let packet = Packet::Connect { client_version: 1 };
+        let packet = Packet::Connect {
+            min_version: CLIENT_MIN_SUPPORTED_VERSION,
+            max_version: CLIENT_MAX_SUPPORTED_VERSION,
+            resume_token: self.resume_token,
+            requested_timeout_secs: CLIENT_REQUESTED_TIMEOUT_SECS,
+            // This client doesn't yet opt into `--authenticate` sessions; a
+            // server run with authentication required will simply reject
+            // unauthenticated input (see `server::client_manager::InputAcceptance`).
+            encrypt_public_key: None,
+            connect_token: self.connect_token.clone(),
+            // This client always plays rather than spectates; see
+            // `shared::Packet::Connect::spectate`.
+            spectate: false,
+        };
         self.send_packet(&packet).await?;
         Ok(())
     }
@@ -128,30 +301,238 @@ impl Client {
         self.ping_ms = self.fake_ping_ms;
         self.ping_history.clear();
         self.last_packet_received = Instant::now();
-        self.outgoing_packets.clear();
-        self.incoming_packets.clear();
+        self.last_packet_sent = Instant::now();
+        self.srtt_ms = None;
+        self.rttvar_ms = 0.0;
+        self.rtt_window.clear();
+        self.keepalive_sent = false;
+        self.negotiated_timeout = None;
+        self.congestion = CongestionController::new(Algorithm::NewReno);
+        self.bytes_in_flight = 0;
+        self.inflight_inputs.clear();
+        self.last_acked_input_sequence = None;
+        self.last_applied_snapshot_tick = 0;
+        self.outgoing_impairment.clear();
+        self.incoming_impairment.clear();
         self.game_state = ClientGameState::new();
 
         self.connect().await
     }
 
-    fn check_connection_health(&mut self) {
-        if self.connected && self.last_packet_received.elapsed() > self.connection_timeout {
+    /// Replays a `.demo` file recorded by a previous session instead of
+    /// sampling live keyboard input, e.g. to reproduce a prediction or
+    /// reconciliation bug deterministically.
+    pub fn load_demo(&mut self, path: &std::path::Path) -> std::io::Result<()> {
+        self.input_manager.load_playback(path)
+    }
+
+    /// Clock sync, RTT, congestion-ack, and network-graph bookkeeping shared
+    /// by `Packet::GameState` and `Packet::GameStateDelta` — everything that
+    /// only depends on the packet's ack fields, not its player payload.
+    fn record_snapshot_telemetry(
+        &mut self,
+        tick: u32,
+        timestamp: u64,
+        last_processed_input: &HashMap<u32, u32>,
+        input_receive_ms: &HashMap<u32, u64>,
+    ) {
+        // Feed the clock-sync estimator first: it's the source of truth for
+        // ping now, so it has to see this packet's echo before we read
+        // `clock_rtt_ms()` below.
+        if let Some(client_id) = self.client_id {
+            if let Some(&acked_sequence) = last_processed_input.get(&client_id) {
+                let receive_ms = input_receive_ms.get(&client_id).copied();
+                self.input_manager.on_server_ack(acked_sequence, timestamp, receive_ms);
+                self.record_congestion_ack(acked_sequence);
+            }
+        }
+
+        // `clock_rtt_ms()` already discards echoes with an implausible
+        // round-trip delay (see `ClockSync`'s `max_rtt_ms`), so an
+        // unreasonable sample simply leaves the estimate — and so
+        // `ping_history` — untouched.
+        if let Some(rtt_ms) = self.input_manager.clock_rtt_ms() {
+            self.record_rtt_sample(rtt_ms as f32);
+
+            // Add to history for smoothing
+            self.ping_history.push_back(rtt_ms);
+
+            // Keep only last 10 ping samples
+            while self.ping_history.len() > 10 {
+                self.ping_history.pop_front();
+            }
+
+            // Use moving average of last few pings for smoother display
+            if !self.ping_history.is_empty() {
+                let sum: u64 = self.ping_history.iter().sum();
+                self.real_ping_ms = sum / self.ping_history.len() as u64;
+            }
+        }
+
+        self.ping_ms = self.real_ping_ms + self.fake_ping_ms;
+
+        // Record packet received for network graph
+        self.network_graph.record_packet_received(self.ping_ms as f32);
+        self.network_graph.record_sequence(tick);
+
+        self.network_graph.record_clock_sync(
+            self.input_manager.clock_offset_ms(),
+            self.input_manager.clock_rtt_ms(),
+        );
+        self.network_graph.record_input_redundancy(
+            self.input_manager.unacked_count(),
+            self.input_manager.resend_rate(),
+        );
+    }
+
+    /// Folds a valid round-trip sample (milliseconds) into the smoothed-RTT
+    /// estimator, RFC 6298 style: the first sample seeds `srtt`/`rttvar`
+    /// outright, later samples update them via the standard EWMA.
+    fn record_rtt_sample(&mut self, sample_ms: f32) {
+        match self.srtt_ms {
+            None => {
+                self.srtt_ms = Some(sample_ms);
+                self.rttvar_ms = sample_ms / 2.0;
+            }
+            Some(srtt) => {
+                self.rttvar_ms = 0.75 * self.rttvar_ms + 0.25 * (srtt - sample_ms).abs();
+                self.srtt_ms = Some(0.875 * srtt + 0.125 * sample_ms);
+            }
+        }
+
+        self.rtt_window.push_back(sample_ms);
+        while self.rtt_window.len() > RTT_WINDOW_SIZE {
+            self.rtt_window.pop_front();
+        }
+    }
+
+    /// Latched minimum RTT (milliseconds) over the current sliding window,
+    /// i.e. the best-case link RTT with queueing delay stripped out.
+    pub fn min_rtt_ms(&self) -> Option<f32> {
+        self.rtt_window.iter().copied().fold(None, |min, sample| {
+            Some(min.map_or(sample, |m: f32| m.min(sample)))
+        })
+    }
+
+    /// Probe timeout: `srtt + max(4*rttvar, granularity)`, QUIC-style,
+    /// clamped to a floor/ceiling so a fresh connection with no samples yet
+    /// (or a pathologically noisy one) still gets a sane timeout.
+    fn pto(&self) -> Duration {
+        const GRANULARITY_MS: f32 = 20.0;
+        const FLOOR_MS: f32 = 200.0;
+        const CEILING_MS: f32 = 5000.0;
+
+        let srtt = self.srtt_ms.unwrap_or(FLOOR_MS);
+        let pto_ms = (srtt + (4.0 * self.rttvar_ms).max(GRANULARITY_MS)).clamp(FLOOR_MS, CEILING_MS);
+        Duration::from_secs_f32(pto_ms / 1000.0)
+    }
+
+    /// Checks connection liveness against an adaptive threshold instead of a
+    /// fixed timeout: once one `pto()` elapses with no server packet, sends a
+    /// single proactive keepalive `Ping` (the server always replies `Pong`,
+    /// which refreshes `last_packet_received`); once a small multiple of
+    /// `pto()` elapses with still nothing back, declares the connection
+    /// dead.
+    async fn check_connection_health(&mut self) {
+        if !self.connected {
+            return;
+        }
+
+        let pto = self.pto();
+        let silence = self.last_packet_received.elapsed();
+
+        if silence > pto * 3 {
             warn!("Connection timeout detected");
             self.connected = false;
             self.client_id = None;
+            self.keepalive_sent = false;
+            return;
+        }
+
+        if silence > pto && !self.keepalive_sent {
+            self.keepalive_sent = true;
+            let _ = self.send_packet(&Packet::Ping { nonce: 0 }).await;
         }
+
+        if silence <= pto {
+            self.keepalive_sent = false;
+        }
+    }
+
+    /// The interval `maintain_nat_keepalive` pings at: an explicit
+    /// `--keep-alive-ms` always wins, otherwise falls back to a fraction of
+    /// the handshake-negotiated idle timeout. Loopback connections (the
+    /// server running locally, never actually subject to NAT or loss) use
+    /// the full negotiated interval rather than a third of it — there's no
+    /// mapping to refresh and no lossy link to keep warm, so pinging a third
+    /// as often as a real connection would just be wasted traffic.
+    fn effective_keep_alive(&self) -> Option<Duration> {
+        self.keep_alive.or_else(|| {
+            self.negotiated_timeout.map(|timeout| {
+                if self.server_addr.ip().is_loopback() {
+                    timeout
+                } else {
+                    timeout / 3
+                }
+            })
+        })
+    }
+
+    /// The inbound-silence threshold `enforce_session_timeout` acts on: an
+    /// explicit `--session-timeout-secs` always wins, otherwise falls back
+    /// to the handshake-negotiated idle timeout directly.
+    fn effective_session_timeout(&self) -> Option<Duration> {
+        self.session_timeout.or(self.negotiated_timeout)
     }
 
-    /// Sends packet with optional artificial latency
+    /// Refreshes the NAT's UDP mapping during long outbound silence (e.g. a
+    /// paused game, where `input_manager` has nothing to send). Unlike
+    /// `check_connection_health`'s adaptive probe, this tracks our own send
+    /// side and uses `effective_keep_alive`'s interval — NAT bindings expire
+    /// on a schedule the router picks, not one we can infer from RTT.
+    async fn maintain_nat_keepalive(&mut self) {
+        let Some(keep_alive) = self.effective_keep_alive() else {
+            return;
+        };
+
+        if self.connected && self.last_packet_sent.elapsed() >= keep_alive {
+            let _ = self.send_packet(&Packet::Ping { nonce: 0 }).await;
+        }
+    }
+
+    /// Forces a full reconnect once inbound silence exceeds
+    /// `effective_session_timeout`, distinct from `check_connection_health`'s
+    /// `pto()`-based probe: that mechanism only flags the connection dead, it
+    /// doesn't act on it, and its ceiling adapts to measured RTT rather than
+    /// being the negotiated bound for how long a dead session should be
+    /// tolerated.
+    async fn enforce_session_timeout(&mut self) {
+        let Some(session_timeout) = self.effective_session_timeout() else {
+            return;
+        };
+
+        if self.last_packet_received.elapsed() >= session_timeout {
+            warn!("Session timeout exceeded, forcing reconnect");
+            if let Err(e) = self.reconnect().await {
+                warn!("Reconnect after session timeout failed: {}", e);
+            }
+        }
+    }
+
+    /// Sends packet with optional artificial latency, loss, jitter, and
+    /// reordering
     async fn send_packet(&mut self, packet: &Packet) -> Result<(), Box<dyn std::error::Error>> {
+        self.last_packet_sent = Instant::now();
+
         let data = serialize(packet)?;
+        self.network_graph.record_bytes_sent(data.len());
 
         if self.fake_ping_ms > 0 {
-            // Simulate one-way latency (half of round-trip time)
+            // One-way latency (half of round-trip time) is the base delay;
+            // `outgoing_impairment` perturbs it with loss/jitter/reorder.
             let delay_ms = self.fake_ping_ms / 2;
-            let send_time = Instant::now() + Duration::from_millis(delay_ms);
-            self.outgoing_packets.push_back((data, send_time));
+            self.outgoing_impairment
+                .submit(data, Instant::now(), Duration::from_millis(delay_ms));
         } else {
             self.socket.send_to(&data, self.server_addr)?;
         }
@@ -159,30 +540,23 @@ impl Client {
         Ok(())
     }
 
-    /// Processes queued outgoing packets for artificial latency
+    /// Sends every queued outgoing packet whose simulated release time has
+    /// passed, earliest first (not insertion order — jitter and reordering
+    /// can let a later submission release before an earlier one).
     fn process_outgoing_packets(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let now = Instant::now();
-        while let Some((_data, send_time)) = self.outgoing_packets.front() {
-            if now >= *send_time {
-                let (data, _) = self.outgoing_packets.pop_front().unwrap();
-                self.socket.send_to(&data, self.server_addr)?;
-            } else {
-                break;
-            }
+        for data in self.outgoing_impairment.drain_ready(now) {
+            self.socket.send_to(&data, self.server_addr)?;
         }
         Ok(())
     }
 
-    /// Processes queued incoming packets for artificial latency
+    /// Hands off every queued incoming packet whose simulated release time
+    /// has passed, earliest first, to `handle_packet_sync`.
     fn process_incoming_packets(&mut self) {
         let now = Instant::now();
-        while let Some((_packet, process_time, _receive_time)) = self.incoming_packets.front() {
-            if now >= *process_time {
-                let (packet, _, receive_time) = self.incoming_packets.pop_front().unwrap();
-                self.handle_packet_sync(packet, receive_time);
-            } else {
-                break;
-            }
+        for (packet, receive_time) in self.incoming_impairment.drain_ready(now) {
+            self.handle_packet_sync(packet, receive_time);
         }
     }
 
@@ -191,60 +565,99 @@ impl Client {
         self.last_packet_received = Instant::now();
 
         match packet {
-            Packet::Connected { client_id } => {
-                info!("Connected! Client ID: {}", client_id);
+            Packet::Connected {
+                client_id,
+                resume_token,
+                negotiated_version,
+                negotiated_timeout_secs,
+                connect_token,
+                ..
+            } => {
+                info!(
+                    "Connected! Client ID: {} (protocol v{}, idle timeout negotiated to {}s)",
+                    client_id, negotiated_version, negotiated_timeout_secs
+                );
                 self.client_id = Some(client_id);
+                self.resume_token = Some(resume_token);
+                self.connect_token = connect_token;
                 self.connected = true;
+                self.negotiated_timeout = Some(Duration::from_secs(negotiated_timeout_secs as u64));
             }
 
             Packet::GameState {
                 tick,
                 timestamp,
                 last_processed_input,
+                input_receive_ms,
                 players,
+                checksum,
             } => {
-                // Calculate ping time for display
                 if timestamp > 0 {
-                    let ping_candidate = self.calculate_robust_ping(timestamp);
-
-                    // Sanity check: ping should be reasonable (0-2000ms)
-                    if ping_candidate <= 2000 {
-                        // Add to history for smoothing
-                        self.ping_history.push_back(ping_candidate);
+                    self.record_snapshot_telemetry(tick, timestamp, &last_processed_input, &input_receive_ms);
+                }
 
-                        // Keep only last 10 ping samples
-                        while self.ping_history.len() > 10 {
-                            self.ping_history.pop_front();
-                        }
+                let config = ServerStateConfig {
+                    client_id: self.client_id,
+                    reconciliation_enabled: self.reconciliation_enabled,
+                    interpolation_enabled: self.interpolation_enabled,
+                    extrapolation_enabled: self.extrapolation_enabled,
+                };
 
-                        // Use moving average of last few pings for smoother display
-                        if !self.ping_history.is_empty() {
-                            let sum: u64 = self.ping_history.iter().sum();
-                            self.real_ping_ms = sum / self.ping_history.len() as u64;
-                        }
-                    }
-                    // If ping is unreasonable, keep the previous value
+                self.game_state.apply_server_state(
+                    tick,
+                    timestamp,
+                    players,
+                    last_processed_input,
+                    checksum,
+                    config,
+                );
+                self.last_applied_snapshot_tick = tick;
+            }
 
-                    self.ping_ms = self.real_ping_ms + self.fake_ping_ms;
+            // Bandwidth-saving counterpart to `Packet::GameState`: only the
+            // players that changed since `baseline_tick`. Merged onto our own
+            // `confirmed_state` (which is exactly what the server diffed
+            // against, since it's diffing against whatever we last
+            // acknowledged — see `acked_snapshot_tick` in `send_input`)
+            // rather than tracking a separate tick-indexed history client-side.
+            Packet::GameStateDelta {
+                tick,
+                timestamp,
+                baseline_tick: _,
+                last_processed_input,
+                input_receive_ms,
+                changed_players,
+                removed_player_ids,
+                checksum,
+            } => {
+                if timestamp > 0 {
+                    self.record_snapshot_telemetry(tick, timestamp, &last_processed_input, &input_receive_ms);
+                }
 
-                    // Record packet received for network graph
-                    self.network_graph
-                        .record_packet_received(self.ping_ms as f32);
+                let mut players = self.game_state.confirmed_state.players.clone();
+                for player in changed_players {
+                    players.insert(player.id, player);
+                }
+                for removed_id in removed_player_ids {
+                    players.remove(&removed_id);
                 }
 
                 let config = ServerStateConfig {
                     client_id: self.client_id,
                     reconciliation_enabled: self.reconciliation_enabled,
                     interpolation_enabled: self.interpolation_enabled,
+                    extrapolation_enabled: self.extrapolation_enabled,
                 };
 
                 self.game_state.apply_server_state(
                     tick,
                     timestamp,
-                    players,
+                    players.into_values().collect(),
                     last_processed_input,
+                    checksum,
                     config,
                 );
+                self.last_applied_snapshot_tick = tick;
             }
 
             Packet::Disconnected { reason } => {
@@ -253,6 +666,13 @@ impl Client {
                 self.client_id = None;
             }
 
+            Packet::SetGamemode { client_id, mode, can_fly } => {
+                if Some(client_id) == self.client_id {
+                    info!("Gamemode set to {:?} (can_fly: {})", mode, can_fly);
+                    self.can_fly = can_fly;
+                }
+            }
+
             _ => {
                 warn!("Unexpected packet type");
             }
@@ -271,9 +691,24 @@ impl Client {
             left: input.left,
             right: input.right,
             jump: input.jump,
+            mac: None,
+            sealed: None,
+            redundant: shared::encode_redundant_inputs(&self.input_manager.redundant_inputs()),
+            acked_snapshot_tick: self.last_applied_snapshot_tick,
         };
 
-        self.send_packet(&packet).await?;
+        // `congestion` paces this queue the way `outgoing_impairment` paces
+        // simulated latency: if the window's full, skip this tick's send
+        // rather than burst once room frees up. The skipped sequence isn't
+        // lost, just deferred — `redundant_inputs()` carries recent inputs
+        // along with every later send, so it catches up once acked bytes
+        // free the window.
+        let packet_bytes = bincode::serialized_size(&packet).unwrap_or(0) as usize;
+        if self.congestion.can_send(self.bytes_in_flight, packet_bytes) {
+            self.send_packet(&packet).await?;
+            self.bytes_in_flight += packet_bytes;
+            self.inflight_inputs.push_back((input.sequence, packet_bytes));
+        }
 
         // Apply client-side prediction
         if self.prediction_enabled {
@@ -285,8 +720,43 @@ impl Client {
         Ok(())
     }
 
-    /// Handles runtime toggle of netcode features and network graph
-    fn handle_toggles(&mut self, toggles: (bool, bool, bool, bool, bool)) -> bool {
+    /// Feeds the outgoing congestion controller from the server's acked
+    /// input sequence. A gap since the last ack means at least one input
+    /// sequence in between was never processed — the same signal a gap in
+    /// acked packet numbers gives a TCP sender — so it's treated as a loss
+    /// before folding the newly-acked bytes into `on_ack`.
+    fn record_congestion_ack(&mut self, acked_sequence: u32) {
+        if let Some(last_acked) = self.last_acked_input_sequence {
+            if acked_sequence > last_acked + 1 {
+                self.congestion.on_loss();
+            }
+        }
+        self.last_acked_input_sequence = Some(acked_sequence);
+
+        let mut acked_bytes = 0;
+        while let Some(&(sequence, bytes)) = self.inflight_inputs.front() {
+            if sequence > acked_sequence {
+                break;
+            }
+            acked_bytes += bytes;
+            self.bytes_in_flight = self.bytes_in_flight.saturating_sub(bytes);
+            self.inflight_inputs.pop_front();
+        }
+        if acked_bytes > 0 {
+            self.congestion.on_ack(acked_bytes);
+            self.network_graph.record_delivery_ack(acked_bytes, Instant::now());
+        }
+    }
+
+    /// Handles runtime toggle of netcode features and network graph.
+    ///
+    /// The fly toggle (`toggles.7`) is handled by the caller rather than
+    /// here, since sending `Packet::ToggleFly` to the server requires an
+    /// async context that this method doesn't have.
+    fn handle_toggles(
+        &mut self,
+        toggles: (bool, bool, bool, bool, bool, bool, bool, bool, bool, bool, bool),
+    ) -> bool {
         let mut reconnect_requested = false;
 
         if toggles.0 {
@@ -316,10 +786,65 @@ impl Client {
                 }
             );
         }
+        if toggles.5 {
+            self.toggle_recording();
+        }
+        if toggles.6 {
+            self.network_graph.toggle_axis_scaling();
+        }
+        if toggles.8 {
+            self.show_own_nametag = !self.show_own_nametag;
+            info!("Own nametag: {}", self.show_own_nametag);
+        }
+        if toggles.9 {
+            self.show_reconciliation_debug = !self.show_reconciliation_debug;
+            info!("Reconciliation debug overlay: {}", self.show_reconciliation_debug);
+        }
+        if toggles.10 {
+            self.impairment_enabled = !self.impairment_enabled;
+            let config = if self.impairment_enabled {
+                self.impairment_config
+            } else {
+                ImpairmentConfig::default()
+            };
+            self.outgoing_impairment.set_config(config);
+            self.incoming_impairment.set_config(config);
+            info!("Packet loss/jitter/reorder impairment: {}", self.impairment_enabled);
+        }
 
         reconnect_requested
     }
 
+    /// Arms input and rendered-frame recording together, or stops both and
+    /// writes the captured sessions to timestamped `.demo`/`.frames` files
+    /// in the working directory (sharing one timestamp so the pair is easy
+    /// to spot alongside each other).
+    fn toggle_recording(&mut self) {
+        if self.input_manager.is_recording() {
+            let timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+
+            let demo_path = std::path::PathBuf::from(format!("recording_{}.demo", timestamp));
+            match self.input_manager.stop_recording_and_save(&demo_path) {
+                Ok(()) => info!("Saved input recording to {}", demo_path.display()),
+                Err(e) => error!("Failed to save input recording: {}", e),
+            }
+
+            if let Some(recorder) = self.frame_recorder.take() {
+                let frames_path = std::path::PathBuf::from(format!("recording_{}.frames", timestamp));
+                match recorder.save(&frames_path) {
+                    Ok(()) => info!("Saved render replay to {}", frames_path.display()),
+                    Err(e) => error!("Failed to save render replay: {}", e),
+                }
+            }
+        } else {
+            self.input_manager.start_recording();
+            self.frame_recorder = Some(FrameRecorder::new());
+        }
+    }
+
     /// Main client game loop handling network, input, and rendering
     pub async fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         self.connect().await?;
@@ -341,12 +866,15 @@ impl Client {
             match self.socket.recv_from(&mut buffer) {
                 Ok((len, _)) => {
                     let receive_time = Instant::now();
+                    self.network_graph.record_bytes_received(len);
                     if let Ok(packet) = deserialize::<Packet>(&buffer[0..len]) {
                         if self.fake_ping_ms > 0 {
                             let delay_ms = self.fake_ping_ms / 2;
-                            let process_time = receive_time + Duration::from_millis(delay_ms);
-                            self.incoming_packets
-                                .push_back((packet, process_time, receive_time));
+                            self.incoming_impairment.submit(
+                                (packet, receive_time),
+                                receive_time,
+                                Duration::from_millis(delay_ms),
+                            );
                         } else {
                             self.handle_packet_sync(packet, receive_time);
                         }
@@ -364,6 +892,7 @@ impl Client {
             if last_input_time.elapsed() >= input_interval {
                 let (toggles, input_to_send) = self.input_manager.update();
 
+                let fly_toggle_requested = toggles.7;
                 let reconnect_requested = self.handle_toggles(toggles);
 
                 if reconnect_requested {
@@ -372,6 +901,12 @@ impl Client {
                     }
                 }
 
+                if fly_toggle_requested {
+                    if let Err(e) = self.send_packet(&Packet::ToggleFly).await {
+                        error!("Error sending fly toggle: {}", e);
+                    }
+                }
+
                 if let Some(input) = input_to_send {
                     if let Err(e) = self.send_input(input).await {
                         error!("Error sending input: {}", e);
@@ -380,36 +915,74 @@ impl Client {
                 last_input_time = Instant::now();
             }
 
-            self.check_connection_health();
+            self.check_connection_health().await;
+            self.maintain_nat_keepalive().await;
+            self.enforce_session_timeout().await;
 
             // Rendering at 60 FPS
             if last_render_time.elapsed() >= render_interval {
-                if !self.prediction_enabled {
-                    let dt = 1.0 / 60.0;
-                    self.game_state.update_physics(dt);
-                }
+                if let Some(player) = self.render_replay_player.as_mut() {
+                    // Scrubbable playback of a recorded `.frames` timeline,
+                    // entirely decoupled from live network/game state.
+                    if is_key_pressed(KeyCode::Space) {
+                        player.toggle_play_pause();
+                    }
+                    if is_key_pressed(KeyCode::Period) {
+                        player.step();
+                    }
+                    player.advance();
 
-                let players = self.game_state.get_render_players(
-                    self.client_id,
-                    self.prediction_enabled,
-                    self.interpolation_enabled,
-                );
+                    if let Some((players, config)) = player.current() {
+                        self.renderer.render_frame(players, config);
+                    }
+                    let (current_frame, total_frames) = player.progress();
+                    self.renderer.draw_scrub_bar(current_frame, total_frames, player.is_playing());
+                } else {
+                    if !self.prediction_enabled {
+                        let dt = 1.0 / 60.0;
+                        self.game_state.update_physics(dt);
+                    }
 
-                let render_config = RenderConfig {
-                    client_id: self.client_id,
-                    prediction_enabled: self.prediction_enabled,
-                    reconciliation_enabled: self.reconciliation_enabled,
-                    interpolation_enabled: self.interpolation_enabled,
-                    real_ping_ms: self.real_ping_ms,
-                    fake_ping_ms: self.fake_ping_ms,
-                    ping_ms: self.ping_ms,
-                    current_input: Some(self.input_manager.get_current_input().clone()),
-                };
+                    let players = self.game_state.get_render_players(
+                        self.client_id,
+                        self.prediction_enabled,
+                        self.interpolation_enabled,
+                        self.input_manager.server_now_ms(),
+                    );
+
+                    let (incoming_avg_bandwidth, outgoing_avg_bandwidth) = self.network_graph.current_bandwidth_bps();
+                    let (incoming_max_bandwidth, outgoing_max_bandwidth) = self.network_graph.max_bandwidth_bps();
+
+                    let render_config = RenderConfig {
+                        client_id: self.client_id,
+                        prediction_enabled: self.prediction_enabled,
+                        reconciliation_enabled: self.reconciliation_enabled,
+                        interpolation_enabled: self.interpolation_enabled,
+                        real_ping_ms: self.real_ping_ms,
+                        fake_ping_ms: self.fake_ping_ms,
+                        ping_ms: self.ping_ms,
+                        current_input: Some(self.input_manager.get_current_input().clone()),
+                        show_own_nametag: self.show_own_nametag,
+                        show_reconciliation_debug: self.show_reconciliation_debug,
+                        server_position: self
+                            .client_id
+                            .and_then(|id| self.game_state.confirmed_local_player(id))
+                            .map(|player| (player.x, player.y)),
+                        incoming_avg_bandwidth,
+                        outgoing_avg_bandwidth,
+                        incoming_max_bandwidth,
+                        outgoing_max_bandwidth,
+                    };
+
+                    if let Some(recorder) = self.frame_recorder.as_mut() {
+                        recorder.record(players.clone(), render_config.clone());
+                    }
 
-                self.renderer.render(&players, render_config);
+                    self.renderer.render_frame(&players, &render_config);
 
-                // Render network graph on top of everything else
-                self.network_graph.render();
+                    // Render network graph on top of everything else
+                    self.network_graph.render();
+                }
 
                 last_render_time = Instant::now();
                 next_frame().await;
@@ -428,108 +1001,6 @@ impl Client {
         Ok(())
     }
 
-    /// Calculates ping using clock-drift resistant method for remote servers
-    fn calculate_robust_ping(&mut self, server_timestamp: u64) -> u64 {
-        // For localhost testing, use simple calculation
-        if self.server_addr.ip().is_loopback() {
-            let now_ms = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::from_secs(0))
-                .as_millis() as u64;
-
-            return if now_ms >= server_timestamp {
-                now_ms.saturating_sub(server_timestamp).min(10)
-            } else {
-                0
-            };
-        }
-
-        // Track the relationship between server and client timestamps to detect clock drift
-        self.last_server_timestamp = Some(server_timestamp);
-
-        // Get current time safely
-        let now_ms = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or(Duration::from_secs(0))
-            .as_millis();
-
-        // Safe conversion with overflow protection
-        let now_ms_safe = (now_ms.min(u64::MAX as u128)) as u64;
-
-        // Use timestamp deltas for drift-resistant calculation when we have history
-        if let Some((prev_server_ts, prev_recv_time)) = self.packet_send_times.back() {
-            let prev_server_ts = *prev_server_ts;
-            let prev_recv_time = *prev_recv_time;
-
-            // Calculate time differences on both sides
-            let server_time_diff = server_timestamp.saturating_sub(prev_server_ts);
-            let client_time_diff = prev_recv_time.elapsed().as_millis() as u64;
-
-            // If the differences are reasonable, use them to estimate ping
-            if server_time_diff > 0 && server_time_diff < 5000 && client_time_diff < 5000 {
-                // Estimate RTT based on time progression
-                let estimated_ping = if client_time_diff > server_time_diff {
-                    (client_time_diff - server_time_diff) / 2
-                } else {
-                    // Server clock is faster, use a conservative estimate
-                    server_time_diff.min(self.real_ping_ms.max(50))
-                };
-
-                // Store this measurement for next calculation
-                self.packet_send_times
-                    .push_back((server_timestamp, Instant::now()));
-                if self.packet_send_times.len() > 20 {
-                    self.packet_send_times.pop_front();
-                }
-
-                return estimated_ping.clamp(10, 2000);
-            }
-        }
-
-        // Fallback: Calculate clock offset to detect systematic drift
-        let raw_ping = if now_ms_safe >= server_timestamp {
-            now_ms_safe.saturating_sub(server_timestamp)
-        } else {
-            // Server is ahead - this suggests clock skew
-            let clock_offset = server_timestamp.saturating_sub(now_ms_safe);
-
-            // Track clock offset samples for drift detection
-            self.clock_offset_samples.push_back(clock_offset as i64);
-            if self.clock_offset_samples.len() > 10 {
-                self.clock_offset_samples.pop_front();
-            }
-
-            // Use median offset to handle clock corrections
-            if self.clock_offset_samples.len() >= 3 {
-                let mut offsets: Vec<i64> = self.clock_offset_samples.iter().cloned().collect();
-                offsets.sort();
-                let median_offset = offsets[offsets.len() / 2];
-
-                // Apply offset correction if it's consistent
-                if median_offset.abs() < 10000 {
-                    // Less than 10 seconds offset
-                    let corrected_server_time =
-                        server_timestamp.saturating_sub(median_offset.unsigned_abs());
-                    now_ms_safe.saturating_sub(corrected_server_time)
-                } else {
-                    // Large offset, use previous ping
-                    self.real_ping_ms.min(1000)
-                }
-            } else {
-                // Not enough samples, use previous ping
-                self.real_ping_ms.min(1000)
-            }
-        };
-
-        // Store this measurement for next calculation
-        self.packet_send_times
-            .push_back((server_timestamp, Instant::now()));
-        if self.packet_send_times.len() > 20 {
-            self.packet_send_times.pop_front();
-        }
-
-        raw_ping.clamp(0, 2000)
-    }
 }
 
 #[cfg(test)]
@@ -598,41 +1069,6 @@ mod tests {
         assert!(client_id.is_none());
     }
 
-    #[test]
-    fn test_ping_calculation_localhost() {
-        let mut client = create_test_client();
-
-        // Test localhost ping calculation
-        let now_ms = SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-
-        // Server timestamp slightly in the past
-        let server_timestamp = now_ms - 50;
-        let ping = client.calculate_robust_ping(server_timestamp);
-
-        // Should be small for localhost
-        assert!(ping <= 10);
-    }
-
-    #[test]
-    fn test_ping_calculation_future_timestamp() {
-        let mut client = create_test_client();
-
-        let now_ms = SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as u64;
-
-        // Server timestamp in the future (clock skew)
-        let server_timestamp = now_ms + 1000;
-        let ping = client.calculate_robust_ping(server_timestamp);
-
-        // Should handle gracefully
-        assert!(ping <= 2000);
-    }
-
     #[test]
     fn test_ping_history_management() {
         let mut ping_history = VecDeque::new();
@@ -676,6 +1112,98 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_srtt_rttvar_first_sample_seeds_estimator() {
+        let mut srtt_ms: Option<f32> = None;
+        let mut rttvar_ms = 0.0f32;
+
+        let sample = 40.0;
+        match srtt_ms {
+            None => {
+                srtt_ms = Some(sample);
+                rttvar_ms = sample / 2.0;
+            }
+            Some(_) => unreachable!(),
+        }
+
+        assert_eq!(srtt_ms, Some(40.0));
+        assert_eq!(rttvar_ms, 20.0);
+    }
+
+    #[test]
+    fn test_srtt_rttvar_converges_on_stable_samples() {
+        let mut srtt_ms = 40.0f32;
+        let mut rttvar_ms = 20.0f32;
+
+        for _ in 0..50 {
+            let sample = 40.0;
+            rttvar_ms = 0.75 * rttvar_ms + 0.25 * (srtt_ms - sample).abs();
+            srtt_ms = 0.875 * srtt_ms + 0.125 * sample;
+        }
+
+        assert!((srtt_ms - 40.0).abs() < 0.01);
+        assert!(rttvar_ms < 0.01);
+    }
+
+    #[test]
+    fn test_pto_floor_applies_with_no_samples_yet() {
+        let srtt_ms: Option<f32> = None;
+        let rttvar_ms = 0.0f32;
+        let floor_ms = 200.0f32;
+
+        let srtt = srtt_ms.unwrap_or(floor_ms);
+        let pto_ms = (srtt + (4.0 * rttvar_ms).max(20.0)).clamp(floor_ms, 5000.0);
+
+        assert_eq!(pto_ms, floor_ms);
+    }
+
+    #[test]
+    fn test_pto_grows_with_rtt_variance() {
+        let stable_pto = {
+            let srtt = 40.0f32;
+            let rttvar = 2.0f32;
+            (srtt + (4.0 * rttvar).max(20.0)).clamp(200.0, 5000.0)
+        };
+        let jittery_pto = {
+            let srtt = 40.0f32;
+            let rttvar = 80.0f32;
+            (srtt + (4.0 * rttvar).max(20.0)).clamp(200.0, 5000.0)
+        };
+
+        assert!(jittery_pto > stable_pto);
+    }
+
+    #[test]
+    fn test_min_rtt_latches_lowest_sample_in_window() {
+        let mut window: VecDeque<f32> = VecDeque::new();
+        for sample in [80.0, 40.0, 200.0, 60.0] {
+            window.push_back(sample);
+            while window.len() > 10 {
+                window.pop_front();
+            }
+        }
+
+        let min_rtt = window.iter().copied().fold(None, |min, sample| Some(min.map_or(sample, |m: f32| m.min(sample))));
+
+        assert_eq!(min_rtt, Some(40.0));
+    }
+
+    #[test]
+    fn test_min_rtt_evicts_samples_that_fall_out_of_window() {
+        let mut window: VecDeque<f32> = VecDeque::new();
+        window.push_back(10.0);
+        for _ in 0..10 {
+            window.push_back(50.0);
+            while window.len() > 10 {
+                window.pop_front();
+            }
+        }
+
+        let min_rtt = window.iter().copied().fold(None, |min, sample| Some(min.map_or(sample, |m: f32| m.min(sample))));
+
+        assert_eq!(min_rtt, Some(50.0));
+    }
+
     #[test]
     fn test_connection_timeout_logic() {
         let last_packet_received = Instant::now();
@@ -689,6 +1217,36 @@ mod tests {
         assert!(old_time.elapsed() > connection_timeout);
     }
 
+    #[test]
+    fn test_effective_timeout_prefers_explicit_override_over_negotiated() {
+        let explicit: Option<Duration> = Some(Duration::from_secs(3));
+        let negotiated: Option<Duration> = Some(Duration::from_secs(20));
+        assert_eq!(explicit.or(negotiated), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn test_effective_timeout_falls_back_to_negotiated_when_unset() {
+        let explicit: Option<Duration> = None;
+        let negotiated: Option<Duration> = Some(Duration::from_secs(20));
+        assert_eq!(explicit.or(negotiated), Some(Duration::from_secs(20)));
+    }
+
+    #[test]
+    fn test_keepalive_interval_is_a_third_of_negotiated_timeout_on_a_real_link() {
+        let negotiated = Duration::from_secs(21);
+        let is_loopback = false;
+        let interval = if is_loopback { negotiated } else { negotiated / 3 };
+        assert_eq!(interval, Duration::from_secs(7));
+    }
+
+    #[test]
+    fn test_keepalive_interval_uses_the_full_negotiated_window_on_loopback() {
+        let negotiated = Duration::from_secs(21);
+        let is_loopback = true;
+        let interval = if is_loopback { negotiated } else { negotiated / 3 };
+        assert_eq!(interval, negotiated);
+    }
+
     #[test]
     fn test_clock_offset_calculation() {
         let client_time = 1000u64;
@@ -845,25 +1403,6 @@ mod tests {
         assert!(timestamp < year_2100_ms);
     }
 
-    #[test]
-    fn test_clock_drift_detection() {
-        let mut clock_offset_samples = VecDeque::new();
-
-        // Simulate consistent clock offset
-        let consistent_offset = 100i64;
-        for _ in 0..5 {
-            clock_offset_samples.push_back(consistent_offset);
-        }
-
-        // Calculate median
-        let mut offsets: Vec<i64> = clock_offset_samples.iter().cloned().collect();
-        offsets.sort();
-        let median = offsets[offsets.len() / 2];
-
-        assert_eq!(median, consistent_offset);
-        assert!(median.abs() < 10000); // Reasonable offset
-    }
-
     #[test]
     fn test_ping_clamping() {
         let test_pings = vec![0, 50, 100, 1000, 2000, 5000, 10000];
@@ -882,79 +1421,6 @@ mod tests {
         }
     }
 
-    #[test]
-    fn test_packet_send_time_tracking() {
-        let mut packet_send_times = VecDeque::new();
-        let max_samples = 20;
-
-        // Add samples
-        for i in 0..25 {
-            packet_send_times.push_back((i as u64, Instant::now()));
-
-            // Keep only last 20 samples
-            if packet_send_times.len() > max_samples {
-                packet_send_times.pop_front();
-            }
-        }
-
-        assert_eq!(packet_send_times.len(), max_samples);
-        assert_eq!(packet_send_times.front().unwrap().0, 5); // Should start from 5th element
-        assert_eq!(packet_send_times.back().unwrap().0, 24);
-    }
-
-    // Helper function for creating test client components
-    fn create_test_client() -> TestClientMock {
-        TestClientMock {
-            server_addr: "127.0.0.1:8080".parse().unwrap(),
-            real_ping_ms: 0,
-            ping_history: VecDeque::new(),
-            clock_offset_samples: VecDeque::new(),
-            packet_send_times: VecDeque::new(),
-        }
-    }
-
-    // Mock client for testing without actual network
-    #[allow(dead_code)]
-    struct TestClientMock {
-        server_addr: SocketAddr,
-        real_ping_ms: u64,
-        ping_history: VecDeque<u64>,
-        clock_offset_samples: VecDeque<i64>,
-        packet_send_times: VecDeque<(u64, Instant)>,
-    }
-
-    impl TestClientMock {
-        fn calculate_robust_ping(&mut self, server_timestamp: u64) -> u64 {
-            // For localhost testing, use simple calculation
-            if self.server_addr.ip().is_loopback() {
-                let now_ms = SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap_or(Duration::from_secs(0))
-                    .as_millis() as u64;
-
-                return if now_ms >= server_timestamp {
-                    now_ms.saturating_sub(server_timestamp).min(10)
-                } else {
-                    0
-                };
-            }
-
-            // Simplified version of the ping calculation for testing
-            let now_ms = SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap_or(Duration::from_secs(0))
-                .as_millis() as u64;
-
-            let raw_ping = if now_ms >= server_timestamp {
-                now_ms.saturating_sub(server_timestamp)
-            } else {
-                self.real_ping_ms.min(1000)
-            };
-
-            raw_ping.clamp(0, 2000)
-        }
-    }
-
     #[test]
     fn test_connection_state_transitions() {
         // Test the full connection lifecycle
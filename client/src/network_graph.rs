@@ -1,15 +1,341 @@
 //! Network performance graph for real-time network diagnostics
 
+use clap::ValueEnum;
 use macroquad::prelude::*;
-use std::collections::VecDeque;
+use std::collections::{HashSet, VecDeque};
 use std::time::{Duration, Instant};
 
+/// How a graph's value axis maps a quantity to screen position. `Linear`
+/// compresses normal values whenever a single spike occurs; `Log` keeps a
+/// 20ms baseline and a 2000ms spike both legible on the same axis.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum AxisScaling {
+    Linear,
+    Log,
+}
+
+impl Default for AxisScaling {
+    fn default() -> Self {
+        AxisScaling::Linear
+    }
+}
+
+impl AxisScaling {
+    fn toggled(self) -> Self {
+        match self {
+            AxisScaling::Linear => AxisScaling::Log,
+            AxisScaling::Log => AxisScaling::Linear,
+        }
+    }
+
+    /// Maps `value` (clamped to `[0, scale_max]`) to a `[0, 1]` fraction of
+    /// the axis, either linearly or via `ln(v + 1)` normalized against
+    /// `ln(scale_max + 1)` so both a small baseline and a large spike stay
+    /// visible on the same axis.
+    fn fraction(self, value: f32, scale_max: f32) -> f32 {
+        let value = value.max(0.0);
+        let scale_max = scale_max.max(1.0);
+        match self {
+            AxisScaling::Linear => (value / scale_max).min(1.0),
+            AxisScaling::Log => ((value + 1.0).ln() / (scale_max + 1.0).ln()).min(1.0),
+        }
+    }
+
+    /// Grid-line values to draw for this scaling: the existing fixed set for
+    /// `Linear`, or decade boundaries (1, 10, 100, 1000, ...) for `Log`.
+    fn grid_lines(self, scale_max: f32) -> Vec<f32> {
+        match self {
+            AxisScaling::Linear => vec![25.0, 50.0, 100.0, 150.0, 200.0]
+                .into_iter()
+                .filter(|&level| level <= scale_max)
+                .collect(),
+            AxisScaling::Log => {
+                let mut levels = Vec::new();
+                let mut decade = 1.0;
+                while decade <= scale_max {
+                    levels.push(decade);
+                    decade *= 10.0;
+                }
+                levels
+            }
+        }
+    }
+}
+
+/// RFC 6298 RTT-estimator smoothing factors: `alpha` weights each new sample
+/// into the smoothed RTT, `beta` weights it into the RTT variance.
+const SRTT_ALPHA: f32 = 1.0 / 8.0;
+const RTTVAR_BETA: f32 = 1.0 / 4.0;
+
+/// How many sequences ahead of a gap must arrive before that gap is declared
+/// lost rather than just reordered — tolerates up to this many packets
+/// arriving out of order, mirroring QUIC's `kPacketThreshold`.
+const PACKET_THRESHOLD: u32 = 3;
+/// How many recent lost/received declarations the sliding-window loss rate
+/// is computed over.
+const LOSS_WINDOW_SIZE: usize = 100;
+
+/// Declares a sequence lost only once something `PACKET_THRESHOLD` higher
+/// has arrived, so normal reordering doesn't get conflated with genuine
+/// loss the way a raw `sent - received` counter does. Feeds a sliding
+/// window of recent declarations (rather than a periodically-reset raw
+/// counter) into a stable loss-rate estimate, alongside lifetime totals.
+struct SequenceLossDetector {
+    next_expected: Option<u32>,
+    highest_seen: Option<u32>,
+    /// Sequences that arrived ahead of `next_expected` and are still
+    /// awaiting their turn to be declared received.
+    pending: HashSet<u32>,
+    /// `true` per declaration that was lost, oldest first.
+    declarations: VecDeque<bool>,
+    total_lost: u64,
+    total_received: u64,
+}
+
+impl SequenceLossDetector {
+    fn new() -> Self {
+        Self {
+            next_expected: None,
+            highest_seen: None,
+            pending: HashSet::new(),
+            declarations: VecDeque::new(),
+            total_lost: 0,
+            total_received: 0,
+        }
+    }
+
+    /// Records an incoming packet's sequence number and resolves any gap
+    /// that's now old enough to declare lost or received.
+    fn record(&mut self, sequence: u32) {
+        let next_expected = *self.next_expected.get_or_insert(sequence);
+        self.highest_seen = Some(self.highest_seen.map_or(sequence, |h| h.max(sequence)));
+        if sequence >= next_expected {
+            self.pending.insert(sequence);
+        }
+
+        let highest_seen = self.highest_seen.unwrap();
+        while self
+            .next_expected
+            .is_some_and(|next| next + PACKET_THRESHOLD <= highest_seen)
+        {
+            let next = self.next_expected.unwrap();
+            let was_lost = !self.pending.remove(&next);
+            self.declare(was_lost);
+            self.next_expected = Some(next + 1);
+        }
+    }
+
+    fn declare(&mut self, was_lost: bool) {
+        if was_lost {
+            self.total_lost += 1;
+        } else {
+            self.total_received += 1;
+        }
+        self.declarations.push_back(was_lost);
+        while self.declarations.len() > LOSS_WINDOW_SIZE {
+            self.declarations.pop_front();
+        }
+    }
+
+    /// Loss percentage over the sliding window of recent declarations.
+    fn window_loss_percent(&self) -> f32 {
+        if self.declarations.is_empty() {
+            return 0.0;
+        }
+        let lost = self.declarations.iter().filter(|&&lost| lost).count();
+        (lost as f32 / self.declarations.len() as f32) * 100.0
+    }
+}
+
+/// How many recent per-sample bandwidth readings the quick-reference table
+/// (used for the smoothed "current" figure) retains, mirroring veilid's
+/// bandwidth tables.
+const BANDWIDTH_TABLE_SIZE: usize = 10;
+
+/// Count-weighted running average, updated incrementally so neither the
+/// sample count nor a running sum ever needs to be rescanned or can
+/// overflow — the same approach as parity-zcash's `RunningAverage`.
+struct RunningAverage {
+    avg: f64,
+    count: u64,
+}
+
+impl RunningAverage {
+    fn new() -> Self {
+        Self { avg: 0.0, count: 0 }
+    }
+
+    fn update(&mut self, sample: f64) {
+        self.count += 1;
+        self.avg += (sample - self.avg) / self.count as f64;
+    }
+}
+
+/// Tracks RX/TX byte rates: a rolling-window total accumulated since the
+/// last sample, a fixed-size recent-samples table, a lifetime running
+/// average, and a running maximum.
+struct BandwidthTracker {
+    bytes_received_interval: u64,
+    bytes_sent_interval: u64,
+    last_sample_time: Instant,
+
+    rx_table: VecDeque<f64>,
+    tx_table: VecDeque<f64>,
+    rx_lifetime_avg: RunningAverage,
+    tx_lifetime_avg: RunningAverage,
+    max_rx_bps: f64,
+    max_tx_bps: f64,
+}
+
+impl BandwidthTracker {
+    fn new(now: Instant) -> Self {
+        Self {
+            bytes_received_interval: 0,
+            bytes_sent_interval: 0,
+            last_sample_time: now,
+            rx_table: VecDeque::new(),
+            tx_table: VecDeque::new(),
+            rx_lifetime_avg: RunningAverage::new(),
+            tx_lifetime_avg: RunningAverage::new(),
+            max_rx_bps: 0.0,
+            max_tx_bps: 0.0,
+        }
+    }
+
+    fn record_received(&mut self, bytes: usize) {
+        self.bytes_received_interval += bytes as u64;
+    }
+
+    fn record_sent(&mut self, bytes: usize) {
+        self.bytes_sent_interval += bytes as u64;
+    }
+
+    /// Converts the bytes accumulated since the last sample into a
+    /// bytes-per-second rate for each direction, folds it into the table
+    /// and running stats, and resets the accumulators for the next window.
+    fn sample(&mut self, now: Instant) -> (f64, f64) {
+        let elapsed_secs = now
+            .duration_since(self.last_sample_time)
+            .as_secs_f64()
+            .max(f64::EPSILON);
+        let rx_bps = self.bytes_received_interval as f64 / elapsed_secs;
+        let tx_bps = self.bytes_sent_interval as f64 / elapsed_secs;
+
+        self.bytes_received_interval = 0;
+        self.bytes_sent_interval = 0;
+        self.last_sample_time = now;
+
+        self.rx_table.push_back(rx_bps);
+        while self.rx_table.len() > BANDWIDTH_TABLE_SIZE {
+            self.rx_table.pop_front();
+        }
+        self.tx_table.push_back(tx_bps);
+        while self.tx_table.len() > BANDWIDTH_TABLE_SIZE {
+            self.tx_table.pop_front();
+        }
+
+        self.rx_lifetime_avg.update(rx_bps);
+        self.tx_lifetime_avg.update(tx_bps);
+        self.max_rx_bps = self.max_rx_bps.max(rx_bps);
+        self.max_tx_bps = self.max_tx_bps.max(tx_bps);
+
+        (rx_bps, tx_bps)
+    }
+
+    /// The table's average, used as a smoothed "current" reading instead of
+    /// the (noisier) single latest sample.
+    fn table_avg(table: &VecDeque<f64>) -> f64 {
+        if table.is_empty() {
+            0.0
+        } else {
+            table.iter().sum::<f64>() / table.len() as f64
+        }
+    }
+}
+
+/// Tracks goodput: a delivery-rate estimate derived from bytes the server
+/// has actually acknowledged, rather than raw bytes handed to the socket.
+/// `BandwidthTracker` answers "how much are we pushing onto the wire"; this
+/// answers "how much of that is actually getting through and confirmed",
+/// which is what pacing/congestion logic needs as an input rather than raw
+/// throughput (a high send rate into a lossy link is not the same as a high
+/// delivery rate).
+struct DeliveryRateTracker {
+    last_ack_time: Option<Instant>,
+    rate_table: VecDeque<f64>,
+    lifetime_avg: RunningAverage,
+    max_bps: f64,
+}
+
+impl DeliveryRateTracker {
+    fn new() -> Self {
+        Self {
+            last_ack_time: None,
+            rate_table: VecDeque::new(),
+            lifetime_avg: RunningAverage::new(),
+            max_bps: 0.0,
+        }
+    }
+
+    /// Folds in `acked_bytes` newly confirmed at `ack_time`: the rate is
+    /// `acked_bytes / (ack_time - last_ack_time)`, the same
+    /// earliest-to-latest-ack formula TCP delivery-rate estimation uses.
+    /// The first ack has no prior ack to measure an interval against, so it
+    /// only seeds `last_ack_time`.
+    fn record_ack(&mut self, acked_bytes: usize, ack_time: Instant) {
+        if let Some(last_ack_time) = self.last_ack_time {
+            let elapsed_secs = ack_time.duration_since(last_ack_time).as_secs_f64();
+            if elapsed_secs > 0.0 {
+                let rate_bps = acked_bytes as f64 / elapsed_secs;
+
+                self.rate_table.push_back(rate_bps);
+                while self.rate_table.len() > BANDWIDTH_TABLE_SIZE {
+                    self.rate_table.pop_front();
+                }
+                self.lifetime_avg.update(rate_bps);
+                self.max_bps = self.max_bps.max(rate_bps);
+            }
+        }
+        self.last_ack_time = Some(ack_time);
+    }
+
+    fn current_bps(&self) -> f64 {
+        BandwidthTracker::table_avg(&self.rate_table)
+    }
+}
+
+/// Formats a bytes-per-second rate with a human-readable binary unit.
+fn format_bytes_per_sec(bps: f64) -> String {
+    const KIB: f64 = 1024.0;
+    const MIB: f64 = 1024.0 * 1024.0;
+    if bps >= MIB {
+        format!("{:.2} MiB/s", bps / MIB)
+    } else if bps >= KIB {
+        format!("{:.1} KiB/s", bps / KIB)
+    } else {
+        format!("{:.0} B/s", bps)
+    }
+}
+
 /// Network performance metrics collected over time
 #[derive(Debug, Clone)]
 pub struct NetworkMetrics {
     pub ping_ms: f32,
     pub packet_loss_percent: f32,
-    pub jitter_ms: f32,
+    /// Smoothed round-trip time, RFC 6298 style.
+    pub srtt_ms: f32,
+    /// Smoothed RTT variance, RFC 6298 style — replaces the old flat jitter
+    /// average, and is what gets shaded as a band around `srtt_ms`.
+    pub rttvar_ms: f32,
+    /// Minimum ping observed since startup, drawn as a flat
+    /// reference line so queueing delay above the floor is visible.
+    pub min_rtt_ms: f32,
+    /// Maximum ping observed since startup, paired with
+    /// `min_rtt_ms` and `srtt_ms` for the min/max/avg readout.
+    pub max_rtt_ms: f32,
+    /// Inbound/outbound bytes-per-second sampled over this interval.
+    pub rx_bps: f64,
+    pub tx_bps: f64,
     pub timestamp: Instant,
 }
 
@@ -18,7 +344,12 @@ impl Default for NetworkMetrics {
         Self {
             ping_ms: 0.0,
             packet_loss_percent: 0.0,
-            jitter_ms: 0.0,
+            srtt_ms: 0.0,
+            rttvar_ms: 0.0,
+            min_rtt_ms: 0.0,
+            max_rtt_ms: 0.0,
+            rx_bps: 0.0,
+            tx_bps: 0.0,
             timestamp: Instant::now(),
         }
     }
@@ -43,21 +374,54 @@ pub struct NetworkGraph {
     packets_received: u32,
     last_packet_count_reset: Instant,
     
-    // Jitter calculation
-    last_ping: Option<f32>,
-    jitter_accumulator: f32,
-    jitter_samples: u32,
+    // RFC 6298-style smoothed RTT estimation
+    srtt: Option<f32>,
+    rttvar: f32,
+    min_rtt: Option<f32>,
+    max_rtt: Option<f32>,
     
     // Graph scaling
     ping_scale_max: f32,
     auto_scale: bool,
+    /// Axis mapping for the ping line, the min-RTT line, and the packet-loss
+    /// bars, toggled at runtime and seeded from `--axis-scaling`.
+    ping_axis_scaling: AxisScaling,
+
+    // RX/TX byte-rate accounting, rendered as a stacked sub-graph below ping
+    bandwidth: BandwidthTracker,
+    bandwidth_graph_height: f32,
+    bandwidth_scale_max: f64,
+    /// Axis mapping for the bandwidth sub-graph. Kept as its own field
+    /// (rather than reusing `ping_axis_scaling`) since the two series have
+    /// independent scales, even though the single runtime toggle flips both
+    /// together.
+    bandwidth_axis_scaling: AxisScaling,
+
+    /// Goodput accounting, fed from acked input bytes rather than raw
+    /// sent/received bytes. See `DeliveryRateTracker`.
+    delivery_rate: DeliveryRateTracker,
+
+    // Clock synchronization, for display alongside ping
+    clock_offset_ms: i64,
+    clock_rtt_ms: Option<u64>,
+
+    // Redundant-input bundling stats, for display alongside packet loss
+    unacked_count: usize,
+    resend_rate: f64,
+
+    // Adaptive heartbeat scheduler stats, for display alongside ping
+    ping_spacing_ms: f32,
+    outstanding_ping_count: usize,
+
+    // Sequence-gap packet-loss detection
+    loss_detector: SequenceLossDetector,
 }
 
 impl NetworkGraph {
     pub fn new() -> Self {
         Self {
             metrics_history: VecDeque::new(),
-            max_samples: 100, // Store last 100 samples (10 seconds at 100ms intervals)
+            max_samples: 300, // Store last 300 samples (30 seconds at 100ms intervals)
             sample_interval: Duration::from_millis(100), // Sample every 100ms
             last_sample_time: Instant::now(),
             
@@ -70,14 +434,120 @@ impl NetworkGraph {
             packets_received: 0,
             last_packet_count_reset: Instant::now(),
             
-            last_ping: None,
-            jitter_accumulator: 0.0,
-            jitter_samples: 0,
+            srtt: None,
+            rttvar: 0.0,
+            min_rtt: None,
+            max_rtt: None,
             
             ping_scale_max: 100.0,
             auto_scale: true,
+            ping_axis_scaling: AxisScaling::default(),
+
+            bandwidth: BandwidthTracker::new(Instant::now()),
+            bandwidth_graph_height: 50.0,
+            bandwidth_scale_max: 1024.0,
+            bandwidth_axis_scaling: AxisScaling::default(),
+
+            delivery_rate: DeliveryRateTracker::new(),
+
+            clock_offset_ms: 0,
+            clock_rtt_ms: None,
+
+            unacked_count: 0,
+            resend_rate: 0.0,
+
+            ping_spacing_ms: 0.0,
+            outstanding_ping_count: 0,
+
+            loss_detector: SequenceLossDetector::new(),
         }
     }
+
+    /// Records the latest clock-sync estimate for display in the legend.
+    pub fn record_clock_sync(&mut self, offset_ms: i64, rtt_ms: Option<u64>) {
+        self.clock_offset_ms = offset_ms;
+        self.clock_rtt_ms = rtt_ms;
+    }
+
+    /// Records the adaptive heartbeat scheduler's current spacing and
+    /// outstanding-ping count for display in the legend.
+    pub fn record_ping_schedule(&mut self, spacing: Duration, outstanding_count: usize) {
+        self.ping_spacing_ms = spacing.as_secs_f32() * 1000.0;
+        self.outstanding_ping_count = outstanding_count;
+    }
+
+    /// Records an incoming packet's sequence number (e.g. a `GameState`
+    /// tick) for sequence-gap loss detection, tolerating up to
+    /// `PACKET_THRESHOLD` packets of reordering before declaring a gap lost.
+    pub fn record_sequence(&mut self, sequence: u32) {
+        self.loss_detector.record(sequence);
+    }
+
+    /// Records the latest redundant-input bundling stats for display in the
+    /// legend.
+    pub fn record_input_redundancy(&mut self, unacked_count: usize, resend_rate: f64) {
+        self.unacked_count = unacked_count;
+        self.resend_rate = resend_rate;
+    }
+
+    /// Records bytes received in this tick, e.g. a `Packet`'s serialized
+    /// length, for the RX/TX throughput sub-graph.
+    pub fn record_bytes_received(&mut self, bytes: usize) {
+        self.bandwidth.record_received(bytes);
+    }
+
+    /// Records bytes sent in this tick, e.g. a `Packet`'s serialized length,
+    /// for the RX/TX throughput sub-graph.
+    pub fn record_bytes_sent(&mut self, bytes: usize) {
+        self.bandwidth.record_sent(bytes);
+    }
+
+    /// Smoothed current incoming/outgoing byte rates (the bandwidth table's
+    /// average), for display outside the graph overlay itself — e.g. on the
+    /// always-visible HUD via `RenderConfig`.
+    pub fn current_bandwidth_bps(&self) -> (f64, f64) {
+        (
+            BandwidthTracker::table_avg(&self.bandwidth.rx_table),
+            BandwidthTracker::table_avg(&self.bandwidth.tx_table),
+        )
+    }
+
+    /// Peak incoming/outgoing byte rates observed since startup.
+    pub fn max_bandwidth_bps(&self) -> (f64, f64) {
+        (self.bandwidth.max_rx_bps, self.bandwidth.max_tx_bps)
+    }
+
+    /// Records `acked_bytes` newly confirmed by the server as of `ack_time`,
+    /// for the goodput/delivery-rate estimate. See `DeliveryRateTracker`.
+    pub fn record_delivery_ack(&mut self, acked_bytes: usize, ack_time: Instant) {
+        self.delivery_rate.record_ack(acked_bytes, ack_time);
+    }
+
+    /// Smoothed current delivery rate (bytes/sec actually acknowledged by
+    /// the server), for pacing/congestion logic and the debug HUD to use as
+    /// a goodput figure distinct from raw send/receive throughput.
+    pub fn delivery_rate_bps(&self) -> f64 {
+        self.delivery_rate.current_bps()
+    }
+
+    /// Peak delivery rate observed since startup.
+    pub fn max_delivery_rate_bps(&self) -> f64 {
+        self.delivery_rate.max_bps
+    }
+
+    /// Seeds both the ping and bandwidth axes from `--axis-scaling` at
+    /// startup.
+    pub fn set_axis_scaling(&mut self, scaling: AxisScaling) {
+        self.ping_axis_scaling = scaling;
+        self.bandwidth_axis_scaling = scaling;
+    }
+
+    /// Flips both axes between `Linear` and `Log`, bound to a runtime toggle
+    /// key.
+    pub fn toggle_axis_scaling(&mut self) {
+        self.ping_axis_scaling = self.ping_axis_scaling.toggled();
+        self.bandwidth_axis_scaling = self.bandwidth_axis_scaling.toggled();
+    }
     
     /// Toggle graph visibility
     pub fn toggle_visibility(&mut self) {
@@ -97,15 +567,23 @@ impl NetworkGraph {
     /// Record a packet being received with ping data
     pub fn record_packet_received(&mut self, ping_ms: f32) {
         self.packets_received += 1;
-        
-        // Calculate jitter (ping variance)
-        if let Some(last_ping) = self.last_ping {
-            let ping_diff = (ping_ms - last_ping).abs();
-            self.jitter_accumulator += ping_diff;
-            self.jitter_samples += 1;
+
+        // RFC 6298-style smoothed RTT estimation, adapted from QUIC/overnet
+        // ping trackers: this converges where a flat mean of |ping - last_ping|
+        // never does.
+        match self.srtt {
+            None => {
+                self.srtt = Some(ping_ms);
+                self.rttvar = ping_ms / 2.0;
+            }
+            Some(srtt) => {
+                self.rttvar = (1.0 - RTTVAR_BETA) * self.rttvar + RTTVAR_BETA * (srtt - ping_ms).abs();
+                self.srtt = Some((1.0 - SRTT_ALPHA) * srtt + SRTT_ALPHA * ping_ms);
+            }
         }
-        self.last_ping = Some(ping_ms);
-        
+        self.min_rtt = Some(self.min_rtt.map_or(ping_ms, |min_rtt| min_rtt.min(ping_ms)));
+        self.max_rtt = Some(self.max_rtt.map_or(ping_ms, |max_rtt| max_rtt.max(ping_ms)));
+
         // Sample metrics at fixed intervals for consistent graph
         let now = Instant::now();
         if now.duration_since(self.last_sample_time) >= self.sample_interval {
@@ -117,26 +595,21 @@ impl NetworkGraph {
     /// Sample current network metrics and add to history
     fn sample_metrics(&mut self, current_ping: f32) {
         let now = Instant::now();
-        
-        // Calculate packet loss percentage over last sample period
-        let packet_loss = if self.packets_sent > 0 {
-            let lost_packets = self.packets_sent.saturating_sub(self.packets_received);
-            (lost_packets as f32 / self.packets_sent as f32) * 100.0
-        } else {
-            0.0
-        };
-        
-        // Calculate average jitter over sample period
-        let jitter = if self.jitter_samples > 0 {
-            self.jitter_accumulator / self.jitter_samples as f32
-        } else {
-            0.0
-        };
-        
+
+        let (rx_bps, tx_bps) = self.bandwidth.sample(now);
+
         let metrics = NetworkMetrics {
             ping_ms: current_ping,
-            packet_loss_percent: packet_loss.min(100.0),
-            jitter_ms: jitter,
+            // Sequence-gap loss rate over the sliding window, rather than
+            // the raw sent/received counters, so normal reordering and
+            // in-flight packets don't get counted as drops.
+            packet_loss_percent: self.loss_detector.window_loss_percent(),
+            srtt_ms: self.srtt.unwrap_or(current_ping),
+            rttvar_ms: self.rttvar,
+            min_rtt_ms: self.min_rtt.unwrap_or(current_ping),
+            max_rtt_ms: self.max_rtt.unwrap_or(current_ping),
+            rx_bps,
+            tx_bps,
             timestamp: now,
         };
         
@@ -152,8 +625,6 @@ impl NetworkGraph {
             self.packets_sent = 0;
             self.packets_received = 0;
             self.last_packet_count_reset = now;
-            self.jitter_accumulator = 0.0;
-            self.jitter_samples = 0;
         }
         
         // Auto-scale the graph if enabled
@@ -161,23 +632,31 @@ impl NetworkGraph {
             self.update_auto_scale();
         }
     }
-    
+
     /// Update ping scale based on recent data
     fn update_auto_scale(&mut self) {
         if self.metrics_history.is_empty() {
             return;
         }
-        
+
         let max_ping = self.metrics_history
             .iter()
             .map(|m| m.ping_ms)
             .fold(0.0f32, f32::max);
-        
+
         // Set scale to accommodate highest ping with some headroom
         let desired_scale = (max_ping * 1.2).max(50.0);
-        
+
         // Smooth scale changes to prevent jittery scaling
         self.ping_scale_max = self.ping_scale_max * 0.9 + desired_scale * 0.1;
+
+        let max_bps = self
+            .metrics_history
+            .iter()
+            .map(|m| m.rx_bps.max(m.tx_bps))
+            .fold(0.0f64, f64::max);
+        let desired_bandwidth_scale = (max_bps * 1.2).max(1024.0);
+        self.bandwidth_scale_max = self.bandwidth_scale_max * 0.9 + desired_bandwidth_scale * 0.1;
     }
     
     /// Render the network graph in the top-right corner
@@ -189,7 +668,7 @@ impl NetworkGraph {
         let screen_w = screen_width();
         let base_margin = 20.0;
         let label_space = 40.0;
-        let legend_space = 50.0;
+        let legend_space = 76.0;
         
         // Calculate consistent margins - both top and right need same base margin
         // plus their respective extra spaces
@@ -206,20 +685,30 @@ impl NetworkGraph {
         
         self.draw_background(bg_x, bg_y);
         self.draw_legend(bg_x, bg_y);
-        
+
         // Graph content uses padded position
         self.draw_grid(graph_x, graph_y);
+        self.draw_min_rtt_line(graph_x, graph_y);
+        self.draw_max_rtt_line(graph_x, graph_y);
         self.draw_ping_line(graph_x, graph_y);
         self.draw_packet_loss_bars(graph_x, graph_y);
+        self.draw_bandwidth_graph(graph_x, graph_y);
         self.draw_labels(graph_x, graph_y);
     }
-    
+
+    /// How far below the ping graph's usable area the bandwidth sub-graph's
+    /// panel top sits, mirroring the gap above the time-axis labels.
+    const BANDWIDTH_PANEL_GAP: f32 = 20.0;
+
     /// Draw semi-transparent background
     fn draw_background(&self, x: f32, y: f32) {
         let background_padding = 8.0;
         let label_space = 40.0;
-        let legend_space = 50.0;
-        let bottom_space = 30.0;
+        let legend_space = 76.0;
+        // Room for the time-axis labels, the stacked bandwidth sub-graph and
+        // its own label row, and the throughput, SRTT/loss, lifetime-loss and
+        // goodput stat lines below.
+        let bottom_space = 70.0 + self.bandwidth_graph_height;
         
         draw_rectangle(
             x - background_padding,
@@ -246,13 +735,12 @@ impl NetworkGraph {
         let usable_width = self.graph_width - (self.internal_padding * 2.0);
         let usable_height = self.graph_height - (self.internal_padding * 2.0);
         
-        // Horizontal grid lines (ping levels)
-        let ping_intervals = [25.0, 50.0, 100.0, 150.0, 200.0];
-        for &ping_level in &ping_intervals {
-            if ping_level <= self.ping_scale_max {
-                let grid_y = y + usable_height - (ping_level / self.ping_scale_max * usable_height);
-                draw_line(x, grid_y, x + usable_width, grid_y, 1.0, grid_color);
-            }
+        // Horizontal grid lines (ping levels) — fixed intervals in linear
+        // mode, decade boundaries in log mode.
+        for ping_level in self.ping_axis_scaling.grid_lines(self.ping_scale_max) {
+            let frac = self.ping_axis_scaling.fraction(ping_level, self.ping_scale_max);
+            let grid_y = y + usable_height - frac * usable_height;
+            draw_line(x, grid_y, x + usable_width, grid_y, 1.0, grid_color);
         }
         
         // Vertical grid lines (time intervals)
@@ -278,52 +766,106 @@ impl NetworkGraph {
         }
     }
     
-    /// Draw ping as a continuous line graph
+    /// Draw the smoothed RTT (`srtt`) as a continuous line graph, shaded with
+    /// a `±rttvar` band so the reader can see how much the estimate is
+    /// still bouncing around versus how settled it is.
     fn draw_ping_line(&self, x: f32, y: f32) {
         if self.metrics_history.len() < 2 {
             return;
         }
-        
+
         let usable_width = self.graph_width - (self.internal_padding * 2.0);
         let usable_height = self.graph_height - (self.internal_padding * 2.0);
-        
+
         // Calculate time span for proper X-axis scaling
         let time_span_ms = self.get_time_span_ms();
         if time_span_ms <= 0.0 {
             return;
         }
-        
+
         // Get the oldest timestamp as our reference point
         let oldest_timestamp = self.metrics_history.front().unwrap().timestamp;
-        
+        let to_x = |timestamp: Instant| {
+            let offset = timestamp.duration_since(oldest_timestamp).as_millis() as f32;
+            x + (offset / time_span_ms) * usable_width
+        };
+        let to_y = |value_ms: f32| {
+            y + usable_height - self.ping_axis_scaling.fraction(value_ms, self.ping_scale_max) * usable_height
+        };
+
+        for metrics in self.metrics_history.iter() {
+            let cx = to_x(metrics.timestamp);
+            let band_top = to_y(metrics.srtt_ms + metrics.rttvar_ms);
+            let band_bottom = to_y((metrics.srtt_ms - metrics.rttvar_ms).max(0.0));
+            draw_line(cx, band_top, cx, band_bottom, 1.0, Color::from_rgba(100, 150, 255, 60));
+        }
+
         for i in 1..self.metrics_history.len() {
             let prev_metrics = &self.metrics_history[i - 1];
             let curr_metrics = &self.metrics_history[i];
-            
-            // Calculate X positions based on time differences
-            let prev_time_offset = prev_metrics.timestamp.duration_since(oldest_timestamp).as_millis() as f32;
-            let curr_time_offset = curr_metrics.timestamp.duration_since(oldest_timestamp).as_millis() as f32;
-            
-            let x1 = x + (prev_time_offset / time_span_ms) * usable_width;
-            let y1 = y + usable_height - (prev_metrics.ping_ms / self.ping_scale_max * usable_height);
-            let x2 = x + (curr_time_offset / time_span_ms) * usable_width;
-            let y2 = y + usable_height - (curr_metrics.ping_ms / self.ping_scale_max * usable_height);
-            
-            // Color based on ping quality (these are the colored lines you see)
-            let ping_color = if curr_metrics.ping_ms < 30.0 {
+
+            let x1 = to_x(prev_metrics.timestamp);
+            let y1 = to_y(prev_metrics.srtt_ms);
+            let x2 = to_x(curr_metrics.timestamp);
+            let y2 = to_y(curr_metrics.srtt_ms);
+
+            // Color based on smoothed RTT quality (these are the colored lines you see)
+            let srtt_color = if curr_metrics.srtt_ms < 30.0 {
                 GREEN
-            } else if curr_metrics.ping_ms < 60.0 {
+            } else if curr_metrics.srtt_ms < 60.0 {
                 YELLOW
-            } else if curr_metrics.ping_ms < 100.0 {
+            } else if curr_metrics.srtt_ms < 100.0 {
                 ORANGE
             } else {
                 RED
             };
-            
-            draw_line(x1, y1, x2, y2, 2.0, ping_color);
+
+            draw_line(x1, y1, x2, y2, 2.0, srtt_color);
         }
     }
+
+    /// Draw the running `min_rtt` as a flat reference line, so queueing
+    /// delay above the floor (the gap between `srtt` and this line) is
+    /// visible at a glance.
+    fn draw_min_rtt_line(&self, x: f32, y: f32) {
+        let Some(latest) = self.metrics_history.back() else {
+            return;
+        };
+        let usable_width = self.graph_width - (self.internal_padding * 2.0);
+        let usable_height = self.graph_height - (self.internal_padding * 2.0);
+        let line_y = y + usable_height
+            - self.ping_axis_scaling.fraction(latest.min_rtt_ms, self.ping_scale_max) * usable_height;
+        draw_line(
+            x,
+            line_y,
+            x + usable_width,
+            line_y,
+            1.0,
+            Color::from_rgba(0, 200, 200, 180),
+        );
+    }
     
+    /// Draw the running `max_rtt` as a flat reference line, mirroring
+    /// `draw_min_rtt_line` so the full min/max/avg readout has a visual
+    /// counterpart for the ceiling as well as the floor.
+    fn draw_max_rtt_line(&self, x: f32, y: f32) {
+        let Some(latest) = self.metrics_history.back() else {
+            return;
+        };
+        let usable_width = self.graph_width - (self.internal_padding * 2.0);
+        let usable_height = self.graph_height - (self.internal_padding * 2.0);
+        let line_y = y + usable_height
+            - self.ping_axis_scaling.fraction(latest.max_rtt_ms, self.ping_scale_max) * usable_height;
+        draw_line(
+            x,
+            line_y,
+            x + usable_width,
+            line_y,
+            1.0,
+            Color::from_rgba(200, 100, 0, 180),
+        );
+    }
+
     /// Draw packet loss as colored bars
     fn draw_packet_loss_bars(&self, x: f32, y: f32) {
         let usable_width = self.graph_width - (self.internal_padding * 2.0);
@@ -344,7 +886,8 @@ impl NetworkGraph {
                 // Calculate X position based on time
                 let time_offset = metrics.timestamp.duration_since(oldest_timestamp).as_millis() as f32;
                 let bar_x = x + (time_offset / time_span_ms) * usable_width;
-                let bar_height = (metrics.packet_loss_percent / 10.0 * usable_height * 0.3).min(usable_height * 0.3);
+                let loss_fraction = self.ping_axis_scaling.fraction(metrics.packet_loss_percent, 10.0);
+                let bar_height = loss_fraction * usable_height * 0.3;
                 let bar_y = y + usable_height - bar_height;
                 
                 let loss_color = if metrics.packet_loss_percent < 1.0 {
@@ -357,17 +900,107 @@ impl NetworkGraph {
             }
         }
     }
-    
+
+    /// Draw the RX/TX throughput sub-graph stacked below the ping line,
+    /// sharing the same time axis.
+    fn draw_bandwidth_graph(&self, x: f32, y: f32) {
+        if self.metrics_history.len() < 2 {
+            return;
+        }
+
+        let usable_width = self.graph_width - (self.internal_padding * 2.0);
+        let usable_height = self.graph_height - (self.internal_padding * 2.0);
+        let time_span_ms = self.get_time_span_ms();
+        if time_span_ms <= 0.0 {
+            return;
+        }
+
+        let panel_y = y + usable_height + Self::BANDWIDTH_PANEL_GAP;
+        let panel_height = self.bandwidth_graph_height;
+
+        draw_rectangle_lines(
+            x,
+            panel_y,
+            usable_width,
+            panel_height,
+            1.0,
+            Color::from_rgba(80, 80, 80, 255),
+        );
+
+        let grid_color = Color::from_rgba(50, 50, 50, 255);
+        for level in self
+            .bandwidth_axis_scaling
+            .grid_lines(self.bandwidth_scale_max as f32)
+        {
+            let frac = self
+                .bandwidth_axis_scaling
+                .fraction(level, self.bandwidth_scale_max as f32);
+            let grid_y = panel_y + panel_height - frac * panel_height;
+            draw_line(x, grid_y, x + usable_width, grid_y, 1.0, grid_color);
+        }
+
+        let oldest_timestamp = self.metrics_history.front().unwrap().timestamp;
+        let to_x = |timestamp: Instant| {
+            let offset = timestamp.duration_since(oldest_timestamp).as_millis() as f32;
+            x + (offset / time_span_ms) * usable_width
+        };
+        let to_y = |bps: f64| {
+            let frac = self
+                .bandwidth_axis_scaling
+                .fraction(bps as f32, self.bandwidth_scale_max as f32);
+            panel_y + panel_height - frac * panel_height
+        };
+
+        for i in 1..self.metrics_history.len() {
+            let prev = &self.metrics_history[i - 1];
+            let curr = &self.metrics_history[i];
+
+            let x1 = to_x(prev.timestamp);
+            let x2 = to_x(curr.timestamp);
+
+            draw_line(
+                x1,
+                to_y(prev.rx_bps),
+                x2,
+                to_y(curr.rx_bps),
+                1.5,
+                Color::from_rgba(0, 200, 255, 220),
+            );
+            draw_line(
+                x1,
+                to_y(prev.tx_bps),
+                x2,
+                to_y(curr.tx_bps),
+                1.5,
+                Color::from_rgba(255, 120, 220, 220),
+            );
+        }
+    }
+
     /// Draw scale labels and current values
     fn draw_labels(&self, x: f32, y: f32) {
         let label_color = WHITE;
         let font_size = 11.0;
         let usable_width = self.graph_width - (self.internal_padding * 2.0);
         let usable_height = self.graph_height - (self.internal_padding * 2.0);
-        
-        let ping_levels = [0.0, self.ping_scale_max * 0.25, self.ping_scale_max * 0.5, self.ping_scale_max * 0.75, self.ping_scale_max];
+
+        let ping_levels: Vec<f32> = match self.ping_axis_scaling {
+            AxisScaling::Linear => vec![
+                0.0,
+                self.ping_scale_max * 0.25,
+                self.ping_scale_max * 0.5,
+                self.ping_scale_max * 0.75,
+                self.ping_scale_max,
+            ],
+            AxisScaling::Log => {
+                let mut levels = vec![0.0];
+                levels.extend(self.ping_axis_scaling.grid_lines(self.ping_scale_max));
+                levels
+            }
+        };
         for &ping_level in &ping_levels {
-            let label_y = y + usable_height - (ping_level / self.ping_scale_max * usable_height);
+            let label_y = y + usable_height
+                - self.ping_axis_scaling.fraction(ping_level, self.ping_scale_max) * usable_height;
             let label_text = if ping_level == 0.0 {
                 "0ms".to_string()
             } else {
@@ -375,22 +1008,58 @@ impl NetworkGraph {
             };
             draw_text(&label_text, x + usable_width + 8.0, label_y + 4.0, font_size, label_color);
         }
-        
+
+        let panel_bottom = y + usable_height + Self::BANDWIDTH_PANEL_GAP + self.bandwidth_graph_height;
+        draw_text(
+            &format!("{}/s", format_bytes_per_sec(self.bandwidth_scale_max)),
+            x + usable_width + 8.0,
+            panel_bottom - self.bandwidth_graph_height + 4.0,
+            font_size,
+            label_color,
+        );
+        draw_text("0/s", x + usable_width + 8.0, panel_bottom + 4.0, font_size, label_color);
+
         if let Some(latest) = self.metrics_history.back() {
+            let throughput_info = format!(
+                "RX: {} (avg {}, max {}) | TX: {} (avg {}, max {})",
+                format_bytes_per_sec(BandwidthTracker::table_avg(&self.bandwidth.rx_table)),
+                format_bytes_per_sec(self.bandwidth.rx_lifetime_avg.avg),
+                format_bytes_per_sec(self.bandwidth.max_rx_bps),
+                format_bytes_per_sec(BandwidthTracker::table_avg(&self.bandwidth.tx_table)),
+                format_bytes_per_sec(self.bandwidth.tx_lifetime_avg.avg),
+                format_bytes_per_sec(self.bandwidth.max_tx_bps),
+            );
+            draw_text(&throughput_info, x - self.internal_padding, panel_bottom + 14.0, 10.0, label_color);
+
             let current_info = format!(
-                "Ping: {:.0}ms | Loss: {:.1}% | Jitter: {:.1}ms",
-                latest.ping_ms,
+                "SRTT (avg): {:.0}ms | Loss: {:.1}% | RTTVAR: {:.1}ms | Min: {:.0}ms | Max: {:.0}ms",
+                latest.srtt_ms,
                 latest.packet_loss_percent,
-                latest.jitter_ms
+                latest.rttvar_ms,
+                latest.min_rtt_ms,
+                latest.max_rtt_ms
             );
-            
-            draw_text(&current_info, x - self.internal_padding, y + usable_height + 28.0, font_size, label_color);
+
+            draw_text(&current_info, x - self.internal_padding, panel_bottom + 28.0, font_size, label_color);
+
+            let lifetime_info = format!(
+                "Lifetime: {} lost / {} received",
+                self.loss_detector.total_lost, self.loss_detector.total_received
+            );
+            draw_text(&lifetime_info, x - self.internal_padding, panel_bottom + 40.0, font_size, label_color);
+
+            let goodput_info = format!(
+                "Goodput: {} (max {})",
+                format_bytes_per_sec(self.delivery_rate.current_bps()),
+                format_bytes_per_sec(self.delivery_rate.max_bps),
+            );
+            draw_text(&goodput_info, x - self.internal_padding, panel_bottom + 52.0, font_size, label_color);
         }
     }
     
     /// Draw legend explaining the graph elements
     fn draw_legend(&self, x: f32, y: f32) {
-        let legend_space = 50.0;
+        let legend_space = 76.0;
         let legend_y = y - legend_space + 10.0;
         let font_size = 11.0;
         
@@ -399,28 +1068,66 @@ impl NetworkGraph {
         
         // Explanation of different visual elements
         let explanation_y = legend_y + 12.0;
-        draw_text("Line = Ping | Red bars = Packet loss", x, explanation_y, 10.0, Color::from_rgba(180, 180, 180, 255));
+        draw_text("Line = Ping | Red bars = Packet loss | Cyan/orange = Min/Max", x, explanation_y, 10.0, Color::from_rgba(180, 180, 180, 255));
         
-        // Current ping quality indicator
+        // Current smoothed-RTT quality indicator
         if let Some(latest) = self.metrics_history.back() {
-            let ping_explanation = if latest.ping_ms < 30.0 {
+            let ping_explanation = if latest.srtt_ms < 30.0 {
                 "Excellent"
-            } else if latest.ping_ms < 60.0 {
+            } else if latest.srtt_ms < 60.0 {
                 "Good"
-            } else if latest.ping_ms < 100.0 {
+            } else if latest.srtt_ms < 100.0 {
                 "Fair"
             } else {
                 "Poor"
             };
-            
-            let quality_color = if latest.ping_ms < 30.0 { GREEN }
-            else if latest.ping_ms < 60.0 { YELLOW }
-            else if latest.ping_ms < 100.0 { ORANGE }
+
+            let quality_color = if latest.srtt_ms < 30.0 { GREEN }
+            else if latest.srtt_ms < 60.0 { YELLOW }
+            else if latest.srtt_ms < 100.0 { ORANGE }
             else { RED };
             
             draw_text("Quality:", x, explanation_y + 12.0, 10.0, WHITE);
             draw_text(ping_explanation, x + 45.0, explanation_y + 12.0, 10.0, quality_color);
         }
+
+        // Clock sync offset/RTT, so a desynced clock is visible at a glance
+        let clock_sync_text = match self.clock_rtt_ms {
+            Some(rtt_ms) => format!("Clock offset: {}ms (rtt {}ms)", self.clock_offset_ms, rtt_ms),
+            None => "Clock offset: (no sync yet)".to_string(),
+        };
+        draw_text(&clock_sync_text, x, explanation_y + 24.0, 10.0, Color::from_rgba(180, 180, 180, 255));
+
+        // Redundant-input bundling stats, so a lossy link resending heavily
+        // is visible alongside the packet-loss bars above
+        let redundancy_text = format!(
+            "Unacked: {} | Resent: {:.0}%",
+            self.unacked_count,
+            self.resend_rate * 100.0
+        );
+        draw_text(&redundancy_text, x, explanation_y + 36.0, 10.0, Color::from_rgba(180, 180, 180, 255));
+
+        // Adaptive heartbeat scheduler stats, so a shrinking spacing (link
+        // getting unstable) is visible alongside the rest of the legend
+        let ping_schedule_text = format!(
+            "Ping spacing: {:.0}ms | Outstanding: {}",
+            self.ping_spacing_ms, self.outstanding_ping_count
+        );
+        draw_text(&ping_schedule_text, x, explanation_y + 48.0, 10.0, Color::from_rgba(180, 180, 180, 255));
+
+        // Bandwidth sub-graph key, so the cyan/magenta lines below are
+        // legible without having to guess which direction is which
+        let axis_scaling_text = format!(
+            "Bandwidth: cyan = RX, magenta = TX | Axis: {:?} (L to toggle)",
+            self.ping_axis_scaling
+        );
+        draw_text(
+            &axis_scaling_text,
+            x,
+            explanation_y + 60.0,
+            10.0,
+            Color::from_rgba(180, 180, 180, 255),
+        );
     }
     
     /// Calculate the time span covered by the current metrics history in milliseconds
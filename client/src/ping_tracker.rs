@@ -0,0 +1,247 @@
+//! Adaptive heartbeat scheduling, modeled on overnet's `ping_tracker`.
+//!
+//! `network_graph::NetworkGraph` derives its ping estimate from the
+//! `GameState` snapshot timestamps the server already sends every tick, so
+//! `network::Client` has never needed a dedicated heartbeat to measure RTT.
+//! `PingTracker` is the piece that *would* drive `Packet::Ping`/`Packet::Pong`
+//! on a standalone cadence if a caller wants one independent of snapshot
+//! traffic (e.g. while disconnected from game state, or to keep a NAT mapping
+//! alive the way `nat_traversal::KeepaliveMonitor` does) — it tracks
+//! outstanding pings by id, expires ones that never got a reply, and adapts
+//! how often to send the next one based on how surprising the last RTT
+//! sample was, shrinking toward `MIN_PING_SPACING` when the link looks
+//! unstable and growing back toward `MAX_PING_SPACING` when it's calm.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Never ping more often than this, even on a wildly unstable link.
+pub const MIN_PING_SPACING: Duration = Duration::from_millis(100);
+/// Never let the spacing drift past this on a calm link.
+pub const MAX_PING_SPACING: Duration = Duration::from_secs(20);
+/// An outstanding ping older than this is presumed lost rather than still
+/// in flight.
+const OUTSTANDING_PING_TIMEOUT: Duration = Duration::from_secs(15);
+
+const SPACING_SHRINK_FACTOR: f32 = 0.5;
+const SPACING_GROWTH_FACTOR: f32 = 1.25;
+
+const SRTT_ALPHA: f32 = 1.0 / 8.0;
+const RTTVAR_BETA: f32 = 1.0 / 4.0;
+
+/// Tracks outstanding pings and adapts the spacing between them.
+pub struct PingTracker {
+    spacing: Duration,
+    last_sent: Option<Instant>,
+    next_ping_id: u64,
+    outstanding: HashMap<u64, Instant>,
+    srtt: Option<f32>,
+    rttvar: f32,
+    lost_count: u64,
+}
+
+impl PingTracker {
+    pub fn new() -> Self {
+        Self {
+            spacing: MAX_PING_SPACING,
+            last_sent: None,
+            next_ping_id: 0,
+            outstanding: HashMap::new(),
+            srtt: None,
+            rttvar: 0.0,
+            lost_count: 0,
+        }
+    }
+
+    /// Whether it's time to send another ping, given the current spacing.
+    pub fn due(&self, now: Instant) -> bool {
+        match self.last_sent {
+            Some(last_sent) => now.duration_since(last_sent) >= self.spacing,
+            None => true,
+        }
+    }
+
+    /// Assigns the next monotonically increasing ping id, records it as
+    /// outstanding, and returns it to be echoed as the `Heartbeat`/`Ping`
+    /// timestamp/nonce.
+    pub fn next_ping(&mut self, now: Instant) -> u64 {
+        let ping_id = self.next_ping_id;
+        self.next_ping_id += 1;
+        self.outstanding.insert(ping_id, now);
+        self.last_sent = Some(now);
+        ping_id
+    }
+
+    /// Records a reply to `ping_id`, updates the smoothed RTT estimate, and
+    /// adapts `spacing` based on how surprising this sample was relative to
+    /// the estimate as it stood *before* this sample. Returns the measured
+    /// RTT, or `None` if `ping_id` isn't outstanding (already replied to, or
+    /// expired as lost).
+    pub fn on_pong(&mut self, ping_id: u64, now: Instant) -> Option<Duration> {
+        let sent_at = self.outstanding.remove(&ping_id)?;
+        let rtt = now.saturating_duration_since(sent_at);
+        let rtt_ms = rtt.as_secs_f32() * 1000.0;
+
+        match self.srtt {
+            None => {
+                self.srtt = Some(rtt_ms);
+                self.rttvar = rtt_ms / 2.0;
+            }
+            Some(srtt) => {
+                let deviation = (srtt - rtt_ms).abs();
+                let surprising = deviation > self.rttvar;
+
+                self.rttvar = (1.0 - RTTVAR_BETA) * self.rttvar + RTTVAR_BETA * deviation;
+                self.srtt = Some((1.0 - SRTT_ALPHA) * srtt + SRTT_ALPHA * rtt_ms);
+
+                let spacing_secs = self.spacing.as_secs_f32();
+                let adapted_secs = if surprising {
+                    spacing_secs * SPACING_SHRINK_FACTOR
+                } else {
+                    spacing_secs * SPACING_GROWTH_FACTOR
+                };
+                self.spacing = Duration::from_secs_f32(adapted_secs)
+                    .clamp(MIN_PING_SPACING, MAX_PING_SPACING);
+            }
+        }
+
+        Some(rtt)
+    }
+
+    /// Drops outstanding pings older than [`OUTSTANDING_PING_TIMEOUT`],
+    /// counting each as lost. Returns how many were expired.
+    pub fn expire_stale(&mut self, now: Instant) -> usize {
+        let before = self.outstanding.len();
+        self.outstanding
+            .retain(|_, sent_at| now.duration_since(*sent_at) < OUTSTANDING_PING_TIMEOUT);
+        let expired = before - self.outstanding.len();
+        self.lost_count += expired as u64;
+        expired
+    }
+
+    pub fn spacing(&self) -> Duration {
+        self.spacing
+    }
+
+    pub fn outstanding_count(&self) -> usize {
+        self.outstanding.len()
+    }
+
+    pub fn lost_count(&self) -> u64 {
+        self.lost_count
+    }
+}
+
+impl Default for PingTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn not_due_until_spacing_elapses() {
+        let mut tracker = PingTracker::new();
+        let now = Instant::now();
+        assert!(tracker.due(now));
+
+        tracker.next_ping(now);
+        assert!(!tracker.due(now));
+        assert!(tracker.due(now + MAX_PING_SPACING));
+    }
+
+    #[test]
+    fn first_sample_seeds_srtt_without_changing_spacing() {
+        let mut tracker = PingTracker::new();
+        let now = Instant::now();
+        let id = tracker.next_ping(now);
+
+        let spacing_before = tracker.spacing();
+        tracker.on_pong(id, now + Duration::from_millis(40));
+        assert_eq!(tracker.spacing(), spacing_before);
+    }
+
+    #[test]
+    fn a_surprising_sample_shrinks_spacing_toward_the_minimum() {
+        let mut tracker = PingTracker::new();
+        let now = Instant::now();
+
+        let id1 = tracker.next_ping(now);
+        tracker.on_pong(id1, now + Duration::from_millis(40));
+        let spacing_after_first = tracker.spacing();
+
+        // Wildly higher than srtt (40ms) and rttvar (20ms) -> surprising.
+        let id2 = tracker.next_ping(now + Duration::from_secs(1));
+        tracker.on_pong(id2, now + Duration::from_secs(1) + Duration::from_millis(900));
+
+        assert!(tracker.spacing() < spacing_after_first);
+    }
+
+    #[test]
+    fn a_calm_sample_grows_spacing_toward_the_maximum() {
+        let mut tracker = PingTracker::new();
+        let now = Instant::now();
+
+        let id1 = tracker.next_ping(now);
+        tracker.on_pong(id1, now + Duration::from_millis(40));
+        // Force spacing down from the default max so growth is observable.
+        tracker.spacing = Duration::from_secs(1);
+        let spacing_before = tracker.spacing();
+
+        let id2 = tracker.next_ping(now + Duration::from_secs(2));
+        tracker.on_pong(id2, now + Duration::from_secs(2) + Duration::from_millis(41));
+
+        assert!(tracker.spacing() > spacing_before);
+    }
+
+    #[test]
+    fn spacing_never_exceeds_its_configured_bounds() {
+        let mut tracker = PingTracker::new();
+        tracker.spacing = MIN_PING_SPACING;
+        let now = Instant::now();
+        let id1 = tracker.next_ping(now);
+        tracker.on_pong(id1, now + Duration::from_millis(40));
+
+        for i in 0..50 {
+            let t = now + Duration::from_secs(i + 1);
+            let id = tracker.next_ping(t);
+            tracker.on_pong(id, t + Duration::from_millis(40));
+            assert!(tracker.spacing() >= MIN_PING_SPACING);
+            assert!(tracker.spacing() <= MAX_PING_SPACING);
+        }
+    }
+
+    #[test]
+    fn a_pong_for_an_unknown_ping_id_is_ignored() {
+        let mut tracker = PingTracker::new();
+        assert!(tracker.on_pong(999, Instant::now()).is_none());
+    }
+
+    #[test]
+    fn stale_outstanding_pings_expire_and_count_as_lost() {
+        let mut tracker = PingTracker::new();
+        let now = Instant::now();
+        tracker.next_ping(now);
+        assert_eq!(tracker.outstanding_count(), 1);
+
+        let expired = tracker.expire_stale(now + Duration::from_secs(16));
+        assert_eq!(expired, 1);
+        assert_eq!(tracker.outstanding_count(), 0);
+        assert_eq!(tracker.lost_count(), 1);
+    }
+
+    #[test]
+    fn a_ping_replied_to_before_its_timeout_does_not_expire() {
+        let mut tracker = PingTracker::new();
+        let now = Instant::now();
+        let id = tracker.next_ping(now);
+        tracker.on_pong(id, now + Duration::from_millis(50));
+
+        let expired = tracker.expire_stale(now + Duration::from_secs(16));
+        assert_eq!(expired, 0);
+        assert_eq!(tracker.lost_count(), 0);
+    }
+}
@@ -0,0 +1,276 @@
+//! Rendered-frame recording and scrubbable playback.
+//!
+//! Mirrors `input_replay`'s `InputRecorder`/`InputPlayer` pair, but captures
+//! the *output* of a frame rather than the input that produced it:
+//! `FrameRecorder` appends each rendered frame's `players` slice and
+//! `RenderConfig` netcode flags to an in-memory timeline, serializable to
+//! disk. `FramePlayer` reads such a timeline back and exposes VCR-style
+//! scrubbing (play/pause/step) over it, letting a recorded session be
+//! replayed and inspected frame-by-frame independent of any live connection.
+
+use crate::rendering::RenderConfig;
+use shared::Player;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Everything `Renderer::render_frame` needs to draw one frame, captured
+/// verbatim from a live session.
+pub type FrameSnapshot = (Vec<Player>, RenderConfig);
+
+/// Captures a live sequence of rendered frames for later playback.
+#[derive(Debug, Default)]
+pub struct FrameRecorder {
+    frames: Vec<FrameSnapshot>,
+}
+
+impl FrameRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends one rendered frame to the timeline.
+    pub fn record(&mut self, players: Vec<Player>, config: RenderConfig) {
+        self.frames.push((players, config));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn frames(&self) -> &[FrameSnapshot] {
+        &self.frames
+    }
+
+    /// Serializes the recording to `path` as bincode.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let bytes = bincode::serialize(&self.frames)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, bytes)
+    }
+}
+
+/// Whether playback is advancing on its own or held on the current frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PlaybackState {
+    Playing,
+    Paused,
+}
+
+/// Replays a recording made by `FrameRecorder`, with a scrub cursor a caller
+/// can drive by elapsed time (`advance`) or one frame at a time (`step`).
+#[derive(Debug)]
+pub struct FramePlayer {
+    frames: Vec<FrameSnapshot>,
+    cursor: usize,
+    state: PlaybackState,
+}
+
+impl FramePlayer {
+    /// Loads a recording previously written by `FrameRecorder::save`.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let frames: Vec<FrameSnapshot> = bincode::deserialize(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self {
+            frames,
+            cursor: 0,
+            state: PlaybackState::Paused,
+        })
+    }
+
+    pub fn play(&mut self) {
+        self.state = PlaybackState::Playing;
+    }
+
+    pub fn pause(&mut self) {
+        self.state = PlaybackState::Paused;
+    }
+
+    pub fn toggle_play_pause(&mut self) {
+        self.state = match self.state {
+            PlaybackState::Playing => PlaybackState::Paused,
+            PlaybackState::Paused => PlaybackState::Playing,
+        };
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.state == PlaybackState::Playing
+    }
+
+    /// Advances the cursor by one frame, regardless of play/pause state.
+    /// Clamped at the last frame rather than wrapping, so repeated stepping
+    /// past the end just holds on the final frame.
+    pub fn step(&mut self) {
+        if self.cursor + 1 < self.frames.len() {
+            self.cursor += 1;
+        }
+    }
+
+    /// Steps the cursor forward if playing; a no-op while paused. Intended
+    /// to be called once per rendered frame at the recording's own frame
+    /// rate, mirroring how `FrameRecorder::record` was originally driven.
+    pub fn advance(&mut self) {
+        if self.state == PlaybackState::Playing {
+            self.step();
+        }
+    }
+
+    /// The frame the scrub cursor currently sits on, if the recording has
+    /// any frames at all.
+    pub fn current(&self) -> Option<&FrameSnapshot> {
+        self.frames.get(self.cursor)
+    }
+
+    /// 1-based current frame number and total frame count, for the scrub
+    /// bar's "current / total" readout.
+    pub fn progress(&self) -> (usize, usize) {
+        let total = self.frames.len();
+        let current = if total == 0 { 0 } else { self.cursor + 1 };
+        (current, total)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !self.frames.is_empty() && self.cursor + 1 >= self.frames.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture_config() -> RenderConfig {
+        RenderConfig {
+            client_id: Some(1),
+            prediction_enabled: true,
+            reconciliation_enabled: true,
+            interpolation_enabled: true,
+            real_ping_ms: 20,
+            fake_ping_ms: 0,
+            ping_ms: 20,
+            current_input: None,
+            show_own_nametag: true,
+            show_reconciliation_debug: false,
+            server_position: None,
+            incoming_avg_bandwidth: 0.0,
+            outgoing_avg_bandwidth: 0.0,
+            incoming_max_bandwidth: 0.0,
+            outgoing_max_bandwidth: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_recorder_starts_empty() {
+        let recorder = FrameRecorder::new();
+        assert!(recorder.is_empty());
+        assert_eq!(recorder.len(), 0);
+    }
+
+    #[test]
+    fn test_recorder_appends_frames_in_order() {
+        let mut recorder = FrameRecorder::new();
+        recorder.record(vec![Player::new(1, 0.0, 0.0)], fixture_config());
+        recorder.record(vec![Player::new(1, 10.0, 0.0)], fixture_config());
+
+        assert_eq!(recorder.len(), 2);
+        assert_eq!(recorder.frames()[1].0[0].x, 10.0);
+    }
+
+    #[test]
+    fn test_player_progress_reports_one_based_current_frame() {
+        let frames = vec![
+            (vec![Player::new(1, 0.0, 0.0)], fixture_config()),
+            (vec![Player::new(1, 5.0, 0.0)], fixture_config()),
+            (vec![Player::new(1, 10.0, 0.0)], fixture_config()),
+        ];
+        let player = FramePlayer {
+            frames,
+            cursor: 1,
+            state: PlaybackState::Paused,
+        };
+
+        assert_eq!(player.progress(), (2, 3));
+    }
+
+    #[test]
+    fn test_step_advances_one_frame_and_clamps_at_the_end() {
+        let frames = vec![
+            (vec![Player::new(1, 0.0, 0.0)], fixture_config()),
+            (vec![Player::new(1, 5.0, 0.0)], fixture_config()),
+        ];
+        let mut player = FramePlayer {
+            frames,
+            cursor: 0,
+            state: PlaybackState::Paused,
+        };
+
+        player.step();
+        assert_eq!(player.progress(), (2, 2));
+        assert!(player.is_finished());
+
+        // Stepping past the end holds on the last frame.
+        player.step();
+        assert_eq!(player.progress(), (2, 2));
+    }
+
+    #[test]
+    fn test_advance_only_moves_the_cursor_while_playing() {
+        let frames = vec![
+            (vec![Player::new(1, 0.0, 0.0)], fixture_config()),
+            (vec![Player::new(1, 5.0, 0.0)], fixture_config()),
+        ];
+        let mut player = FramePlayer {
+            frames,
+            cursor: 0,
+            state: PlaybackState::Paused,
+        };
+
+        player.advance();
+        assert_eq!(player.progress(), (1, 2));
+
+        player.play();
+        player.advance();
+        assert_eq!(player.progress(), (2, 2));
+    }
+
+    #[test]
+    fn test_toggle_play_pause_flips_state() {
+        let mut player = FramePlayer {
+            frames: Vec::new(),
+            cursor: 0,
+            state: PlaybackState::Paused,
+        };
+
+        assert!(!player.is_playing());
+        player.toggle_play_pause();
+        assert!(player.is_playing());
+        player.toggle_play_pause();
+        assert!(!player.is_playing());
+    }
+
+    #[test]
+    fn test_record_save_load_round_trips_byte_for_byte() {
+        let mut recorder = FrameRecorder::new();
+        recorder.record(vec![Player::new(1, 3.0, 4.0)], fixture_config());
+        recorder.record(vec![Player::new(1, 6.0, 8.0)], fixture_config());
+
+        let mut path = std::env::temp_dir();
+        path.push(format!("render_replay_test_{}.replay", std::process::id()));
+        recorder.save(&path).unwrap();
+
+        let player = FramePlayer::load(&path).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(player.frames.len(), 2);
+        assert_eq!(player.frames[0].0[0].x, 3.0);
+        assert_eq!(player.frames[1].0[0].y, 8.0);
+        assert_eq!(
+            bincode::serialize(&player.frames).unwrap(),
+            bincode::serialize(recorder.frames()).unwrap()
+        );
+    }
+}
@@ -1,10 +1,108 @@
 //! Client rendering system for players, UI, and debug visualizations
 
+use crate::effects::{EffectKind, EffectSystem};
 use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
 use shared::{Player, FLOOR_Y, PLAYER_SIZE};
+use std::time::Instant;
+
+/// Fixed virtual resolution the world is authored at. World-space drawing
+/// goes through `Camera::world_to_screen`/`scale_length`, which letterboxes
+/// this canvas onto whatever the actual window size is, so the view stays
+/// proportioned correctly on resize instead of stretching.
+const VIRTUAL_WIDTH: f32 = 800.0;
+const VIRTUAL_HEIGHT: f32 = 600.0;
+
+/// Camera transform from world space to screen space: a world-space center,
+/// a zoom factor, and fixed-aspect letterboxing (bars on whichever axis is
+/// relatively too wide, rather than stretching world entities). `center` can
+/// smoothly follow any world position via `follow_target` — not just the
+/// local player's — so the same camera also supports spectating a remote
+/// player.
+pub struct Camera {
+    pub center: (f32, f32),
+    pub zoom: f32,
+    pub follow: bool,
+    /// Time constant (seconds) for `center`'s exponential-decay lerp toward
+    /// a followed target, mirroring `ClientGameState::render_smoothing_time_constant`'s
+    /// CrystalOrb-style smoothing.
+    follow_time_constant: f32,
+}
 
-/// Configuration for rendering a single frame
-#[derive(Debug, Clone)]
+impl Camera {
+    pub fn new() -> Self {
+        Self {
+            center: (VIRTUAL_WIDTH / 2.0, VIRTUAL_HEIGHT / 2.0),
+            zoom: 1.0,
+            follow: true,
+            follow_time_constant: 0.15,
+        }
+    }
+
+    pub fn toggle_follow(&mut self) {
+        self.follow = !self.follow;
+    }
+
+    /// Smoothly lerps `center` toward `target`, exponential-decay style over
+    /// `dt` seconds. A no-op while `follow` is disabled, so a spectator or
+    /// free-look mode can hold the camera still.
+    pub fn follow_target(&mut self, target: (f32, f32), dt: f32) {
+        if !self.follow || dt <= 0.0 {
+            return;
+        }
+        let alpha = 1.0 - (-dt / self.follow_time_constant).exp();
+        self.center.0 += (target.0 - self.center.0) * alpha;
+        self.center.1 += (target.1 - self.center.1) * alpha;
+    }
+
+    /// The uniform scale factor and screen-space origin offset that
+    /// letterboxes the `VIRTUAL_WIDTH`x`VIRTUAL_HEIGHT` world canvas onto the
+    /// actual window, preserving aspect ratio. Recomputed from the current
+    /// `screen_width`/`screen_height` every call, so a `WindowResized` event
+    /// needs no explicit handling — the next frame just picks up the new
+    /// dimensions.
+    fn viewport(&self) -> (f32, f32, f32) {
+        let screen_w = screen_width();
+        let screen_h = screen_height();
+        let scale = (screen_w / VIRTUAL_WIDTH).min(screen_h / VIRTUAL_HEIGHT);
+        let offset_x = (screen_w - VIRTUAL_WIDTH * scale) / 2.0;
+        let offset_y = (screen_h - VIRTUAL_HEIGHT * scale) / 2.0;
+        (scale, offset_x, offset_y)
+    }
+
+    /// Converts a world-space point to screen-space pixels, applying zoom,
+    /// the camera center, and the letterboxed viewport scale.
+    pub fn world_to_screen(&self, world: (f32, f32)) -> (f32, f32) {
+        let (scale, offset_x, offset_y) = self.viewport();
+        let effective_scale = scale * self.zoom;
+        let x = offset_x + VIRTUAL_WIDTH * scale / 2.0 + (world.0 - self.center.0) * effective_scale;
+        let y = offset_y + VIRTUAL_HEIGHT * scale / 2.0 + (world.1 - self.center.1) * effective_scale;
+        (x, y)
+    }
+
+    /// Scales a world-space length (e.g. `PLAYER_SIZE`) into screen pixels.
+    pub fn scale_length(&self, length: f32) -> f32 {
+        let (scale, _, _) = self.viewport();
+        length * scale * self.zoom
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Formats a bytes-per-second rate compactly for the HUD's bandwidth
+/// readout, in KiB/s.
+fn format_kbps(bps: f64) -> String {
+    format!("{:.1}KB/s", bps / 1024.0)
+}
+
+/// Configuration for rendering a single frame. Serializable so a
+/// `render_replay::FrameRecorder` can capture it verbatim alongside the
+/// `players` slice it was drawn with.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RenderConfig {
     pub client_id: Option<u32>,
     pub prediction_enabled: bool,
@@ -14,6 +112,24 @@ pub struct RenderConfig {
     pub fake_ping_ms: u64,
     pub ping_ms: u64,
     pub current_input: Option<shared::InputState>,
+    /// Whether the local player's own nametag is drawn. Remote players'
+    /// nametags are unaffected; this only lets a player hide their own
+    /// label from cluttering their view of themselves.
+    pub show_own_nametag: bool,
+    /// Whether the server-confirmed "ghost" overlay for the local player is
+    /// drawn, along with the reconciliation-error line and magnitude text.
+    pub show_reconciliation_debug: bool,
+    /// The local player's last server-confirmed position, ignoring
+    /// prediction entirely. `None` until the first `GameState` arrives.
+    pub server_position: Option<(f32, f32)>,
+    /// Smoothed current incoming/outgoing bytes-per-second, from
+    /// `NetworkGraph::current_bandwidth_bps`, so the HUD can show a
+    /// bandwidth readout even while the graph overlay is hidden.
+    pub incoming_avg_bandwidth: f64,
+    pub outgoing_avg_bandwidth: f64,
+    /// Peak incoming/outgoing bytes-per-second observed since startup.
+    pub incoming_max_bandwidth: f64,
+    pub outgoing_max_bandwidth: f64,
 }
 
 /// Extended configuration for UI rendering
@@ -27,18 +143,58 @@ pub struct UiConfig {
     pub fake_ping_ms: u64,
     pub ping_ms: u64,
     pub player_count: usize,
+    pub incoming_avg_bandwidth: f64,
+    pub outgoing_avg_bandwidth: f64,
 }
 
 /// Handles all game rendering including players, UI, and debug visualizations
-pub struct Renderer {}
+pub struct Renderer {
+    pub camera: Camera,
+    last_frame_time: Instant,
+    effects: EffectSystem,
+}
 
 impl Renderer {
     pub fn new() -> Result<Self, Box<dyn std::error::Error>> {
-        Ok(Renderer {})
+        Ok(Renderer {
+            camera: Camera::new(),
+            last_frame_time: Instant::now(),
+            effects: EffectSystem::new(),
+        })
     }
 
-    /// Renders a complete frame with players and UI
+    /// Renders a complete frame with players and UI, from live network state.
+    /// Thin wrapper around `render_frame` so live callers don't need to know
+    /// about the snapshot-based playback path.
     pub fn render(&mut self, players: &[Player], config: RenderConfig) {
+        self.render_frame(players, &config);
+    }
+
+    /// Renders a complete frame from a `(players, config)` snapshot, with no
+    /// assumption about where it came from — a live `Client` tick or a
+    /// `render_replay::FramePlayer` frame draw identically through here.
+    /// World entities (floor, players) are transformed through `camera`;
+    /// `draw_ui`/`draw_scrub_bar` stay pinned in raw screen space.
+    pub fn render_frame(&mut self, players: &[Player], config: &RenderConfig) {
+        let now = Instant::now();
+        let dt = (now - self.last_frame_time).as_secs_f32();
+        self.last_frame_time = now;
+
+        if let Some(local) = players.iter().find(|p| Some(p.id) == config.client_id) {
+            let target = (local.x + PLAYER_SIZE / 2.0, local.y + PLAYER_SIZE / 2.0);
+            self.camera.follow_target(target, dt);
+        }
+
+        for player in players {
+            self.effects.observe_player(player);
+        }
+        for (i, a) in players.iter().enumerate() {
+            for b in &players[i + 1..] {
+                self.effects.observe_collision(a, b);
+            }
+        }
+        self.effects.update(dt);
+
         clear_background(Color::from_rgba(26, 26, 26, 255));
 
         self.draw_floor();
@@ -55,12 +211,20 @@ impl Renderer {
 
             // Show velocity vector for local player only
             if is_local_player {
-                self.draw_velocity_vector(player, &config);
+                self.draw_velocity_vector(player, config);
             }
 
-            self.draw_player_id(player);
+            if !is_local_player || config.show_own_nametag {
+                self.draw_player_id(player);
+            }
+
+            if is_local_player && config.show_reconciliation_debug {
+                self.draw_reconciliation_debug(player, config);
+            }
         }
 
+        self.draw_effects();
+
         let ui_config = UiConfig {
             client_id: config.client_id,
             prediction_enabled: config.prediction_enabled,
@@ -70,35 +234,62 @@ impl Renderer {
             fake_ping_ms: config.fake_ping_ms,
             ping_ms: config.ping_ms,
             player_count: players.len(),
+            incoming_avg_bandwidth: config.incoming_avg_bandwidth,
+            outgoing_avg_bandwidth: config.outgoing_avg_bandwidth,
         };
         self.draw_ui(ui_config);
     }
 
     fn draw_floor(&mut self) {
-        let floor_y = FLOOR_Y;
-        let current_width = screen_width();
-        let current_height = screen_height();
+        let (left, top) = self.camera.world_to_screen((0.0, FLOOR_Y));
+        let (right, bottom) = self.camera.world_to_screen((VIRTUAL_WIDTH, VIRTUAL_HEIGHT));
         draw_rectangle(
-            0.0,
-            floor_y,
-            current_width,
-            current_height - floor_y,
+            left,
+            top,
+            right - left,
+            bottom - top,
             Color::from_rgba(68, 68, 68, 255),
         );
     }
 
     fn draw_player(&mut self, player: &Player, color: Color) {
-        draw_rectangle(player.x, player.y, PLAYER_SIZE, PLAYER_SIZE, color);
-        draw_rectangle_lines(player.x, player.y, PLAYER_SIZE, PLAYER_SIZE, 2.0, WHITE);
+        let (x, y) = self.camera.world_to_screen((player.x, player.y));
+        let size = self.camera.scale_length(PLAYER_SIZE);
+        draw_rectangle(x, y, size, size, color);
+        draw_rectangle_lines(x, y, size, size, 2.0, WHITE);
+    }
+
+    /// Draws every live transient effect, fading each one's alpha out over
+    /// its lifetime. Dust puffs are small gray squares, trails a thin yellow
+    /// streak, and collision flashes an expanding white ring.
+    fn draw_effects(&mut self) {
+        for effect in self.effects.effects() {
+            let (x, y) = self.camera.world_to_screen((effect.x, effect.y));
+            let alpha = (effect.alpha() * 255.0) as u8;
+
+            match effect.kind {
+                EffectKind::Dust => {
+                    let size = self.camera.scale_length(6.0);
+                    draw_rectangle(x - size / 2.0, y - size / 2.0, size, size, Color::from_rgba(180, 170, 150, alpha));
+                }
+                EffectKind::Trail => {
+                    let half = self.camera.scale_length(PLAYER_SIZE / 2.0);
+                    draw_line(x - half, y, x + half, y, 2.0, Color::from_rgba(255, 230, 100, alpha));
+                }
+                EffectKind::Flash => {
+                    let radius = self.camera.scale_length(PLAYER_SIZE * (1.0 - effect.alpha()) + 4.0);
+                    draw_circle_lines(x, y, radius, 2.0, Color::from_rgba(255, 255, 255, alpha));
+                }
+            }
+        }
     }
 
     /// Draws velocity vector for debugging player movement
     fn draw_velocity_vector(&mut self, player: &Player, config: &RenderConfig) {
-        let center_x = player.x + PLAYER_SIZE / 2.0;
-        let center_y = player.y + PLAYER_SIZE / 2.0;
+        let center_world = (player.x + PLAYER_SIZE / 2.0, player.y + PLAYER_SIZE / 2.0);
 
         let vel_scale = 0.15;
-        
+
         // When predictions are enabled, use the player's actual velocity
         // When predictions are disabled, calculate velocity from current input to avoid stale server data
         let (vel_x, vel_y) = if config.prediction_enabled {
@@ -119,11 +310,12 @@ impl Renderer {
             (player.vel_x, player.vel_y)
         };
 
-        let end_x = center_x + vel_x * vel_scale;
-        let end_y = center_y + vel_y * vel_scale;
+        let end_world = (center_world.0 + vel_x * vel_scale, center_world.1 + vel_y * vel_scale);
 
         // Only draw if moving significantly
         if vel_x.abs() > 10.0 || vel_y.abs() > 10.0 {
+            let (center_x, center_y) = self.camera.world_to_screen(center_world);
+            let (end_x, end_y) = self.camera.world_to_screen(end_world);
             draw_line(center_x, center_y, end_x, end_y, 2.0, YELLOW);
             self.draw_arrow_head(center_x, center_y, end_x, end_y);
         }
@@ -156,7 +348,7 @@ impl Renderer {
         draw_line(x1, y1, right_x, right_y, 1.0, YELLOW);
     }
 
-    /// Draws colored ID indicator above each player
+    /// Draws a colored ID indicator and nametag above each player
     fn draw_player_id(&mut self, player: &Player) {
         let id_color = match player.id % 8 {
             0 => WHITE,
@@ -169,10 +361,90 @@ impl Renderer {
             _ => Color::from_rgba(136, 136, 136, 255), // Gray
         };
 
-        let id_x = player.x + PLAYER_SIZE / 2.0 - 2.0;
-        let id_y = player.y - 8.0;
+        let size = self.camera.scale_length(PLAYER_SIZE);
+        let (top_x, top_y) = self.camera.world_to_screen((player.x, player.y));
+        let id_x = top_x + size / 2.0 - 2.0;
+        let id_y = top_y - 8.0;
 
         draw_rectangle(id_x, id_y, 4.0, 4.0, id_color);
+
+        self.draw_player_nametag(player, id_y - 4.0);
+    }
+
+    /// Draws `player`'s username (or a generated fallback, for a player
+    /// nobody's named yet) centered above the cube, with its baseline at
+    /// `baseline_y` (already in screen space). Clamped so a name near the
+    /// window's left/right edge stays fully on screen instead of running off
+    /// it.
+    fn draw_player_nametag(&mut self, player: &Player, baseline_y: f32) {
+        const FONT_SIZE: f32 = 14.0;
+
+        let label = if player.username.is_empty() {
+            format!("Player {}", player.id)
+        } else {
+            player.username.clone()
+        };
+
+        let size = self.camera.scale_length(PLAYER_SIZE);
+        let (player_screen_x, _) = self.camera.world_to_screen((player.x, player.y));
+        let dims = measure_text(&label, None, FONT_SIZE as u16, 1.0);
+        let centered_x = player_screen_x + size / 2.0 - dims.width / 2.0;
+        let clamped_x = centered_x.clamp(2.0, (screen_width() - dims.width - 2.0).max(2.0));
+
+        draw_text(&label, clamped_x, baseline_y, FONT_SIZE, WHITE);
+    }
+
+    /// Draws the last server-confirmed position of the local player as a
+    /// translucent outlined "ghost" cube, connects it to the predicted cube
+    /// with a line whose length is the reconciliation error in pixels, and
+    /// prints the error magnitude. The ghost is colored cyan when
+    /// reconciliation is enabled (it's merely informational — reconciliation
+    /// is already correcting this divergence) and orange when disabled (the
+    /// divergence shown here is what reconciliation would otherwise fix).
+    fn draw_reconciliation_debug(&mut self, predicted: &Player, config: &RenderConfig) {
+        let Some(server_world) = config.server_position else {
+            return;
+        };
+
+        let ghost_color = if config.reconciliation_enabled {
+            Color::from_rgba(0, 220, 220, 140)
+        } else {
+            Color::from_rgba(255, 150, 0, 140)
+        };
+
+        let (server_x, server_y) = self.camera.world_to_screen(server_world);
+        let size = self.camera.scale_length(PLAYER_SIZE);
+        draw_rectangle_lines(server_x, server_y, size, size, 2.0, ghost_color);
+
+        let predicted_world_center = (predicted.x + PLAYER_SIZE / 2.0, predicted.y + PLAYER_SIZE / 2.0);
+        let server_world_center = (server_world.0 + PLAYER_SIZE / 2.0, server_world.1 + PLAYER_SIZE / 2.0);
+
+        // Reconciliation error is measured in world units, independent of
+        // camera zoom, so it reflects actual prediction divergence rather
+        // than how zoomed-in the view happens to be.
+        let dx = predicted_world_center.0 - server_world_center.0;
+        let dy = predicted_world_center.1 - server_world_center.1;
+        let error_px = (dx * dx + dy * dy).sqrt();
+
+        let (predicted_x, predicted_y) = self.camera.world_to_screen(predicted_world_center);
+        let (server_screen_x, server_screen_y) = self.camera.world_to_screen(server_world_center);
+
+        draw_line(
+            predicted_x,
+            predicted_y,
+            server_screen_x,
+            server_screen_y,
+            1.0,
+            ghost_color,
+        );
+
+        draw_text(
+            &format!("err: {:.1}px", error_px),
+            server_x,
+            server_y - 4.0,
+            12.0,
+            ghost_color,
+        );
     }
 
     /// Renders debug UI showing netcode status and connection info
@@ -263,5 +535,39 @@ impl Renderer {
         }
         let player_text = format!("{} players", config.player_count);
         draw_text(&player_text, 45.0, player_y + 3.0, 12.0, WHITE);
+
+        // Bandwidth readout, alongside ping, so it's visible even with the
+        // toggleable network graph overlay hidden.
+        let bandwidth_y = player_y + 14.0;
+        let bandwidth_text = format!(
+            "↓{} ↑{}",
+            format_kbps(config.incoming_avg_bandwidth),
+            format_kbps(config.outgoing_avg_bandwidth)
+        );
+        draw_text(&bandwidth_text, 10.0, bandwidth_y, 12.0, Color::from_rgba(0, 170, 255, 255));
+    }
+
+    /// Draws a scrub bar across the bottom of the screen for a
+    /// `render_replay::FramePlayer` session: a filled track showing playback
+    /// progress, the current/total frame readout, and the play/pause state.
+    pub fn draw_scrub_bar(&mut self, current_frame: usize, total_frames: usize, playing: bool) {
+        let bar_height = 10.0;
+        let margin = 20.0;
+        let bar_width = screen_width() - margin * 2.0;
+        let bar_y = screen_height() - margin - bar_height;
+
+        draw_rectangle(margin, bar_y, bar_width, bar_height, Color::from_rgba(51, 51, 51, 255));
+
+        let progress = if total_frames == 0 {
+            0.0
+        } else {
+            current_frame as f32 / total_frames as f32
+        };
+        draw_rectangle(margin, bar_y, bar_width * progress, bar_height, Color::from_rgba(0, 170, 255, 255));
+        draw_rectangle_lines(margin, bar_y, bar_width, bar_height, 1.0, WHITE);
+
+        let state_text = if playing { "PLAYING" } else { "PAUSED" };
+        let label = format!("{} frame {}/{}", state_text, current_frame, total_frames);
+        draw_text(&label, margin, bar_y - 6.0, 14.0, WHITE);
     }
 }
\ No newline at end of file
@@ -0,0 +1,124 @@
+//! Standalone master/discovery server.
+//!
+//! Game servers started with `--master <this address>` heartbeat here, and
+//! clients can send `Packet::QueryServers` to get back a `Packet::ServerList`
+//! of everything currently live. Runs independently of any single game server.
+
+use bincode::{deserialize, serialize};
+use clap::Parser;
+use log::{debug, info, warn};
+use server::discovery::MasterServer;
+use shared::Packet;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::sync::RwLock;
+
+/// Command line arguments for the master server
+#[derive(Parser, Debug)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Address to bind the master server to
+    #[arg(short = 'H', long, default_value = "0.0.0.0")]
+    host: String,
+
+    /// Port to listen on
+    #[arg(short = 'p', long, default_value = "9000")]
+    port: u16,
+
+    /// Seconds a registered server may go without heartbeating before eviction
+    #[arg(short = 't', long, default_value = "15")]
+    stale_timeout_secs: u64,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::init();
+
+    let args = Args::parse();
+    let addr = format!("{}:{}", args.host, args.port);
+    let socket = UdpSocket::bind(&addr).await?;
+    info!("Master server listening on {}", addr);
+
+    let registry = RwLock::new(MasterServer::with_stale_timeout(Duration::from_secs(
+        args.stale_timeout_secs,
+    )));
+
+    let eviction = async {
+        let mut interval = tokio::time::interval(Duration::from_secs(5));
+        loop {
+            interval.tick().await;
+            let evicted = registry.write().await.evict_stale();
+            for addr in evicted {
+                info!("Evicted stale server {}", addr);
+            }
+        }
+    };
+
+    let receive = async {
+        let mut buffer = [0u8; 2048];
+        loop {
+            match socket.recv_from(&mut buffer).await {
+                Ok((len, addr)) => {
+                    handle_datagram(&socket, &registry, &buffer[0..len], addr).await;
+                }
+                Err(e) => {
+                    warn!("Error receiving packet: {}", e);
+                }
+            }
+        }
+    };
+
+    tokio::join!(eviction, receive);
+    Ok(())
+}
+
+async fn handle_datagram(
+    socket: &UdpSocket,
+    registry: &RwLock<MasterServer>,
+    data: &[u8],
+    addr: SocketAddr,
+) {
+    let Ok(packet) = deserialize::<Packet>(data) else {
+        warn!("Failed to deserialize packet from {}", addr);
+        return;
+    };
+
+    match packet {
+        Packet::Heartbeat {
+            name,
+            map,
+            current_players,
+            max_players,
+            version,
+        } => {
+            debug!("Heartbeat from {} ({})", addr, name);
+            registry
+                .write()
+                .await
+                .record_heartbeat(addr, name, map, current_players, max_players, version);
+        }
+
+        Packet::QueryServers => {
+            let entries = registry.read().await.snapshot();
+            let response = Packet::ServerList { entries };
+            if let Ok(data) = serialize(&response) {
+                if let Err(e) = socket.send_to(&data, addr).await {
+                    warn!("Failed to send server list to {}: {}", addr, e);
+                }
+            }
+        }
+
+        Packet::Ping { nonce } => {
+            if let Ok(data) = serialize(&Packet::Pong { nonce }) {
+                if let Err(e) = socket.send_to(&data, addr).await {
+                    warn!("Failed to send pong to {}: {}", addr, e);
+                }
+            }
+        }
+
+        _ => {
+            warn!("Unexpected packet type from {}", addr);
+        }
+    }
+}
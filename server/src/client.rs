@@ -1,8 +1,10 @@
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::time::Instant;
 use tokio::sync::mpsc;
 use tokio_tungstenite::tungstenite::Message;
 
+use crate::game::GameState;
 use crate::packets::InputState;
 
 // Client representation
@@ -13,14 +15,17 @@ pub struct Client {
     pub last_seen: Instant,
     pub entity_id: u32,
     pub last_processed_input: u32,
-    pub input_buffer: Vec<InputState>,
+    // Authoritative inputs keyed by the tick they apply to, rather than a
+    // flat buffer. A late input for a tick at or before `last_processed_input`
+    // triggers a rollback instead of being silently discarded.
+    pub pending_inputs: HashMap<u32, InputState>,
     pub sender: mpsc::Sender<Message>,
 }
 
 impl Client {
     pub fn new(
-        id: u32, 
-        addr: SocketAddr, 
+        id: u32,
+        addr: SocketAddr,
         entity_id: u32,
         sender: mpsc::Sender<Message>
     ) -> Self {
@@ -30,45 +35,96 @@ impl Client {
             last_seen: Instant::now(),
             entity_id,
             last_processed_input: 0,
-            input_buffer: Vec::new(),
+            pending_inputs: HashMap::new(),
             sender,
         }
     }
-    
+
     // Update the client's last seen time
     pub fn refresh_last_seen(&mut self) {
         self.last_seen = Instant::now();
     }
-    
+
     // Check if client has timed out
     pub fn is_timed_out(&self, timeout_duration: std::time::Duration) -> bool {
         Instant::now().duration_since(self.last_seen) > timeout_duration
     }
-    
-    // Add input to the client's input buffer
+
+    // Record an input against the tick it applies to
     pub fn add_input(&mut self, input: InputState) {
         self.refresh_last_seen();
-        self.input_buffer.push(input);
+        self.pending_inputs.insert(input.sequence, input);
     }
-    
-    // Process inputs and return the ones that were processed
-    pub fn process_inputs(&mut self) -> u32 {
-        if self.input_buffer.is_empty() {
-            return self.last_processed_input;
+
+    // How many ticks' worth of backlog `next_input` tolerates before it
+    // stops waiting for the exact next sequence and fast-forwards to
+    // whatever is oldest, so a burst of jitter can't stall playout forever.
+    const PLAYOUT_BACKLOG_TICKS: usize = 3;
+
+    // Pops exactly one input per call, in ascending sequence order, for
+    // callers that want deterministic one-input-per-simulation-tick
+    // draining instead of `process_inputs`' rollback-on-late-arrival model.
+    // Returns `None` when the next sequence hasn't arrived yet (starvation
+    // — the caller should predict/hold), unless the backlog has grown past
+    // `PLAYOUT_BACKLOG_TICKS`, in which case it skips ahead to the oldest
+    // buffered input rather than waiting any longer.
+    pub fn next_input(&mut self) -> Option<InputState> {
+        if self.pending_inputs.is_empty() {
+            return None;
+        }
+
+        let next_tick = self.last_processed_input + 1;
+        if let Some(input) = self.pending_inputs.remove(&next_tick) {
+            self.last_processed_input = next_tick;
+            return Some(input);
+        }
+
+        if self.pending_inputs.len() > Self::PLAYOUT_BACKLOG_TICKS {
+            let fallback_tick = *self.pending_inputs.keys().min().unwrap();
+            let input = self.pending_inputs.remove(&fallback_tick).unwrap();
+            self.last_processed_input = fallback_tick;
+            return Some(input);
         }
-        
-        // Sort inputs by sequence number
-        self.input_buffer.sort_by_key(|input| input.sequence);
-        
-        // Get the highest sequence number
-        let highest_seq = self.input_buffer.last().unwrap().sequence;
-        
-        // Update last processed input
-        self.last_processed_input = highest_seq;
-        
-        // Clear input buffer
-        self.input_buffer.clear();
-        
-        highest_seq
+
+        None
+    }
+
+    // Buffer depth, for the caller to distinguish starvation (depth stays
+    // at 0) from overrun (depth keeps growing past `PLAYOUT_BACKLOG_TICKS`).
+    pub fn buffer_depth(&self) -> usize {
+        self.pending_inputs.len()
     }
-}
\ No newline at end of file
+
+    // Drains the pending per-tick inputs into `game_state`'s rollback
+    // history. If the earliest pending tick is already behind
+    // `last_processed_input`, it arrived late: `game_state` restores the
+    // snapshot from just before that tick and replays forward with the
+    // corrected input in place. Returns the earliest tick that had to be
+    // re-simulated, or `None` if every input only extended the confirmed
+    // tick forward.
+    pub fn process_inputs(&mut self, game_state: &mut GameState, dt: f32) -> Option<u32> {
+        if self.pending_inputs.is_empty() {
+            return None;
+        }
+
+        let mut ticks: Vec<u32> = self.pending_inputs.keys().copied().collect();
+        ticks.sort_unstable();
+
+        let earliest = ticks[0];
+        let highest = *ticks.last().unwrap();
+        let needs_resimulate = earliest <= self.last_processed_input;
+
+        for tick in &ticks {
+            let input = self.pending_inputs.remove(tick).unwrap();
+            game_state.record_input(*tick, self.id, input);
+        }
+
+        self.last_processed_input = self.last_processed_input.max(highest);
+
+        if needs_resimulate && game_state.resimulate_from(earliest, dt) {
+            Some(earliest)
+        } else {
+            None
+        }
+    }
+}
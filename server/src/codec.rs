@@ -0,0 +1,68 @@
+//! Wire codec decoupled from IO.
+//!
+//! Previously `spawn_network_receiver` deserialized straight off a fixed stack
+//! buffer and `send_packet_impl` re-serialized into a fresh `Vec<u8>` on every
+//! single `send_to`, with `GameMessage::BroadcastPacket` repeating that work once
+//! per client for an identical snapshot. This module centralizes encode/decode on
+//! `bytes::Bytes`/`BytesMut` so a broadcast packet can be serialized exactly once
+//! into a shared, cheaply-cloneable `Bytes` that every client send slices from,
+//! and so the wire format is reusable outside the IO tasks (e.g. by a future
+//! standalone protocol crate).
+
+use crate::transport::Frame;
+use bytes::Bytes;
+use shared::Packet;
+
+/// Encodes a packet into a shared, reference-counted buffer. Cloning the result is
+/// O(1) (it bumps a refcount rather than copying), which is what lets a broadcast
+/// serialize once and hand the same bytes to every client.
+pub fn encode_packet(packet: &Packet) -> Result<Bytes, bincode::Error> {
+    Ok(Bytes::from(bincode::serialize(packet)?))
+}
+
+pub fn decode_packet(buf: &[u8]) -> Result<Packet, bincode::Error> {
+    bincode::deserialize(buf)
+}
+
+pub fn encode_frame(frame: &Frame) -> Result<Bytes, bincode::Error> {
+    Ok(Bytes::from(bincode::serialize(frame)?))
+}
+
+pub fn decode_frame(buf: &[u8]) -> Result<Frame, bincode::Error> {
+    bincode::deserialize(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packet_roundtrips_through_bytes() {
+        let packet = Packet::Connect {
+            min_version: 1,
+            max_version: 3,
+            resume_token: None,
+            requested_timeout_secs: 15,
+            encrypt_public_key: None,
+            connect_token: None,
+            spectate: false,
+        };
+        let encoded = encode_packet(&packet).unwrap();
+        let decoded = decode_packet(&encoded).unwrap();
+        match decoded {
+            Packet::Connect { max_version, .. } => assert_eq!(max_version, 3),
+            _ => panic!("wrong packet variant"),
+        }
+    }
+
+    #[test]
+    fn encoded_bytes_clone_cheaply() {
+        let packet = Packet::Disconnect;
+        let encoded = encode_packet(&packet).unwrap();
+        let clone_a = encoded.clone();
+        let clone_b = encoded.clone();
+        // All three share the same backing storage.
+        assert_eq!(clone_a.as_ptr(), encoded.as_ptr());
+        assert_eq!(clone_b.as_ptr(), encoded.as_ptr());
+    }
+}
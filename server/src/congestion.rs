@@ -0,0 +1,286 @@
+//! CUBIC/HyStart-style congestion controller pacing the server's state
+//! snapshots.
+//!
+//! `GameState` traffic goes out over `Transport`'s unreliable channel with no
+//! feedback-driven pacing at all, so on a congested link it just keeps
+//! blasting at the tick rate and induces bufferbloat and loss instead of
+//! backing off. This mirrors the window-based controllers QUIC implementations
+//! use: a byte-counting HyStart slow start that exits the moment round-trip
+//! time rises sharply rather than waiting for a loss, followed by RFC 8312
+//! CUBIC congestion avoidance once it does. `allowed_bytes_per_tick` turns the
+//! resulting window into a per-tick budget the snapshot encoder can use to
+//! drop or coalesce updates.
+//!
+//! This is intentionally decoupled from `Transport`: a caller feeds it ack/RTT
+//! samples (e.g. from `Transport::rtt_estimate`) and loss signals (e.g. from a
+//! gap in the reliability layer's ack bitfield older than its reorder window)
+//! rather than it reaching into the transport layer itself, the same way
+//! `NetConditions` stays decoupled from the socket it eventually feeds.
+
+use std::time::{Duration, Instant};
+
+/// Approximate max snapshot size, used only to seed a sane initial window;
+/// mirrors `transport::MSS`.
+const MSS_BYTES: f64 = 1400.0;
+const INITIAL_CWND_BYTES: f64 = MSS_BYTES * 2.0;
+/// The window never shrinks below one snapshot's worth of budget.
+const MIN_CWND_BYTES: f64 = MSS_BYTES;
+
+/// RFC 8312's recommended CUBIC scaling constant.
+const CUBIC_C: f64 = 0.4;
+/// CUBIC's multiplicative-decrease factor, applied to `cwnd` on loss.
+const CUBIC_BETA: f64 = 0.7;
+
+/// Floor and ceiling on HyStart's delay-increase threshold (`baseline / 8`,
+/// clamped), so a near-zero baseline RTT doesn't trip on ordinary jitter and a
+/// very high one doesn't delay exiting slow start indefinitely.
+const HYSTART_DELAY_MIN: Duration = Duration::from_millis(4);
+const HYSTART_DELAY_MAX: Duration = Duration::from_millis(16);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    SlowStart,
+    CongestionAvoidance,
+}
+
+/// Tracks one peer's congestion window in bytes, growing it in HyStart-gated
+/// slow start and CUBIC congestion avoidance, and cutting it sharply on loss.
+#[derive(Debug)]
+pub struct CongestionController {
+    phase: Phase,
+    cwnd_bytes: f64,
+    /// Window at the last loss (or HyStart slow-start exit); CUBIC's window
+    /// function is centered on this.
+    w_max_bytes: f64,
+    /// `t = 0` point for CUBIC's window function: reset on every loss and on
+    /// the slow-start-to-congestion-avoidance transition.
+    cubic_epoch: Instant,
+
+    /// Rolling minimum round-trip time across completed HyStart rounds,
+    /// against which each new round's minimum is compared.
+    baseline_min_rtt: Option<Duration>,
+    round_start: Instant,
+    round_min_rtt: Option<Duration>,
+    last_rtt: Option<Duration>,
+}
+
+impl CongestionController {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            phase: Phase::SlowStart,
+            cwnd_bytes: INITIAL_CWND_BYTES,
+            w_max_bytes: INITIAL_CWND_BYTES,
+            cubic_epoch: now,
+            baseline_min_rtt: None,
+            round_start: now,
+            round_min_rtt: None,
+            last_rtt: None,
+        }
+    }
+
+    pub fn cwnd_bytes(&self) -> f64 {
+        self.cwnd_bytes
+    }
+
+    pub fn is_in_slow_start(&self) -> bool {
+        self.phase == Phase::SlowStart
+    }
+
+    /// Feeds one acked packet's size and the RTT sample it was acked under
+    /// (already smoothed by the caller, e.g. `Transport::rtt_estimate`) into
+    /// the controller.
+    pub fn on_ack(&mut self, acked_bytes: usize, rtt_sample: Duration) {
+        self.last_rtt = Some(rtt_sample);
+        self.round_min_rtt = Some(match self.round_min_rtt {
+            Some(min) => min.min(rtt_sample),
+            None => rtt_sample,
+        });
+
+        match self.phase {
+            Phase::SlowStart => {
+                self.cwnd_bytes += acked_bytes as f64;
+                self.maybe_exit_slow_start(rtt_sample);
+            }
+            Phase::CongestionAvoidance => {
+                self.cwnd_bytes = self.cubic_window().max(MIN_CWND_BYTES);
+            }
+        }
+    }
+
+    /// A HyStart "round" is one RTT's worth of acks. Once one elapses, this
+    /// compares the round's minimum RTT against the rolling baseline and
+    /// exits slow start if it rose by more than the delay-increase
+    /// threshold — the link is starting to queue before any loss occurs.
+    fn maybe_exit_slow_start(&mut self, rtt_sample: Duration) {
+        if self.round_start.elapsed() < rtt_sample {
+            return;
+        }
+        let round_min = self.round_min_rtt.unwrap_or(rtt_sample);
+
+        if let Some(baseline) = self.baseline_min_rtt {
+            let threshold = (baseline / 8).clamp(HYSTART_DELAY_MIN, HYSTART_DELAY_MAX);
+            if round_min > baseline + threshold {
+                self.exit_slow_start();
+                self.start_new_round();
+                return;
+            }
+        }
+
+        self.baseline_min_rtt = Some(match self.baseline_min_rtt {
+            Some(baseline) => baseline.min(round_min),
+            None => round_min,
+        });
+        self.start_new_round();
+    }
+
+    fn start_new_round(&mut self) {
+        self.round_start = Instant::now();
+        self.round_min_rtt = None;
+    }
+
+    /// HyStart exits slow start conservatively on a detected delay increase,
+    /// without the multiplicative cut a real loss triggers below.
+    fn exit_slow_start(&mut self) {
+        self.phase = Phase::CongestionAvoidance;
+        self.w_max_bytes = self.cwnd_bytes;
+        self.cubic_epoch = Instant::now();
+    }
+
+    /// RFC 8312's CUBIC window function, evaluated at the time elapsed since
+    /// `cubic_epoch`: `W(t) = C*(t - K)^3 + W_max` where
+    /// `K = cbrt(W_max * beta / C)`.
+    fn cubic_window(&self) -> f64 {
+        let t = self.cubic_epoch.elapsed().as_secs_f64();
+        let k = (self.w_max_bytes * CUBIC_BETA / CUBIC_C).cbrt();
+        CUBIC_C * (t - k).powi(3) + self.w_max_bytes
+    }
+
+    /// Signals a detected loss — e.g. a gap in the reliability layer's ack
+    /// bitfield older than its reorder window. Cuts `cwnd` by `beta` and
+    /// restarts CUBIC's window function from that reduced point.
+    pub fn on_loss(&mut self) {
+        self.phase = Phase::CongestionAvoidance;
+        self.w_max_bytes = self.cwnd_bytes;
+        self.cwnd_bytes = (self.cwnd_bytes * CUBIC_BETA).max(MIN_CWND_BYTES);
+        self.cubic_epoch = Instant::now();
+    }
+
+    /// Bytes of snapshot traffic allowed this tick. `cwnd / rtt` is the
+    /// window's implied sending rate; this scales that down to one tick's
+    /// share of it. Falls back to the raw window before any RTT sample has
+    /// arrived, since there's nothing yet to derive a rate from.
+    pub fn allowed_bytes_per_tick(&self, tick_interval: Duration) -> usize {
+        let Some(rtt) = self.last_rtt.filter(|rtt| !rtt.is_zero()) else {
+            return self.cwnd_bytes as usize;
+        };
+        let rate_bytes_per_sec = self.cwnd_bytes / rtt.as_secs_f64();
+        (rate_bytes_per_sec * tick_interval.as_secs_f64()) as usize
+    }
+}
+
+impl Default for CongestionController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_new_starts_in_slow_start_with_initial_window() {
+        let controller = CongestionController::new();
+        assert!(controller.is_in_slow_start());
+        assert_eq!(controller.cwnd_bytes(), INITIAL_CWND_BYTES);
+    }
+
+    #[test]
+    fn test_slow_start_grows_cwnd_by_exactly_the_acked_bytes() {
+        let mut controller = CongestionController::new();
+        let before = controller.cwnd_bytes();
+
+        // A huge RTT sample means the round never completes within this
+        // call, so the growth itself is exercised in isolation.
+        controller.on_ack(1200, Duration::from_secs(10));
+
+        assert_eq!(controller.cwnd_bytes(), before + 1200.0);
+        assert!(controller.is_in_slow_start());
+    }
+
+    #[test]
+    fn test_hystart_exits_slow_start_once_a_rounds_min_rtt_jumps_past_baseline() {
+        let mut controller = CongestionController::new();
+        let small_rtt = Duration::from_millis(1);
+
+        // First round: a quick, low-RTT ack establishes the baseline.
+        thread::sleep(Duration::from_millis(2));
+        controller.on_ack(100, small_rtt);
+        assert!(controller.is_in_slow_start());
+
+        // Second round: a much higher RTT sample should trip the
+        // baseline-plus-clamp delay-increase check.
+        let much_higher_rtt = HYSTART_DELAY_MAX * 2;
+        thread::sleep(much_higher_rtt + Duration::from_millis(1));
+        controller.on_ack(100, much_higher_rtt);
+
+        assert!(!controller.is_in_slow_start());
+    }
+
+    #[test]
+    fn test_on_loss_applies_cubic_beta_multiplicative_decrease() {
+        let mut controller = CongestionController::new();
+        controller.cwnd_bytes = 10_000.0;
+
+        controller.on_loss();
+
+        assert!((controller.cwnd_bytes() - 7_000.0).abs() < 1e-6);
+        assert!(!controller.is_in_slow_start());
+    }
+
+    #[test]
+    fn test_on_loss_never_cuts_cwnd_below_the_floor() {
+        let mut controller = CongestionController::new();
+        controller.cwnd_bytes = MIN_CWND_BYTES * 1.1;
+
+        controller.on_loss();
+
+        assert!(controller.cwnd_bytes() >= MIN_CWND_BYTES);
+    }
+
+    #[test]
+    fn test_congestion_avoidance_cwnd_grows_back_over_time_after_loss() {
+        let mut controller = CongestionController::new();
+        controller.cwnd_bytes = 100_000.0;
+        controller.on_loss();
+        let reduced = controller.cwnd_bytes();
+
+        thread::sleep(Duration::from_millis(50));
+        controller.on_ack(0, Duration::from_millis(20));
+
+        assert!(controller.cwnd_bytes() >= reduced);
+    }
+
+    #[test]
+    fn test_allowed_bytes_per_tick_falls_back_to_cwnd_before_any_rtt_sample() {
+        let controller = CongestionController::new();
+        assert_eq!(
+            controller.allowed_bytes_per_tick(Duration::from_millis(16)),
+            controller.cwnd_bytes() as usize
+        );
+    }
+
+    #[test]
+    fn test_allowed_bytes_per_tick_scales_cwnd_by_rate_and_tick_length() {
+        let mut controller = CongestionController::new();
+        controller.on_ack(0, Duration::from_millis(100));
+
+        let tick = Duration::from_millis(16);
+        let allowed = controller.allowed_bytes_per_tick(tick);
+        let expected = (controller.cwnd_bytes() / 0.1 * 0.016) as usize;
+
+        assert_eq!(allowed, expected);
+    }
+}
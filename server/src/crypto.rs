@@ -0,0 +1,133 @@
+//! Ephemeral ECDH handshake and rolling MAC for `--authenticate` sessions.
+//!
+//! Adapted from OpenEthereum's encrypted-devp2p handshake: each side
+//! generates an ephemeral key pair, exchanges public keys once (piggybacked
+//! on `Packet::Connect`/`Packet::Connected`), and derives symmetric key
+//! material by hashing the shared point with Keccak. From then on, every
+//! datagram carries a MAC folded from the previous one, so a replayed or
+//! forged packet can't resynchronize the rolling state undetected. See
+//! `ClientManager::establish_session`.
+//!
+//! This is authentication, not confidentiality: `SessionKeys::encrypt_key` is
+//! derived alongside the MAC keys but nothing in this crate actually
+//! encrypts the wire payload with it today, so an `--authenticate` session
+//! makes inputs tamper-evident (a forged or replayed one fails its MAC
+//! check) without making them secret from an on-path observer. Sealing the
+//! payload for real is `netcode_handshake::seal_packet`/`open_packet`'s job.
+
+use rand::rngs::OsRng;
+use sha3::{Digest, Keccak256};
+use x25519_dalek::{EphemeralSecret, PublicKey};
+
+/// Symmetric key material derived from a completed handshake. Separate
+/// ingress/egress MAC keys mean a packet reflected back at its sender can't
+/// be replayed as if it came from the other side.
+pub struct SessionKeys {
+    /// Derived alongside the MAC keys below but not currently consumed by
+    /// anything in this crate — no wire payload is encrypted with it. Kept
+    /// derived (rather than dropped) so a future payload-sealing layer has
+    /// it available without renegotiating the handshake.
+    pub encrypt_key: [u8; 32],
+    pub ingress_mac_key: [u8; 32],
+    pub egress_mac_key: [u8; 32],
+    /// Seed the rolling MAC starts from before any payload has been folded
+    /// into it.
+    pub initial_mac: [u8; 32],
+}
+
+/// One half of an ephemeral ECDH exchange. `complete` consumes it, so the
+/// secret can't outlive the single handshake it was generated for.
+pub struct Handshake {
+    secret: EphemeralSecret,
+    pub public_key: PublicKey,
+}
+
+impl Handshake {
+    pub fn new() -> Self {
+        let secret = EphemeralSecret::random_from_rng(OsRng);
+        let public_key = PublicKey::from(&secret);
+        Self { secret, public_key }
+    }
+
+    /// Derives session key material from the shared secret with
+    /// `peer_public`, consuming this handshake's ephemeral secret.
+    pub fn complete(self, peer_public: PublicKey) -> SessionKeys {
+        let shared = self.secret.diffie_hellman(&peer_public);
+        SessionKeys {
+            encrypt_key: keccak_derive(shared.as_bytes(), b"encryption"),
+            ingress_mac_key: keccak_derive(shared.as_bytes(), b"mac-ingress"),
+            egress_mac_key: keccak_derive(shared.as_bytes(), b"mac-egress"),
+            initial_mac: keccak_derive(shared.as_bytes(), b"mac-seed"),
+        }
+    }
+}
+
+impl Default for Handshake {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn keccak_derive(shared_secret: &[u8], domain: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(shared_secret);
+    hasher.update(domain);
+    hasher.finalize().into()
+}
+
+/// Folds `payload` into `running_mac`, keyed by `mac_key`, and returns the
+/// next rolling tag. Callers keep one `running_mac` per direction so a
+/// verification failure never silently resynchronizes to a forged value.
+pub fn compute_mac(mac_key: &[u8; 32], running_mac: &[u8; 32], payload: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(mac_key);
+    hasher.update(running_mac);
+    hasher.update(payload);
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_derives_shared_encrypt_key() {
+        let server = Handshake::new();
+        let client = Handshake::new();
+        let server_public = server.public_key;
+        let client_public = client.public_key;
+
+        let server_keys = server.complete(client_public);
+        let client_keys = client.complete(server_public);
+
+        assert_eq!(server_keys.encrypt_key, client_keys.encrypt_key);
+        assert_eq!(server_keys.initial_mac, client_keys.initial_mac);
+    }
+
+    #[test]
+    fn session_keys_are_distinct_per_purpose() {
+        let keys = Handshake::new().complete(Handshake::new().public_key);
+        assert_ne!(keys.encrypt_key, keys.ingress_mac_key);
+        assert_ne!(keys.ingress_mac_key, keys.egress_mac_key);
+        assert_ne!(keys.egress_mac_key, keys.initial_mac);
+    }
+
+    #[test]
+    fn compute_mac_changes_with_running_state() {
+        let mac_key = [1u8; 32];
+        let seed = [0u8; 32];
+        let first = compute_mac(&mac_key, &seed, b"payload-one");
+        let second = compute_mac(&mac_key, &first, b"payload-one");
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn compute_mac_is_deterministic() {
+        let mac_key = [2u8; 32];
+        let seed = [3u8; 32];
+        assert_eq!(
+            compute_mac(&mac_key, &seed, b"same payload"),
+            compute_mac(&mac_key, &seed, b"same payload")
+        );
+    }
+}
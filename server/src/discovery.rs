@@ -0,0 +1,166 @@
+//! Master-server registry backing the server-browser query protocol.
+//!
+//! A [`MasterServer`] is the in-memory state for a discovery endpoint: running
+//! `Server` instances periodically send `Packet::Heartbeat` to it, and browsing
+//! clients send `Packet::QueryServers` to get back a `Packet::ServerList`. Entries
+//! that stop heartbeating are evicted on the same timeout-interval pattern
+//! `spawn_timeout_checker` uses for client connections.
+
+use shared::ServerListEntry;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// A registered game server, keyed by the `SocketAddr` its heartbeats arrive from.
+struct ServerEntry {
+    name: String,
+    map: String,
+    current_players: u32,
+    max_players: u32,
+    #[allow(dead_code)]
+    version: u32,
+    last_seen: Instant,
+}
+
+/// How long a server can go without heartbeating before it's considered gone.
+pub const DEFAULT_STALE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// In-memory registry of live game servers, as tracked by a master/discovery node.
+pub struct MasterServer {
+    servers: HashMap<SocketAddr, ServerEntry>,
+    stale_timeout: Duration,
+}
+
+impl MasterServer {
+    pub fn new() -> Self {
+        Self::with_stale_timeout(DEFAULT_STALE_TIMEOUT)
+    }
+
+    pub fn with_stale_timeout(stale_timeout: Duration) -> Self {
+        Self {
+            servers: HashMap::new(),
+            stale_timeout,
+        }
+    }
+
+    /// Records (or refreshes) a heartbeat from `addr`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_heartbeat(
+        &mut self,
+        addr: SocketAddr,
+        name: String,
+        map: String,
+        current_players: u32,
+        max_players: u32,
+        version: u32,
+    ) {
+        self.servers.insert(
+            addr,
+            ServerEntry {
+                name,
+                map,
+                current_players,
+                max_players,
+                version,
+                last_seen: Instant::now(),
+            },
+        );
+    }
+
+    /// Drops entries that haven't heartbeated within the stale timeout, returning
+    /// the addresses that were evicted.
+    pub fn evict_stale(&mut self) -> Vec<SocketAddr> {
+        let now = Instant::now();
+        let stale_timeout = self.stale_timeout;
+        let stale: Vec<SocketAddr> = self
+            .servers
+            .iter()
+            .filter(|(_, entry)| now.duration_since(entry.last_seen) > stale_timeout)
+            .map(|(addr, _)| *addr)
+            .collect();
+
+        for addr in &stale {
+            self.servers.remove(addr);
+        }
+
+        stale
+    }
+
+    /// Returns the current registry as a `ServerList` payload.
+    pub fn snapshot(&self) -> Vec<ServerListEntry> {
+        self.servers
+            .iter()
+            .map(|(addr, entry)| ServerListEntry {
+                addr: *addr,
+                name: entry.name.clone(),
+                map: entry.map.clone(),
+                current_players: entry.current_players,
+                max_players: entry.max_players,
+            })
+            .collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.servers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.servers.is_empty()
+    }
+}
+
+impl Default for MasterServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        format!("127.0.0.1:{}", port).parse().unwrap()
+    }
+
+    #[test]
+    fn heartbeat_registers_server() {
+        let mut master = MasterServer::new();
+        master.record_heartbeat(addr(9000), "Arena".into(), "de_dust".into(), 2, 8, 1);
+
+        assert_eq!(master.len(), 1);
+        let snapshot = master.snapshot();
+        assert_eq!(snapshot[0].name, "Arena");
+        assert_eq!(snapshot[0].current_players, 2);
+    }
+
+    #[test]
+    fn repeated_heartbeat_updates_existing_entry() {
+        let mut master = MasterServer::new();
+        master.record_heartbeat(addr(9000), "Arena".into(), "de_dust".into(), 2, 8, 1);
+        master.record_heartbeat(addr(9000), "Arena".into(), "de_dust".into(), 3, 8, 1);
+
+        assert_eq!(master.len(), 1);
+        assert_eq!(master.snapshot()[0].current_players, 3);
+    }
+
+    #[test]
+    fn stale_servers_are_not_evicted_before_timeout() {
+        let mut master = MasterServer::with_stale_timeout(Duration::from_secs(60));
+        master.record_heartbeat(addr(9000), "Arena".into(), "de_dust".into(), 2, 8, 1);
+
+        let evicted = master.evict_stale();
+        assert!(evicted.is_empty());
+        assert_eq!(master.len(), 1);
+    }
+
+    #[test]
+    fn evict_stale_removes_expired_entries_immediately_with_zero_timeout() {
+        let mut master = MasterServer::with_stale_timeout(Duration::from_secs(0));
+        master.record_heartbeat(addr(9000), "Arena".into(), "de_dust".into(), 2, 8, 1);
+
+        let evicted = master.evict_stale();
+        assert_eq!(evicted, vec![addr(9000)]);
+        assert!(master.is_empty());
+    }
+}
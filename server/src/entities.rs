@@ -0,0 +1,218 @@
+//! Generational-index entity store.
+//!
+//! An `EntityId` pairs a slot index with the generation that was live when
+//! it was handed out, so a stale id pointing at a freed (and possibly
+//! recycled) slot is caught via a generation mismatch instead of silently
+//! aliasing whatever now occupies that slot. See `game::Entity` for the
+//! game-specific payload stored here.
+
+/// A generation-checked handle into an `EntityStore`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+#[derive(Debug, Clone)]
+enum Slot<T> {
+    Occupied { generation: u32, value: T },
+    Free { next_generation: u32 },
+}
+
+/// A store of `T` values addressed by generation-checked `EntityId`s rather
+/// than raw indices, so freed slots can be safely recycled.
+#[derive(Debug, Clone)]
+pub struct EntityStore<T> {
+    slots: Vec<Slot<T>>,
+    free_list: Vec<u32>,
+}
+
+impl<T> EntityStore<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free_list: Vec::new(),
+        }
+    }
+
+    /// Inserts `value`, reusing a freed slot at its next generation if one
+    /// is available, and returns the id to retrieve it by.
+    pub fn spawn(&mut self, value: T) -> EntityId {
+        if let Some(index) = self.free_list.pop() {
+            let generation = match self.slots[index as usize] {
+                Slot::Free { next_generation } => next_generation,
+                Slot::Occupied { .. } => unreachable!("free list pointed at an occupied slot"),
+            };
+            self.slots[index as usize] = Slot::Occupied { generation, value };
+            EntityId { index, generation }
+        } else {
+            let index = self.slots.len() as u32;
+            self.slots.push(Slot::Occupied {
+                generation: 0,
+                value,
+            });
+            EntityId { index, generation: 0 }
+        }
+    }
+
+    /// Frees `id`'s slot for reuse at the next generation, returning the
+    /// value that was there. A stale or already-despawned id is a no-op
+    /// that returns `None`.
+    pub fn despawn(&mut self, id: EntityId) -> Option<T> {
+        let slot = self.slots.get_mut(id.index as usize)?;
+        match slot {
+            Slot::Occupied { generation, .. } if *generation == id.generation => {
+                let next_generation = generation.wrapping_add(1);
+                let Slot::Occupied { value, .. } =
+                    std::mem::replace(slot, Slot::Free { next_generation })
+                else {
+                    unreachable!("just matched Occupied above");
+                };
+                self.free_list.push(id.index);
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    /// Looks up `id`, returning `None` if its slot was freed or its
+    /// generation no longer matches (the slot was recycled since `id` was
+    /// issued).
+    pub fn get(&self, id: EntityId) -> Option<&T> {
+        match self.slots.get(id.index as usize) {
+            Some(Slot::Occupied { generation, value }) if *generation == id.generation => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, id: EntityId) -> Option<&mut T> {
+        match self.slots.get_mut(id.index as usize) {
+            Some(Slot::Occupied { generation, value }) if *generation == id.generation => {
+                Some(value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (EntityId, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| match slot {
+            Slot::Occupied { generation, value } => Some((
+                EntityId {
+                    index: index as u32,
+                    generation: *generation,
+                },
+                value,
+            )),
+            Slot::Free { .. } => None,
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (EntityId, &mut T)> {
+        self.slots.iter_mut().enumerate().filter_map(|(index, slot)| match slot {
+            Slot::Occupied { generation, value } => Some((
+                EntityId {
+                    index: index as u32,
+                    generation: *generation,
+                },
+                value,
+            )),
+            Slot::Free { .. } => None,
+        })
+    }
+
+    /// Number of currently-occupied slots (freed slots don't count, even
+    /// before they're recycled).
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free_list.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Default for EntityStore<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawn_then_get_returns_the_value() {
+        let mut store = EntityStore::new();
+        let id = store.spawn("hello");
+        assert_eq!(store.get(id), Some(&"hello"));
+    }
+
+    #[test]
+    fn get_is_none_for_an_id_from_a_different_store() {
+        let mut store_a = EntityStore::new();
+        let store_b: EntityStore<&str> = EntityStore::new();
+        let id = store_a.spawn("hello");
+        assert_eq!(store_b.get(id), None);
+    }
+
+    #[test]
+    fn despawn_frees_the_slot_and_invalidates_the_old_id() {
+        let mut store = EntityStore::new();
+        let id = store.spawn(1);
+
+        assert_eq!(store.despawn(id), Some(1));
+        assert_eq!(store.get(id), None);
+        assert_eq!(store.despawn(id), None);
+    }
+
+    #[test]
+    fn respawning_in_a_freed_slot_bumps_the_generation() {
+        let mut store = EntityStore::new();
+        let first = store.spawn("first");
+        store.despawn(first);
+        let second = store.spawn("second");
+
+        assert_eq!(second.index, first.index);
+        assert_ne!(second.generation, first.generation);
+        // The stale id from before the slot was recycled must not alias
+        // the new occupant.
+        assert_eq!(store.get(first), None);
+        assert_eq!(store.get(second), Some(&"second"));
+    }
+
+    #[test]
+    fn len_and_is_empty_exclude_freed_slots() {
+        let mut store = EntityStore::new();
+        let a = store.spawn(1);
+        store.spawn(2);
+        assert_eq!(store.len(), 2);
+
+        store.despawn(a);
+        assert_eq!(store.len(), 1);
+        assert!(!store.is_empty());
+
+        let b = *store.iter().next().map(|(id, _)| id).as_ref().unwrap_or(&a);
+        store.despawn(b);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn iter_and_iter_mut_skip_freed_slots() {
+        let mut store = EntityStore::new();
+        let a = store.spawn(1);
+        store.spawn(2);
+        store.despawn(a);
+
+        let values: Vec<_> = store.iter().map(|(_, v)| *v).collect();
+        assert_eq!(values, vec![2]);
+
+        for (_, v) in store.iter_mut() {
+            *v += 10;
+        }
+        let values: Vec<_> = store.iter().map(|(_, v)| *v).collect();
+        assert_eq!(values, vec![12]);
+    }
+}
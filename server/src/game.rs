@@ -1,17 +1,75 @@
 //! Server-side game state management and physics simulation
 
+use crate::entities::{EntityId, EntityStore};
 use log::info;
+use shared::ecs::{self, Grounded, Position, Velocity};
 use shared::{
-    resolve_collision, InputState, Player, FLOOR_Y, GRAVITY, JUMP_VELOCITY, PLAYER_SIZE,
+    resolve_collision, Gamemode, InputState, Player, FLOOR_Y, GRAVITY, JUMP_VELOCITY, PLAYER_SIZE,
     PLAYER_SPEED, WORLD_WIDTH,
 };
 use std::collections::HashMap;
 
-/// Authoritative game state maintained by the server
+/// How many past ticks' snapshots (and recorded inputs) `resimulate_from`
+/// can roll back to before the oldest entry is evicted, bounding memory use
+/// on a long-running server.
+const ROLLBACK_HISTORY_TICKS: u32 = 64;
+
+/// How many ticks in the past `rewind_to` may reach, independent of
+/// `ROLLBACK_HISTORY_TICKS` (which bounds what's actually kept in
+/// `history`). A client reporting an inflated RTT still can't push the
+/// rewound view further back than this, so a forged estimate can't force
+/// lag-compensated validation against arbitrarily old state.
+const MAX_REWIND_TICKS: u32 = 32;
+
+/// A dynamic object tracked by a `GameState`'s `entities` store. Only
+/// `Player` exists today, but the store is generic so boxes, projectiles,
+/// or pickups can be added as further variants without another overhaul.
 #[derive(Debug, Clone)]
+pub enum Entity {
+    Player(Player),
+}
+
+/// Authoritative game state maintained by the server
+#[derive(Debug)]
 pub struct GameState {
     pub tick: u32,
+    /// Read-through view of `entities`/`player_entities`, kept in sync by
+    /// `sync_players_view` after every mutation. Exists so the many
+    /// existing callers that expect a flat `HashMap<u32, Player>` don't
+    /// need to learn about `EntityId`s.
     pub players: HashMap<u32, Player>,
+
+    /// Generational-index store backing `players`. The source of truth for
+    /// per-entity state; see `entities::EntityStore`.
+    entities: EntityStore<Entity>,
+    /// Maps a connected client to its `EntityId`, so a client id that gets
+    /// reused (e.g. after a reconnect) is spawned into a fresh slot
+    /// generation rather than aliasing the previous occupant.
+    player_entities: HashMap<u32, EntityId>,
+
+    /// Snapshots keyed by tick, for `resimulate_from` to restore from when
+    /// a late or corrected input arrives for an earlier tick than the one
+    /// already simulated. See `save_state`/`remember_tick`.
+    history: HashMap<u32, GameState>,
+    /// Every client's input as actually applied at each tick, replayed by
+    /// `resimulate_from`. Pruned in lockstep with `history`.
+    inputs_by_tick: HashMap<u32, HashMap<u32, InputState>>,
+}
+
+impl Clone for GameState {
+    /// Clones only the simulatable state (tick + entities); the rollback
+    /// buffers live on the authoritative instance and are never duplicated,
+    /// so a clone (and hence a `save_state` snapshot) always starts empty.
+    fn clone(&self) -> Self {
+        Self {
+            tick: self.tick,
+            players: self.players.clone(),
+            entities: self.entities.clone(),
+            player_entities: self.player_entities.clone(),
+            history: HashMap::new(),
+            inputs_by_tick: HashMap::new(),
+        }
+    }
 }
 
 impl GameState {
@@ -19,104 +77,382 @@ impl GameState {
         Self {
             tick: 0,
             players: HashMap::new(),
+            entities: EntityStore::new(),
+            player_entities: HashMap::new(),
+            history: HashMap::new(),
+            inputs_by_tick: HashMap::new(),
+        }
+    }
+
+    /// Rebuilds the public `players` view from the authoritative entity
+    /// store. Call after any mutation to `entities`/`player_entities`.
+    fn sync_players_view(&mut self) {
+        self.players.clear();
+        for (client_id, entity_id) in &self.player_entities {
+            if let Some(Entity::Player(player)) = self.entities.get(*entity_id) {
+                self.players.insert(*client_id, player.clone());
+            }
         }
     }
 
-    /// Adds a new player at a safe spawn position
+    /// Adds a new player at a safe spawn position. If `client_id` already
+    /// has an entity (e.g. a stale reconnect), it's despawned first so any
+    /// handle still referring to it is invalidated rather than aliased.
     pub fn add_player(&mut self, client_id: u32) {
+        if let Some(old_entity_id) = self.player_entities.remove(&client_id) {
+            self.entities.despawn(old_entity_id);
+        }
+
         // Distribute spawn positions to avoid collisions
         let spawn_x = 100.0 + (client_id as f32 * 60.0) % (WORLD_WIDTH - 200.0);
         let spawn_y = FLOOR_Y - PLAYER_SIZE;
 
         let player = Player::new(client_id, spawn_x, spawn_y);
         info!("Added player {} at ({}, {})", client_id, player.x, player.y);
-        self.players.insert(client_id, player);
+        let entity_id = self.entities.spawn(Entity::Player(player));
+        self.player_entities.insert(client_id, entity_id);
+        self.sync_players_view();
     }
 
     pub fn remove_player(&mut self, client_id: &u32) {
-        self.players.remove(client_id);
+        if let Some(entity_id) = self.player_entities.remove(client_id) {
+            self.entities.despawn(entity_id);
+        }
         info!("Removed player {}", client_id);
+        self.sync_players_view();
     }
 
     /// Applies validated client input to update player state
     pub fn apply_input(&mut self, client_id: u32, input: &InputState, _dt: f32) {
-        if let Some(player) = self.players.get_mut(&client_id) {
-            // Reset horizontal velocity (no momentum)
-            player.vel_x = 0.0;
-
-            // Apply horizontal movement
-            if input.left {
-                player.vel_x -= PLAYER_SPEED;
-            }
-            if input.right {
-                player.vel_x += PLAYER_SPEED;
-            }
+        if let Some(entity_id) = self.player_entities.get(&client_id) {
+            if let Some(Entity::Player(player)) = self.entities.get_mut(*entity_id) {
+                // Reset horizontal velocity (no momentum)
+                player.vel_x = 0.0;
+
+                // Apply horizontal movement
+                if input.left {
+                    player.vel_x -= PLAYER_SPEED;
+                }
+                if input.right {
+                    player.vel_x += PLAYER_SPEED;
+                }
 
-            // Apply jump only when on ground
-            if input.jump && player.on_ground {
-                player.vel_y = JUMP_VELOCITY;
-                player.on_ground = false;
+                // Apply jump only when on ground
+                if input.jump && player.on_ground {
+                    player.vel_y = JUMP_VELOCITY;
+                    player.on_ground = false;
+                }
             }
         }
+        self.sync_players_view();
     }
 
-    /// Updates physics simulation using fixed timestep
+    /// Updates physics simulation using a fixed timestep. Steps gravity and
+    /// integration through `shared::ecs`'s
+    /// `gravity_system`/`integrate_system`, then enforces world bounds and
+    /// resolves collisions on the `Player`s those systems wrote back into.
+    ///
+    /// This only ports the gravity+integrate half of the old inline loop,
+    /// not `handle_collisions` below it — `resolve_collision` still needs
+    /// `Player`'s `collider`/`gamemode`/`layer`/`mask` fields, which have no
+    /// ECS component yet, so turning it into a `Filter`-driven system is
+    /// left for whenever those fields get ported too. Flying players are
+    /// left out of `manager` entirely (`gravity_system` only knows to skip
+    /// `Grounded` entities, not flying ones) and keep integrating by hand
+    /// below, same as before this change.
     pub fn update_physics(&mut self, dt: f32) {
-        for player in self.players.values_mut() {
-            // Apply gravity when not on ground
-            if !player.on_ground {
-                player.vel_y += GRAVITY * dt;
+        let mut manager = ecs::Manager::new();
+        let mut handles: Vec<(EntityId, ecs::Entity)> = Vec::new();
+
+        for (entity_id, entity) in self.entities.iter() {
+            let Entity::Player(player) = entity;
+            if player.flying {
+                continue;
             }
+            let ecs_entity = manager.spawn();
+            manager.add_component(ecs_entity, Position { x: player.x, y: player.y });
+            manager.add_component(ecs_entity, Velocity { x: player.vel_x, y: player.vel_y });
+            manager.add_component(ecs_entity, Grounded(player.on_ground));
+            handles.push((entity_id, ecs_entity));
+        }
+
+        ecs::gravity_system(&mut manager, GRAVITY, dt);
+        ecs::integrate_system(&mut manager, dt);
+
+        for (entity_id, ecs_entity) in handles {
+            let Some(Entity::Player(player)) = self.entities.get_mut(entity_id) else {
+                continue;
+            };
+            let position = manager.get::<Position>(ecs_entity).expect("just inserted");
+            let velocity = manager.get::<Velocity>(ecs_entity).expect("just inserted");
+            player.x = position.x;
+            player.y = position.y;
+            player.vel_x = velocity.x;
+            player.vel_y = velocity.y;
+        }
 
-            // Update position based on velocity
-            player.x += player.vel_x * dt;
-            player.y += player.vel_y * dt;
+        for (_, entity) in self.entities.iter_mut() {
+            let Entity::Player(player) = entity;
+
+            if player.flying {
+                // Not in `manager` (see the doc comment above), so integrate
+                // position under the current velocity by hand, with no
+                // gravity term.
+                player.x += player.vel_x * dt;
+                player.y += player.vel_y * dt;
+            }
 
             // Enforce horizontal boundaries
             player.x = player.x.clamp(0.0, WORLD_WIDTH - PLAYER_SIZE);
 
-            // Handle floor collision
-            if player.y + PLAYER_SIZE >= FLOOR_Y {
-                player.y = FLOOR_Y - PLAYER_SIZE;
-                player.vel_y = 0.0;
-                player.on_ground = true;
-            }
+            if !player.flying {
+                // Handle floor collision
+                if player.y + PLAYER_SIZE >= FLOOR_Y {
+                    player.y = FLOOR_Y - PLAYER_SIZE;
+                    player.vel_y = 0.0;
+                    player.on_ground = true;
+                }
 
-            // Handle ceiling collision
-            if player.y <= 0.0 {
-                player.y = 0.0;
-                player.vel_y = 0.0;
+                // Handle ceiling collision
+                if player.y <= 0.0 {
+                    player.y = 0.0;
+                    player.vel_y = 0.0;
+                }
             }
         }
 
         self.handle_collisions();
+        self.sync_players_view();
+    }
+
+    /// Sets `client_id`'s `flying` flag, if it's the gamemode allows it
+    /// (anything but `Gamemode::Survival`). Applied in response to
+    /// `Packet::ToggleFly`; the caller answers with `Packet::SetGamemode`
+    /// reflecting whatever actually took effect.
+    pub fn set_flying(&mut self, client_id: u32, flying: bool) {
+        if let Some(entity_id) = self.player_entities.get(&client_id) {
+            if let Some(Entity::Player(player)) = self.entities.get_mut(*entity_id) {
+                if player.gamemode != Gamemode::Survival {
+                    player.flying = flying;
+                }
+            }
+        }
+        self.sync_players_view();
     }
 
     /// Handles collision detection and resolution between all players
     fn handle_collisions(&mut self) {
-        let player_ids: Vec<u32> = self.players.keys().cloned().collect();
+        let client_ids: Vec<u32> = self.player_entities.keys().cloned().collect();
 
         // Check all pairs of players for collisions
-        for i in 0..player_ids.len() {
-            for j in (i + 1)..player_ids.len() {
-                let id1 = player_ids[i];
-                let id2 = player_ids[j];
-
-                if let (Some(p1), Some(p2)) = (
-                    self.players.get(&id1).cloned(),
-                    self.players.get(&id2).cloned(),
+        for i in 0..client_ids.len() {
+            for j in (i + 1)..client_ids.len() {
+                let id1 = client_ids[i];
+                let id2 = client_ids[j];
+                let (Some(&entity_id1), Some(&entity_id2)) = (
+                    self.player_entities.get(&id1),
+                    self.player_entities.get(&id2),
+                ) else {
+                    continue;
+                };
+
+                if let (
+                    Some(Entity::Player(p1)),
+                    Some(Entity::Player(p2)),
+                ) = (
+                    self.entities.get(entity_id1).cloned(),
+                    self.entities.get(entity_id2).cloned(),
                 ) {
+                    if p1.gamemode == Gamemode::Spectator || p2.gamemode == Gamemode::Spectator {
+                        continue;
+                    }
+
                     let mut player1 = p1;
                     let mut player2 = p2;
 
                     resolve_collision(&mut player1, &mut player2);
 
-                    self.players.insert(id1, player1);
-                    self.players.insert(id2, player2);
+                    if let Some(Entity::Player(slot)) = self.entities.get_mut(entity_id1) {
+                        *slot = player1;
+                    }
+                    if let Some(Entity::Player(slot)) = self.entities.get_mut(entity_id2) {
+                        *slot = player2;
+                    }
                 }
             }
         }
     }
+
+    /// Deterministic checksum over the current players, for detecting
+    /// divergence between this authoritative simulation and a client's
+    /// prediction of the same tick. See `shared::compute_checksum` and the
+    /// `sync_test` binary, which asserts this stays equal across two
+    /// independently-stepped instances fed identical inputs.
+    pub fn checksum(&self) -> u32 {
+        let players: Vec<Player> = self.players.values().cloned().collect();
+        shared::compute_checksum(&players)
+    }
+
+    /// Snapshots the current players into a restorable copy tagged with
+    /// `tick`. The returned copy's own rollback buffers are always empty
+    /// (see the `Clone` impl); `load_state` restores only its tick, `players`
+    /// and entity store.
+    pub fn save_state(&self, tick: u32) -> GameState {
+        GameState {
+            tick,
+            ..self.clone()
+        }
+    }
+
+    /// Restores `tick`, `players`, and the entity store from a
+    /// previously-saved snapshot. This instance's own rollback buffers
+    /// (`history`, recorded inputs) are left untouched, since those belong
+    /// to the authoritative instance, not a point-in-time copy of it.
+    pub fn load_state(&mut self, snapshot: GameState) {
+        self.tick = snapshot.tick;
+        self.players = snapshot.players;
+        self.entities = snapshot.entities;
+        self.player_entities = snapshot.player_entities;
+    }
+
+    /// Records `client_id`'s input as applied at `tick`, so a later
+    /// `resimulate_from` can replay it. Call once per input alongside
+    /// `apply_input`.
+    pub fn record_input(&mut self, tick: u32, client_id: u32, input: InputState) {
+        self.inputs_by_tick
+            .entry(tick)
+            .or_default()
+            .insert(client_id, input);
+    }
+
+    /// Snapshots the current state into the rollback ring buffer under
+    /// `tick`, evicting the entry (and recorded inputs) that just fell
+    /// outside the last `ROLLBACK_HISTORY_TICKS` ticks.
+    pub fn remember_tick(&mut self, tick: u32) {
+        self.history.insert(tick, self.save_state(tick));
+        if let Some(evicted) = tick.checked_sub(ROLLBACK_HISTORY_TICKS) {
+            self.history.remove(&evicted);
+            self.inputs_by_tick.remove(&evicted);
+        }
+    }
+
+    /// Looks up the `players` view of the snapshot kept for `tick`, for a
+    /// caller that wants to diff against a past tick (e.g. `GameStateDelta`
+    /// compression) without rolling the authoritative state back to it.
+    /// `None` if `tick` fell outside `ROLLBACK_HISTORY_TICKS` or was never
+    /// remembered at all.
+    pub fn players_at(&self, tick: u32) -> Option<&HashMap<u32, Player>> {
+        self.history.get(&tick).map(|snapshot| &snapshot.players)
+    }
+
+    /// Restores the snapshot taken at `tick - 1` and replays every tick from
+    /// `tick` through this instance's current tick, re-applying each tick's
+    /// recorded inputs and `update_physics`, overwriting the buffered
+    /// snapshots as it goes. Returns `false` (leaving `self` untouched) if
+    /// no snapshot was kept for `tick - 1` — e.g. it already fell outside
+    /// `ROLLBACK_HISTORY_TICKS`, or `tick` is 0.
+    pub fn resimulate_from(&mut self, tick: u32, dt: f32) -> bool {
+        let Some(prev_tick) = tick.checked_sub(1) else {
+            return false;
+        };
+        let Some(base) = self.history.get(&prev_tick) else {
+            return false;
+        };
+        let base = base.clone();
+        let replay_through = self.tick;
+        self.load_state(base);
+
+        let mut replay_tick = tick;
+        while replay_tick <= replay_through {
+            let inputs = self
+                .inputs_by_tick
+                .get(&replay_tick)
+                .cloned()
+                .unwrap_or_default();
+            for (client_id, input) in &inputs {
+                self.apply_input(*client_id, input, dt);
+            }
+            self.update_physics(dt);
+            self.tick = replay_tick;
+            self.remember_tick(replay_tick);
+            replay_tick += 1;
+        }
+        true
+    }
+
+    /// Rewinds to the (possibly fractional) world tick `view_tick` — computed
+    /// by `ClientManager::lag_compensated_view_tick` as `current_tick -
+    /// (rtt_ticks + interpolation_delay)` — for validating a
+    /// latency-sensitive action (e.g. a hitscan) against what the acting
+    /// client actually saw, without mutating the authoritative state.
+    /// `view_tick` is clamped to at most `MAX_REWIND_TICKS` in the past, so a
+    /// client-reported RTT can't force validation against arbitrarily old
+    /// state. Interpolates between the two stored snapshots bracketing the
+    /// clamped tick; `None` if the lower bracketing snapshot already fell
+    /// outside `history`.
+    ///
+    /// This is scaffolding: nothing in the server yet sends an action packet
+    /// to validate, so there's no caller for it today, even though
+    /// `lag_compensated_view_tick` can now actually produce the `view_tick`
+    /// it takes. It's exposed and tested directly so hit/interaction
+    /// validation has lag compensation to build on when that packet exists
+    /// (see `shared::ecs` for a similar
+    /// not-yet-wired-in scaffold).
+    pub fn rewind_to(&self, view_tick: f32) -> Option<InterpolatedView> {
+        let earliest_allowed = self.tick.saturating_sub(MAX_REWIND_TICKS) as f32;
+        let clamped = view_tick.clamp(earliest_allowed, self.tick as f32);
+
+        let lower_tick = clamped.floor() as u32;
+        let fraction = clamped - lower_tick as f32;
+        let lower = self.players_at(lower_tick)?;
+
+        let players = if fraction <= f32::EPSILON {
+            lower.clone()
+        } else {
+            match self.players_at(lower_tick + 1) {
+                Some(upper) => interpolate_players(lower, upper, fraction),
+                None => lower.clone(),
+            }
+        };
+
+        Some(InterpolatedView { tick: clamped, players })
+    }
+}
+
+/// Linearly interpolates each player's position between `lower` and `upper`
+/// by `fraction` (`0.0` = `lower`, `1.0` = `upper`). A player present in
+/// `lower` but missing from `upper` (e.g. it disconnected) is returned
+/// unmoved rather than interpolated toward nothing.
+fn interpolate_players(
+    lower: &HashMap<u32, Player>,
+    upper: &HashMap<u32, Player>,
+    fraction: f32,
+) -> HashMap<u32, Player> {
+    lower
+        .iter()
+        .map(|(id, lo)| {
+            let player = match upper.get(id) {
+                Some(hi) => Player {
+                    x: lo.x + (hi.x - lo.x) * fraction,
+                    y: lo.y + (hi.y - lo.y) * fraction,
+                    ..lo.clone()
+                },
+                None => lo.clone(),
+            };
+            (*id, player)
+        })
+        .collect()
+}
+
+/// A read-only, time-rewound view of `players`, returned by
+/// `GameState::rewind_to`. Carries the (possibly fractional) tick it was
+/// interpolated to, alongside the interpolated players themselves, so a
+/// caller validating an action can report exactly what state it checked
+/// against.
+#[derive(Debug, Clone)]
+pub struct InterpolatedView {
+    pub tick: f32,
+    pub players: HashMap<u32, Player>,
 }
 
 impl Default for GameState {
@@ -572,4 +908,219 @@ mod tests {
         assert_approx_eq!(player1.vel_y, player2.vel_y, 0.001);
         assert_eq!(player1.on_ground, player2.on_ground);
     }
+
+    #[test]
+    fn test_checksum_matches_across_identically_driven_instances() {
+        let mut game_state1 = GameState::new();
+        let mut game_state2 = GameState::new();
+        game_state1.add_player(1);
+        game_state2.add_player(1);
+
+        let input = InputState {
+            sequence: 1,
+            timestamp: 0,
+            left: false,
+            right: true,
+            jump: true,
+        };
+        let dt = 1.0 / 60.0;
+
+        for _ in 0..30 {
+            game_state1.apply_input(1, &input, dt);
+            game_state1.update_physics(dt);
+            game_state2.apply_input(1, &input, dt);
+            game_state2.update_physics(dt);
+
+            assert_eq!(game_state1.checksum(), game_state2.checksum());
+        }
+    }
+
+    #[test]
+    fn test_checksum_changes_when_a_player_diverges() {
+        let mut game_state = GameState::new();
+        game_state.add_player(1);
+        let baseline = game_state.checksum();
+
+        game_state.players.get_mut(&1).unwrap().x += 10.0;
+        assert_ne!(game_state.checksum(), baseline);
+    }
+
+    #[test]
+    fn test_save_state_then_load_state_restores_players() {
+        let mut game_state = GameState::new();
+        game_state.add_player(1);
+        let dt = 1.0 / 60.0;
+        let input = InputState {
+            sequence: 1,
+            timestamp: 0,
+            left: true,
+            right: false,
+            jump: false,
+        };
+        game_state.apply_input(1, &input, dt);
+        game_state.update_physics(dt);
+        let snapshot = game_state.save_state(1);
+
+        // Keep diverging past the snapshot.
+        game_state.update_physics(dt);
+        game_state.update_physics(dt);
+        let diverged_x = game_state.players[&1].x;
+
+        game_state.load_state(snapshot);
+        assert_ne!(game_state.players[&1].x, diverged_x);
+        assert_eq!(game_state.tick, 1);
+    }
+
+    #[test]
+    fn test_save_state_snapshot_has_no_rollback_buffers() {
+        let mut game_state = GameState::new();
+        game_state.add_player(1);
+        game_state.remember_tick(0);
+        let snapshot = game_state.save_state(1);
+
+        assert!(snapshot.history.is_empty());
+        assert!(snapshot.inputs_by_tick.is_empty());
+    }
+
+    #[test]
+    fn test_resimulate_from_without_snapshot_returns_false() {
+        let mut game_state = GameState::new();
+        assert!(!game_state.resimulate_from(5, 1.0 / 60.0));
+    }
+
+    #[test]
+    fn test_remember_tick_evicts_beyond_history_window() {
+        let mut game_state = GameState::new();
+        game_state.add_player(1);
+
+        for tick in 0..(ROLLBACK_HISTORY_TICKS + 5) {
+            game_state.remember_tick(tick);
+        }
+
+        assert!(!game_state.history.contains_key(&0));
+        assert!(game_state
+            .history
+            .contains_key(&(ROLLBACK_HISTORY_TICKS + 4)));
+        assert!(game_state.history.len() as u32 <= ROLLBACK_HISTORY_TICKS);
+    }
+
+    #[test]
+    fn test_rewind_to_interpolates_between_bracketing_snapshots() {
+        let mut game_state = GameState::new();
+        game_state.add_player(1);
+        game_state.remember_tick(0);
+
+        game_state.tick = 1;
+        game_state.players.get_mut(&1).unwrap().x += 10.0;
+        game_state.remember_tick(1);
+
+        let view = game_state.rewind_to(0.5).unwrap();
+        let start_x = game_state.players_at(0).unwrap()[&1].x;
+        assert_eq!(view.tick, 0.5);
+        assert_eq!(view.players[&1].x, start_x + 5.0);
+    }
+
+    #[test]
+    fn test_rewind_to_clamps_to_max_rewind_ticks() {
+        let mut game_state = GameState::new();
+        game_state.add_player(1);
+
+        for tick in 0..=ROLLBACK_HISTORY_TICKS {
+            game_state.tick = tick;
+            game_state.remember_tick(tick);
+        }
+
+        // Ask for a view far enough back that, uncapped, it would have
+        // fallen outside `history` entirely.
+        let view = game_state.rewind_to(0.0).unwrap();
+        assert_eq!(view.tick, (ROLLBACK_HISTORY_TICKS - MAX_REWIND_TICKS) as f32);
+    }
+
+    #[test]
+    fn test_rewind_to_without_any_history_returns_none() {
+        let game_state = GameState::new();
+        assert!(game_state.rewind_to(0.0).is_none());
+    }
+
+    #[test]
+    fn test_resimulate_from_reproduces_live_simulation() {
+        let dt = 1.0 / 60.0;
+        let input = InputState {
+            sequence: 1,
+            timestamp: 0,
+            left: false,
+            right: true,
+            jump: false,
+        };
+
+        // Live: apply the same input/physics tick-by-tick, recording
+        // history exactly like an authoritative server tick loop would.
+        let mut live = GameState::new();
+        live.add_player(1);
+        live.remember_tick(0);
+        for tick in 1..=5u32 {
+            live.record_input(tick, 1, input.clone());
+            live.apply_input(1, &input, dt);
+            live.update_physics(dt);
+            live.tick = tick;
+            live.remember_tick(tick);
+        }
+        let expected = live.players[&1].clone();
+
+        // Rolled back: same history, but resimulated forward from tick 3
+        // instead of applied directly, as if tick 3's input arrived late.
+        let mut rolled_back = GameState::new();
+        rolled_back.add_player(1);
+        rolled_back.remember_tick(0);
+        for tick in 1..=2u32 {
+            rolled_back.record_input(tick, 1, input.clone());
+            rolled_back.apply_input(1, &input, dt);
+            rolled_back.update_physics(dt);
+            rolled_back.tick = tick;
+            rolled_back.remember_tick(tick);
+        }
+        rolled_back.tick = 5;
+        for tick in 3..=5u32 {
+            rolled_back.record_input(tick, 1, input.clone());
+        }
+
+        assert!(rolled_back.resimulate_from(3, dt));
+        let resimulated = rolled_back.players[&1].clone();
+
+        assert_approx_eq!(expected.x, resimulated.x, 0.001);
+        assert_approx_eq!(expected.y, resimulated.y, 0.001);
+        assert_approx_eq!(expected.vel_x, resimulated.vel_x, 0.001);
+        assert_approx_eq!(expected.vel_y, resimulated.vel_y, 0.001);
+        assert_eq!(rolled_back.tick, 5);
+    }
+
+    #[test]
+    fn test_reconnect_with_same_client_id_gets_a_fresh_entity() {
+        let mut game_state = GameState::new();
+        game_state.add_player(1);
+        let first_entity_id = game_state.player_entities[&1];
+
+        // Simulate a reconnect under the same client id without an
+        // intervening remove_player.
+        game_state.add_player(1);
+        let second_entity_id = game_state.player_entities[&1];
+
+        assert_ne!(first_entity_id, second_entity_id);
+        // The old handle must not resolve to the new player's entity.
+        assert!(game_state.entities.get(first_entity_id).is_none());
+        assert_eq!(game_state.players.len(), 1);
+    }
+
+    #[test]
+    fn test_remove_player_despawns_its_entity() {
+        let mut game_state = GameState::new();
+        game_state.add_player(1);
+        let entity_id = game_state.player_entities[&1];
+
+        game_state.remove_player(&1);
+
+        assert!(game_state.entities.get(entity_id).is_none());
+        assert!(!game_state.player_entities.contains_key(&1));
+        assert!(game_state.players.is_empty());
+    }
 }
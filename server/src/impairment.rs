@@ -0,0 +1,279 @@
+//! Deterministic drop/duplicate/delay/reorder impairment stage for the
+//! packet pipeline.
+//!
+//! `NetConditions` already emulates latency/jitter/loss, but only for
+//! outgoing packets assigned to a client's region, and it never duplicates a
+//! packet or calls out a separate reorder knob. `ImpairmentStage` is a
+//! smaller, queue-level version of the same idea — a userspace verdict
+//! stage, the way a netfilter `NFQUEUE` handler decides a packet's fate
+//! before it continues through the stack — sitting between a
+//! `PacketPipeline` producer and consumer rather than between the transport
+//! and a specific client. Every verdict (drop, duplicate, how late to
+//! deliver) is drawn from a seeded RNG, so a fixed seed reproduces the exact
+//! same sequence of decisions run to run, which is what lets a test assert
+//! behavior under adverse conditions instead of just "it didn't panic".
+//!
+//! Delayed packets are held in the same reversed-`BinaryHeap`-by-deadline
+//! shape `net_conditions::NetConditions` uses and released once their
+//! deadline passes; `drain_ready` is how a consumer pulls whatever's due.
+
+use crate::pipeline::IngressItem;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::time::{Duration, Instant};
+
+/// Tunable impairment parameters. All probabilities are clamped to `[0, 1]`
+/// at construction so a misconfigured value can't invert its own meaning.
+#[derive(Debug, Clone, Copy)]
+pub struct ImpairmentConfig {
+    pub drop_probability: f64,
+    pub duplication_probability: f64,
+    pub base_latency: Duration,
+    pub jitter_stddev_ms: f64,
+    /// Width of an additional, independent random delay applied to every
+    /// surviving packet on top of `base_latency`/jitter, so packets sent
+    /// close together can still be released out of their original order.
+    pub reorder_window: Duration,
+}
+
+impl ImpairmentConfig {
+    pub fn new(
+        drop_probability: f64,
+        duplication_probability: f64,
+        base_latency: Duration,
+        jitter_stddev_ms: f64,
+        reorder_window: Duration,
+    ) -> Self {
+        Self {
+            drop_probability: drop_probability.clamp(0.0, 1.0),
+            duplication_probability: duplication_probability.clamp(0.0, 1.0),
+            base_latency,
+            jitter_stddev_ms,
+            reorder_window,
+        }
+    }
+}
+
+impl Default for ImpairmentConfig {
+    /// No drop, no duplication, no delay — equivalent to the stage not
+    /// being in the path at all.
+    fn default() -> Self {
+        Self::new(0.0, 0.0, Duration::ZERO, 0.0, Duration::ZERO)
+    }
+}
+
+/// One item held until its simulated arrival time.
+struct Delayed {
+    release_at: Instant,
+    item: IngressItem,
+}
+
+impl PartialEq for Delayed {
+    fn eq(&self, other: &Self) -> bool {
+        self.release_at == other.release_at
+    }
+}
+impl Eq for Delayed {}
+
+impl PartialOrd for Delayed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Delayed {
+    // Reversed so `BinaryHeap` (a max-heap) pops the earliest release time first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.release_at.cmp(&self.release_at)
+    }
+}
+
+/// Applies drop/duplicate/delay/reorder verdicts to items passing through a
+/// queue, holding survivors until their simulated release time.
+pub struct ImpairmentStage {
+    config: ImpairmentConfig,
+    rng: StdRng,
+    pending: BinaryHeap<Delayed>,
+}
+
+impl ImpairmentStage {
+    /// `seed` makes every drop/duplicate/delay roll reproducible: the same
+    /// seed and the same sequence of `submit` calls always produce the same
+    /// verdicts.
+    pub fn new(config: ImpairmentConfig, seed: u64) -> Self {
+        Self {
+            config,
+            rng: StdRng::seed_from_u64(seed),
+            pending: BinaryHeap::new(),
+        }
+    }
+
+    /// How many items are still held awaiting their release time.
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Rolls a verdict for `item` and, if it survives, queues it (and
+    /// possibly a duplicate) for release at a simulated future time. Returns
+    /// `true` if the item was dropped outright.
+    pub fn submit(&mut self, item: IngressItem, now: Instant) -> bool {
+        if self.rng.gen_bool(self.config.drop_probability) {
+            return true;
+        }
+
+        self.enqueue(item.clone(), now);
+        if self.rng.gen_bool(self.config.duplication_probability) {
+            self.enqueue(item, now);
+        }
+        false
+    }
+
+    fn enqueue(&mut self, item: IngressItem, now: Instant) {
+        let release_at = now + self.config.base_latency + self.jitter() + self.reorder_offset();
+        self.pending.push(Delayed { release_at, item });
+    }
+
+    fn jitter(&mut self) -> Duration {
+        if self.config.jitter_stddev_ms <= 0.0 {
+            return Duration::ZERO;
+        }
+        // Box-Muller, matching `NetConditions::jitter_sample`'s approach to
+        // turning a uniform RNG into a normal one without an extra dependency.
+        let u1: f64 = self.rng.gen_range(f64::EPSILON..1.0);
+        let u2: f64 = self.rng.gen_range(0.0..1.0);
+        let standard_normal = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        let jitter_ms = (standard_normal * self.config.jitter_stddev_ms).max(0.0);
+        Duration::from_secs_f64(jitter_ms / 1000.0)
+    }
+
+    fn reorder_offset(&mut self) -> Duration {
+        if self.config.reorder_window.is_zero() {
+            return Duration::ZERO;
+        }
+        self.rng.gen_range(Duration::ZERO..self.config.reorder_window)
+    }
+
+    /// Releases every held item whose simulated arrival time has passed,
+    /// earliest first.
+    pub fn drain_ready(&mut self, now: Instant) -> Vec<IngressItem> {
+        let mut ready = Vec::new();
+        while let Some(next) = self.pending.peek() {
+            if next.release_at > now {
+                break;
+            }
+            ready.push(self.pending.pop().unwrap().item);
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shared::Packet;
+    use std::net::SocketAddr;
+
+    fn addr() -> SocketAddr {
+        "127.0.0.1:9400".parse().unwrap()
+    }
+
+    fn dummy_packet(nonce: u64) -> Packet {
+        Packet::Ping { nonce }
+    }
+
+    #[test]
+    fn never_drop_never_duplicate_delivers_exactly_one_copy() {
+        let mut stage = ImpairmentStage::new(ImpairmentConfig::default(), 1);
+        let now = Instant::now();
+
+        let dropped = stage.submit((addr(), dummy_packet(1)), now);
+        assert!(!dropped);
+
+        let ready = stage.drain_ready(now);
+        assert_eq!(ready.len(), 1);
+        assert_eq!(stage.pending_count(), 0);
+    }
+
+    #[test]
+    fn always_drop_never_releases_anything() {
+        let config = ImpairmentConfig::new(1.0, 0.0, Duration::ZERO, 0.0, Duration::ZERO);
+        let mut stage = ImpairmentStage::new(config, 2);
+        let now = Instant::now();
+
+        let dropped = stage.submit((addr(), dummy_packet(1)), now);
+        assert!(dropped);
+        assert_eq!(stage.pending_count(), 0);
+    }
+
+    #[test]
+    fn always_duplicate_releases_two_copies_per_surviving_submission() {
+        let config = ImpairmentConfig::new(0.0, 1.0, Duration::ZERO, 0.0, Duration::ZERO);
+        let mut stage = ImpairmentStage::new(config, 3);
+        let now = Instant::now();
+
+        stage.submit((addr(), dummy_packet(1)), now);
+        assert_eq!(stage.drain_ready(now).len(), 2);
+    }
+
+    #[test]
+    fn delayed_packets_are_not_ready_until_their_release_time() {
+        let config = ImpairmentConfig::new(0.0, 0.0, Duration::from_millis(50), 0.0, Duration::ZERO);
+        let mut stage = ImpairmentStage::new(config, 4);
+        let now = Instant::now();
+
+        stage.submit((addr(), dummy_packet(1)), now);
+        assert!(stage.drain_ready(now).is_empty());
+        assert!(stage
+            .drain_ready(now + Duration::from_millis(60))
+            .len()
+            == 1);
+    }
+
+    #[test]
+    fn same_seed_produces_the_same_sequence_of_verdicts() {
+        let config = ImpairmentConfig::new(0.3, 0.2, Duration::from_millis(10), 5.0, Duration::from_millis(5));
+        let now = Instant::now();
+
+        let run = |seed: u64| {
+            let mut stage = ImpairmentStage::new(config, seed);
+            (0..50)
+                .map(|i| stage.submit((addr(), dummy_packet(i)), now))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(run(42), run(42));
+    }
+
+    #[test]
+    fn consumed_plus_remaining_accounts_for_every_submission_once_drained() {
+        let config = ImpairmentConfig::new(0.2, 0.3, Duration::from_millis(20), 3.0, Duration::from_millis(10));
+        let mut stage = ImpairmentStage::new(config, 7);
+        let now = Instant::now();
+        let total_submitted = 100u32;
+        let mut dropped = 0u32;
+        let mut enqueued = 0u32;
+
+        for i in 0..total_submitted {
+            let before = stage.pending_count();
+            let was_dropped = stage.submit((addr(), dummy_packet(i as u64)), now);
+            if was_dropped {
+                dropped += 1;
+            } else {
+                enqueued += (stage.pending_count() - before) as u32;
+            }
+        }
+
+        // Drain far enough into the future that even the worst-case
+        // base_latency + jitter + reorder_window has elapsed.
+        let consumed = stage
+            .drain_ready(now + Duration::from_secs(1))
+            .len() as u32;
+        let remaining = stage.pending_count() as u32;
+
+        assert_eq!(consumed + remaining, enqueued);
+        assert_eq!(dropped + enqueued, total_submitted);
+        assert_eq!(remaining, 0);
+    }
+}
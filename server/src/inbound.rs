@@ -0,0 +1,175 @@
+//! Bounded inbound packet queue.
+//!
+//! `spawn_network_receiver` used to hand every decoded packet straight to the
+//! main loop's unbounded `server_tx` channel, so a burst from a misbehaving or
+//! malicious client (or a main loop briefly stalled on something else) could
+//! grow memory without limit. `InboundQueue` caps how much can pile up: once
+//! `MAX_PENDING` pending packets are queued, the oldest unreliable packet is
+//! evicted to make room rather than growing further, since an unreliable input
+//! or state packet is superseded by the next one anyway. Reliable control
+//! packets (connect/disconnect handshakes) are never evicted this way.
+
+use shared::Packet;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+
+/// Maximum number of packets allowed to sit in the queue awaiting processing.
+pub const MAX_PENDING: usize = 1024;
+
+struct PendingPacket {
+    packet: Packet,
+    addr: SocketAddr,
+    reliable: bool,
+}
+
+/// Snapshot of queue occupancy, suitable for logging or load-shedding decisions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueInfo {
+    pub pending: usize,
+    pub processing: usize,
+    pub full: bool,
+}
+
+/// A bounded FIFO of decoded-but-unprocessed inbound packets.
+pub struct InboundQueue {
+    pending: VecDeque<PendingPacket>,
+    processing: usize,
+    capacity: usize,
+}
+
+impl InboundQueue {
+    pub fn new() -> Self {
+        Self::with_capacity(MAX_PENDING)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            processing: 0,
+            capacity,
+        }
+    }
+
+    /// Enqueues a packet, evicting the oldest unreliable entry first if the
+    /// queue is full. Returns `true` if the packet was queued, `false` if it
+    /// was dropped outright (the queue was full of packets this one couldn't
+    /// displace).
+    pub fn push(&mut self, packet: Packet, addr: SocketAddr, reliable: bool) -> bool {
+        if self.pending.len() >= self.capacity {
+            match self.pending.iter().position(|p| !p.reliable) {
+                Some(index) => {
+                    self.pending.remove(index);
+                }
+                None if !reliable => return false,
+                None => {} // queue is full of reliable packets; let this one through anyway
+            }
+        }
+
+        self.pending.push_back(PendingPacket {
+            packet,
+            addr,
+            reliable,
+        });
+        true
+    }
+
+    /// Pops the oldest pending packet and marks it as being processed. Callers
+    /// must pair this with [`InboundQueue::mark_processed`] once handling
+    /// finishes so `queue_info()` reflects in-flight work.
+    pub fn pop(&mut self) -> Option<(Packet, SocketAddr)> {
+        let next = self.pending.pop_front()?;
+        self.processing += 1;
+        Some((next.packet, next.addr))
+    }
+
+    /// Marks one in-flight packet (from a prior `pop`) as finished processing.
+    pub fn mark_processed(&mut self) {
+        self.processing = self.processing.saturating_sub(1);
+    }
+
+    pub fn queue_info(&self) -> QueueInfo {
+        QueueInfo {
+            pending: self.pending.len(),
+            processing: self.processing,
+            full: self.pending.len() >= self.capacity,
+        }
+    }
+}
+
+impl Default for InboundQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn addr() -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 9000)
+    }
+
+    fn input_packet() -> Packet {
+        Packet::Disconnect
+    }
+
+    #[test]
+    fn queue_info_reports_pending_count() {
+        let mut queue = InboundQueue::with_capacity(4);
+        queue.push(input_packet(), addr(), false);
+        queue.push(input_packet(), addr(), false);
+
+        let info = queue.queue_info();
+        assert_eq!(info.pending, 2);
+        assert_eq!(info.processing, 0);
+        assert!(!info.full);
+    }
+
+    #[test]
+    fn pop_moves_packet_from_pending_to_processing() {
+        let mut queue = InboundQueue::with_capacity(4);
+        queue.push(input_packet(), addr(), false);
+
+        assert!(queue.pop().is_some());
+        let info = queue.queue_info();
+        assert_eq!(info.pending, 0);
+        assert_eq!(info.processing, 1);
+
+        queue.mark_processed();
+        assert_eq!(queue.queue_info().processing, 0);
+    }
+
+    #[test]
+    fn full_queue_evicts_oldest_unreliable_to_make_room() {
+        let mut queue = InboundQueue::with_capacity(2);
+        queue.push(input_packet(), addr(), false);
+        queue.push(input_packet(), addr(), false);
+        assert!(queue.queue_info().full);
+
+        // A third unreliable packet should evict the oldest one rather than grow.
+        assert!(queue.push(input_packet(), addr(), false));
+        assert_eq!(queue.queue_info().pending, 2);
+    }
+
+    #[test]
+    fn full_queue_preserves_reliable_packets_over_unreliable() {
+        let mut queue = InboundQueue::with_capacity(1);
+        queue.push(input_packet(), addr(), true); // reliable, fills capacity
+
+        // An unreliable arrival can't displace the reliable packet and gets dropped.
+        assert!(!queue.push(input_packet(), addr(), false));
+        assert_eq!(queue.queue_info().pending, 1);
+    }
+
+    #[test]
+    fn reliable_packet_can_evict_unreliable_when_full() {
+        let mut queue = InboundQueue::with_capacity(1);
+        queue.push(input_packet(), addr(), false);
+
+        assert!(queue.push(input_packet(), addr(), true));
+        let info = queue.queue_info();
+        assert_eq!(info.pending, 1);
+    }
+}
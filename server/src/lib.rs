@@ -142,5 +142,22 @@
 //! through modified clients.
 
 pub mod client_manager;
+pub mod codec;
+pub mod congestion;
+pub mod connection;
+pub mod crypto;
+pub mod discovery;
+pub mod entities;
 pub mod game;
+pub mod impairment;
+pub mod inbound;
+pub mod mailbox;
+pub mod net_conditions;
+pub mod netcode_handshake;
 pub mod network;
+pub mod pcap;
+pub mod pipeline;
+pub mod rate_limiter;
+pub mod reliable;
+pub mod scheduler;
+pub mod transport;
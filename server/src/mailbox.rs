@@ -0,0 +1,237 @@
+//! Per-client inbox/outbox mailbox model for a `Request` → computation →
+//! `Update` data flow.
+//!
+//! Without a mailbox, a client's inbound traffic is matched inline against the raw
+//! `Packet` wire type as it's decoded (see the example in this crate's root
+//! doc comment), and its outbound traffic is written straight to its
+//! `Sender` the moment it's produced. `ClientMailbox` replaces that ad-hoc
+//! pairing with an explicit typed inbox of `Request`s and outbox of
+//! `Update`s: the main loop drains every client's inbox, dispatches each
+//! `Request` through a `RequestHandler` uniformly rather than matching
+//! inline, and a separate flush step is the only place that touches the
+//! client's `Sender`. Decoupling networking from simulation this way makes
+//! the single-threaded loop easier to test deterministically (feed
+//! `Request`s, assert the resulting `Update`s) and is a prerequisite for
+//! batching multiple `Update`s into one outgoing datagram.
+//!
+//! `client_manager::ClientManager::process_mailbox` wires this into the real
+//! `Packet::Input`/`Packet::Disconnect` path: `Server::handle_packet` pushes
+//! a `Request` instead of calling `add_input_with_mac`/`remove_client`
+//! inline, and a `MailboxHandler` dispatches it through the same methods it
+//! used to call directly. `Server::handle_packet` sends no packet for any
+//! returned `Update`: it discards them after an `Input`, and after a
+//! `Disconnect` only checks them for `Update::Disconnected` before removing
+//! the player. Nothing is coalesced into one datagram.
+use std::collections::VecDeque;
+
+use shared::InputState;
+
+/// One inbound message a client's mailbox can hold, narrowed from the full
+/// wire `Packet` down to what a tick's simulation step actually consumes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// `mac` is carried alongside the input rather than checked before the
+    /// `Request` is built, so `MailboxHandler::handle_input` can route it
+    /// through the same `add_input_with_mac` verification the old inline
+    /// match used.
+    Input { input: InputState, mac: Option<[u8; 32]> },
+    AcknowledgeSnapshot { tick: u32 },
+    Disconnect,
+}
+
+/// One outbound message produced for a client, queued in its outbox until a
+/// flush step serializes it to the client's `Sender`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Update {
+    /// Echoes the input sequence this client's inputs are now caught up to.
+    InputAccepted { last_processed_input: u32 },
+    /// A `GameState`/`GameStateDelta` is due at this tick; the flush step
+    /// decides which of the two to actually encode.
+    SnapshotDue { tick: u32 },
+    Disconnected { reason: String },
+}
+
+/// A client's inbound queue, drained once per tick.
+#[derive(Debug, Default)]
+pub struct Inbox {
+    pending: VecDeque<Request>,
+}
+
+impl Inbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, request: Request) {
+        self.pending.push_back(request);
+    }
+
+    /// Takes every pending `Request`, in arrival order, leaving the inbox
+    /// empty for the next tick.
+    pub fn drain(&mut self) -> VecDeque<Request> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// A client's outbound queue, filled by a `RequestHandler` and drained by a
+/// flush step.
+#[derive(Debug, Default)]
+pub struct Outbox {
+    pending: VecDeque<Update>,
+}
+
+impl Outbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, update: Update) {
+        self.pending.push_back(update);
+    }
+
+    /// Takes every pending `Update`, in the order handlers produced them,
+    /// leaving the outbox empty for the next tick.
+    pub fn drain(&mut self) -> VecDeque<Update> {
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Paired inbox/outbox for one client. This is the mailbox half of a
+/// `Client`; it doesn't itself hold connection state like `addr` or
+/// `last_seen`; see the module docs for where it's meant to sit.
+#[derive(Debug, Default)]
+pub struct ClientMailbox {
+    pub inbox: Inbox,
+    pub outbox: Outbox,
+}
+
+impl ClientMailbox {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drains `self.inbox`, dispatching each `Request` through `handler` in
+    /// arrival order, then drains and returns the `Update`s that produced
+    /// for a flush step to serialize.
+    pub fn process(&mut self, handler: &mut impl RequestHandler) -> VecDeque<Update> {
+        for request in self.inbox.drain() {
+            dispatch(handler, request, &mut self.outbox);
+        }
+        self.outbox.drain()
+    }
+}
+
+/// Per-`Request`-kind computation, kept as small uniform methods so
+/// `dispatch` is the only place that matches on `Request` at all — a handler
+/// implementation just fills in what connecting, applying an input, or
+/// disconnecting means for its simulation state.
+pub trait RequestHandler {
+    fn handle_input(&mut self, input: InputState, mac: Option<[u8; 32]>, outbox: &mut Outbox);
+    fn handle_acknowledge_snapshot(&mut self, tick: u32, outbox: &mut Outbox);
+    fn handle_disconnect(&mut self, outbox: &mut Outbox);
+}
+
+/// Routes one `Request` to the matching `RequestHandler` method. The single
+/// match site the rest of the mailbox model is built to avoid repeating.
+fn dispatch(handler: &mut impl RequestHandler, request: Request, outbox: &mut Outbox) {
+    match request {
+        Request::Input { input, mac } => handler.handle_input(input, mac, outbox),
+        Request::AcknowledgeSnapshot { tick } => handler.handle_acknowledge_snapshot(tick, outbox),
+        Request::Disconnect => handler.handle_disconnect(outbox),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(sequence: u32) -> InputState {
+        InputState {
+            sequence,
+            timestamp: 0,
+            left: false,
+            right: false,
+            jump: false,
+        }
+    }
+
+    /// Records which handler methods fired and in what order, without
+    /// needing a real `game::GameState` to exercise the dispatch plumbing.
+    struct RecordingHandler {
+        last_processed_input: u32,
+    }
+
+    impl RequestHandler for RecordingHandler {
+        fn handle_input(&mut self, input: InputState, _mac: Option<[u8; 32]>, outbox: &mut Outbox) {
+            self.last_processed_input = input.sequence;
+            outbox.push(Update::InputAccepted {
+                last_processed_input: input.sequence,
+            });
+        }
+
+        fn handle_acknowledge_snapshot(&mut self, tick: u32, outbox: &mut Outbox) {
+            outbox.push(Update::SnapshotDue { tick });
+        }
+
+        fn handle_disconnect(&mut self, outbox: &mut Outbox) {
+            outbox.push(Update::Disconnected {
+                reason: "client requested disconnect".to_string(),
+            });
+        }
+    }
+
+    #[test]
+    fn process_dispatches_every_queued_request_in_order() {
+        let mut mailbox = ClientMailbox::new();
+        mailbox.inbox.push(Request::Input { input: input(1), mac: None });
+        mailbox.inbox.push(Request::Input { input: input(2), mac: None });
+        mailbox.inbox.push(Request::AcknowledgeSnapshot { tick: 7 });
+
+        let mut handler = RecordingHandler {
+            last_processed_input: 0,
+        };
+        let updates: Vec<_> = mailbox.process(&mut handler).into_iter().collect();
+
+        assert_eq!(handler.last_processed_input, 2);
+        assert_eq!(
+            updates,
+            vec![
+                Update::InputAccepted {
+                    last_processed_input: 1
+                },
+                Update::InputAccepted {
+                    last_processed_input: 2
+                },
+                Update::SnapshotDue { tick: 7 },
+            ]
+        );
+    }
+
+    #[test]
+    fn process_leaves_the_inbox_empty_for_the_next_tick() {
+        let mut mailbox = ClientMailbox::new();
+        mailbox.inbox.push(Request::Disconnect);
+
+        let mut handler = RecordingHandler {
+            last_processed_input: 0,
+        };
+        mailbox.process(&mut handler);
+
+        assert!(mailbox.inbox.drain().is_empty());
+    }
+
+    #[test]
+    fn outbox_drain_is_empty_immediately_after_a_process_call_drained_it() {
+        let mut mailbox = ClientMailbox::new();
+        mailbox.inbox.push(Request::Disconnect);
+
+        let mut handler = RecordingHandler {
+            last_processed_input: 0,
+        };
+        let first = mailbox.process(&mut handler);
+        assert_eq!(first.len(), 1);
+
+        let second = mailbox.outbox.drain();
+        assert!(second.is_empty());
+    }
+}
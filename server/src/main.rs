@@ -1,8 +1,23 @@
 //! Game server entry point
 
 mod client_manager;
+mod codec;
+mod congestion;
+mod connection;
+mod crypto;
+mod entities;
 mod game;
+mod impairment;
+mod inbound;
+mod mailbox;
+mod net_conditions;
+mod netcode_handshake;
 mod network;
+mod pcap;
+mod pipeline;
+mod rate_limiter;
+mod scheduler;
+mod transport;
 
 use clap::Parser;
 use log::info;
@@ -27,6 +42,25 @@ struct Args {
     /// Maximum number of concurrent client connections
     #[arg(short = 'm', long, default_value = "16")]
     max_clients: usize,
+
+    /// Master server address to heartbeat to for server-browser discovery
+    #[arg(long)]
+    master: Option<String>,
+
+    /// Server name advertised to the master server
+    #[arg(long, default_value = "Untitled Server")]
+    name: String,
+
+    /// Map name advertised to the master server
+    #[arg(long, default_value = "default")]
+    map: String,
+
+    /// Require clients to complete an authenticated handshake (ECDH key
+    /// exchange + rolling MAC on every input — inputs are tamper-evident but
+    /// not confidential); unauthenticated `Connect`s are refused instead of
+    /// falling back to unauthenticated
+    #[arg(long)]
+    authenticate: bool,
 }
 
 #[tokio::main]
@@ -49,6 +83,18 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("Max clients: {}", args.max_clients);
 
     let mut server = network::Server::new(&addr, tick_duration, args.max_clients).await?;
+
+    if let Some(master) = args.master {
+        let master_addr = master.parse()?;
+        info!("Announcing to master server at {}", master_addr);
+        server = server.with_master(master_addr, args.name, args.map);
+    }
+
+    if args.authenticate {
+        info!("Requiring authenticated sessions for all clients");
+        server = server.with_authentication();
+    }
+
     server.run().await?;
 
     Ok(())
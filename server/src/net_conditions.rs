@@ -0,0 +1,300 @@
+//! Network-condition emulation for outgoing packets.
+//!
+//! Each client is assigned to a named region; a region-to-region matrix of
+//! base latency, jitter, and packet-loss probability decides, for every
+//! outgoing packet, whether it arrives at all and if so how late. This turns
+//! the ping/loss numbers `NetworkGraph` already renders into a reproducible
+//! experiment instead of whatever the real network happens to be doing, and
+//! lets the client reconciliation path be exercised under adverse conditions
+//! with a fixed RNG seed.
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use shared::Packet;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+/// Base latency, jitter, and loss for one region pair.
+#[derive(Debug, Clone, Copy)]
+pub struct RegionLink {
+    pub base_latency: Duration,
+    pub jitter_stddev_ms: f64,
+    pub loss_probability: f64,
+}
+
+impl RegionLink {
+    pub fn new(base_latency: Duration, jitter_stddev_ms: f64, loss_probability: f64) -> Self {
+        Self {
+            base_latency,
+            jitter_stddev_ms,
+            loss_probability: loss_probability.clamp(0.0, 1.0),
+        }
+    }
+}
+
+impl Default for RegionLink {
+    /// A link with no added latency, jitter, or loss — effectively the same
+    /// as emulation being off, used when a client's region has no explicit
+    /// entry in the matrix.
+    fn default() -> Self {
+        Self::new(Duration::ZERO, 0.0, 0.0)
+    }
+}
+
+/// A packet queued for delivery at a simulated future time.
+#[derive(Debug)]
+struct QueuedDelivery {
+    deliver_at: Instant,
+    addr: SocketAddr,
+    packet: Packet,
+}
+
+impl PartialEq for QueuedDelivery {
+    fn eq(&self, other: &Self) -> bool {
+        self.deliver_at == other.deliver_at
+    }
+}
+impl Eq for QueuedDelivery {}
+
+impl PartialOrd for QueuedDelivery {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedDelivery {
+    // Reversed so `BinaryHeap` (a max-heap) pops the earliest delivery time first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.deliver_at.cmp(&self.deliver_at)
+    }
+}
+
+/// Server-region assignment plus a region-to-region latency/jitter/loss
+/// matrix, applied to packets before they're handed to the transport layer.
+/// Disabled by default; see `Server::with_net_conditions`.
+pub struct NetConditions {
+    enabled: bool,
+    server_region: String,
+    client_regions: HashMap<u32, String>,
+    links: HashMap<(String, String), RegionLink>,
+    default_link: RegionLink,
+    rng: StdRng,
+    queue: BinaryHeap<QueuedDelivery>,
+}
+
+impl NetConditions {
+    /// `seed` makes the jitter and loss rolls reproducible across runs, so
+    /// tests can assert an exact sequence of drop/delay decisions.
+    pub fn new(server_region: impl Into<String>, seed: u64) -> Self {
+        Self {
+            enabled: false,
+            server_region: server_region.into(),
+            client_regions: HashMap::new(),
+            links: HashMap::new(),
+            default_link: RegionLink::default(),
+            rng: StdRng::seed_from_u64(seed),
+            queue: BinaryHeap::new(),
+        }
+    }
+
+    pub fn enable(&mut self) {
+        self.enabled = true;
+    }
+
+    pub fn disable(&mut self) {
+        self.enabled = false;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_client_region(&mut self, client_id: u32, region: impl Into<String>) {
+        self.client_regions.insert(client_id, region.into());
+    }
+
+    pub fn remove_client(&mut self, client_id: u32) {
+        self.client_regions.remove(&client_id);
+    }
+
+    /// Region pairs are symmetric: `set_link("eu", "us", ...)` also governs
+    /// packets sent `"us" -> "eu"`.
+    pub fn set_link(&mut self, region_a: &str, region_b: &str, link: RegionLink) {
+        self.links.insert(Self::link_key(region_a, region_b), link);
+    }
+
+    fn link_key(a: &str, b: &str) -> (String, String) {
+        if a <= b {
+            (a.to_string(), b.to_string())
+        } else {
+            (b.to_string(), a.to_string())
+        }
+    }
+
+    fn link_for(&self, client_id: u32) -> RegionLink {
+        let Some(client_region) = self.client_regions.get(&client_id) else {
+            return self.default_link;
+        };
+        self.links
+            .get(&Self::link_key(&self.server_region, client_region))
+            .copied()
+            .unwrap_or(self.default_link)
+    }
+
+    /// Rolls loss and jitter for a packet bound for `client_id`. `None` means
+    /// the packet is dropped; `Some(delay)` is the simulated one-way latency:
+    /// `base_latency` plus a jitter draw clamped at zero.
+    fn decide(&mut self, client_id: u32) -> Option<Duration> {
+        let link = self.link_for(client_id);
+        if self.rng.gen_bool(link.loss_probability) {
+            return None;
+        }
+        let jitter_ms = Self::sample_gaussian(&mut self.rng, link.jitter_stddev_ms).max(0.0);
+        Some(link.base_latency + Duration::from_secs_f64(jitter_ms / 1000.0))
+    }
+
+    /// Box-Muller transform, since `rand_distr` isn't part of this project's
+    /// dependency set and a single standard-normal sample is all we need.
+    fn sample_gaussian(rng: &mut StdRng, stddev: f64) -> f64 {
+        if stddev <= 0.0 {
+            return 0.0;
+        }
+        let u1: f64 = rng.gen_range(f64::EPSILON..1.0);
+        let u2: f64 = rng.gen_range(0.0..1.0);
+        let z0 = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+        z0 * stddev
+    }
+
+    /// Applies this tick's drop/delay decision for `packet` and, if it
+    /// survives, schedules it into the delivery queue relative to `now`.
+    pub fn enqueue(&mut self, client_id: u32, addr: SocketAddr, packet: Packet, now: Instant) {
+        if let Some(delay) = self.decide(client_id) {
+            self.queue.push(QueuedDelivery {
+                deliver_at: now + delay,
+                addr,
+                packet,
+            });
+        }
+    }
+
+    /// Pops every packet whose simulated delivery time has arrived, earliest first.
+    pub fn drain_ready(&mut self, now: Instant) -> Vec<(SocketAddr, Packet)> {
+        let mut ready = Vec::new();
+        while let Some(next) = self.queue.peek() {
+            if next.deliver_at > now {
+                break;
+            }
+            let next = self.queue.pop().expect("just peeked");
+            ready.push((next.addr, next.packet));
+        }
+        ready
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_addr() -> SocketAddr {
+        "127.0.0.1:9000".parse().unwrap()
+    }
+
+    #[test]
+    fn test_disabled_by_default() {
+        let conditions = NetConditions::new("eu", 1);
+        assert!(!conditions.is_enabled());
+    }
+
+    #[test]
+    fn test_unassigned_client_uses_default_link_with_no_delay_or_loss() {
+        let mut conditions = NetConditions::new("eu", 1);
+        let delay = conditions.decide(42);
+        assert_eq!(delay, Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_same_seed_produces_the_same_drop_delay_sequence() {
+        let link = RegionLink::new(Duration::from_millis(80), 10.0, 0.3);
+        let build = || {
+            let mut conditions = NetConditions::new("eu", 7);
+            conditions.set_client_region(1, "us");
+            conditions.set_link("eu", "us", link);
+            conditions
+        };
+
+        let mut a = build();
+        let mut b = build();
+        for _ in 0..50 {
+            assert_eq!(a.decide(1), b.decide(1));
+        }
+    }
+
+    #[test]
+    fn test_zero_loss_probability_never_drops() {
+        let mut conditions = NetConditions::new("eu", 3);
+        conditions.set_client_region(1, "us");
+        conditions.set_link("eu", "us", RegionLink::new(Duration::from_millis(50), 5.0, 0.0));
+
+        for _ in 0..200 {
+            assert!(conditions.decide(1).is_some());
+        }
+    }
+
+    #[test]
+    fn test_full_loss_probability_always_drops() {
+        let mut conditions = NetConditions::new("eu", 3);
+        conditions.set_client_region(1, "us");
+        conditions.set_link("eu", "us", RegionLink::new(Duration::from_millis(50), 5.0, 1.0));
+
+        for _ in 0..200 {
+            assert_eq!(conditions.decide(1), None);
+        }
+    }
+
+    #[test]
+    fn test_link_is_symmetric_regardless_of_argument_order() {
+        let mut a = NetConditions::new("eu", 9);
+        a.set_link("eu", "us", RegionLink::new(Duration::from_millis(123), 0.0, 0.0));
+        let mut b = NetConditions::new("eu", 9);
+        b.set_link("us", "eu", RegionLink::new(Duration::from_millis(123), 0.0, 0.0));
+
+        a.set_client_region(1, "us");
+        b.set_client_region(1, "us");
+        assert_eq!(a.decide(1), b.decide(1));
+    }
+
+    #[test]
+    fn test_drain_ready_releases_only_packets_whose_delivery_time_has_passed() {
+        let mut conditions = NetConditions::new("eu", 5);
+        conditions.set_client_region(1, "us");
+        conditions.set_link("eu", "us", RegionLink::new(Duration::from_millis(100), 0.0, 0.0));
+
+        let now = Instant::now();
+        conditions.enqueue(1, test_addr(), Packet::Ping { nonce: 1 }, now);
+
+        assert!(conditions.drain_ready(now).is_empty());
+        let ready = conditions.drain_ready(now + Duration::from_millis(100));
+        assert_eq!(ready.len(), 1);
+    }
+
+    #[test]
+    fn test_drain_ready_releases_in_delivery_order_even_if_enqueued_out_of_order() {
+        let mut conditions = NetConditions::new("eu", 5);
+        conditions.set_client_region(1, "us");
+        conditions.set_client_region(2, "jp");
+        conditions.set_link("eu", "us", RegionLink::new(Duration::from_millis(200), 0.0, 0.0));
+        conditions.set_link("eu", "jp", RegionLink::new(Duration::from_millis(50), 0.0, 0.0));
+
+        let now = Instant::now();
+        // Enqueued in the order slow-then-fast; delivery order should still be fast-then-slow.
+        conditions.enqueue(1, test_addr(), Packet::Ping { nonce: 1 }, now);
+        conditions.enqueue(2, test_addr(), Packet::Ping { nonce: 2 }, now);
+
+        let ready = conditions.drain_ready(now + Duration::from_millis(200));
+        assert_eq!(ready.len(), 2);
+        assert!(matches!(ready[0].1, Packet::Ping { nonce: 2 }));
+        assert!(matches!(ready[1].1, Packet::Ping { nonce: 1 }));
+    }
+}
@@ -1,16 +1,54 @@
 //! Server network layer handling UDP communications and game loop coordination
 
 use crate::client_manager::ClientManager;
+use crate::codec;
+use crate::crypto::Handshake;
 use crate::game::GameState;
+use crate::inbound::InboundQueue;
+use crate::mailbox;
+use crate::net_conditions::NetConditions;
+use crate::netcode_handshake::{self, Netcode};
+use crate::rate_limiter::ConnectRateLimiter;
+use crate::transport::Transport;
 use bincode::{deserialize, serialize};
+use bytes::Bytes;
 use log::{debug, error, info, warn};
-use shared::{InputState, Packet, Player, PLAYER_SIZE, PLAYER_SPEED};
+use shared::{Gamemode, InputState, Packet, Player, PLAYER_SIZE, PLAYER_SPEED};
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::net::UdpSocket;
 use tokio::sync::{mpsc, RwLock};
 use tokio::time::interval;
+use x25519_dalek::PublicKey;
+
+/// Packets that must not be silently dropped go through the reliable transport
+/// window; frequent state traffic stays unreliable since a lost `GameState` is
+/// superseded by the next tick anyway.
+fn is_reliable(packet: &Packet) -> bool {
+    matches!(
+        packet,
+        Packet::Connect { .. } | Packet::Connected { .. } | Packet::Disconnect | Packet::Disconnected { .. }
+    )
+}
+
+/// Splits `current` against `baseline` the way `Packet::GameStateDelta`
+/// wants it: players whose fields differ (or who are new since `baseline`),
+/// and ids that existed in `baseline` but are gone from `current`.
+fn diff_players(current: &HashMap<u32, Player>, baseline: &HashMap<u32, Player>) -> (Vec<Player>, Vec<u32>) {
+    let changed: Vec<Player> = current
+        .values()
+        .filter(|player| baseline.get(&player.id).map(|b| b != *player).unwrap_or(true))
+        .cloned()
+        .collect();
+    let removed: Vec<u32> = baseline
+        .keys()
+        .filter(|id| !current.contains_key(id))
+        .copied()
+        .collect();
+    (changed, removed)
+}
 
 /// Messages sent from network tasks to main server loop
 #[derive(Debug)]
@@ -22,6 +60,11 @@ pub enum ServerMessage {
     ClientTimeout {
         client_id: u32,
     },
+    /// A timed-out client's reconnect grace period expired without a resume;
+    /// its player should now actually be removed.
+    SessionExpired {
+        client_id: u32,
+    },
     #[allow(dead_code)]
     Shutdown,
 }
@@ -35,7 +78,7 @@ pub enum GameMessage {
     },
     BroadcastPacket {
         packet: Packet,
-        exclude: Option<u32>,
+        targets: Vec<SocketAddr>,
     },
 }
 
@@ -43,8 +86,52 @@ pub enum GameMessage {
 pub struct Server {
     socket: Arc<UdpSocket>,
     clients: Arc<RwLock<ClientManager>>,
+    transport: Arc<RwLock<Transport>>,
+    inbound: Arc<RwLock<InboundQueue>>,
+    /// Gates `Packet::Connect` processing per source IP so a flood from one
+    /// address can't force expensive handshake work before game logic even
+    /// runs. Already-established clients never go through this.
+    connect_limiter: Arc<ConnectRateLimiter>,
     game_state: GameState,
     tick_duration: Duration,
+    max_clients: usize,
+
+    /// Recent broadcast `GameState` packets not yet old enough to release to
+    /// spectators (see `SPECTATOR_SNAPSHOT_DELAY_TICKS`), oldest first. A
+    /// late-joining spectator this way always starts watching a tick that's
+    /// already fully buffered on every other spectator's screen too, instead
+    /// of a thinner, jumpier stream than players get.
+    spectator_snapshot_buffer: VecDeque<Packet>,
+
+    // Server-browser discovery
+    name: String,
+    map: String,
+    master_addr: Option<SocketAddr>,
+
+    /// When set via `with_authentication`, a `Connect` without an
+    /// `encrypt_public_key` is refused instead of falling back to an
+    /// unauthenticated session. Note this buys tamper-evidence (a rolling
+    /// MAC over every input) rather than confidentiality — the wire payload
+    /// itself still isn't encrypted; see `crypto`'s module doc comment.
+    authentication_required: bool,
+
+    /// Seals/opens the `connect_token` carried on `Connect`/`Connected` (see
+    /// `netcode_handshake::Netcode`). One instance's keys are shared across
+    /// every client this server issues a token to.
+    netcode: Netcode,
+    /// When set via `with_resume_token_required`, a reconnect that presents
+    /// a `resume_token` without also presenting a valid `connect_token` is
+    /// refused rather than resumed. This only protects *returning* traffic —
+    /// a client's very first `Connect` has nothing to echo (no separate
+    /// pre-registration backend exists in this repo) and sails straight
+    /// through `add_client`/`add_player` regardless of this flag, so don't
+    /// read the name as "every connection needs a token".
+    resume_token_required: bool,
+
+    /// When set via `with_net_conditions`, outgoing packets are routed
+    /// through emulated per-region latency/jitter/loss before transmission.
+    /// `None` means no emulation, the same as disabled.
+    net_conditions: Option<Arc<RwLock<NetConditions>>>,
 
     // Communication channels
     server_tx: mpsc::UnboundedSender<ServerMessage>,
@@ -53,6 +140,20 @@ pub struct Server {
     game_rx: mpsc::UnboundedReceiver<GameMessage>,
 }
 
+/// Heartbeats announce this protocol/content version to the master server.
+const SERVER_VERSION: u32 = 1;
+
+/// Range of wire-protocol versions this server can speak. A `Connect`
+/// handshake negotiates down to the highest version in the overlap with the
+/// client's advertised `min_version..=max_version`.
+const SERVER_MIN_PROTOCOL_VERSION: u32 = 1;
+const SERVER_MAX_PROTOCOL_VERSION: u32 = 1;
+
+/// How many ticks a broadcast `GameState` snapshot is held before it's
+/// released to spectators, so a late joiner sees a stable, already-buffered
+/// stream rather than whatever partial tick it happened to connect on.
+const SPECTATOR_SNAPSHOT_DELAY_TICKS: usize = 30;
+
 impl Server {
     pub async fn new(
         addr: &str,
@@ -67,9 +168,24 @@ impl Server {
 
         Ok(Server {
             socket,
-            clients: Arc::new(RwLock::new(ClientManager::new(max_clients))),
+            clients: Arc::new(RwLock::new(ClientManager::with_tick_rate(
+                max_clients,
+                1.0 / tick_duration.as_secs_f64(),
+            ))),
+            transport: Arc::new(RwLock::new(Transport::new())),
+            inbound: Arc::new(RwLock::new(InboundQueue::new())),
+            connect_limiter: Arc::new(ConnectRateLimiter::default()),
             game_state: GameState::new(),
             tick_duration,
+            max_clients,
+            spectator_snapshot_buffer: VecDeque::new(),
+            name: "Untitled Server".to_string(),
+            map: "default".to_string(),
+            master_addr: None,
+            authentication_required: false,
+            netcode: Netcode::new(),
+            resume_token_required: false,
+            net_conditions: None,
             server_tx,
             server_rx,
             game_tx,
@@ -77,10 +193,117 @@ impl Server {
         })
     }
 
+    /// Registers a master-server address to heartbeat to, so browsing clients can
+    /// discover this instance. Without this, the server simply never announces
+    /// itself and behaves exactly as before.
+    pub fn with_master(mut self, master_addr: SocketAddr, name: String, map: String) -> Self {
+        self.master_addr = Some(master_addr);
+        self.name = name;
+        self.map = map;
+        self
+    }
+
+    /// Requires every connecting client to offer an `encrypt_public_key` on
+    /// `Connect` and complete the ECDH handshake, rejecting anyone who
+    /// doesn't. Without this, authentication is opportunistic: a client that
+    /// offers a key gets an authenticated session, one that doesn't gets an
+    /// unauthenticated one.
+    pub fn with_authentication(mut self) -> Self {
+        self.authentication_required = true;
+        self
+    }
+
+    /// Requires a reconnecting client to present a `connect_token` this
+    /// server itself sealed and issued in an earlier `Connected` (see
+    /// `netcode_handshake::Netcode::issue_connection_token`), rejecting a
+    /// `resume_token` presented without one. Named for what it actually
+    /// gates — *resuming* — rather than `with_connect_token_required`: a
+    /// fresh (non-resuming) `Connect` is unaffected and reaches
+    /// `add_client`/`add_player` with no token involved at all, so this is
+    /// not a blanket "every connection needs a token" guarantee.
+    pub fn with_resume_token_required(mut self) -> Self {
+        self.resume_token_required = true;
+        self
+    }
+
+    /// Installs a region-based `NetConditions` emulator. Still has to be
+    /// enabled via `NetConditions::enable` (directly, or before passing it
+    /// here) to actually start delaying/dropping packets.
+    pub fn with_net_conditions(mut self, net_conditions: NetConditions) -> Self {
+        self.net_conditions = Some(Arc::new(RwLock::new(net_conditions)));
+        self
+    }
+
+    /// Overrides the default per-source handshake rate limit (one attempt
+    /// per second, burst of five, five-minute TTL).
+    pub fn with_connect_rate_limit(mut self, rate_per_sec: f32, burst: f32, ttl: Duration) -> Self {
+        self.connect_limiter = Arc::new(ConnectRateLimiter::new(rate_per_sec, burst, ttl));
+        self
+    }
+
+    /// Spawns task that periodically announces this server to its configured
+    /// master address. A no-op if no master address was configured.
+    async fn spawn_heartbeat_sender(&self) {
+        let Some(master_addr) = self.master_addr else {
+            return;
+        };
+
+        let name = self.name.clone();
+        let map = self.map.clone();
+        let max_clients = self.max_clients as u32;
+        let clients = Arc::clone(&self.clients);
+        let game_tx = self.game_tx.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(5));
+            loop {
+                interval.tick().await;
+
+                let current_players = {
+                    let clients = clients.read().await;
+                    clients.len() as u32
+                };
+
+                let heartbeat = Packet::Heartbeat {
+                    name: name.clone(),
+                    map: map.clone(),
+                    current_players,
+                    max_players: max_clients,
+                    version: SERVER_VERSION,
+                };
+
+                if let Err(e) = game_tx.send(GameMessage::SendPacket {
+                    packet: heartbeat,
+                    addr: master_addr,
+                }) {
+                    error!("Failed to queue heartbeat to master: {}", e);
+                    return;
+                }
+            }
+        });
+    }
+
+    /// Spawns task that periodically evicts stale entries from the connect
+    /// rate limiter, bounding its memory under a flood of spoofed, never-
+    /// repeating source addresses.
+    fn spawn_connect_limiter_gc(&self) {
+        let connect_limiter = Arc::clone(&self.connect_limiter);
+
+        tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_secs(30));
+            loop {
+                ticker.tick().await;
+                connect_limiter.gc().await;
+            }
+        });
+    }
+
     /// Spawns task that continuously listens for incoming packets
     async fn spawn_network_receiver(&self) {
         let socket = Arc::clone(&self.socket);
-        let server_tx = self.server_tx.clone();
+        let transport = Arc::clone(&self.transport);
+        let clients = Arc::clone(&self.clients);
+        let inbound = Arc::clone(&self.inbound);
 
         tokio::spawn(async move {
             let mut buffer = [0u8; 2048];
@@ -88,15 +311,31 @@ impl Server {
             loop {
                 match socket.recv_from(&mut buffer).await {
                     Ok((len, addr)) => {
-                        if let Ok(packet) = deserialize::<Packet>(&buffer[0..len]) {
-                            if let Err(e) =
-                                server_tx.send(ServerMessage::PacketReceived { packet, addr })
-                            {
-                                error!("Failed to send packet to main loop: {}", e);
-                                break;
+                        clients.write().await.record_bytes_received(addr, len);
+
+                        if let Ok(frame) = codec::decode_frame(&buffer[0..len]) {
+                            let payloads = {
+                                let mut transport = transport.write().await;
+                                transport.on_frame_received(addr, frame)
+                            };
+
+                            for payload in payloads {
+                                if let Ok(packet) = codec::decode_packet(&payload) {
+                                    let reliable = is_reliable(&packet);
+                                    let mut queue = inbound.write().await;
+                                    if !queue.push(packet, addr, reliable) {
+                                        warn!(
+                                            "Inbound queue full ({:?}), dropping packet from {}",
+                                            queue.queue_info(),
+                                            addr
+                                        );
+                                    }
+                                } else {
+                                    warn!("Failed to deserialize packet payload from {}", addr);
+                                }
                             }
                         } else {
-                            warn!("Failed to deserialize packet from {}", addr);
+                            warn!("Failed to deserialize frame from {}", addr);
                         }
                     }
                     Err(e) => {
@@ -108,33 +347,136 @@ impl Server {
         });
     }
 
+    /// Spawns task that drains the bounded inbound queue into the main loop.
+    /// This is where the `MAX_PENDING` cap set in `InboundQueue` actually
+    /// protects memory: the receiver task only ever buffers up to that many
+    /// packets before dropping, regardless of how far behind the main loop
+    /// falls.
+    async fn spawn_inbound_dispatcher(&self) {
+        let inbound = Arc::clone(&self.inbound);
+        let server_tx = self.server_tx.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let next = inbound.write().await.pop();
+                match next {
+                    Some((packet, addr)) => {
+                        if let Err(e) =
+                            server_tx.send(ServerMessage::PacketReceived { packet, addr })
+                        {
+                            error!("Failed to send packet to main loop: {}", e);
+                            return;
+                        }
+                        inbound.write().await.mark_processed();
+                    }
+                    None => tokio::time::sleep(Duration::from_millis(1)).await,
+                }
+            }
+        });
+    }
+
+    /// If network-condition emulation is configured and enabled for the
+    /// client at `addr`, hands the packet to the delivery queue and returns
+    /// `true` so the caller skips its own immediate send; the queued copy is
+    /// released later by `spawn_net_conditions_drain`. Returns `false`
+    /// (send normally) when emulation is off or `addr` isn't a known client.
+    async fn maybe_emulate(
+        net_conditions: &Option<Arc<RwLock<NetConditions>>>,
+        clients: &Arc<RwLock<ClientManager>>,
+        packet: &Packet,
+        addr: SocketAddr,
+    ) -> bool {
+        let Some(net_conditions) = net_conditions else {
+            return false;
+        };
+        let mut net_conditions = net_conditions.write().await;
+        if !net_conditions.is_enabled() {
+            return false;
+        }
+        let Some(client_id) = clients.read().await.find_client_by_addr(addr) else {
+            return false;
+        };
+        net_conditions.enqueue(client_id, addr, packet.clone(), Instant::now());
+        true
+    }
+
+    /// Spawns a task that periodically releases packets whose emulated
+    /// delivery time has arrived. A no-op if no `NetConditions` was configured.
+    fn spawn_net_conditions_drain(&self) {
+        let Some(net_conditions) = self.net_conditions.clone() else {
+            return;
+        };
+        let socket = Arc::clone(&self.socket);
+        let transport = Arc::clone(&self.transport);
+        let clients = Arc::clone(&self.clients);
+
+        tokio::spawn(async move {
+            let mut ticker = interval(Duration::from_millis(5));
+            loop {
+                ticker.tick().await;
+                let ready = net_conditions.write().await.drain_ready(Instant::now());
+                for (addr, packet) in ready {
+                    if let Err(e) =
+                        Self::send_packet_impl(&socket, &transport, &clients, &packet, addr).await
+                    {
+                        error!("Failed to send emulated packet to {}: {}", addr, e);
+                    }
+                }
+            }
+        });
+    }
+
     /// Spawns task that processes outgoing packet queue
     async fn spawn_network_sender(&mut self) {
         let socket = Arc::clone(&self.socket);
         let clients = Arc::clone(&self.clients);
+        let transport = Arc::clone(&self.transport);
+        let net_conditions = self.net_conditions.clone();
         let mut game_rx = std::mem::replace(&mut self.game_rx, mpsc::unbounded_channel().1);
 
         tokio::spawn(async move {
             while let Some(message) = game_rx.recv().await {
                 match message {
                     GameMessage::SendPacket { packet, addr } => {
-                        if let Err(e) = Self::send_packet_impl(&socket, &packet, addr).await {
+                        if Self::maybe_emulate(&net_conditions, &clients, &packet, addr).await {
+                            continue;
+                        }
+                        if let Err(e) = Self::send_packet_impl(
+                            &socket, &transport, &clients, &packet, addr,
+                        )
+                        .await
+                        {
                             error!("Failed to send packet to {}: {}", addr, e);
                         }
                     }
-                    GameMessage::BroadcastPacket { packet, exclude } => {
-                        let client_addrs = {
-                            let clients_guard = clients.read().await;
-                            clients_guard.get_client_addrs()
+                    GameMessage::BroadcastPacket { packet, targets } => {
+                        // Encode the snapshot exactly once; every client send below
+                        // just clones the `Bytes` handle (a refcount bump) instead of
+                        // re-serializing the identical payload per recipient.
+                        let payload = match codec::encode_packet(&packet) {
+                            Ok(payload) => payload,
+                            Err(e) => {
+                                error!("Failed to encode broadcast packet: {}", e);
+                                continue;
+                            }
                         };
+                        let reliable = is_reliable(&packet);
 
-                        for (client_id, addr) in client_addrs {
-                            if Some(client_id) == exclude {
+                        for addr in targets {
+                            if Self::maybe_emulate(&net_conditions, &clients, &packet, addr).await {
                                 continue;
                             }
-
-                            if let Err(e) = Self::send_packet_impl(&socket, &packet, addr).await {
-                                error!("Failed to send to client {}: {}", client_id, e);
+                            if let Err(e) = Self::send_payload_impl(
+                                &socket,
+                                &transport,
+                                &clients,
+                                payload.clone(),
+                                reliable,
+                                addr,
+                            )
+                            .await
+                            {
+                                error!("Failed to send to {}: {}", addr, e);
                             }
                         }
                     }
@@ -143,9 +485,11 @@ impl Server {
         });
     }
 
-    /// Spawns task that monitors client timeouts
+    /// Spawns task that monitors client timeouts and retransmits unacked reliable frames
     async fn spawn_timeout_checker(&self) {
         let clients = Arc::clone(&self.clients);
+        let transport = Arc::clone(&self.transport);
+        let socket = Arc::clone(&self.socket);
         let server_tx = self.server_tx.clone();
 
         tokio::spawn(async move {
@@ -154,9 +498,17 @@ impl Server {
             loop {
                 interval.tick().await;
 
-                let timed_out = {
+                let (timed_out, expired_sessions, peer_addrs, due_for_keepalive) = {
                     let mut clients_guard = clients.write().await;
-                    clients_guard.check_timeouts()
+                    let timed_out = clients_guard.check_timeouts();
+                    let expired_sessions = clients_guard.purge_expired_sessions();
+                    let peer_addrs: Vec<SocketAddr> = clients_guard
+                        .get_client_addrs()
+                        .into_iter()
+                        .map(|(_, addr)| addr)
+                        .collect();
+                    let due_for_keepalive = clients_guard.clients_due_for_keepalive();
+                    (timed_out, expired_sessions, peer_addrs, due_for_keepalive)
                 };
 
                 for client_id in timed_out {
@@ -165,17 +517,85 @@ impl Server {
                         break;
                     }
                 }
+
+                for client_id in expired_sessions {
+                    if let Err(e) = server_tx.send(ServerMessage::SessionExpired { client_id }) {
+                        error!("Failed to send session-expired message: {}", e);
+                        break;
+                    }
+                }
+
+                // Nudge clients that haven't been heard from in a while, before
+                // their (possibly short, e.g. NAT-shortened) timeout evicts them.
+                for (client_id, addr) in due_for_keepalive {
+                    let ping = Packet::Ping { nonce: client_id as u64 };
+                    if let Err(e) = Self::send_packet_impl(&socket, &transport, &clients, &ping, addr).await {
+                        warn!("Failed to send keepalive ping to {}: {}", addr, e);
+                    }
+                }
+
+                for addr in peer_addrs {
+                    let due_frames = {
+                        let mut transport = transport.write().await;
+                        transport.expired_retransmits(addr)
+                    };
+
+                    for frame in due_frames {
+                        if let Ok(data) = codec::encode_frame(&frame) {
+                            match socket.send_to(&data, addr).await {
+                                Ok(_) => {
+                                    clients.write().await.record_bytes_sent(addr, data.len());
+                                }
+                                Err(e) => {
+                                    warn!("Failed to retransmit frame to {}: {}", addr, e);
+                                }
+                            }
+                        }
+                    }
+                }
             }
         });
     }
 
     async fn send_packet_impl(
         socket: &UdpSocket,
+        transport: &Arc<RwLock<Transport>>,
+        clients: &Arc<RwLock<ClientManager>>,
         packet: &Packet,
         addr: SocketAddr,
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let data = serialize(packet)?;
+        let payload = codec::encode_packet(packet)?;
+        Self::send_payload_impl(socket, transport, clients, payload, is_reliable(packet), addr)
+            .await
+    }
+
+    /// Frames and sends an already-encoded payload. Broadcasts share one encoded
+    /// `Bytes` across every recipient by calling this directly instead of
+    /// `send_packet_impl`, which would re-encode per client.
+    async fn send_payload_impl(
+        socket: &UdpSocket,
+        transport: &Arc<RwLock<Transport>>,
+        clients: &Arc<RwLock<ClientManager>>,
+        payload: Bytes,
+        reliable: bool,
+        addr: SocketAddr,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let frame = {
+            let mut transport = transport.write().await;
+            // The server only ever needs in-order delivery for the packets that
+            // go through the reliable window (connection lifecycle messages);
+            // `Channel::ReliableUnordered` in `connection.rs` is for callers
+            // that care about delivery but not ordering.
+            transport.frame_outgoing(addr, payload, reliable, true)
+        };
+        let Some(frame) = frame else {
+            // Peer is over its buffered-byte budget; this frame is coalesced away
+            // rather than queued, since state traffic is superseded by the next tick.
+            return Ok(());
+        };
+        let data = codec::encode_frame(&frame)?;
         socket.send_to(&data, addr).await?;
+        clients.write().await.record_bytes_sent(addr, data.len());
         Ok(())
     }
 
@@ -188,10 +608,34 @@ impl Server {
         }
     }
 
-    async fn broadcast_packet(&self, packet: &Packet, exclude: Option<u32>) {
+    /// Seals a fresh `ConnectionToken` for `client_id` to hand back in
+    /// `Connected`, installing its `client_to_server_key` so a later
+    /// `Packet::Input::sealed` from this client can be opened. Returns
+    /// `None` (and installs nothing) if connect-token enforcement isn't
+    /// enabled or this server couldn't resolve its own bound address to
+    /// list as valid for the token.
+    async fn issue_connect_token(
+        &self,
+        client_id: u32,
+        timeout_secs: u32,
+    ) -> Option<(Vec<u8>, [u8; 32], [u8; 32])> {
+        if !self.resume_token_required {
+            return None;
+        }
+        let local_addr = self.socket.local_addr().ok()?;
+        let (sealed, token) =
+            self.netcode.issue_connection_token(client_id as u64, timeout_secs, vec![local_addr]);
+        self.clients
+            .write()
+            .await
+            .install_input_channel_key(client_id, token.client_to_server_key);
+        Some((sealed, token.client_to_server_key, token.server_to_client_key))
+    }
+
+    async fn broadcast_packet(&self, packet: &Packet, targets: Vec<SocketAddr>) {
         if let Err(e) = self.game_tx.send(GameMessage::BroadcastPacket {
             packet: packet.clone(),
-            exclude,
+            targets,
         }) {
             error!("Failed to queue broadcast packet: {}", e);
         }
@@ -200,12 +644,121 @@ impl Server {
     /// Processes incoming packets and updates game state
     async fn handle_packet(&mut self, packet: Packet, addr: SocketAddr) {
         match packet {
-            Packet::Connect { client_version } => {
+            Packet::Connect {
+                min_version,
+                max_version,
+                resume_token,
+                requested_timeout_secs,
+                encrypt_public_key,
+                connect_token,
+                spectate,
+            } => {
+                // Already-established clients reconnecting or resuming never hit
+                // this: only a source with no live session pays the rate-limit
+                // check, so a flood can't force the handshake work below for
+                // legitimate traffic.
+                if self.clients.read().await.find_client_by_addr(addr).is_none()
+                    && !self.connect_limiter.check(addr.ip()).await
+                {
+                    debug!("Dropping Connect from {}: rate limited", addr);
+                    return;
+                }
+
                 info!(
-                    "Client connecting from {} (version: {})",
-                    addr, client_version
+                    "Client connecting from {} (versions {}..={})",
+                    addr, min_version, max_version
                 );
 
+                let negotiated_version =
+                    max_version.min(SERVER_MAX_PROTOCOL_VERSION);
+                if negotiated_version < min_version.max(SERVER_MIN_PROTOCOL_VERSION) {
+                    let response = Packet::Disconnected {
+                        reason: format!(
+                            "No common protocol version: client supports {}..={}, server supports {}..={}",
+                            min_version, max_version, SERVER_MIN_PROTOCOL_VERSION, SERVER_MAX_PROTOCOL_VERSION
+                        ),
+                    };
+                    self.send_packet(&response, addr).await;
+                    return;
+                }
+
+                if self.authentication_required && encrypt_public_key.is_none() {
+                    let response = Packet::Disconnected {
+                        reason: "Server requires an authenticated session".to_string(),
+                    };
+                    self.send_packet(&response, addr).await;
+                    return;
+                }
+
+                // If the client offered a public key, complete our half of the
+                // ECDH handshake now; `establish_session` installs the
+                // resulting keys once we know which client ID they belong to.
+                let handshake = encrypt_public_key.map(|client_public| {
+                    let handshake = Handshake::new();
+                    let server_public_key = *handshake.public_key.as_bytes();
+                    let keys = handshake.complete(PublicKey::from(client_public));
+                    (server_public_key, keys)
+                });
+                let server_public_key = handshake.as_ref().map(|(pk, _)| *pk);
+
+                // When connect-token enforcement is on, a reconnect has to
+                // prove it holds a token this server actually issued before
+                // its resume_token is even looked at — otherwise anyone who
+                // captured or guessed a resume_token could ride it back in.
+                if self.resume_token_required && resume_token.is_some() {
+                    let local_addr = self.socket.local_addr().ok();
+                    let valid = connect_token.as_deref().is_some_and(|sealed| {
+                        local_addr
+                            .map(|local_addr| self.netcode.handle_connection_request(sealed, local_addr).is_some())
+                            .unwrap_or(false)
+                    });
+                    if !valid {
+                        let response = Packet::Disconnected {
+                            reason: "Server requires a valid connect token to resume a session".to_string(),
+                        };
+                        self.send_packet(&response, addr).await;
+                        return;
+                    }
+                }
+
+                // A valid resume token reclaims the existing player and input
+                // sequence counter rather than starting a fresh session.
+                if let Some(token) = resume_token {
+                    let resumed = {
+                        let mut clients = self.clients.write().await;
+                        clients.resume_client(token, addr)
+                    };
+
+                    if let Some(client_id) = resumed {
+                        if let Some((_, keys)) = handshake {
+                            let mut clients = self.clients.write().await;
+                            clients.establish_session(client_id, keys);
+                        }
+                        let negotiated_timeout_secs = self
+                            .clients
+                            .read()
+                            .await
+                            .negotiated_timeout(client_id)
+                            .map(|d| d.as_secs() as u32)
+                            .unwrap_or(requested_timeout_secs);
+                        let issued_token = self.issue_connect_token(client_id, negotiated_timeout_secs).await;
+                        let response = Packet::Connected {
+                            client_id,
+                            resume_token: token,
+                            negotiated_version,
+                            encrypt_public_key: server_public_key,
+                            negotiated_timeout_secs,
+                            connect_token: issued_token.as_ref().map(|(sealed, _, _)| sealed.clone()),
+                            client_to_server_key: issued_token.as_ref().map(|(_, c2s, _)| *c2s),
+                            server_to_client_key: issued_token.as_ref().map(|(_, _, s2c)| *s2c),
+                        };
+                        self.send_packet(&response, addr).await;
+                        return;
+                    }
+
+                    info!("Resume token from {} did not match a live session, issuing a fresh one", addr);
+                }
+
                 // Remove existing connection if present
                 let existing_client_id = {
                     let clients = self.clients.read().await;
@@ -215,19 +768,50 @@ impl Server {
                 if let Some(existing_id) = existing_client_id {
                     info!("Removing existing client {} from {}", existing_id, addr);
                     let mut clients = self.clients.write().await;
+                    let was_spectator = clients.is_spectator(existing_id);
                     clients.remove_client(&existing_id);
-                    self.game_state.remove_player(&existing_id);
+                    if !was_spectator {
+                        self.game_state.remove_player(&existing_id);
+                    }
                 }
 
                 // Try to add new client
-                let client_id = {
+                let requested_timeout = Duration::from_secs(requested_timeout_secs as u64);
+                let new_client = {
                     let mut clients = self.clients.write().await;
-                    clients.add_client(addr)
+                    if spectate {
+                        clients.add_spectator(addr, requested_timeout)
+                    } else {
+                        clients.add_client(addr, requested_timeout)
+                    }
                 };
 
-                if let Some(client_id) = client_id {
-                    self.game_state.add_player(client_id);
-                    let response = Packet::Connected { client_id };
+                if let Some((client_id, resume_token)) = new_client {
+                    if !spectate {
+                        self.game_state.add_player(client_id);
+                    }
+                    if let Some((_, keys)) = handshake {
+                        let mut clients = self.clients.write().await;
+                        clients.establish_session(client_id, keys);
+                    }
+                    let negotiated_timeout_secs = self
+                        .clients
+                        .read()
+                        .await
+                        .negotiated_timeout(client_id)
+                        .map(|d| d.as_secs() as u32)
+                        .unwrap_or(requested_timeout_secs);
+                    let issued_token = self.issue_connect_token(client_id, negotiated_timeout_secs).await;
+                    let response = Packet::Connected {
+                        client_id,
+                        resume_token,
+                        negotiated_version,
+                        encrypt_public_key: server_public_key,
+                        negotiated_timeout_secs,
+                        connect_token: issued_token.as_ref().map(|(sealed, _, _)| sealed.clone()),
+                        client_to_server_key: issued_token.as_ref().map(|(_, c2s, _)| *c2s),
+                        server_to_client_key: issued_token.as_ref().map(|(_, _, s2c)| *s2c),
+                    };
                     self.send_packet(&response, addr).await;
                 } else {
                     let response = Packet::Disconnected {
@@ -243,6 +827,10 @@ impl Server {
                 left,
                 right,
                 jump,
+                mac,
+                sealed,
+                redundant,
+                acked_snapshot_tick,
             } => {
                 let client_id = {
                     let clients = self.clients.read().await;
@@ -250,16 +838,38 @@ impl Server {
                 };
 
                 if let Some(client_id) = client_id {
-                    let input = InputState {
-                        sequence,
-                        timestamp,
-                        left,
-                        right,
-                        jump,
+                    let input = match sealed {
+                        Some(sealed) => {
+                            let key = self.clients.read().await.input_channel_key(client_id);
+                            Self::open_sealed_input(key, sequence, &sealed)
+                        }
+                        None => Some(InputState {
+                            sequence,
+                            timestamp,
+                            left,
+                            right,
+                            jump,
+                        }),
+                    };
+
+                    let Some(input) = input else {
+                        warn!("Dropping Input from client {}: failed to open sealed payload", client_id);
+                        return;
                     };
 
                     let mut clients = self.clients.write().await;
-                    clients.add_input(client_id, input);
+                    clients.push_request(client_id, mailbox::Request::Input { input, mac });
+
+                    // Redundant copies ride along unauthenticated (see
+                    // `shared::Packet::Input::redundant`); the dedup check in
+                    // `Client::add_input` makes replaying an already-queued
+                    // or already-processed sequence a harmless no-op.
+                    for input in shared::decode_redundant_inputs(&redundant) {
+                        clients.push_request(client_id, mailbox::Request::Input { input, mac: None });
+                    }
+
+                    clients.push_request(client_id, mailbox::Request::AcknowledgeSnapshot { tick: acked_snapshot_tick });
+                    clients.process_mailbox(client_id);
                 }
             }
 
@@ -271,8 +881,47 @@ impl Server {
 
                 if let Some(client_id) = client_id {
                     let mut clients = self.clients.write().await;
-                    clients.remove_client(&client_id);
-                    self.game_state.remove_player(&client_id);
+                    let was_spectator = clients.is_spectator(client_id);
+                    clients.push_request(client_id, mailbox::Request::Disconnect);
+                    let updates = clients.process_mailbox(client_id);
+                    drop(clients);
+                    let disconnected = updates.iter().any(|u| matches!(u, mailbox::Update::Disconnected { .. }));
+                    if disconnected && !was_spectator {
+                        self.game_state.remove_player(&client_id);
+                    }
+                    self.transport.write().await.remove_peer(addr);
+                }
+            }
+
+            Packet::Ping { nonce } => {
+                self.send_packet(&Packet::Pong { nonce }, addr).await;
+            }
+
+            Packet::ToggleFly => {
+                let client_id = {
+                    let clients = self.clients.read().await;
+                    clients.find_client_by_addr(addr)
+                };
+
+                if let Some(client_id) = client_id {
+                    let player_state = self
+                        .game_state
+                        .players
+                        .get(&client_id)
+                        .map(|player| (player.gamemode, player.flying));
+
+                    if let Some((mode, flying)) = player_state {
+                        let can_fly = mode != Gamemode::Survival;
+                        if can_fly {
+                            self.game_state.set_flying(client_id, !flying);
+                        }
+                        let response = Packet::SetGamemode {
+                            client_id,
+                            mode,
+                            can_fly,
+                        };
+                        self.send_packet(&response, addr).await;
+                    }
                 }
             }
 
@@ -282,15 +931,35 @@ impl Server {
         }
     }
 
+    /// Opens a `Packet::Input::sealed` payload, recovering the `InputState`
+    /// it was sealed over. `None` if this client has no installed
+    /// `input_channel_key` or the ciphertext fails to authenticate (forged,
+    /// corrupted, or sealed under a stale key from before the last
+    /// `Connected` rotated it) — either way, the caller must not fall back
+    /// to the packet's plaintext fields. A replayed packet still opens here;
+    /// it is dropped later as a duplicate by `Client::add_input`.
+    fn open_sealed_input(key: Option<[u8; 32]>, sequence: u32, sealed: &[u8]) -> Option<InputState> {
+        let key = key?;
+        let plaintext = netcode_handshake::open_packet(
+            &key,
+            netcode_handshake::PROTOCOL_ID,
+            netcode_handshake::SEALED_INPUT_PACKET_TYPE,
+            sequence as u64,
+            sealed,
+        )?;
+        deserialize(&plaintext).ok()
+    }
+
     /// Processes queued inputs and advances physics simulation
     async fn process_inputs(&mut self, dt: f32) {
         // Calculate physics substeps needed to prevent tunneling
-        let total_substeps = self.calculate_required_substeps(dt);
+        let rtt_jitter = self.max_peer_rtt_jitter().await;
+        let total_substeps = self.calculate_required_substeps(dt, rtt_jitter);
         let substep_dt = dt / total_substeps as f32;
 
         let all_inputs = {
-            let clients = self.clients.read().await;
-            clients.get_chronological_inputs()
+            let mut clients = self.clients.write().await;
+            clients.drain_tick_inputs()
         };
 
         if all_inputs.is_empty() {
@@ -322,7 +991,19 @@ impl Server {
             for _ in 0..inputs_this_step {
                 if input_index < all_inputs.len() {
                     let (client_id, input) = &all_inputs[input_index];
-                    self.game_state.apply_input(*client_id, input, substep_dt);
+                    let input_tick = input.sequence;
+                    self.game_state.record_input(input_tick, *client_id, input.clone());
+
+                    if input_tick < self.game_state.tick {
+                        // This input's tick was already simulated -- it sat in
+                        // the client's reorder buffer past when its tick came
+                        // due. Rewind to it and replay forward with the input
+                        // now on record, instead of applying it out of order
+                        // at the wrong point in the simulation.
+                        self.game_state.resimulate_from(input_tick, dt);
+                    } else {
+                        self.game_state.apply_input(*client_id, input, substep_dt);
+                    }
 
                     let mut clients = self.clients.write().await;
                     clients.mark_input_processed(*client_id, input.sequence);
@@ -343,38 +1024,84 @@ impl Server {
         clients.cleanup_processed_inputs();
     }
 
-    /// Calculates physics substeps required to prevent collision tunneling
-    fn calculate_required_substeps(&self, dt: f32) -> u32 {
+    /// Calculates physics substeps required to prevent collision tunneling.
+    ///
+    /// `rtt_jitter` is the worst-case smoothed RTT jitter across connected
+    /// peers (see `Transport::rtt_estimate`); a jittery link means inputs for
+    /// a given tick can arrive bunched up, so we add extra substeps to keep
+    /// resolution fine enough to still avoid tunneling when that happens.
+    fn calculate_required_substeps(&self, dt: f32, rtt_jitter: Duration) -> u32 {
         const MAX_PLAYER_SPEED: f32 = PLAYER_SPEED;
         const MIN_COLLISION_RADIUS: f32 = PLAYER_SIZE / 2.0;
         const SAFETY_FACTOR: f32 = 0.5;
+        /// Every this many milliseconds of jitter buys one extra substep.
+        const JITTER_SUBSTEP_MILLIS: u128 = 20;
 
         let max_movement_per_step = MIN_COLLISION_RADIUS * SAFETY_FACTOR;
         let max_movement_this_tick = MAX_PLAYER_SPEED * dt;
 
-        if max_movement_this_tick > max_movement_per_step {
+        let base_substeps = if max_movement_this_tick > max_movement_per_step {
             (max_movement_this_tick / max_movement_per_step).ceil() as u32
         } else {
             1
-        }
+        };
+
+        let jitter_substeps = (rtt_jitter.as_millis() / JITTER_SUBSTEP_MILLIS) as u32;
+        base_substeps + jitter_substeps
+    }
+
+    /// Worst-case smoothed RTT jitter across all connected peers, used to
+    /// scale up physics substeps and (eventually) reconciliation rewind depth
+    /// under rough network conditions. Peers with no RTT sample yet (e.g. one
+    /// that just connected) don't contribute.
+    async fn max_peer_rtt_jitter(&self) -> Duration {
+        let addrs = self.clients.read().await.get_client_addrs();
+        let transport = self.transport.read().await;
+        addrs
+            .into_iter()
+            .filter_map(|(_, addr)| transport.rtt_estimate(addr))
+            .map(|estimate| estimate.jitter)
+            .max()
+            .unwrap_or(Duration::ZERO)
     }
 
-    /// Broadcasts current game state to all connected clients
+    /// Broadcasts current game state to connected clients, skipping or
+    /// downsampling clients whose recent outbound rate is over their cap so a
+    /// large client count degrades gracefully instead of saturating the uplink.
     async fn broadcast_game_state(&mut self) {
-        let client_count = {
-            let clients = self.clients.read().await;
-            clients.len()
+        let (targets, throttled) = {
+            let mut clients = self.clients.write().await;
+            clients.snapshot_targets()
         };
 
-        if client_count == 0 {
+        if targets.is_empty() {
             return;
         }
 
+        if !throttled.is_empty() {
+            debug!(
+                "Throttling GameState snapshot for {} client(s) over their outbound rate cap: {:?}",
+                throttled.len(),
+                throttled
+            );
+        }
+
+        // Spectators can't affect `last_processed_input` and don't get a say
+        // in reconciliation, but they still watch the same full entity
+        // snapshot players do, just released a few ticks later (see
+        // `spectator_snapshot_buffer`).
+        let (player_targets, spectator_targets): (Vec<(u32, SocketAddr)>, Vec<(u32, SocketAddr)>) = {
+            let clients = self.clients.read().await;
+            targets.into_iter().partition(|(id, _)| !clients.is_spectator(*id))
+        };
+        let spectator_addrs: Vec<SocketAddr> = spectator_targets.into_iter().map(|(_, addr)| addr).collect();
+
         // Prepare packet data first
-        let players: Vec<Player> = self.game_state.players.values().cloned().collect();
-        let last_processed_input = {
+        let current_players: HashMap<u32, Player> = self.game_state.players.clone();
+        let players: Vec<Player> = current_players.values().cloned().collect();
+        let (last_processed_input, input_receive_ms) = {
             let clients = self.clients.read().await;
-            clients.get_last_processed_inputs()
+            (clients.get_last_processed_inputs(), clients.get_last_processed_receive_ms())
         };
 
         // Take timestamp as close to transmission as possible
@@ -384,22 +1111,109 @@ impl Server {
             .as_millis();
         let timestamp_safe = (timestamp.min(u64::MAX as u128)) as u64;
 
+        let checksum = self.game_state.checksum();
+        let current_tick = self.game_state.tick;
         let packet = Packet::GameState {
-            tick: self.game_state.tick,
+            tick: current_tick,
             timestamp: timestamp_safe,
-            last_processed_input,
+            last_processed_input: last_processed_input.clone(),
+            input_receive_ms: input_receive_ms.clone(),
             players,
+            checksum,
+        };
+
+        // A client with a usable acknowledged baseline gets a `GameStateDelta`
+        // diffed against it instead of the shared full keyframe above —
+        // everyone else (no ack yet, or its ack fell outside the server's
+        // snapshot history) still gets `packet` verbatim.
+        let delta_packets: HashMap<u32, Packet> = {
+            let clients = self.clients.read().await;
+            player_targets
+                .iter()
+                .filter_map(|&(client_id, _)| {
+                    let baseline_tick = clients.acknowledged_snapshot_tick(client_id)?;
+                    let baseline = self.game_state.players_at(baseline_tick)?;
+                    let (changed_players, removed_player_ids) = diff_players(&current_players, baseline);
+                    let delta = Packet::GameStateDelta {
+                        tick: current_tick,
+                        timestamp: timestamp_safe,
+                        baseline_tick,
+                        last_processed_input: last_processed_input.clone(),
+                        input_receive_ms: input_receive_ms.clone(),
+                        changed_players,
+                        removed_player_ids,
+                        checksum,
+                    };
+                    Some((client_id, delta))
+                })
+                .collect()
+        };
+
+        // Each player has its own outbound bandwidth budget, so a packet that
+        // fits for one client may need to be deferred for another. Everything
+        // here runs under a single lock scope with no `.await` inside it;
+        // the actual sends happen afterwards once the lock is released.
+        let (keyframe_send_addrs, per_client_sends, deferred_sends): (
+            Vec<SocketAddr>,
+            Vec<(SocketAddr, Packet)>,
+            Vec<(SocketAddr, Vec<Packet>)>,
+        ) = {
+            let mut clients = self.clients.write().await;
+            clients.refill_bandwidth_budgets();
+
+            let mut keyframe_send_addrs = Vec::new();
+            let mut per_client_sends = Vec::new();
+            let mut deferred_sends = Vec::new();
+            for (client_id, addr) in player_targets {
+                let this_packet = delta_packets.get(&client_id).unwrap_or(&packet);
+                if clients.try_send_within_budget(client_id, this_packet.clone()).is_some() {
+                    if delta_packets.contains_key(&client_id) {
+                        per_client_sends.push((addr, this_packet.clone()));
+                    } else {
+                        keyframe_send_addrs.push(addr);
+                    }
+                }
+                let ready = clients.drain_ready_queue(client_id);
+                if !ready.is_empty() {
+                    deferred_sends.push((addr, ready));
+                }
+            }
+            (keyframe_send_addrs, per_client_sends, deferred_sends)
         };
 
-        self.broadcast_packet(&packet, None).await;
+        if !keyframe_send_addrs.is_empty() {
+            self.broadcast_packet(&packet, keyframe_send_addrs).await;
+        }
+
+        for (addr, delta) in per_client_sends {
+            self.send_packet(&delta, addr).await;
+        }
+
+        for (addr, packets) in deferred_sends {
+            for deferred in packets {
+                self.send_packet(&deferred, addr).await;
+            }
+        }
+
+        if !spectator_addrs.is_empty() {
+            self.spectator_snapshot_buffer.push_back(packet);
+            if self.spectator_snapshot_buffer.len() > SPECTATOR_SNAPSHOT_DELAY_TICKS {
+                let delayed = self.spectator_snapshot_buffer.pop_front().expect("just checked non-empty");
+                self.broadcast_packet(&delayed, spectator_addrs).await;
+            }
+        }
     }
 
     /// Main server loop coordinating all operations
     pub async fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         // Initialize concurrent tasks
         self.spawn_network_receiver().await;
+        self.spawn_inbound_dispatcher().await;
         self.spawn_network_sender().await;
         self.spawn_timeout_checker().await;
+        self.spawn_heartbeat_sender().await;
+        self.spawn_net_conditions_drain();
+        self.spawn_connect_limiter_gc();
 
         let mut tick_interval = interval(self.tick_duration);
         let mut last_tick = Instant::now();
@@ -415,6 +1229,11 @@ impl Server {
                             self.handle_packet(packet, addr).await;
                         },
                         Some(ServerMessage::ClientTimeout { client_id }) => {
+                            // Player stays in game_state during the grace period so a
+                            // reconnect with the right resume token can reclaim it.
+                            info!("Client {} timed out, awaiting possible reconnect", client_id);
+                        },
+                        Some(ServerMessage::SessionExpired { client_id }) => {
                             self.game_state.remove_player(&client_id);
                         },
                         Some(ServerMessage::Shutdown) | None => {
@@ -432,19 +1251,36 @@ impl Server {
 
                     self.process_inputs(dt).await;
                     self.game_state.tick += 1;
+                    self.game_state.remember_tick(self.game_state.tick);
                     self.broadcast_game_state().await;
 
                     // Periodic performance monitoring
                     if self.game_state.tick % 60 == 0 {
-                        let client_count = {
-                            let clients = self.clients.read().await;
-                            clients.len()
+                        let (client_count, up_kbps, down_kbps) = {
+                            let mut clients = self.clients.write().await;
+                            let (up_kbps, down_kbps) = clients.take_throughput_kbps();
+                            (clients.len(), up_kbps, down_kbps)
                         };
 
                         if client_count > 0 {
-                            let substeps = self.calculate_required_substeps(dt);
-                            debug!("Tick {}: {} clients, {:.1}Hz, {} physics substeps",
-                                   self.game_state.tick, client_count, 1.0 / dt, substeps);
+                            let rtt_jitter = self.max_peer_rtt_jitter().await;
+                            let substeps = self.calculate_required_substeps(dt, rtt_jitter);
+                            debug!("Tick {}: {} clients, {:.1}Hz, {} physics substeps, {:.1} KB/s up, {:.1} KB/s down",
+                                   self.game_state.tick, client_count, 1.0 / dt, substeps, up_kbps, down_kbps);
+
+                            let client_addrs = self.clients.read().await.get_client_addrs();
+                            let transport = self.transport.read().await;
+                            for (client_id, addr) in client_addrs {
+                                if let Some(throughput) = transport.throughput(addr) {
+                                    debug!(
+                                        "Client {}: {:.1} B/s up, {:.1} B/s down, {:.1}% est. loss",
+                                        client_id,
+                                        throughput.sent_bytes_per_sec,
+                                        throughput.received_bytes_per_sec,
+                                        throughput.loss_estimate * 100.0
+                                    );
+                                }
+                            }
                         }
                     }
                 },
@@ -463,7 +1299,7 @@ mod tests {
 
     #[test]
     fn test_server_message_creation() {
-        let packet = Packet::Connect { client_version: 1 };
+        let packet = Packet::Connect { min_version: 1, max_version: 1, resume_token: None, requested_timeout_secs: 15, encrypt_public_key: None, connect_token: None, spectate: false };
         let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
 
         let msg = ServerMessage::PacketReceived {
@@ -475,8 +1311,8 @@ mod tests {
             ServerMessage::PacketReceived { packet: p, addr: a } => {
                 assert_eq!(a, addr);
                 match p {
-                    Packet::Connect { client_version } => {
-                        assert_eq!(client_version, 1);
+                    Packet::Connect { max_version, .. } => {
+                        assert_eq!(max_version, 1);
                     }
                     _ => panic!("Unexpected packet type"),
                 }
@@ -500,7 +1336,7 @@ mod tests {
 
     #[test]
     fn test_game_message_send_packet() {
-        let packet = Packet::Connected { client_id: 123 };
+        let packet = Packet::Connected { client_id: 123, resume_token: 0, negotiated_version: 1, encrypt_public_key: None, negotiated_timeout_secs: 15, connect_token: None, client_to_server_key: None, server_to_client_key: None };
         let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)), 9090);
 
         let msg = GameMessage::SendPacket {
@@ -512,7 +1348,7 @@ mod tests {
             GameMessage::SendPacket { packet: p, addr: a } => {
                 assert_eq!(a, addr);
                 match p {
-                    Packet::Connected { client_id } => {
+                    Packet::Connected { client_id, .. } => {
                         assert_eq!(client_id, 123);
                     }
                     _ => panic!("Unexpected packet type"),
@@ -528,17 +1364,20 @@ mod tests {
             tick: 100,
             timestamp: 1234567890,
             last_processed_input: std::collections::HashMap::new(),
+            input_receive_ms: std::collections::HashMap::new(),
             players: vec![],
+            checksum: 0,
         };
+        let targets = vec![SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080)];
 
         let msg = GameMessage::BroadcastPacket {
             packet: packet.clone(),
-            exclude: Some(5),
+            targets: targets.clone(),
         };
 
         match msg {
-            GameMessage::BroadcastPacket { packet: p, exclude } => {
-                assert_eq!(exclude, Some(5));
+            GameMessage::BroadcastPacket { packet: p, targets: t } => {
+                assert_eq!(t, targets);
                 match p {
                     Packet::GameState { tick, .. } => {
                         assert_eq!(tick, 100);
@@ -555,20 +1394,31 @@ mod tests {
         let server = create_test_server();
 
         // Test normal case - should require 1 substep
-        let substeps = server.calculate_required_substeps(1.0 / 60.0); // 60 FPS
+        let substeps = server.calculate_required_substeps(1.0 / 60.0, Duration::ZERO); // 60 FPS
         assert_eq!(substeps, 1);
 
         // Test high speed case - should require multiple substeps
         let large_dt = 1.0; // 1 second
-        let substeps = server.calculate_required_substeps(large_dt);
+        let substeps = server.calculate_required_substeps(large_dt, Duration::ZERO);
         assert!(substeps > 1);
 
         // Test edge case - very small dt
         let tiny_dt = 1.0 / 1000.0; // 1000 FPS
-        let substeps = server.calculate_required_substeps(tiny_dt);
+        let substeps = server.calculate_required_substeps(tiny_dt, Duration::ZERO);
         assert_eq!(substeps, 1);
     }
 
+    #[test]
+    fn test_substep_calculation_scales_with_rtt_jitter() {
+        let server = create_test_server();
+
+        let baseline = server.calculate_required_substeps(1.0 / 60.0, Duration::ZERO);
+        let jittery = server.calculate_required_substeps(1.0 / 60.0, Duration::from_millis(45));
+
+        // 45ms of jitter should buy 2 extra substeps at 20ms-per-substep.
+        assert_eq!(jittery, baseline + 2);
+    }
+
     #[test]
     fn test_substep_safety_calculations() {
         const PLAYER_SPEED: f32 = 300.0;
@@ -627,7 +1477,7 @@ mod tests {
     fn test_channel_communication() {
         let (tx, mut rx) = mpsc::unbounded_channel::<ServerMessage>();
 
-        let packet = Packet::Connect { client_version: 1 };
+        let packet = Packet::Connect { min_version: 1, max_version: 1, resume_token: None, requested_timeout_secs: 15, encrypt_public_key: None, connect_token: None, spectate: false };
         let addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1)), 8080);
 
         let msg = ServerMessage::PacketReceived {
@@ -646,8 +1496,8 @@ mod tests {
             ServerMessage::PacketReceived { packet: p, addr: a } => {
                 assert_eq!(a, addr);
                 match p {
-                    Packet::Connect { client_version } => {
-                        assert_eq!(client_version, 1);
+                    Packet::Connect { max_version, .. } => {
+                        assert_eq!(max_version, 1);
                     }
                     _ => panic!("Unexpected packet type"),
                 }
@@ -752,8 +1602,8 @@ mod tests {
     #[test]
     fn test_packet_serialization_roundtrip() {
         let test_packets = vec![
-            Packet::Connect { client_version: 1 },
-            Packet::Connected { client_id: 42 },
+            Packet::Connect { min_version: 1, max_version: 1, resume_token: None, requested_timeout_secs: 15, encrypt_public_key: None, connect_token: None, spectate: false },
+            Packet::Connected { client_id: 42, resume_token: 0, negotiated_version: 1, encrypt_public_key: None, negotiated_timeout_secs: 15, connect_token: None, client_to_server_key: None, server_to_client_key: None },
             Packet::Disconnect,
             Packet::Disconnected {
                 reason: "Test".to_string(),
@@ -764,6 +1614,10 @@ mod tests {
                 left: true,
                 right: false,
                 jump: true,
+                mac: None,
+                sealed: None,
+                redundant: Vec::new(),
+                acked_snapshot_tick: 0,
             },
         ];
 
@@ -819,19 +1673,20 @@ mod tests {
     }
 
     #[test]
-    fn test_client_version_compatibility() {
-        let supported_versions = [1];
-        let test_versions = vec![0, 1, 2, 999];
-
-        for version in test_versions {
-            let is_supported = supported_versions.contains(&version);
+    fn test_version_negotiation_picks_highest_overlapping_version() {
+        // Client range fully covers the server's single supported version.
+        let (min_version, max_version) = (1, 5);
+        let negotiated = max_version.min(SERVER_MAX_PROTOCOL_VERSION);
+        assert!(negotiated >= min_version.max(SERVER_MIN_PROTOCOL_VERSION));
+        assert_eq!(negotiated, SERVER_MAX_PROTOCOL_VERSION);
+    }
 
-            if version == 1 {
-                assert!(is_supported);
-            } else {
-                assert!(!is_supported);
-            }
-        }
+    #[test]
+    fn test_version_negotiation_rejects_disjoint_ranges() {
+        // A client that only speaks versions above what the server supports.
+        let (min_version, max_version) = (SERVER_MAX_PROTOCOL_VERSION + 1, 99);
+        let negotiated = max_version.min(SERVER_MAX_PROTOCOL_VERSION);
+        assert!(negotiated < min_version.max(SERVER_MIN_PROTOCOL_VERSION));
     }
 
     #[test]
@@ -874,19 +1729,23 @@ mod tests {
     }
 
     impl TestServerMock {
-        fn calculate_required_substeps(&self, dt: f32) -> u32 {
+        fn calculate_required_substeps(&self, dt: f32, rtt_jitter: Duration) -> u32 {
             const MAX_PLAYER_SPEED: f32 = PLAYER_SPEED;
             const MIN_COLLISION_RADIUS: f32 = PLAYER_SIZE / 2.0;
             const SAFETY_FACTOR: f32 = 0.5;
+            const JITTER_SUBSTEP_MILLIS: u128 = 20;
 
             let max_movement_per_step = MIN_COLLISION_RADIUS * SAFETY_FACTOR;
             let max_movement_this_tick = MAX_PLAYER_SPEED * dt;
 
-            if max_movement_this_tick > max_movement_per_step {
+            let base_substeps = if max_movement_this_tick > max_movement_per_step {
                 (max_movement_this_tick / max_movement_per_step).ceil() as u32
             } else {
                 1
-            }
+            };
+
+            let jitter_substeps = (rtt_jitter.as_millis() / JITTER_SUBSTEP_MILLIS) as u32;
+            base_substeps + jitter_substeps
         }
     }
 }
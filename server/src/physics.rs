@@ -1,4 +1,5 @@
 use serde::Serialize;
+use shared::Collider;
 
 ///Represents a vector in 2D space.
 #[derive(Debug, Clone, Copy, Serialize, Default)]
@@ -54,6 +55,21 @@ pub struct Object {
     pub height: f32,
     ///The positional center of the object.
     pub position: Vector2,
+    /// Bit(s) this object occupies for collision-mask filtering. See
+    /// `DynamicObject::resolve_collisions`.
+    pub layer: u32,
+    /// Which layers this object collides with. Lets e.g. a pass-through
+    /// platform, a ghost object, or a projectile opt out of colliding with
+    /// everything without forking the physics code. See
+    /// `DynamicObject::resolve_collisions`.
+    pub mask: u32,
+    /// This object's collision shape for narrow-phase resolution. `width`/
+    /// `height` remain the broad-phase AABB used everywhere else in this
+    /// module (the sweep test, `check_grounded`); `collider` only changes
+    /// how `resolve_collisions` separates a discrete (non-swept) overlap
+    /// once the broad phase has already found one. See
+    /// `shared::collider_contact`.
+    pub collider: Collider,
 }
 
 impl Default for Object {
@@ -62,6 +78,9 @@ impl Default for Object {
             width: 1.0,
             height: 1.0,
             position: Vector2::default(),
+            layer: u32::MAX,
+            mask: u32::MAX,
+            collider: Collider::Aabb { w: 1.0, h: 1.0 },
         }
     }
 }
@@ -75,6 +94,11 @@ pub struct DynamicObject {
     pub max_speed: f32,
     pub gravity: f32,
     pub grounded: bool,
+    /// Ignores gravity while set. See `integrate_velocity`.
+    pub flying: bool,
+    /// Skips `resolve_collisions` entirely while set, like a Creative-mode
+    /// spectator camera passing through players and geometry.
+    pub noclip: bool,
 }
 
 impl Default for DynamicObject {
@@ -86,16 +110,67 @@ impl Default for DynamicObject {
             max_speed: 2.0,
             gravity: 9.81,
             grounded: bool::default(),
+            flying: false,
+            noclip: false,
         }
     }
 }
 
+/// Result of a swept-AABB test: the fraction of the tested displacement
+/// travelled before impact, and the surface normal (axis-aligned, so only
+/// one of `normal_x`/`normal_y` is ever non-zero) to resolve along.
+#[derive(Debug, Clone, Copy)]
+struct SweepHit {
+    entry: f32,
+    normal_x: f32,
+    normal_y: f32,
+}
+
+/// One contact produced by `DynamicObject::resolve_collisions`: the
+/// separating normal, how deep the objects had overlapped, which `Object`
+/// in `others` it was (`other_index`), and how far `self` could still move
+/// in each direction, post-resolution, before it would touch that `Object`
+/// again. The `allowed_*` distances are what let gameplay code tell a
+/// one-way platform from a wall or a ceiling without re-deriving bounds
+/// itself.
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    pub normal: Vector2,
+    pub penetration: f32,
+    pub allowed_left: f32,
+    pub allowed_right: f32,
+    pub allowed_top: f32,
+    pub allowed_bottom: f32,
+    pub other_index: usize,
+}
+
+/// Manifest returned by `DynamicObject::resolve_collisions`: every contact
+/// resolved during that call, in place of the bare `bool` it used to
+/// return. Jump buffering, wall-jumps, one-way platforms, and impact
+/// damage all need to know *how* something collided, not just that it did.
+#[derive(Debug, Clone, Default)]
+pub struct CollisionManifest {
+    pub contacts: Vec<Contact>,
+}
+
+impl CollisionManifest {
+    /// True if any contact's normal points up, i.e. some `Object` was
+    /// resolved as being underneath `self`.
+    pub fn is_grounded(&self) -> bool {
+        self.contacts.iter().any(|contact| contact.normal.y > 0.0)
+    }
+}
+
 impl DynamicObject {
-    ///Simulates physics on the dynamic object.
-    pub fn simulate(&mut self, dt: f32) {
-        // Apply gravity to vertical acceleration
+    /// Applies gravity and clamps to `max_speed`, leaving position
+    /// untouched. Shared by `simulate` (discrete) and `simulate_swept`
+    /// (continuous), which differ only in how they integrate position.
+    fn integrate_velocity(&mut self, dt: f32) {
+        // Apply gravity to vertical acceleration, unless flying
         let mut total_acceleration = self.acceleration;
-        total_acceleration.y -= self.gravity;
+        if !self.flying {
+            total_acceleration.y -= self.gravity;
+        }
 
         // Update velocity calculated byw acceleration
         self.velocity = self.velocity.add(&total_acceleration.scale(dt));
@@ -105,16 +180,143 @@ impl DynamicObject {
         if speed > self.max_speed {
             self.velocity = self.velocity.normalize().scale(self.max_speed);
         }
+    }
+
+    ///Simulates physics on the dynamic object.
+    pub fn simulate(&mut self, dt: f32) {
+        self.integrate_velocity(dt);
 
         // Update position based on velocity
         self.object.position = self.object.position.add(&self.velocity.scale(dt));
     }
 
+    /// Continuous-collision counterpart to `simulate` + `resolve_collisions`:
+    /// integrates velocity as usual, but sweeps the resulting displacement
+    /// against `others` *before* moving, so a fast-moving object can't
+    /// tunnel through a thin one in a single tick. Stops at the first
+    /// surface hit, zeroes the velocity component along its normal, and
+    /// re-sweeps the remaining displacement along the surviving axis so the
+    /// object slides along the wall instead of simply stopping dead.
+    pub fn simulate_swept(&mut self, dt: f32, others: &[Object]) {
+        self.integrate_velocity(dt);
+
+        let mut d = self.velocity.scale(dt);
+
+        // At most one real hit plus one slide re-sweep; a third iteration
+        // would only happen by corner-clipping into a second surface, which
+        // is rare enough not to need chasing further in a single tick.
+        for _ in 0..2 {
+            if d.x == 0.0 && d.y == 0.0 {
+                break;
+            }
+
+            let hit = others
+                .iter()
+                .filter_map(|other| self.sweep_against(d, other))
+                .min_by(|a, b| a.entry.partial_cmp(&b.entry).unwrap());
+
+            let Some(hit) = hit else {
+                self.object.position = self.object.position.add(&d);
+                return;
+            };
+
+            self.object.position = self.object.position.add(&d.scale(hit.entry));
+
+            if hit.normal_x != 0.0 {
+                self.velocity.x = 0.0;
+            }
+            if hit.normal_y != 0.0 {
+                self.velocity.y = 0.0;
+            }
+
+            // Re-sweep the remaining displacement along the surviving axis.
+            let remaining = 1.0 - hit.entry;
+            d = Vector2 {
+                x: if hit.normal_x != 0.0 { 0.0 } else { d.x * remaining },
+                y: if hit.normal_y != 0.0 { 0.0 } else { d.y * remaining },
+            };
+        }
+    }
+
+    /// Swept-AABB test of this object moving by displacement `d` against
+    /// `other`. Expands `other` by this object's half-extents (Minkowski
+    /// sum), reducing the test to a point moving along `d` against the
+    /// expanded box, then finds the per-axis entry/exit fractions of that
+    /// ray. Returns `None` if the displacement never enters the box within
+    /// this tick.
+    fn sweep_against(&self, d: Vector2, other: &Object) -> Option<SweepHit> {
+        if self.object.mask & other.layer == 0 {
+            return None;
+        }
+
+        let half_w = self.object.width / 2.0;
+        let half_h = self.object.height / 2.0;
+
+        let expanded_left = other.position.x - other.width / 2.0 - half_w;
+        let expanded_right = other.position.x + other.width / 2.0 + half_w;
+        let expanded_bottom = other.position.y - other.height / 2.0 - half_h;
+        let expanded_top = other.position.y + other.height / 2.0 + half_h;
+
+        let (entry_x, exit_x) = Self::axis_entry_exit(
+            self.object.position.x,
+            d.x,
+            expanded_left,
+            expanded_right,
+        );
+        let (entry_y, exit_y) = Self::axis_entry_exit(
+            self.object.position.y,
+            d.y,
+            expanded_bottom,
+            expanded_top,
+        );
+
+        let entry = entry_x.max(entry_y);
+        let exit = exit_x.min(exit_y);
+
+        if entry > exit || !(0.0..=1.0).contains(&entry) || (entry_x < 0.0 && entry_y < 0.0) {
+            return None;
+        }
+
+        let (normal_x, normal_y) = if entry_x > entry_y {
+            (if d.x > 0.0 { -1.0 } else { 1.0 }, 0.0)
+        } else {
+            (0.0, if d.y > 0.0 { -1.0 } else { 1.0 })
+        };
+
+        Some(SweepHit {
+            entry,
+            normal_x,
+            normal_y,
+        })
+    }
+
+    /// Entry/exit fraction of a point at `pos` moving by displacement `d`
+    /// against the span `[min, max]`. A stationary axis (`d == 0`) can
+    /// never be the one that constrains the hit window, so it's reported
+    /// as `(-inf, inf)` and left to the moving axis to decide.
+    fn axis_entry_exit(pos: f32, d: f32, min: f32, max: f32) -> (f32, f32) {
+        if d == 0.0 {
+            return (f32::NEG_INFINITY, f32::INFINITY);
+        }
+        let (near, far) = if d > 0.0 { (min, max) } else { (max, min) };
+        ((near - pos) / d, (far - pos) / d)
+    }
+
     ///Checks for and resolves collisions between the dynamic object and a collection of objects.
-    ///Returns true if any collision was detected and resolved.
-    pub fn resolve_collisions(&mut self, others: &[Object]) -> bool {
-        let mut collided = false;
-        for other in others {
+    ///Returns a `CollisionManifest` describing every contact that was resolved, so callers like
+    ///jump buffering, wall-jumps, one-way platforms, and impact damage can see *how* it collided
+    ///instead of just whether it did.
+    pub fn resolve_collisions(&mut self, others: &[Object]) -> CollisionManifest {
+        let mut manifest = CollisionManifest::default();
+        if self.noclip {
+            return manifest;
+        }
+
+        for (other_index, other) in others.iter().enumerate() {
+            if self.object.mask & other.layer == 0 {
+                continue;
+            }
+
             // Calculate the bounds of both objects (position is center)
             let self_left = self.object.position.x - self.object.width / 2.0;
             let self_right = self.object.position.x + self.object.width / 2.0;
@@ -132,45 +334,105 @@ impl DynamicObject {
                 || self_bottom >= other_top
                 || self_top <= other_bottom);
 
-            if collision {
+            if !collision {
+                continue;
+            }
+
+            let is_both_aabb = matches!(self.object.collider, Collider::Aabb { .. })
+                && matches!(other.collider, Collider::Aabb { .. });
+
+            // The broad-phase box test above already found an overlap; the
+            // collider-aware narrow phase only changes *how* that overlap is
+            // separated. Box-vs-box keeps the exact separating-axis math
+            // this engine always used rather than routing through the
+            // generic `shared::collider_contact` dispatch, so a pair of
+            // plain `Object`s (the common case) is untouched by this.
+            let (normal, penetration) = if is_both_aabb {
                 // Calculate overlap amounts (use corrected bounds here)
                 let overlap_x = (self_right.min(other_right) - self_left.max(other_left)).abs();
                 let overlap_y = (self_top.min(other_top) - self_bottom.max(other_bottom)).abs();
 
                 // Determine which axis had the shallowest penetration and resolve along that axis
-                if overlap_x < overlap_y {
+                let normal = if overlap_x < overlap_y {
                     // Horizontal collision
                     if self.object.position.x < other.position.x {
                         // Self is to the left of other, move self left
                         self.object.position.x = other_left - self.object.width / 2.0;
+                        Vector2 { x: -1.0, y: 0.0 }
                     } else {
                         // Self is to the right of other, move self right
                         self.object.position.x = other_right + self.object.width / 2.0;
+                        Vector2 { x: 1.0, y: 0.0 }
                     }
-                    // Stop horizontal movement
-                    self.velocity.x = 0.0;
-                    self.acceleration.x = 0.0;
                 } else {
                     // Vertical collision
                     if self.object.position.y < other.position.y {
                         self.object.position.y = other_bottom + self.object.height / 2.0;
+                        Vector2 { x: 0.0, y: -1.0 }
                     } else {
                         self.object.position.y = other_top + self.object.height / 2.0;
+                        Vector2 { x: 0.0, y: 1.0 }
                     }
-                    self.velocity.y = 0.0;
-                    if self.velocity.y < 0.0 {
-                        self.acceleration.y = 0.0;
-                    }
+                };
+
+                (normal, overlap_x.min(overlap_y))
+            } else {
+                let Some((nx, ny, penetration)) = shared::collider_contact(
+                    (self.object.position.x, self.object.position.y),
+                    self.object.collider,
+                    (other.position.x, other.position.y),
+                    other.collider,
+                ) else {
+                    continue;
+                };
+
+                // `collider_contact`'s normal points from self towards
+                // other, the opposite sense of this loop's convention
+                // (self moves away from other), so self is pushed backwards
+                // along it.
+                self.object.position.x -= nx * penetration;
+                self.object.position.y -= ny * penetration;
+                (Vector2 { x: -nx, y: -ny }, penetration)
+            };
+
+            if normal.x != 0.0 {
+                self.velocity.x = 0.0;
+                self.acceleration.x = 0.0;
+            } else {
+                self.velocity.y = 0.0;
+                if self.velocity.y < 0.0 {
+                    self.acceleration.y = 0.0;
                 }
-                collided = true;
             }
+
+            // Re-measure self's bounds post-resolution to report how far it could still
+            // move in each direction before touching `other` again.
+            let self_left = self.object.position.x - self.object.width / 2.0;
+            let self_right = self.object.position.x + self.object.width / 2.0;
+            let self_top = self.object.position.y + self.object.height / 2.0;
+            let self_bottom = self.object.position.y - self.object.height / 2.0;
+
+            manifest.contacts.push(Contact {
+                normal,
+                penetration,
+                allowed_left: self_left - other_right,
+                allowed_right: other_left - self_right,
+                allowed_top: other_bottom - self_top,
+                allowed_bottom: self_bottom - other_top,
+                other_index,
+            });
         }
-        collided
+        manifest
     }
 
     /// Checks if the dynamic object is grounded against any object in the collection.
     /// Updates the grounded property and returns true if it is.
     pub fn check_grounded(&mut self, others: &[Object]) {
+        if self.flying {
+            self.grounded = false;
+            return;
+        }
+
         let tolerance = 0.1;
 
         let self_left = self.object.position.x - self.object.width / 2.0;
@@ -178,6 +440,10 @@ impl DynamicObject {
         let self_bottom = self.object.position.y - self.object.height / 2.0;
 
         for other in others {
+            if self.object.mask & other.layer == 0 {
+                continue;
+            }
+
             let other_left = other.position.x - other.width / 2.0;
             let other_right = other.position.x + other.width / 2.0;
             let other_top = other.position.y + other.height / 2.0;
@@ -0,0 +1,155 @@
+//! Per-source token-bucket rate limiter gating handshake processing.
+//!
+//! `InboundQueue` bounds how many decoded packets can pile up, but it doesn't
+//! stop a single source from spending that whole budget on `Packet::Connect`:
+//! each one is cheap to receive but triggers ECDH key generation and client
+//! allocation downstream. This gates `Connect` per source IP before any of
+//! that runs, the same way boringtun's `RateLimiter` gates WireGuard handshake
+//! messages: a concurrent map of per-IP token buckets, refilled lazily by
+//! elapsed time on each check rather than on a timer, with a periodic GC pass
+//! evicting IPs that haven't been seen in a while so a flood of spoofed
+//! sources can't grow the map forever.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// Default sustained rate: one handshake attempt per second per source.
+pub const DEFAULT_RATE_PER_SEC: f32 = 1.0;
+/// Default burst: a source can spend up to this many tokens at once before
+/// it has to wait for a refill.
+pub const DEFAULT_BURST: f32 = 5.0;
+/// Entries untouched for longer than this are evicted by the GC pass.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+struct Bucket {
+    tokens: f32,
+    last_refill: Instant,
+}
+
+/// Gates `Packet::Connect` processing per source `IpAddr`. Already-established
+/// connections never go through this — only fresh handshake attempts do — so
+/// legitimate reconnects and resumes aren't affected once a session exists.
+pub struct ConnectRateLimiter {
+    rate_per_sec: f32,
+    burst: f32,
+    ttl: Duration,
+    buckets: RwLock<HashMap<IpAddr, Bucket>>,
+}
+
+impl ConnectRateLimiter {
+    pub fn new(rate_per_sec: f32, burst: f32, ttl: Duration) -> Self {
+        Self {
+            rate_per_sec,
+            burst,
+            ttl,
+            buckets: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Refills `source`'s bucket by the time elapsed since it was last
+    /// touched and, if at least one token is available, spends one and
+    /// returns `true`. A source with no bucket yet starts at `burst` tokens,
+    /// so the first handshake from a fresh IP is never held up.
+    pub async fn check(&self, source: IpAddr) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.write().await;
+        let bucket = buckets.entry(source).or_insert_with(|| Bucket {
+            tokens: self.burst,
+            last_refill: now,
+        });
+
+        let elapsed = now.duration_since(bucket.last_refill).as_secs_f32();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate_per_sec).min(self.burst);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Evicts every bucket that hasn't been touched within `ttl`, bounding
+    /// memory under a flood of spoofed, never-repeating source addresses.
+    pub async fn gc(&self) {
+        let now = Instant::now();
+        let ttl = self.ttl;
+        self.buckets
+            .write()
+            .await
+            .retain(|_, bucket| now.duration_since(bucket.last_refill) < ttl);
+    }
+}
+
+impl Default for ConnectRateLimiter {
+    fn default() -> Self {
+        Self::new(DEFAULT_RATE_PER_SEC, DEFAULT_BURST, DEFAULT_TTL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source() -> IpAddr {
+        "127.0.0.1".parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn first_attempt_from_a_fresh_source_is_allowed() {
+        let limiter = ConnectRateLimiter::new(1.0, 5.0, DEFAULT_TTL);
+        assert!(limiter.check(source()).await);
+    }
+
+    #[tokio::test]
+    async fn exhausting_the_burst_blocks_further_attempts() {
+        let limiter = ConnectRateLimiter::new(1.0, 2.0, DEFAULT_TTL);
+        assert!(limiter.check(source()).await);
+        assert!(limiter.check(source()).await);
+        assert!(!limiter.check(source()).await);
+    }
+
+    #[tokio::test]
+    async fn tokens_refill_over_time() {
+        let limiter = ConnectRateLimiter::new(1000.0, 1.0, DEFAULT_TTL);
+        assert!(limiter.check(source()).await);
+        assert!(!limiter.check(source()).await);
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+        assert!(limiter.check(source()).await);
+    }
+
+    #[tokio::test]
+    async fn different_sources_have_independent_buckets() {
+        let limiter = ConnectRateLimiter::new(1.0, 1.0, DEFAULT_TTL);
+        let other: IpAddr = "10.0.0.1".parse().unwrap();
+
+        assert!(limiter.check(source()).await);
+        assert!(!limiter.check(source()).await);
+        assert!(limiter.check(other).await);
+    }
+
+    #[tokio::test]
+    async fn gc_evicts_entries_older_than_the_ttl() {
+        let limiter = ConnectRateLimiter::new(1.0, 1.0, Duration::from_millis(1));
+        limiter.check(source()).await;
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        limiter.gc().await;
+
+        assert_eq!(limiter.buckets.read().await.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn gc_keeps_entries_touched_within_the_ttl() {
+        let limiter = ConnectRateLimiter::new(1.0, 1.0, Duration::from_secs(300));
+        limiter.check(source()).await;
+
+        limiter.gc().await;
+
+        assert_eq!(limiter.buckets.read().await.len(), 1);
+    }
+}
@@ -0,0 +1,272 @@
+//! Selective-reliable channel for packets that need explicit delivery
+//! guarantees without going through the full LEDBAT [`crate::transport::Transport`]
+//! window — e.g. one-off critical events like spawn announcements or
+//! disconnect reasons, where `Input`/`GameState` traffic should stay
+//! unreliable regardless.
+//!
+//! Each peer gets its own send buffer keyed by sequence number. The receiver
+//! tracks which sequences it has seen and reports gaps back as a
+//! `Packet::Nak` carrying an SRT-style run-length-compressed loss list; the
+//! sender retransmits exactly the sequences listed. A `Packet::Ack` instead
+//! confirms everything up to a cumulative sequence, letting the sender drop
+//! those entries from its send buffer.
+
+use shared::Packet;
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
+
+/// Marks a loss-list word as the start of a compressed run rather than an
+/// isolated missing sequence number.
+const RANGE_MARKER: u32 = 1 << 31;
+
+/// Encodes a sorted, deduplicated list of missing sequence numbers using
+/// SRT-style run-length compression: a run of two or more consecutive
+/// numbers becomes `[start | RANGE_MARKER, end]`, while an isolated loss is
+/// emitted as a single word with the marker bit clear.
+pub fn encode_loss_list(missing: &[u32]) -> Vec<u32> {
+    let mut words = Vec::new();
+    let mut i = 0;
+
+    while i < missing.len() {
+        let start = missing[i];
+        let mut end = start;
+        let mut j = i + 1;
+        while j < missing.len() && missing[j] == end + 1 {
+            end = missing[j];
+            j += 1;
+        }
+
+        if end > start {
+            words.push(start | RANGE_MARKER);
+            words.push(end);
+        } else {
+            words.push(start);
+        }
+        i = j;
+    }
+
+    words
+}
+
+/// Decodes a loss list produced by [`encode_loss_list`] back into the full,
+/// expanded set of missing sequence numbers.
+pub fn decode_loss_list(words: &[u32]) -> Vec<u32> {
+    let mut missing = Vec::new();
+    let mut i = 0;
+
+    while i < words.len() {
+        let word = words[i];
+        if word & RANGE_MARKER != 0 {
+            let start = word & !RANGE_MARKER;
+            let end = words.get(i + 1).copied().unwrap_or(start);
+            missing.extend(start..=end);
+            i += 2;
+        } else {
+            missing.push(word);
+            i += 1;
+        }
+    }
+
+    missing
+}
+
+/// Per-peer sequencing and buffering state.
+struct PeerChannel {
+    next_seq: u32,
+    send_buffer: HashMap<u32, Packet>,
+    received: BTreeSet<u32>,
+    highest_received: u32,
+}
+
+impl PeerChannel {
+    fn new() -> Self {
+        Self {
+            next_seq: 1,
+            send_buffer: HashMap::new(),
+            received: BTreeSet::new(),
+            highest_received: 0,
+        }
+    }
+}
+
+/// Tracks, per peer, an explicit NAK-based reliable channel layered directly
+/// on top of `Packet`s rather than the framed `Transport`.
+pub struct ReliableChannel {
+    peers: HashMap<SocketAddr, PeerChannel>,
+}
+
+impl ReliableChannel {
+    pub fn new() -> Self {
+        Self {
+            peers: HashMap::new(),
+        }
+    }
+
+    fn peer_mut(&mut self, addr: SocketAddr) -> &mut PeerChannel {
+        self.peers.entry(addr).or_insert_with(PeerChannel::new)
+    }
+
+    /// Assigns the next sequence number for `addr`, buffers `packet` for
+    /// possible retransmission, and returns the sequence it was sent under.
+    pub fn send(&mut self, addr: SocketAddr, packet: Packet) -> u32 {
+        let peer = self.peer_mut(addr);
+        let seq = peer.next_seq;
+        peer.next_seq = peer.next_seq.wrapping_add(1);
+        peer.send_buffer.insert(seq, packet);
+        seq
+    }
+
+    /// Records an incoming sequence number from `addr` and returns a
+    /// `Packet::Nak` if there's now a gap between 1 and the highest sequence
+    /// seen so far.
+    pub fn record_received(&mut self, addr: SocketAddr, seq: u32) -> Option<Packet> {
+        let peer = self.peer_mut(addr);
+        peer.received.insert(seq);
+        peer.highest_received = peer.highest_received.max(seq);
+
+        let missing: Vec<u32> = (1..=peer.highest_received)
+            .filter(|s| !peer.received.contains(s))
+            .collect();
+
+        if missing.is_empty() {
+            None
+        } else {
+            Some(Packet::Nak {
+                loss_list: encode_loss_list(&missing),
+            })
+        }
+    }
+
+    /// Drops everything up to and including `cumulative_seq` from `addr`'s
+    /// send buffer — those sequences are confirmed delivered.
+    pub fn on_ack(&mut self, addr: SocketAddr, cumulative_seq: u32) {
+        let peer = self.peer_mut(addr);
+        peer.send_buffer.retain(|seq, _| *seq > cumulative_seq);
+    }
+
+    /// Looks up the buffered packets for exactly the sequences named in a
+    /// received NAK's loss list, for retransmission.
+    pub fn on_nak(&mut self, addr: SocketAddr, loss_list: &[u32]) -> Vec<(u32, Packet)> {
+        let peer = self.peer_mut(addr);
+        decode_loss_list(loss_list)
+            .into_iter()
+            .filter_map(|seq| peer.send_buffer.get(&seq).cloned().map(|p| (seq, p)))
+            .collect()
+    }
+
+    pub fn remove_peer(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+    }
+}
+
+impl Default for ReliableChannel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr() -> SocketAddr {
+        "127.0.0.1:9100".parse().unwrap()
+    }
+
+    #[test]
+    fn isolated_losses_encode_without_range_marker() {
+        let encoded = encode_loss_list(&[2, 5, 9]);
+        assert_eq!(encoded, vec![2, 5, 9]);
+        assert_eq!(decode_loss_list(&encoded), vec![2, 5, 9]);
+    }
+
+    #[test]
+    fn consecutive_run_compresses_to_two_words() {
+        let encoded = encode_loss_list(&[3, 4, 5, 6]);
+        assert_eq!(encoded, vec![3 | RANGE_MARKER, 6]);
+        assert_eq!(decode_loss_list(&encoded), vec![3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn mixed_runs_and_isolated_losses_roundtrip() {
+        let missing = vec![1, 2, 3, 7, 10, 11];
+        let encoded = encode_loss_list(&missing);
+        assert_eq!(encoded, vec![1 | RANGE_MARKER, 3, 7, 10 | RANGE_MARKER, 11]);
+        assert_eq!(decode_loss_list(&encoded), missing);
+    }
+
+    #[test]
+    fn empty_loss_list_roundtrips() {
+        assert!(encode_loss_list(&[]).is_empty());
+        assert!(decode_loss_list(&[]).is_empty());
+    }
+
+    #[test]
+    fn send_buffers_packet_and_assigns_increasing_sequences() {
+        let mut channel = ReliableChannel::new();
+        let peer_addr = addr();
+
+        let seq1 = channel.send(peer_addr, Packet::Disconnect);
+        let seq2 = channel.send(peer_addr, Packet::Disconnect);
+
+        assert_eq!(seq1, 1);
+        assert_eq!(seq2, 2);
+    }
+
+    #[test]
+    fn ack_prunes_send_buffer_up_to_cumulative_seq() {
+        let mut channel = ReliableChannel::new();
+        let peer_addr = addr();
+
+        channel.send(peer_addr, Packet::Disconnect);
+        channel.send(peer_addr, Packet::Disconnect);
+        channel.send(peer_addr, Packet::Disconnect);
+
+        channel.on_ack(peer_addr, 2);
+
+        let peer = channel.peers.get(&peer_addr).unwrap();
+        assert!(!peer.send_buffer.contains_key(&1));
+        assert!(!peer.send_buffer.contains_key(&2));
+        assert!(peer.send_buffer.contains_key(&3));
+    }
+
+    #[test]
+    fn record_received_reports_gap_as_nak() {
+        let mut channel = ReliableChannel::new();
+        let peer_addr = addr();
+
+        assert!(channel.record_received(peer_addr, 1).is_none());
+
+        // Sequence 3 arrives before 2: the gap at 2 should be reported.
+        let nak = channel.record_received(peer_addr, 3).unwrap();
+        match nak {
+            Packet::Nak { loss_list } => assert_eq!(decode_loss_list(&loss_list), vec![2]),
+            _ => panic!("expected a Nak packet"),
+        }
+
+        // Filling the gap clears it.
+        assert!(channel.record_received(peer_addr, 2).is_none());
+    }
+
+    #[test]
+    fn on_nak_returns_exactly_the_listed_buffered_packets() {
+        let mut channel = ReliableChannel::new();
+        let peer_addr = addr();
+
+        channel.send(peer_addr, Packet::Disconnect);
+        channel.send(
+            peer_addr,
+            Packet::Disconnected {
+                reason: "bye".to_string(),
+            },
+        );
+        channel.send(peer_addr, Packet::Ping { nonce: 7 });
+
+        let loss_list = encode_loss_list(&[2, 3]);
+        let retransmits = channel.on_nak(peer_addr, &loss_list);
+
+        assert_eq!(retransmits.len(), 2);
+        assert_eq!(retransmits[0].0, 2);
+        assert_eq!(retransmits[1].0, 3);
+    }
+}
@@ -0,0 +1,268 @@
+//! Multi-channel priority scheduler backed by a binary heap.
+//!
+//! `InboundQueue` is a single FIFO: every packet drains in arrival order
+//! regardless of what it is, so a burst of bulk traffic on one channel can
+//! sit ahead of an urgent ack or state snapshot on another. `PriorityScheduler`
+//! replaces that with one heap spanning every channel: each push carries a
+//! channel ID and priority, `pop` always returns the highest-priority
+//! channel's oldest still-queued packet, and packets within the same
+//! channel never reorder relative to each other (a monotonic per-channel
+//! sequence counter breaks heap ties). A channel marked `reliable` also
+//! keeps every packet it hands out in a per-channel send buffer — the same
+//! shape `reliable::PeerChannel::send_buffer` uses — until `ack` retires
+//! it, so [`PriorityScheduler::unacked`] can return exactly what still needs
+//! retransmitting for that channel.
+
+use shared::Packet;
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+/// Lower is more urgent, matching Unix `nice` — priority `0` always drains
+/// ahead of priority `1` regardless of arrival order across channels.
+pub type Priority = u8;
+
+struct ScheduledPacket {
+    priority: Priority,
+    channel_id: u32,
+    /// Monotonic within `channel_id`, assigned at push time. Breaks heap
+    /// ties between two packets of equal priority on the same channel so
+    /// they still pop in the order they were pushed.
+    channel_seq: u64,
+    packet: Packet,
+}
+
+impl PartialEq for ScheduledPacket {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+            && self.channel_id == other.channel_id
+            && self.channel_seq == other.channel_seq
+    }
+}
+impl Eq for ScheduledPacket {}
+
+impl PartialOrd for ScheduledPacket {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ScheduledPacket {
+    // Reversed so `BinaryHeap` (a max-heap) pops the lowest `priority` value
+    // first, and the lowest `channel_seq` first among same-priority,
+    // same-channel packets.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .priority
+            .cmp(&self.priority)
+            .then_with(|| match self.channel_id.cmp(&other.channel_id) {
+                Ordering::Equal => other.channel_seq.cmp(&self.channel_seq),
+                non_eq => non_eq,
+            })
+    }
+}
+
+/// Per-channel bookkeeping: the next sequence to assign, and (for a
+/// `reliable` channel) every dispatched packet still awaiting an ack.
+struct ChannelState {
+    reliable: bool,
+    next_seq: u64,
+    unacked: HashMap<u64, Packet>,
+}
+
+impl ChannelState {
+    fn new(reliable: bool) -> Self {
+        Self {
+            reliable,
+            next_seq: 0,
+            unacked: HashMap::new(),
+        }
+    }
+}
+
+/// A single priority-ordered heap spanning every registered channel. FIFO
+/// order within a channel is always preserved regardless of how other
+/// channels interleave.
+pub struct PriorityScheduler {
+    channels: HashMap<u32, ChannelState>,
+    heap: BinaryHeap<ScheduledPacket>,
+}
+
+impl PriorityScheduler {
+    pub fn new() -> Self {
+        Self {
+            channels: HashMap::new(),
+            heap: BinaryHeap::new(),
+        }
+    }
+
+    /// Registers a channel without pushing anything to it. A channel that is
+    /// already registered (including implicitly, by `push`) keeps the
+    /// reliability it already has; `reliable` is ignored for it.
+    pub fn register_channel(&mut self, channel_id: u32, reliable: bool) {
+        self.channels
+            .entry(channel_id)
+            .or_insert_with(|| ChannelState::new(reliable));
+    }
+
+    /// Queues `packet` on `channel_id` at `priority`, registering the
+    /// channel (as unreliable) first if this is its first packet. Returns
+    /// the channel-local sequence this packet was assigned.
+    pub fn push(&mut self, channel_id: u32, priority: Priority, packet: Packet) -> u64 {
+        self.register_channel(channel_id, false);
+        let channel = self.channels.get_mut(&channel_id).unwrap();
+        let channel_seq = channel.next_seq;
+        channel.next_seq += 1;
+
+        self.heap.push(ScheduledPacket {
+            priority,
+            channel_id,
+            channel_seq,
+            packet,
+        });
+        channel_seq
+    }
+
+    /// Pops the highest-priority, oldest-within-channel packet. If its
+    /// channel is reliable, a copy is retained in that channel's unacked
+    /// set until [`PriorityScheduler::ack`] retires it.
+    pub fn pop(&mut self) -> Option<(u32, u64, Packet)> {
+        let scheduled = self.heap.pop()?;
+        if let Some(channel) = self.channels.get_mut(&scheduled.channel_id) {
+            if channel.reliable {
+                channel
+                    .unacked
+                    .insert(scheduled.channel_seq, scheduled.packet.clone());
+            }
+        }
+        Some((scheduled.channel_id, scheduled.channel_seq, scheduled.packet))
+    }
+
+    /// Retires a reliable channel's packet at `channel_seq` once it's been
+    /// confirmed delivered. A no-op for an unknown channel/sequence.
+    pub fn ack(&mut self, channel_id: u32, channel_seq: u64) {
+        if let Some(channel) = self.channels.get_mut(&channel_id) {
+            channel.unacked.remove(&channel_seq);
+        }
+    }
+
+    /// Every packet on `channel_id` that's been popped but not yet acked,
+    /// for a caller to decide when/whether to retransmit — this stage only
+    /// tracks what's outstanding, the same decoupling
+    /// `congestion::CongestionController` uses for its own ack/loss inputs.
+    pub fn unacked(&self, channel_id: u32) -> Vec<(u64, Packet)> {
+        let Some(channel) = self.channels.get(&channel_id) else {
+            return Vec::new();
+        };
+        channel
+            .unacked
+            .iter()
+            .map(|(seq, packet)| (*seq, packet.clone()))
+            .collect()
+    }
+
+    /// Packets still queued and not yet popped, across every channel.
+    pub fn remaining(&self) -> usize {
+        self.heap.len()
+    }
+}
+
+impl Default for PriorityScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packet(nonce: u64) -> Packet {
+        Packet::Ping { nonce }
+    }
+
+    #[test]
+    fn higher_priority_channel_drains_before_lower_priority_one() {
+        let mut scheduler = PriorityScheduler::new();
+        scheduler.push(1, 5, packet(1)); // bulk
+        scheduler.push(2, 0, packet(2)); // urgent
+
+        let (channel_id, _, _) = scheduler.pop().unwrap();
+        assert_eq!(channel_id, 2);
+    }
+
+    #[test]
+    fn same_priority_packets_drain_fifo_within_their_own_channel() {
+        let mut scheduler = PriorityScheduler::new();
+        scheduler.push(1, 0, packet(1));
+        scheduler.push(1, 0, packet(2));
+
+        let (_, _, first) = scheduler.pop().unwrap();
+        let (_, _, second) = scheduler.pop().unwrap();
+        assert!(matches!(first, Packet::Ping { nonce: 1 }));
+        assert!(matches!(second, Packet::Ping { nonce: 2 }));
+    }
+
+    #[test]
+    fn interleaved_channels_preserve_each_channels_own_fifo_order() {
+        let mut scheduler = PriorityScheduler::new();
+        scheduler.push(1, 0, packet(1));
+        scheduler.push(2, 0, packet(10));
+        scheduler.push(1, 0, packet(2));
+        scheduler.push(2, 0, packet(11));
+
+        let mut seen_channel_1 = Vec::new();
+        let mut seen_channel_2 = Vec::new();
+        while let Some((channel_id, _, p)) = scheduler.pop() {
+            match (channel_id, p) {
+                (1, Packet::Ping { nonce }) => seen_channel_1.push(nonce),
+                (2, Packet::Ping { nonce }) => seen_channel_2.push(nonce),
+                _ => panic!("unexpected channel/packet"),
+            }
+        }
+        assert_eq!(seen_channel_1, vec![1, 2]);
+        assert_eq!(seen_channel_2, vec![10, 11]);
+    }
+
+    #[test]
+    fn reliable_channel_retains_a_popped_packet_until_acked() {
+        let mut scheduler = PriorityScheduler::new();
+        scheduler.register_channel(1, true);
+        let seq = scheduler.push(1, 0, packet(1));
+
+        scheduler.pop();
+        assert_eq!(scheduler.unacked(1).len(), 1);
+
+        scheduler.ack(1, seq);
+        assert!(scheduler.unacked(1).is_empty());
+    }
+
+    #[test]
+    fn unreliable_channel_never_retains_popped_packets() {
+        let mut scheduler = PriorityScheduler::new();
+        scheduler.push(1, 0, packet(1));
+
+        scheduler.pop();
+        assert!(scheduler.unacked(1).is_empty());
+    }
+
+    #[test]
+    fn consumed_plus_remaining_accounts_for_every_pushed_packet_across_channels() {
+        let mut scheduler = PriorityScheduler::new();
+        let total_per_channel = 25;
+        for i in 0..total_per_channel {
+            scheduler.push(1, 1, packet(i));
+            scheduler.push(2, 0, packet(i));
+        }
+        let total = total_per_channel * 2;
+
+        let mut consumed = 0;
+        // Pop a bit less than everything so `remaining` has something left
+        // to account for too.
+        for _ in 0..(total - 10) {
+            scheduler.pop();
+            consumed += 1;
+        }
+
+        assert_eq!(consumed + scheduler.remaining() as u64, total);
+    }
+}
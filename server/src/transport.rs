@@ -0,0 +1,883 @@
+//! Reliable-ordered delivery layer over the shared UDP socket.
+//!
+//! `spawn_network_receiver`/`spawn_network_sender` normally treat every `Packet` as
+//! fire-and-forget UDP, so connection-management messages (and any future critical
+//! traffic) can silently drop. `Transport` adds an optional "reliable" send mode with
+//! in-order delivery, modeled after micro-transport protocols (uTP/LEDBAT) layered on
+//! top of UDP rather than opening a second TCP socket.
+//!
+//! Each peer gets its own [`PeerState`] tracking `seq_nr`/`ack_nr`, a send window of
+//! unacked buffered frames, and a reorder buffer that only releases payloads to the
+//! caller once the sequence gap is filled. Every wire frame carries a `timestamp_micros`
+//! stamped at send time and a `timestamp_diff_micros` the receiver fills in as
+//! `local_recv_time - peer_timestamp`; that one-way delay sample drives LEDBAT-style
+//! congestion control on the send window.
+
+use crate::netcode_handshake::SequenceReplayWindow;
+use bytes::Bytes;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// LEDBAT target queuing delay.
+const TARGET_DELAY_MICROS: i64 = 100_000;
+/// LEDBAT gain constant controlling how aggressively cwnd reacts to delay.
+const GAIN: f64 = 1.0;
+/// Approximate maximum segment size used for cwnd math.
+const MSS: f64 = 1400.0;
+/// cwnd never shrinks below one segment.
+const MIN_CWND: f64 = MSS;
+const INITIAL_CWND: f64 = MSS * 2.0;
+const INITIAL_RTO: Duration = Duration::from_millis(500);
+const DUP_ACK_THRESHOLD: u32 = 3;
+/// Classic Jacobson/Karels EWMA gains (RFC 6298) for smoothing RTT samples.
+const RTT_ALPHA: f64 = 0.125;
+const RTT_BETA: f64 = 0.25;
+/// Default cap on bytes a single peer may have buffered in the reliable send
+/// window plus outstanding unreliable traffic before we start shedding state
+/// traffic for them. A slow or stalled client shouldn't be able to make the
+/// outgoing path grow without bound.
+const DEFAULT_MAX_BUFFERED_BYTES: usize = 64 * 1024;
+/// How often per-peer byte/packet counters are folded into a smoothed
+/// bytes/sec rate for the `throughput()` report.
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Header prepended to every reliable-transport frame.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct FrameHeader {
+    pub seq_nr: u32,
+    pub ack_nr: u32,
+    pub reliable: bool,
+    /// Only meaningful when `reliable` is set. `true` holds a frame in the
+    /// reorder buffer until the sequence gap ahead of it fills, so delivery
+    /// matches send order; `false` delivers it the moment it arrives and
+    /// relies on `PeerState::accept_unordered`'s sliding bitfield to drop
+    /// duplicate retransmits instead.
+    pub ordered: bool,
+    pub timestamp_micros: u64,
+    pub timestamp_diff_micros: u64,
+    /// Selective-ack bitfield riding alongside the cumulative `ack_nr`: bit
+    /// `n` set means `ack_nr + n` has also already been received (e.g. it's
+    /// sitting in the receiver's reorder buffer awaiting an earlier gap), so
+    /// the sender can prune it from `send_window` too instead of only ever
+    /// pruning the single cumulative entry.
+    pub ack_mask: u32,
+}
+
+/// A framed payload ready to go on the wire (or just received). `payload` is a
+/// `Bytes` rather than `Vec<u8>` so a payload encoded once (e.g. a broadcast
+/// `GameState`) can be framed per-peer without copying the underlying buffer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Frame {
+    pub header: FrameHeader,
+    pub payload: Bytes,
+}
+
+struct BufferedPacket {
+    frame: Frame,
+    sent_at: Instant,
+}
+
+/// Per-peer connection state for the reliable transport.
+struct PeerState {
+    next_seq_nr: u32,
+    expected_seq_nr: u32,
+    send_window: HashMap<u32, BufferedPacket>,
+    reorder_buffer: HashMap<u32, Bytes>,
+    base_delay_micros: i64,
+    cwnd: f64,
+    rto: Duration,
+    last_ack_seen: Option<u32>,
+    dup_ack_count: u32,
+    max_buffered_bytes: usize,
+
+    // Bandwidth metering
+    bytes_sent: u64,
+    bytes_received: u64,
+    bytes_sent_in_window: u64,
+    bytes_received_in_window: u64,
+    rate_window_start: Instant,
+    sent_rate_bytes_per_sec: f64,
+    received_rate_bytes_per_sec: f64,
+    /// Optional ceiling on this peer's outbound rate. Unreliable frames that
+    /// would push the smoothed rate over this cap are coalesced away, same
+    /// as over-budget unreliable frames.
+    rate_cap_bytes_per_sec: Option<f64>,
+    /// Frames sent/retransmitted, used to derive a loss estimate: every
+    /// retransmit implies the original was presumably lost.
+    frames_sent: u64,
+    retransmits: u64,
+
+    // Round-trip time estimation
+    srtt: Option<Duration>,
+    rttvar: Duration,
+
+    // Reliable-unordered delivery dedup
+    /// Sliding-bitfield replay/dedup window over delivered unordered
+    /// sequences. Shared with `netcode_handshake`'s per-packet AEAD replay
+    /// guard rather than hand-rolling a second copy of the same algorithm —
+    /// widened from the 32-wide window this used to keep inline to the 64-bit
+    /// one `SequenceReplayWindow` tracks, so it tolerates a deeper window of
+    /// reordering for free.
+    unordered_replay_window: SequenceReplayWindow,
+}
+
+impl PeerState {
+    fn new() -> Self {
+        Self {
+            next_seq_nr: 1,
+            expected_seq_nr: 1,
+            send_window: HashMap::new(),
+            reorder_buffer: HashMap::new(),
+            base_delay_micros: i64::MAX,
+            cwnd: INITIAL_CWND,
+            rto: INITIAL_RTO,
+            last_ack_seen: None,
+            dup_ack_count: 0,
+            max_buffered_bytes: DEFAULT_MAX_BUFFERED_BYTES,
+            bytes_sent: 0,
+            bytes_received: 0,
+            bytes_sent_in_window: 0,
+            bytes_received_in_window: 0,
+            rate_window_start: Instant::now(),
+            sent_rate_bytes_per_sec: 0.0,
+            received_rate_bytes_per_sec: 0.0,
+            rate_cap_bytes_per_sec: None,
+            frames_sent: 0,
+            retransmits: 0,
+            srtt: None,
+            rttvar: Duration::ZERO,
+            unordered_replay_window: SequenceReplayWindow::new(),
+        }
+    }
+
+    /// Accepts or rejects a reliable-unordered frame's sequence against the
+    /// sliding bitfield of what's already been delivered. Returns `true` the
+    /// first time a sequence is seen (and records it), `false` for a
+    /// duplicate or one too old for the window to track precisely — in the
+    /// latter case it's treated as a duplicate, since anything that far
+    /// behind the highest seen has almost certainly already been delivered.
+    fn accept_unordered(&mut self, seq_nr: u32) -> bool {
+        self.unordered_replay_window.accept(seq_nr as u64)
+    }
+
+    /// Rolls the windowed send/receive counters into a smoothed rate once
+    /// `RATE_WINDOW` has elapsed.
+    fn roll_rate_window(&mut self) {
+        let elapsed = self.rate_window_start.elapsed();
+        if elapsed >= RATE_WINDOW {
+            self.sent_rate_bytes_per_sec = self.bytes_sent_in_window as f64 / elapsed.as_secs_f64();
+            self.received_rate_bytes_per_sec =
+                self.bytes_received_in_window as f64 / elapsed.as_secs_f64();
+            self.bytes_sent_in_window = 0;
+            self.bytes_received_in_window = 0;
+            self.rate_window_start = Instant::now();
+        }
+    }
+
+    fn record_sent(&mut self, bytes: usize) {
+        self.bytes_sent += bytes as u64;
+        self.bytes_sent_in_window += bytes as u64;
+        self.frames_sent += 1;
+        self.roll_rate_window();
+    }
+
+    fn record_received(&mut self, bytes: usize) {
+        self.bytes_received += bytes as u64;
+        self.bytes_received_in_window += bytes as u64;
+        self.roll_rate_window();
+    }
+
+    /// Whether sending `extra_bytes` now would push this peer's smoothed
+    /// outbound rate over its configured cap, if any.
+    fn over_rate_cap(&self, extra_bytes: usize) -> bool {
+        match self.rate_cap_bytes_per_sec {
+            Some(cap) => self.sent_rate_bytes_per_sec + extra_bytes as f64 > cap,
+            None => false,
+        }
+    }
+
+    /// Fraction of sent frames that were retransmitted, as a rough proxy for
+    /// packet loss (derived from the ack/RTO subsystem rather than an actual
+    /// loss count, since UDP gives no direct signal).
+    fn loss_estimate(&self) -> f64 {
+        self.retransmits as f64 / self.frames_sent.max(1) as f64
+    }
+
+    /// Feeds a fresh round-trip sample (send-to-ack elapsed time) into the
+    /// smoothed RTT/RTTVAR estimator, using the classic RFC 6298 EWMA.
+    fn on_rtt_sample(&mut self, sample: Duration) {
+        match self.srtt {
+            None => {
+                self.srtt = Some(sample);
+                self.rttvar = sample / 2;
+            }
+            Some(srtt) => {
+                let diff = if srtt >= sample {
+                    srtt - sample
+                } else {
+                    sample - srtt
+                };
+                self.rttvar = self.rttvar.mul_f64(1.0 - RTT_BETA) + diff.mul_f64(RTT_BETA);
+                self.srtt = Some(srtt.mul_f64(1.0 - RTT_ALPHA) + sample.mul_f64(RTT_ALPHA));
+            }
+        }
+    }
+
+    /// Feeds a one-way delay sample into the LEDBAT congestion controller.
+    fn on_delay_sample(&mut self, timestamp_diff_micros: i64, bytes_acked: f64) {
+        self.base_delay_micros = self.base_delay_micros.min(timestamp_diff_micros);
+        let queuing_delay = (timestamp_diff_micros - self.base_delay_micros) as f64;
+        let off_target = (TARGET_DELAY_MICROS as f64 - queuing_delay) / TARGET_DELAY_MICROS as f64;
+        self.cwnd += GAIN * off_target * bytes_acked * MSS / self.cwnd;
+        self.cwnd = self.cwnd.max(MIN_CWND);
+    }
+
+    /// Bytes currently in flight in the send window.
+    fn bytes_in_flight(&self) -> f64 {
+        self.send_window
+            .values()
+            .map(|b| b.frame.payload.len() as f64)
+            .sum()
+    }
+
+    fn window_has_room(&self) -> bool {
+        self.bytes_in_flight() < self.cwnd
+    }
+
+    /// Whether buffering `extra_bytes` more for this peer would exceed its
+    /// outgoing byte budget. Reliable traffic already counts against the
+    /// congestion window above; this is a coarser backstop that also accounts
+    /// for the unreliable traffic the congestion window never tracks.
+    fn over_budget(&self, extra_bytes: usize) -> bool {
+        self.bytes_in_flight() + extra_bytes as f64 > self.max_buffered_bytes as f64
+    }
+
+    /// Builds the outgoing selective-ack bitfield from sequences currently
+    /// sitting in the reorder buffer (i.e. received out of order, strictly
+    /// ahead of `expected_seq_nr`). Bit `n` corresponds to sequence
+    /// `expected_seq_nr + n`.
+    fn ack_mask(&self) -> u32 {
+        let mut mask = 0u32;
+        for &seq in self.reorder_buffer.keys() {
+            let offset = seq.wrapping_sub(self.expected_seq_nr);
+            if offset < 32 {
+                mask |= 1 << offset;
+            }
+        }
+        mask
+    }
+}
+
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or(Duration::from_secs(0))
+        .as_micros() as u64
+}
+
+/// Snapshot of a peer's metered throughput, returned by [`Transport::throughput`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Throughput {
+    pub sent_bytes_per_sec: f64,
+    pub received_bytes_per_sec: f64,
+    /// Fraction of sent frames that had to be retransmitted, as a rough
+    /// proxy for loss (UDP gives no direct delivery confirmation).
+    pub loss_estimate: f64,
+}
+
+/// Smoothed round-trip time for a peer, returned by [`Transport::rtt_estimate`].
+/// `jitter` is the RTTVAR term from the same RFC 6298 estimator: the mean
+/// absolute deviation of samples from `smoothed`, which is what reconciliation
+/// or substep sizing should add as a safety margin on top of `smoothed` alone.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RttEstimate {
+    pub smoothed: Duration,
+    pub jitter: Duration,
+}
+
+/// Reliable-ordered transport layered over a plain UDP socket.
+///
+/// Owns no socket itself; callers hand it raw bytes to frame for sending and raw
+/// datagrams to decode, keeping it testable without a real network.
+pub struct Transport {
+    peers: HashMap<SocketAddr, PeerState>,
+}
+
+impl Transport {
+    pub fn new() -> Self {
+        Self {
+            peers: HashMap::new(),
+        }
+    }
+
+    fn peer_mut(&mut self, addr: SocketAddr) -> &mut PeerState {
+        self.peers.entry(addr).or_insert_with(PeerState::new)
+    }
+
+    /// Sets a ceiling on `addr`'s outbound byte rate. Unreliable frames that
+    /// would push the smoothed rate over this cap are coalesced away rather
+    /// than sent, protecting the server from a single abusive or overloaded
+    /// client. `None` (the default) leaves the peer uncapped.
+    pub fn set_rate_cap(&mut self, addr: SocketAddr, cap_bytes_per_sec: Option<f64>) {
+        self.peer_mut(addr).rate_cap_bytes_per_sec = cap_bytes_per_sec;
+    }
+
+    /// Returns `addr`'s current metered throughput, or `None` if it's not a
+    /// known peer.
+    pub fn throughput(&self, addr: SocketAddr) -> Option<Throughput> {
+        self.peers.get(&addr).map(|peer| Throughput {
+            sent_bytes_per_sec: peer.sent_rate_bytes_per_sec,
+            received_bytes_per_sec: peer.received_rate_bytes_per_sec,
+            loss_estimate: peer.loss_estimate(),
+        })
+    }
+
+    /// Returns `addr`'s smoothed round-trip time and jitter (RFC 6298-style
+    /// SRTT/RTTVAR), or `None` if no peer is known yet or no reliable frame
+    /// it sent has been acked. Callers that need to rewind a fixed number of
+    /// ticks for lag compensation, or size a jitter buffer, should use
+    /// `smoothed + jitter` rather than `smoothed` alone.
+    pub fn rtt_estimate(&self, addr: SocketAddr) -> Option<RttEstimate> {
+        let peer = self.peers.get(&addr)?;
+        Some(RttEstimate {
+            smoothed: peer.srtt?,
+            jitter: peer.rttvar,
+        })
+    }
+
+    /// Frames an outgoing payload for `addr`. `reliable` packets are buffered in the
+    /// send window for retransmission; unreliable ones still carry/advance the ack
+    /// fields so piggybacked ACKs keep flowing.
+    ///
+    /// Returns `None` if an unreliable payload would push the peer's buffered bytes
+    /// over its budget, or its smoothed send rate over its configured cap — the
+    /// caller should drop it rather than send, since frequent state traffic (e.g.
+    /// `GameState`) is always superseded by the next one anyway. Reliable payloads
+    /// are never dropped this way; they're bounded by the congestion window instead.
+    ///
+    /// `ordered` is ignored unless `reliable` is set; see [`FrameHeader::ordered`].
+    pub fn frame_outgoing(
+        &mut self,
+        addr: SocketAddr,
+        payload: Bytes,
+        reliable: bool,
+        ordered: bool,
+    ) -> Option<Frame> {
+        let peer = self.peer_mut(addr);
+
+        if !reliable && peer.over_budget(payload.len()) {
+            warn!(
+                "dropping stale unreliable frame for {}: over {}-byte budget",
+                addr, peer.max_buffered_bytes
+            );
+            return None;
+        }
+
+        if !reliable && peer.over_rate_cap(payload.len()) {
+            warn!(
+                "dropping stale unreliable frame for {}: over its {:.0} B/s rate cap",
+                addr,
+                peer.rate_cap_bytes_per_sec.unwrap_or(0.0)
+            );
+            return None;
+        }
+
+        let seq_nr = if reliable {
+            let seq = peer.next_seq_nr;
+            peer.next_seq_nr = peer.next_seq_nr.wrapping_add(1);
+            seq
+        } else {
+            0
+        };
+
+        let header = FrameHeader {
+            seq_nr,
+            ack_nr: peer.expected_seq_nr,
+            reliable,
+            ordered,
+            timestamp_micros: now_micros(),
+            timestamp_diff_micros: 0,
+            ack_mask: peer.ack_mask(),
+        };
+
+        let frame = Frame { header, payload };
+        peer.record_sent(frame.payload.len());
+
+        if reliable {
+            peer.send_window.insert(
+                seq_nr,
+                BufferedPacket {
+                    frame: frame.clone(),
+                    sent_at: Instant::now(),
+                },
+            );
+        }
+
+        Some(frame)
+    }
+
+    /// Decodes an incoming frame, updates congestion/ack state, and returns any
+    /// payloads now ready for delivery in sequence order (possibly more than one if
+    /// a gap was just filled).
+    pub fn on_frame_received(&mut self, addr: SocketAddr, mut frame: Frame) -> Vec<Bytes> {
+        frame.header.timestamp_diff_micros =
+            now_micros().saturating_sub(frame.header.timestamp_micros);
+
+        let peer = self.peer_mut(addr);
+        peer.record_received(frame.payload.len());
+
+        // Piggybacked ACK: clear acknowledged packets from the send window and feed
+        // the delay sample into the congestion controller.
+        let newly_acked = peer.last_ack_seen != Some(frame.header.ack_nr);
+        if let Some(buffered) = peer.send_window.remove(&frame.header.ack_nr) {
+            let bytes_acked = buffered.frame.payload.len() as f64;
+            peer.on_delay_sample(frame.header.timestamp_diff_micros as i64, bytes_acked);
+            peer.on_rtt_sample(buffered.sent_at.elapsed());
+            peer.dup_ack_count = 0;
+        } else if !newly_acked {
+            peer.dup_ack_count += 1;
+            if peer.dup_ack_count >= DUP_ACK_THRESHOLD {
+                debug!("3 duplicate ACKs for {}, fast-retransmitting", addr);
+                // Backdate the first missing frame so the next
+                // `expired_retransmits` call treats it as due.
+                let missing = frame.header.ack_nr.wrapping_add(1);
+                let rto = peer.rto;
+                if let Some(buffered) = peer.send_window.get_mut(&missing) {
+                    if let Some(due_at) = Instant::now().checked_sub(rto) {
+                        buffered.sent_at = due_at;
+                    }
+                }
+                peer.dup_ack_count = 0;
+            }
+        }
+        peer.last_ack_seen = Some(frame.header.ack_nr);
+
+        // The selective-ack bitfield names additional sequences the remote
+        // has already received out of order — prune those from the send
+        // window too, not just the single cumulative `ack_nr` entry above.
+        for bit in 0..32 {
+            if frame.header.ack_mask & (1 << bit) != 0 {
+                peer.send_window.remove(&frame.header.ack_nr.wrapping_add(bit));
+            }
+        }
+
+        if !frame.header.reliable {
+            return vec![frame.payload];
+        }
+
+        if !frame.header.ordered {
+            return if peer.accept_unordered(frame.header.seq_nr) {
+                vec![frame.payload]
+            } else {
+                Vec::new()
+            };
+        }
+
+        let mut ready = Vec::new();
+        if frame.header.seq_nr == peer.expected_seq_nr {
+            ready.push(frame.payload);
+            peer.expected_seq_nr = peer.expected_seq_nr.wrapping_add(1);
+
+            // Drain any buffered out-of-order packets the new arrival unblocked.
+            while let Some(payload) = peer.reorder_buffer.remove(&peer.expected_seq_nr) {
+                ready.push(payload);
+                peer.expected_seq_nr = peer.expected_seq_nr.wrapping_add(1);
+            }
+        } else if frame.header.seq_nr > peer.expected_seq_nr {
+            peer.reorder_buffer.insert(frame.header.seq_nr, frame.payload);
+        }
+        // seq_nr < expected_seq_nr is a duplicate retransmit of something already
+        // delivered; drop it silently.
+
+        ready
+    }
+
+    /// Returns frames whose RTO has expired (or that three duplicate ACKs
+    /// marked due) and should be retransmitted now. Returns nothing while the
+    /// congestion window has no room.
+    pub fn expired_retransmits(&mut self, addr: SocketAddr) -> Vec<Frame> {
+        let Some(peer) = self.peers.get_mut(&addr) else {
+            return Vec::new();
+        };
+
+        if !peer.window_has_room() {
+            return Vec::new();
+        }
+
+        let now = Instant::now();
+        let rto = peer.rto;
+        let mut due = Vec::new();
+        for buffered in peer.send_window.values_mut() {
+            if now.duration_since(buffered.sent_at) >= rto {
+                buffered.sent_at = now;
+                due.push(buffered.frame.clone());
+            }
+        }
+
+        if !due.is_empty() {
+            peer.retransmits += due.len() as u64;
+            // Back off on repeated loss, same idea as TCP's RTO doubling.
+            peer.rto = (peer.rto * 2).min(Duration::from_secs(8));
+        }
+
+        due
+    }
+
+    pub fn remove_peer(&mut self, addr: SocketAddr) {
+        self.peers.remove(&addr);
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr() -> SocketAddr {
+        "127.0.0.1:9000".parse().unwrap()
+    }
+
+    #[test]
+    fn reliable_frame_delivers_in_order() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        let f1 = transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"one"), true, true)
+            .unwrap();
+        let f2 = transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"two"), true, true)
+            .unwrap();
+
+        assert_eq!(f1.header.seq_nr, 1);
+        assert_eq!(f2.header.seq_nr, 2);
+    }
+
+    #[test]
+    fn out_of_order_frames_buffer_until_gap_fills() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        let header2 = FrameHeader {
+            seq_nr: 2,
+            ack_nr: 0,
+            reliable: true,
+            ordered: true,
+            timestamp_micros: now_micros(),
+            timestamp_diff_micros: 0,
+            ack_mask: 0,
+        };
+        let frame2 = Frame {
+            header: header2,
+            payload: Bytes::from_static(b"second"),
+        };
+
+        // Frame 2 arrives before frame 1: nothing should surface yet.
+        let ready = transport.on_frame_received(peer_addr, frame2);
+        assert!(ready.is_empty());
+
+        let header1 = FrameHeader {
+            seq_nr: 1,
+            ack_nr: 0,
+            reliable: true,
+            ordered: true,
+            timestamp_micros: now_micros(),
+            timestamp_diff_micros: 0,
+            ack_mask: 0,
+        };
+        let frame1 = Frame {
+            header: header1,
+            payload: Bytes::from_static(b"first"),
+        };
+
+        // Frame 1 fills the gap: both should be released in order.
+        let ready = transport.on_frame_received(peer_addr, frame1);
+        assert_eq!(
+            ready,
+            vec![Bytes::from_static(b"first"), Bytes::from_static(b"second")]
+        );
+    }
+
+    #[test]
+    fn unreliable_frames_bypass_window_but_advance_acks() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        let frame = transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"state"), false, true)
+            .unwrap();
+        assert_eq!(frame.header.seq_nr, 0);
+        assert!(!frame.header.reliable);
+    }
+
+    #[test]
+    fn acked_packet_is_removed_from_send_window() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"payload"), true, true)
+            .unwrap();
+
+        let ack_header = FrameHeader {
+            seq_nr: 0,
+            ack_nr: 1,
+            reliable: false,
+            ordered: true,
+            timestamp_micros: now_micros(),
+            timestamp_diff_micros: 0,
+            ack_mask: 0,
+        };
+        let ack_frame = Frame {
+            header: ack_header,
+            payload: Bytes::new(),
+        };
+
+        transport.on_frame_received(peer_addr, ack_frame);
+
+        let peer = transport.peers.get(&peer_addr).unwrap();
+        assert!(peer.send_window.is_empty());
+    }
+
+    #[test]
+    fn ack_mask_prunes_out_of_order_acked_entries_beyond_ack_nr() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"one"), true, true)
+            .unwrap();
+        transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"two"), true, true)
+            .unwrap();
+        transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"three"), true, true)
+            .unwrap();
+
+        // seq 1 is confirmed by the cumulative ack_nr as usual; seq 3 arrived
+        // out of order and is reported via bit 2 of the mask (ack_nr + 2),
+        // while seq 2 is still missing and stays in the send window.
+        let ack_frame = Frame {
+            header: FrameHeader {
+                seq_nr: 0,
+                ack_nr: 1,
+                reliable: false,
+                ordered: true,
+                timestamp_micros: now_micros(),
+                timestamp_diff_micros: 0,
+                ack_mask: 1 << 2,
+            },
+            payload: Bytes::new(),
+        };
+        transport.on_frame_received(peer_addr, ack_frame);
+
+        let peer = transport.peers.get(&peer_addr).unwrap();
+        assert!(!peer.send_window.contains_key(&1));
+        assert!(peer.send_window.contains_key(&2));
+        assert!(!peer.send_window.contains_key(&3));
+    }
+
+    #[test]
+    fn no_retransmit_before_rto_expires() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"payload"), true, true)
+            .unwrap();
+        let due = transport.expired_retransmits(peer_addr);
+        assert!(due.is_empty());
+    }
+
+    #[test]
+    fn unreliable_frame_dropped_when_peer_over_budget() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+        transport.peer_mut(peer_addr).max_buffered_bytes = 10;
+
+        // Fill the reliable send window past the tiny budget...
+        transport
+            .frame_outgoing(peer_addr, Bytes::from(vec![0u8; 20]), true, true)
+            .unwrap();
+
+        // ...so a subsequent unreliable frame gets coalesced away rather than queued.
+        let dropped = transport.frame_outgoing(peer_addr, Bytes::from_static(b"stale"), false, true);
+        assert!(dropped.is_none());
+    }
+
+    #[test]
+    fn throughput_is_none_for_unknown_peer() {
+        let transport = Transport::new();
+        assert!(transport.throughput(addr()).is_none());
+    }
+
+    #[test]
+    fn unreliable_frame_dropped_when_peer_over_rate_cap() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+        transport.set_rate_cap(peer_addr, Some(1.0));
+
+        transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"payload"), true, true)
+            .unwrap();
+
+        let dropped = transport.frame_outgoing(peer_addr, Bytes::from_static(b"stale"), false, true);
+        assert!(dropped.is_none());
+    }
+
+    #[test]
+    fn rtt_estimate_is_none_before_any_ack() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+        transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"payload"), true, true)
+            .unwrap();
+
+        assert!(transport.rtt_estimate(peer_addr).is_none());
+    }
+
+    #[test]
+    fn rtt_estimate_tracks_acked_round_trip() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"payload"), true, true)
+            .unwrap();
+
+        let ack_frame = Frame {
+            header: FrameHeader {
+                seq_nr: 0,
+                ack_nr: 1,
+                reliable: false,
+                ordered: true,
+                timestamp_micros: now_micros(),
+                timestamp_diff_micros: 0,
+                ack_mask: 0,
+            },
+            payload: Bytes::new(),
+        };
+        transport.on_frame_received(peer_addr, ack_frame);
+
+        let estimate = transport.rtt_estimate(peer_addr).unwrap();
+        // First sample seeds srtt directly; rttvar starts at half the sample.
+        assert_eq!(estimate.jitter, estimate.smoothed / 2);
+    }
+
+    #[test]
+    fn loss_estimate_reflects_retransmit_ratio() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+        transport.peer_mut(peer_addr).rto = Duration::from_millis(0);
+
+        transport
+            .frame_outgoing(peer_addr, Bytes::from_static(b"payload"), true, true)
+            .unwrap();
+        let due = transport.expired_retransmits(peer_addr);
+        assert_eq!(due.len(), 1);
+
+        let throughput = transport.throughput(peer_addr).unwrap();
+        assert_eq!(throughput.loss_estimate, 1.0);
+    }
+
+    #[test]
+    fn reliable_unordered_delivers_out_of_order_frames_immediately() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        let header2 = FrameHeader {
+            seq_nr: 2,
+            ack_nr: 0,
+            reliable: true,
+            ordered: false,
+            timestamp_micros: now_micros(),
+            timestamp_diff_micros: 0,
+            ack_mask: 0,
+        };
+        let frame2 = Frame {
+            header: header2,
+            payload: Bytes::from_static(b"second"),
+        };
+
+        // Unlike ReliableOrdered, frame 2 delivers right away despite frame 1
+        // never having arrived.
+        let ready = transport.on_frame_received(peer_addr, frame2);
+        assert_eq!(ready, vec![Bytes::from_static(b"second")]);
+    }
+
+    #[test]
+    fn reliable_unordered_drops_duplicate_delivery_of_same_sequence() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        let header = FrameHeader {
+            seq_nr: 5,
+            ack_nr: 0,
+            reliable: true,
+            ordered: false,
+            timestamp_micros: now_micros(),
+            timestamp_diff_micros: 0,
+            ack_mask: 0,
+        };
+        let frame = Frame {
+            header,
+            payload: Bytes::from_static(b"payload"),
+        };
+
+        let first = transport.on_frame_received(peer_addr, frame.clone());
+        assert_eq!(first.len(), 1);
+
+        // A retransmit of the same sequence is suppressed as a duplicate.
+        let second = transport.on_frame_received(peer_addr, frame);
+        assert!(second.is_empty());
+    }
+
+    #[test]
+    fn reliable_unordered_accepts_a_late_sequence_still_inside_the_bitfield_window() {
+        let mut transport = Transport::new();
+        let peer_addr = addr();
+
+        transport.on_frame_received(
+            peer_addr,
+            Frame {
+                header: FrameHeader {
+                    seq_nr: 10,
+                    ack_nr: 0,
+                    reliable: true,
+                    ordered: false,
+                    timestamp_micros: now_micros(),
+                    timestamp_diff_micros: 0,
+                    ack_mask: 0,
+                },
+                payload: Bytes::from_static(b"newer"),
+            },
+        );
+
+        // Sequence 8 arrives late, but it's within the 64-wide window behind
+        // the highest seen (10), so it's still delivered rather than dropped.
+        let ready = transport.on_frame_received(
+            peer_addr,
+            Frame {
+                header: FrameHeader {
+                    seq_nr: 8,
+                    ack_nr: 0,
+                    reliable: true,
+                    ordered: false,
+                    timestamp_micros: now_micros(),
+                    timestamp_diff_micros: 0,
+                    ack_mask: 0,
+                },
+                payload: Bytes::from_static(b"older"),
+            },
+        );
+        assert_eq!(ready, vec![Bytes::from_static(b"older")]);
+    }
+}
@@ -0,0 +1,376 @@
+//! A small, generic entity/component store.
+//!
+//! `Player` bakes position, velocity and ground state into one struct
+//! purpose-built for the two-player demo; there's nowhere to hang a
+//! projectile or a moving platform without growing `Player` into a grab
+//! bag. `Manager` instead stores each component type in its own column,
+//! addressed by `Entity`, so new entity kinds can opt into whichever
+//! components they need (a projectile wants `Position`/`Velocity` but not
+//! `Grounded`) without touching the others. It's deliberately minimal next
+//! to a general-purpose ECS: no archetypes, no query caching, just parallel
+//! `Vec<Option<T>>` columns keyed by `TypeId` and a linear `Filter` scan.
+//!
+//! `server::game::GameState::update_physics` wires `gravity_system`/
+//! `integrate_system` into the real per-tick loop: it builds an ephemeral
+//! `Manager` from every non-flying player's position/velocity/grounded
+//! state, runs both systems, and writes the result back into the `Player`s
+//! those components were copied from. That's only the gravity+integrate
+//! half of a `Player`'s physics, though — collision resolution and the
+//! client-side prediction path (`simulate_tick`) still operate on `Player`
+//! directly, since `resolve_collision` needs fields (`collider`, `gamemode`,
+//! `layer`, `mask`) this module has no component for yet. Porting the rest
+//! over, and giving `Packet::GameState` a way to serialize an arbitrary
+//! `Manager` instead of a fixed `Vec<Player>`, is future work.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// A handle to an entity. `generation` is bumped every time `index` is
+/// reused after a despawn, so a stale `Entity` from before a despawn can't
+/// alias a newer entity that happens to land on the same slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: u32,
+    generation: u32,
+}
+
+/// A typed reference to one entity's `T` component. Carries no data of its
+/// own beyond the `Entity` it was looked up for; it exists so
+/// `Manager::get`/`get_mut` can be generic over `T` without the caller
+/// naming `TypeId` directly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key<T> {
+    entity: Entity,
+    _marker: std::marker::PhantomData<T>,
+}
+
+impl<T> Key<T> {
+    pub fn entity(self) -> Entity {
+        self.entity
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    generation: u32,
+    alive: bool,
+}
+
+/// One component type's storage: a `Vec<Option<T>>` indexed by
+/// `Entity::index`, type-erased behind `Any` so `Manager` can hold many of
+/// these in one map.
+trait Column: Any {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    fn remove_index(&mut self, index: usize) -> bool;
+    fn has_index(&self, index: usize) -> bool;
+}
+
+struct TypedColumn<T> {
+    values: Vec<Option<T>>,
+}
+
+impl<T> Default for TypedColumn<T> {
+    fn default() -> Self {
+        Self { values: Vec::new() }
+    }
+}
+
+impl<T: 'static> Column for TypedColumn<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove_index(&mut self, index: usize) -> bool {
+        self.values
+            .get_mut(index)
+            .and_then(|slot| slot.take())
+            .is_some()
+    }
+
+    fn has_index(&self, index: usize) -> bool {
+        self.values.get(index).is_some_and(Option::is_some)
+    }
+}
+
+/// Stores entities and their components. Spawn an `Entity` with `spawn`,
+/// hang components off it with `add_component`, and read them back
+/// individually or in bulk with `get`/`get_mut`/`filter`.
+#[derive(Default)]
+pub struct Manager {
+    slots: Vec<Slot>,
+    free_indices: Vec<u32>,
+    columns: HashMap<TypeId, Box<dyn Column>>,
+}
+
+impl Manager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allocates a new entity, reusing a despawned slot's index (with its
+    /// generation bumped) when one is available.
+    pub fn spawn(&mut self) -> Entity {
+        if let Some(index) = self.free_indices.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.alive = true;
+            Entity {
+                index,
+                generation: slot.generation,
+            }
+        } else {
+            let index = self.slots.len() as u32;
+            self.slots.push(Slot {
+                generation: 0,
+                alive: true,
+            });
+            Entity {
+                index,
+                generation: 0,
+            }
+        }
+    }
+
+    /// Despawns an entity, dropping every component it owns and freeing its
+    /// slot for reuse under a new generation. No-op if `entity` is already
+    /// stale or despawned.
+    pub fn despawn(&mut self, entity: Entity) {
+        if !self.is_alive(entity) {
+            return;
+        }
+        for column in self.columns.values_mut() {
+            column.remove_index(entity.index as usize);
+        }
+        let slot = &mut self.slots[entity.index as usize];
+        slot.alive = false;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_indices.push(entity.index);
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.slots
+            .get(entity.index as usize)
+            .is_some_and(|slot| slot.alive && slot.generation == entity.generation)
+    }
+
+    fn column_mut<T: 'static>(&mut self) -> &mut TypedColumn<T> {
+        self.columns
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(TypedColumn::<T>::default()))
+            .as_any_mut()
+            .downcast_mut::<TypedColumn<T>>()
+            .expect("column type tag matches its TypeId")
+    }
+
+    fn column<T: 'static>(&self) -> Option<&TypedColumn<T>> {
+        self.columns
+            .get(&TypeId::of::<T>())?
+            .as_any()
+            .downcast_ref::<TypedColumn<T>>()
+    }
+
+    /// Attaches `component` to `entity`, overwriting whatever `T` it already
+    /// had. Returns a `Key<T>` for convenient lookup later.
+    pub fn add_component<T: 'static>(&mut self, entity: Entity, component: T) -> Key<T> {
+        let column = self.column_mut::<T>();
+        let index = entity.index as usize;
+        if column.values.len() <= index {
+            column.values.resize_with(index + 1, || None);
+        }
+        column.values[index] = Some(component);
+        Key {
+            entity,
+            _marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Removes `entity`'s `T` component, if it had one. Returns whether
+    /// anything was actually removed.
+    pub fn remove_component<T: 'static>(&mut self, entity: Entity) -> bool {
+        match self.columns.get_mut(&TypeId::of::<T>()) {
+            Some(column) => column.remove_index(entity.index as usize),
+            None => false,
+        }
+    }
+
+    pub fn get<T: 'static>(&self, entity: Entity) -> Option<&T> {
+        self.column::<T>()?.values.get(entity.index as usize)?.as_ref()
+    }
+
+    pub fn get_mut<T: 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.column_mut::<T>()
+            .values
+            .get_mut(entity.index as usize)?
+            .as_mut()
+    }
+
+    /// All currently-alive entities, in index order.
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.alive.then_some(Entity {
+                index: index as u32,
+                generation: slot.generation,
+            })
+        })
+    }
+}
+
+/// Iterates the entities that possess every component type `filter` was
+/// built for. Built via `Filter::new::<T>()` then widened with `.and::<U>()`
+/// so call sites can read `Filter::new::<Position>().and::<Velocity>()`
+/// left to right, matching the order the components are actually used in.
+pub struct Filter<'a> {
+    manager: &'a Manager,
+    type_ids: Vec<TypeId>,
+}
+
+impl<'a> Filter<'a> {
+    pub fn new<T: 'static>(manager: &'a Manager) -> Self {
+        Self {
+            manager,
+            type_ids: vec![TypeId::of::<T>()],
+        }
+    }
+
+    pub fn and<T: 'static>(mut self) -> Self {
+        self.type_ids.push(TypeId::of::<T>());
+        self
+    }
+
+    fn has_all(&self, entity: Entity) -> bool {
+        self.type_ids.iter().all(|type_id| {
+            self.manager
+                .columns
+                .get(type_id)
+                .is_some_and(|column| column.has_index(entity.index as usize))
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.manager.entities().filter(|entity| self.has_all(*entity))
+    }
+}
+
+/// A 2D position, ported from `Player::x`/`Player::y`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A 2D velocity, ported from `Player::vel_x`/`Player::vel_y`.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Velocity {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// Whether an entity is resting on the ground, ported from
+/// `Player::on_ground`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Grounded(pub bool);
+
+/// Applies gravity to every entity with a `Velocity` that isn't `Grounded`,
+/// mirroring `integrate`'s gravity term in the crate root.
+pub fn gravity_system(manager: &mut Manager, gravity: f32, dt: f32) {
+    let entities: Vec<Entity> = Filter::new::<Velocity>(manager).iter().collect();
+    for entity in entities {
+        let grounded = manager.get::<Grounded>(entity).copied().unwrap_or_default();
+        if grounded.0 {
+            continue;
+        }
+        if let Some(velocity) = manager.get_mut::<Velocity>(entity) {
+            velocity.y += gravity * dt;
+        }
+    }
+}
+
+/// Advances every entity with both `Position` and `Velocity` by `dt`,
+/// mirroring the integration step in the crate root's `integrate`.
+pub fn integrate_system(manager: &mut Manager, dt: f32) {
+    let entities: Vec<Entity> = Filter::new::<Position>(manager).and::<Velocity>().iter().collect();
+    for entity in entities {
+        let velocity = manager.get::<Velocity>(entity).copied().unwrap_or_default();
+        if let Some(position) = manager.get_mut::<Position>(entity) {
+            position.x += velocity.x * dt;
+            position.y += velocity.y * dt;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawn_despawn_reuses_index_with_new_generation() {
+        let mut manager = Manager::new();
+        let a = manager.spawn();
+        manager.despawn(a);
+        let b = manager.spawn();
+
+        assert_eq!(a.index, b.index);
+        assert_ne!(a.generation, b.generation);
+        assert!(!manager.is_alive(a));
+        assert!(manager.is_alive(b));
+    }
+
+    #[test]
+    fn add_get_remove_component_round_trip() {
+        let mut manager = Manager::new();
+        let entity = manager.spawn();
+        manager.add_component(entity, Position { x: 1.0, y: 2.0 });
+
+        assert_eq!(manager.get::<Position>(entity), Some(&Position { x: 1.0, y: 2.0 }));
+        assert!(manager.remove_component::<Position>(entity));
+        assert_eq!(manager.get::<Position>(entity), None);
+        assert!(!manager.remove_component::<Position>(entity));
+    }
+
+    #[test]
+    fn filter_only_returns_entities_with_every_requested_component() {
+        let mut manager = Manager::new();
+        let full = manager.spawn();
+        manager.add_component(full, Position { x: 0.0, y: 0.0 });
+        manager.add_component(full, Velocity::default());
+
+        let position_only = manager.spawn();
+        manager.add_component(position_only, Position { x: 0.0, y: 0.0 });
+
+        let matched: Vec<Entity> = Filter::new::<Position>(&manager).and::<Velocity>().iter().collect();
+        assert_eq!(matched, vec![full]);
+    }
+
+    #[test]
+    fn gravity_and_integrate_systems_mirror_player_physics() {
+        let mut manager = Manager::new();
+        let entity = manager.spawn();
+        manager.add_component(entity, Position { x: 0.0, y: 0.0 });
+        manager.add_component(entity, Velocity::default());
+        manager.add_component(entity, Grounded(false));
+
+        gravity_system(&mut manager, 9.8, 1.0);
+        integrate_system(&mut manager, 1.0);
+
+        let velocity = manager.get::<Velocity>(entity).unwrap();
+        let position = manager.get::<Position>(entity).unwrap();
+        assert_eq!(velocity.y, 9.8);
+        assert_eq!(position.y, 9.8);
+    }
+
+    #[test]
+    fn grounded_entity_is_unaffected_by_gravity() {
+        let mut manager = Manager::new();
+        let entity = manager.spawn();
+        manager.add_component(entity, Velocity::default());
+        manager.add_component(entity, Grounded(true));
+
+        gravity_system(&mut manager, 9.8, 1.0);
+
+        assert_eq!(manager.get::<Velocity>(entity).unwrap().y, 0.0);
+    }
+}
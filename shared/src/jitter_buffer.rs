@@ -0,0 +1,304 @@
+//! Adaptive jitter buffer for reordering and de-duplicating a stream of
+//! sequenced `InputState`s arriving out of order over UDP.
+//!
+//! The target playout delay is derived from measured interarrival jitter
+//! using the RFC 3550 §6.4.1 smoothing recurrence rather than a fixed
+//! constant, so a bursty link gets a deeper buffer automatically while a
+//! clean one sees minimal added latency.
+
+use crate::InputState;
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+/// Multiplier applied to the smoothed jitter estimate to get the playout
+/// delay. RFC 3550 doesn't mandate a value for this; 4 is the commonly
+/// cited rule of thumb for balancing added latency against how much jitter
+/// it absorbs.
+const PLAYOUT_DELAY_MULTIPLIER: f64 = 4.0;
+
+/// Tunable bounds for a `JitterBuffer`.
+#[derive(Debug, Clone, Copy)]
+pub struct JitterBufferConfig {
+    /// Floor applied to the jitter-derived playout delay.
+    pub min_delay: Duration,
+    /// Ceiling applied to the jitter-derived playout delay.
+    pub max_delay: Duration,
+    /// How long a missing sequence can stall the contiguous run before it's
+    /// skipped over so the stream doesn't stall indefinitely.
+    pub max_wait: Duration,
+}
+
+impl Default for JitterBufferConfig {
+    fn default() -> Self {
+        Self {
+            min_delay: Duration::from_millis(20),
+            max_delay: Duration::from_millis(200),
+            max_wait: Duration::from_millis(500),
+        }
+    }
+}
+
+struct Buffered {
+    input: InputState,
+    arrived_at: Instant,
+}
+
+/// Reorders and de-duplicates a stream of sequenced `InputState`s, releasing
+/// them in sequence order once each has sat in the buffer for the current
+/// adaptively-sized playout delay. Feed arrivals in with `insert`, then pull
+/// whatever's ready on each simulation tick with `release_ready`.
+pub struct JitterBuffer {
+    config: JitterBufferConfig,
+    buffered: BTreeMap<u32, Buffered>,
+    last_released: u32,
+
+    /// Smoothed interarrival jitter estimate in milliseconds (RFC 3550's
+    /// units are timestamp ticks; `InputState::timestamp` is already in ms,
+    /// so this estimate is too).
+    jitter_ms: f64,
+    prev_arrival: Option<Instant>,
+    prev_timestamp: Option<u64>,
+}
+
+impl JitterBuffer {
+    pub fn new(config: JitterBufferConfig) -> Self {
+        Self {
+            config,
+            buffered: BTreeMap::new(),
+            last_released: 0,
+            jitter_ms: 0.0,
+            prev_arrival: None,
+            prev_timestamp: None,
+        }
+    }
+
+    /// Feeds a newly-arrived input into the buffer. Every arrival updates
+    /// the jitter estimate; the input is then dropped if its sequence is
+    /// already released or already buffered (a late or duplicate delivery),
+    /// otherwise queued for `release_ready`.
+    pub fn insert(&mut self, input: InputState) {
+        self.observe_arrival(input.timestamp);
+
+        if input.sequence <= self.last_released || self.buffered.contains_key(&input.sequence) {
+            return;
+        }
+
+        self.buffered.insert(
+            input.sequence,
+            Buffered {
+                input,
+                arrived_at: Instant::now(),
+            },
+        );
+    }
+
+    /// RFC 3550 §6.4.1: `D = |(arrival_now - arrival_prev) - (timestamp -
+    /// timestamp_prev)|`, folded into the running estimate as `jitter +=
+    /// (D - jitter) / 16`.
+    fn observe_arrival(&mut self, timestamp: u64) {
+        let now = Instant::now();
+        if let (Some(prev_arrival), Some(prev_timestamp)) = (self.prev_arrival, self.prev_timestamp) {
+            let arrival_delta_ms = now.duration_since(prev_arrival).as_secs_f64() * 1000.0;
+            let timestamp_delta_ms = timestamp as f64 - prev_timestamp as f64;
+            let d = (arrival_delta_ms - timestamp_delta_ms).abs();
+            self.jitter_ms += (d - self.jitter_ms) / 16.0;
+        }
+        self.prev_arrival = Some(now);
+        self.prev_timestamp = Some(timestamp);
+    }
+
+    /// Target playout delay derived from the current jitter estimate,
+    /// clamped to the configured bounds.
+    pub fn playout_delay(&self) -> Duration {
+        let target_ms = (self.jitter_ms * PLAYOUT_DELAY_MULTIPLIER).max(0.0);
+        Duration::from_secs_f64(target_ms / 1000.0).clamp(self.config.min_delay, self.config.max_delay)
+    }
+
+    /// Current smoothed interarrival jitter estimate, in milliseconds.
+    pub fn jitter_ms(&self) -> f64 {
+        self.jitter_ms
+    }
+
+    /// Number of inputs currently held, waiting to be released.
+    pub fn depth(&self) -> usize {
+        self.buffered.len()
+    }
+
+    /// Highest sequence released so far.
+    pub fn last_released(&self) -> u32 {
+        self.last_released
+    }
+
+    /// Releases the contiguous run of inputs starting at `last_released + 1`
+    /// whose buffering time has exceeded the current playout delay, in
+    /// sequence order. If the next sequence is missing but an already-queued
+    /// later one has waited past `max_wait`, skips the gap so the stream
+    /// doesn't stall on a permanently lost packet.
+    pub fn release_ready(&mut self) -> Vec<InputState> {
+        let delay = self.playout_delay();
+        let mut released = Vec::new();
+
+        loop {
+            let next = self.last_released + 1;
+            match self.buffered.get(&next) {
+                Some(entry) if entry.arrived_at.elapsed() >= delay => {
+                    let entry = self.buffered.remove(&next).unwrap();
+                    self.last_released = next;
+                    released.push(entry.input);
+                }
+                _ if self.skip_stalled_gap(next) => continue,
+                _ => break,
+            }
+        }
+
+        released
+    }
+
+    /// If `next` itself isn't buffered but something later is, and that
+    /// later entry has waited past `max_wait`, advances `last_released` to
+    /// just before it so the missing sequence is skipped. Returns whether
+    /// a skip happened.
+    fn skip_stalled_gap(&mut self, next: u32) -> bool {
+        if self.buffered.contains_key(&next) {
+            return false;
+        }
+        let Some((&oldest_sequence, oldest)) = self.buffered.iter().next() else {
+            return false;
+        };
+        if oldest.arrived_at.elapsed() >= self.config.max_wait {
+            self.last_released = oldest_sequence.saturating_sub(1);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+impl Default for JitterBuffer {
+    fn default() -> Self {
+        Self::new(JitterBufferConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn input(sequence: u32, timestamp: u64) -> InputState {
+        InputState {
+            sequence,
+            timestamp,
+            left: false,
+            right: false,
+            jump: false,
+        }
+    }
+
+    fn fast_config() -> JitterBufferConfig {
+        JitterBufferConfig {
+            min_delay: Duration::from_millis(5),
+            max_delay: Duration::from_millis(50),
+            max_wait: Duration::from_millis(30),
+        }
+    }
+
+    #[test]
+    fn test_drops_late_input_at_or_before_last_released() {
+        let mut buffer = JitterBuffer::new(fast_config());
+        buffer.insert(input(1, 0));
+        thread::sleep(Duration::from_millis(10));
+        assert_eq!(buffer.release_ready().len(), 1);
+        assert_eq!(buffer.last_released(), 1);
+
+        buffer.insert(input(1, 0));
+        assert_eq!(buffer.depth(), 0);
+    }
+
+    #[test]
+    fn test_drops_duplicate_still_in_buffer() {
+        let mut buffer = JitterBuffer::new(fast_config());
+        buffer.insert(input(1, 0));
+        buffer.insert(input(1, 0));
+        assert_eq!(buffer.depth(), 1);
+    }
+
+    #[test]
+    fn test_releases_out_of_order_inputs_in_sequence_order() {
+        let mut buffer = JitterBuffer::new(fast_config());
+        buffer.insert(input(2, 10));
+        buffer.insert(input(1, 0));
+        buffer.insert(input(3, 20));
+
+        thread::sleep(Duration::from_millis(10));
+        let released = buffer.release_ready();
+
+        let sequences: Vec<u32> = released.iter().map(|i| i.sequence).collect();
+        assert_eq!(sequences, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_release_ready_holds_inputs_until_playout_delay_elapses() {
+        let mut buffer = JitterBuffer::new(JitterBufferConfig {
+            min_delay: Duration::from_millis(200),
+            max_delay: Duration::from_millis(200),
+            max_wait: Duration::from_secs(5),
+        });
+        buffer.insert(input(1, 0));
+
+        assert!(buffer.release_ready().is_empty());
+        assert_eq!(buffer.depth(), 1);
+    }
+
+    #[test]
+    fn test_skips_gap_once_max_wait_elapses() {
+        let mut buffer = JitterBuffer::new(fast_config());
+        buffer.insert(input(2, 10)); // sequence 1 never arrives
+
+        thread::sleep(Duration::from_millis(40)); // past max_wait (30ms)
+        let released = buffer.release_ready();
+
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].sequence, 2);
+        assert_eq!(buffer.last_released(), 2);
+    }
+
+    #[test]
+    fn test_jitter_estimate_converges_toward_consistent_delay_variance() {
+        let mut buffer = JitterBuffer::new(fast_config());
+        assert_eq!(buffer.jitter_ms(), 0.0);
+
+        // Arrivals consistently ~10ms apart in both wall-clock and
+        // timestamp terms: D should stay near zero, so jitter decays
+        // toward zero rather than drifting upward.
+        buffer.insert(input(1, 0));
+        for sequence in 2..=5 {
+            thread::sleep(Duration::from_millis(10));
+            buffer.insert(input(sequence, (sequence as u64 - 1) * 10));
+        }
+
+        assert!(buffer.jitter_ms() < 10.0);
+    }
+
+    #[test]
+    fn test_playout_delay_is_clamped_to_configured_bounds() {
+        let config = JitterBufferConfig {
+            min_delay: Duration::from_millis(15),
+            max_delay: Duration::from_millis(25),
+            max_wait: Duration::from_millis(100),
+        };
+        let mut buffer = JitterBuffer::new(config);
+
+        assert_eq!(buffer.playout_delay(), Duration::from_millis(15));
+
+        buffer.jitter_ms = 1000.0; // absurdly high, should clamp to max
+        assert_eq!(buffer.playout_delay(), Duration::from_millis(25));
+    }
+
+    #[test]
+    fn test_default_uses_default_config() {
+        let buffer = JitterBuffer::default();
+        assert_eq!(buffer.depth(), 0);
+        assert_eq!(buffer.last_released(), 0);
+    }
+}
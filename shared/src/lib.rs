@@ -1,7 +1,16 @@
 //! Shared data structures and utilities for networked multiplayer game
 
+pub mod byte_codec;
+pub mod ecs;
+pub mod jitter_buffer;
+pub mod netencode;
+pub mod replay_window;
+pub mod sealed_channel;
+
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
 
 // Physics constants
 pub const GRAVITY: f32 = 980.0; // pixels/second²
@@ -17,7 +26,41 @@ pub const PLAYER_SIZE: f32 = 32.0; // pixels
 pub enum Packet {
     // Client → Server
     Connect {
-        client_version: u32,
+        /// Range of protocol versions this client can speak. The server picks
+        /// the highest version in the overlap with its own supported range
+        /// (see `Connected::negotiated_version`), or rejects the connection if
+        /// there is no overlap at all.
+        min_version: u32,
+        max_version: u32,
+        /// Opaque token from a previous `Connected` response. If it matches a
+        /// session still in its reconnect grace period, the server rebinds that
+        /// session to this connection instead of creating a new one.
+        resume_token: Option<u64>,
+        /// How long (in seconds) this client would like the server to wait
+        /// before considering it dead, clamped server-side to a sane range.
+        /// Mobile/high-latency clients can request a longer grace period to
+        /// avoid spurious disconnects during a rough patch of network.
+        requested_timeout_secs: u32,
+        /// This client's ephemeral X25519 public key, present iff it's
+        /// opting into an authenticated session (server run with
+        /// `--authenticate`). Absent for plain LAN play. Despite the name,
+        /// completing this exchange buys tamper-evidence via a rolling MAC
+        /// (see `mac` below), not wire confidentiality — see `server::crypto`'s
+        /// module doc comment.
+        encrypt_public_key: Option<[u8; 32]>,
+        /// Sealed `ConnectionToken` bytes (see
+        /// `server::netcode_handshake::Netcode`) echoed back from an earlier
+        /// `Connected::connect_token`, proving this reconnect holds a session
+        /// the server itself issued rather than one it's guessing at. Only
+        /// checked when the server was started with connect-token
+        /// enforcement, and only on a `resume_token` reconnect — a client's
+        /// very first `Connect` has nothing to echo yet.
+        connect_token: Option<Vec<u8>>,
+        /// If set, this connection is a read-only observer: the server
+        /// replies with `Connected` as usual but never calls
+        /// `GameState::add_player` for it, so it doesn't consume a spawn
+        /// slot. It still receives every broadcast `GameState` snapshot.
+        spectate: bool,
     },
     Input {
         sequence: u32,
@@ -25,26 +68,291 @@ pub enum Packet {
         left: bool,
         right: bool,
         jump: bool,
+        /// Rolling Keccak-based authentication tag over this input, present
+        /// iff an authenticated session was established at connect time. The
+        /// server rejects the input (without queuing it) if this doesn't
+        /// verify against the session's ingress MAC state.
+        mac: Option<[u8; 32]>,
+        /// AEAD seal of this same `(sequence, timestamp, left, right, jump)`
+        /// tuple (bincode-encoded as an `InputState`) under
+        /// `Connected::client_to_server_key`, present iff this session has
+        /// one (see `shared::sealed_channel::seal_packet`). When set,
+        /// `timestamp`/`left`/`right`/`jump` above are left at their default
+        /// values and must be ignored — the server recovers the real values
+        /// by opening this with the session's key and `sequence` as nonce,
+        /// and drops the packet if that fails rather than trusting the
+        /// plaintext fields.
+        sealed: Option<Vec<u8>>,
+        /// Previously-sent inputs the client hasn't seen acknowledged yet,
+        /// bundled alongside this one so a single dropped datagram doesn't
+        /// lose an input until the next change (see
+        /// `client::input::InputManager`'s unacked ring buffer). The server
+        /// de-duplicates by sequence, so redundant copies are harmless.
+        /// Always empty for an authenticated session, since the MAC chain
+        /// above only covers the newest input.
+        ///
+        /// `netencode`-encoded (see `encode_redundant_inputs`/
+        /// `decode_redundant_inputs`) rather than riding on `Packet`'s own
+        /// bincode framing like every other field here: this is exactly the
+        /// "input-history" payload `shared::netencode`'s module doc cites as
+        /// the motivating case for a self-describing format, since a future
+        /// field added to `InputState` can be read here by an older decoder
+        /// without a wire break, where a plain `bincode`-derived
+        /// `Vec<InputState>` would have none of that.
+        redundant: Vec<u8>,
+        /// The tick of the most recent `Packet::GameState`/`GameStateDelta`
+        /// this client has fully applied, echoed back so the server knows
+        /// which snapshot it can safely diff future `GameStateDelta`s
+        /// against (see `Client::acknowledge_snapshot`). `0` means no
+        /// snapshot has been applied yet, e.g. right after connecting.
+        acked_snapshot_tick: u32,
     },
     Disconnect,
+    /// Requests the server flip this client's `Player::flying` flag. Only
+    /// takes effect if the client's current `Gamemode` permits flying
+    /// (anything but `Gamemode::Survival`); the server's authoritative
+    /// answer comes back as `SetGamemode`.
+    ToggleFly,
 
     // Server → Client
     Connected {
         client_id: u32,
+        /// Present the matching value in a future `Connect` to resume this
+        /// session (same player, same input sequence counter) after a drop.
+        resume_token: u64,
+        /// The single protocol version the server selected from the overlap
+        /// with the client's advertised range. Downstream serialization
+        /// should branch on this rather than assume a fixed wire format.
+        negotiated_version: u32,
+        /// The server's ephemeral X25519 public key, present iff the client
+        /// sent one in `Connect` and the handshake completed.
+        encrypt_public_key: Option<[u8; 32]>,
+        /// The idle timeout actually negotiated for this session: the
+        /// minimum of the client's `Connect::requested_timeout_secs` and the
+        /// server's own preference, clamped to the server's bounds. The
+        /// client derives its keepalive cadence and dead-connection
+        /// threshold from this rather than its own request, since the
+        /// server may have asked for something shorter.
+        negotiated_timeout_secs: u32,
+        /// Sealed `ConnectionToken` for this session (see
+        /// `server::netcode_handshake::Netcode::issue_connection_token`),
+        /// present iff the server has connect-token enforcement enabled.
+        /// Store it and echo it back as `Connect::connect_token` on any
+        /// future reconnect.
+        connect_token: Option<Vec<u8>>,
+        /// This session's half of the `sealed_channel` AEAD key pair backing
+        /// `Input::sealed`, present under the same condition as
+        /// `connect_token` above. Sent in the clear alongside it: this
+        /// server plays both matchmaking and game-server roles with no
+        /// separate side channel to deliver keys out of band, so this
+        /// protects the ongoing input stream against passive sniffing and
+        /// after-the-fact tampering rather than against an attacker who
+        /// also captures this very `Connected` packet.
+        client_to_server_key: Option<[u8; 32]>,
+        /// Reserved for sealing a future server→client direction (e.g.
+        /// `GameState`); unused today since only `Input` is sealed.
+        server_to_client_key: Option<[u8; 32]>,
     },
     GameState {
         tick: u32,
         timestamp: u64,
         last_processed_input: HashMap<u32, u32>,
+        /// Server wall-clock receive time (UNIX ms) of each client's
+        /// `last_processed_input`, keyed the same way. This is the "T2" of a
+        /// classic NTP-style four-timestamp exchange: paired with the
+        /// client's own send time for that sequence (its "T1") and this
+        /// packet's `timestamp` (its "T3"), it lets the client solve for
+        /// clock offset and round-trip delay directly instead of estimating
+        /// them from one-sided heuristics. Absent an entry for a client that
+        /// hasn't had an input processed yet.
+        input_receive_ms: HashMap<u32, u64>,
         players: Vec<Player>,
+        /// `compute_checksum(&players)` as computed by the authoritative
+        /// server for this tick. A client running identical prediction
+        /// should get the same value when it recomputes over its own
+        /// `players` for the matching tick; a mismatch means the
+        /// simulations have desynced.
+        checksum: u32,
+    },
+    /// A bandwidth-saving alternative to a full `GameState` snapshot: only
+    /// the players that actually changed since `baseline_tick`, which the
+    /// receiving client must already have applied (see
+    /// `Client::acknowledge_snapshot`). The server falls back to a full
+    /// `GameState` (a keyframe) whenever a client has no usable baseline —
+    /// just connected, or its last ack fell outside the server's snapshot
+    /// history — so this variant is never the *only* way state arrives.
+    GameStateDelta {
+        tick: u32,
+        timestamp: u64,
+        baseline_tick: u32,
+        last_processed_input: HashMap<u32, u32>,
+        input_receive_ms: HashMap<u32, u64>,
+        /// Players whose fields differ from `baseline_tick`, or who didn't
+        /// exist at `baseline_tick` at all. The client applies these on top
+        /// of its own copy of the baseline rather than replacing its state.
+        changed_players: Vec<Player>,
+        /// Player ids present at `baseline_tick` but gone as of `tick`, so
+        /// the client knows to despawn them instead of just not hearing
+        /// about them.
+        removed_player_ids: Vec<u32>,
+        /// Same meaning as `GameState::checksum`, computed over the full
+        /// (not delta'd) player list for `tick`.
+        checksum: u32,
     },
     Disconnected {
         reason: String,
     },
+    /// Grants (or confirms) `client_id`'s gamemode and fly privilege, sent in
+    /// reply to `ToggleFly` or whenever the server changes a player's mode.
+    /// `can_fly` is derived from `mode` (anything but `Gamemode::Survival`)
+    /// rather than trusted from the client, since only the server's `Player`
+    /// is authoritative.
+    SetGamemode {
+        client_id: u32,
+        mode: Gamemode,
+        can_fly: bool,
+    },
+
+    // Server → Master
+    /// Periodic presence announcement a `Server` sends to a configured master
+    /// address so it can be found by browsing clients.
+    Heartbeat {
+        name: String,
+        map: String,
+        current_players: u32,
+        max_players: u32,
+        version: u32,
+    },
+
+    // Client → Master
+    QueryServers,
+    // Master → Client
+    ServerList {
+        entries: Vec<ServerListEntry>,
+    },
+
+    // Bidirectional, used both for master discovery RTT and plain liveness
+    // checks, and reused by `client::nat_traversal::HolePunch` as the
+    // hole-punch probe/reply once both sides have exchanged candidates below
+    // — and, after confirmation, as the keepalive heartbeat that keeps the
+    // punched NAT mapping from expiring.
+    Ping {
+        nonce: u64,
+    },
+    Pong {
+        nonce: u64,
+    },
+
+    // Bidirectional, NAT traversal. A rendezvous point (e.g. the game
+    // server) relays each side's `EndpointReport` to the other as
+    // `PeerEndpoints`, after which both sides simultaneously `Ping` every
+    // candidate and keep whichever replies first (see
+    // `client::nat_traversal`).
+    /// Self-reported candidate address and NAT mapping timeout, sent to a
+    /// rendezvous point to be relayed to a peer.
+    EndpointReport {
+        local_addr: SocketAddr,
+        /// How long this client's NAT keeps a UDP mapping open with no
+        /// traffic, if known (e.g. from a prior traversal attempt). `None`
+        /// until it's been measured.
+        nat_timeout_secs: Option<u32>,
+    },
+    /// A peer's candidate addresses to probe, relayed by a rendezvous point.
+    PeerEndpoints {
+        candidates: Vec<SocketAddr>,
+        peer_nat_timeout_secs: Option<u32>,
+    },
+
+    // Bidirectional, used by `ReliableChannel` for packets that need explicit
+    // delivery guarantees outside the unreliable fast path (e.g. spawn
+    // announcements, disconnect reasons).
+    /// Acknowledges every sequence up to and including `cumulative_seq`.
+    Ack {
+        cumulative_seq: u32,
+    },
+    /// Reports gaps in the sequences received so far. `loss_list` is SRT-style
+    /// run-length compressed: see `ReliableChannel`'s codec for the format.
+    Nak {
+        loss_list: Vec<u32>,
+    },
 }
 
-/// Player entity with position, velocity, and state
+impl Packet {
+    /// Encoded size of this packet on the wire, for bandwidth accounting
+    /// (see `server::client_manager::BandwidthLimiter`). Falls back to `0` on
+    /// a serialization error, which would mean this packet can't be sent at
+    /// all regardless of bandwidth.
+    pub fn wire_size(&self) -> u64 {
+        bincode::serialized_size(self).unwrap_or(0)
+    }
+}
+
+/// One entry in a master server's response to `Packet::QueryServers`.
 #[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServerListEntry {
+    pub addr: SocketAddr,
+    pub name: String,
+    pub map: String,
+    pub current_players: u32,
+    pub max_players: u32,
+}
+
+/// A player's gameplay mode. `Survival` is the default: grounded, gravity
+/// applies, and it collides with everything. `Creative` additionally grants
+/// `flying` (no gravity). `Spectator` flies and, unlike `Creative`, skips
+/// collision resolution entirely (see `resolve_all_collisions`) so it can
+/// pass through players and geometry like a noclip camera.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Gamemode {
+    #[default]
+    Survival,
+    Creative,
+    Spectator,
+}
+
+/// A collision shape for a `Player`. `Circle` is the default and matches
+/// what `resolve_collision`'s separation math has always assumed (players
+/// pushed apart along the line between their centers, as if both were discs
+/// of radius `PLAYER_SIZE / 2`); `Aabb` and `Capsule` let round projectiles,
+/// boxy pickups, or tall capsule characters share the same collision code
+/// with correct closest-point separation instead of a one-size-fits-all
+/// circle push. See `collider_contact`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Collider {
+    Aabb { w: f32, h: f32 },
+    Circle { r: f32 },
+    Capsule { r: f32, half_height: f32 },
+}
+
+impl Default for Collider {
+    fn default() -> Self {
+        Collider::Circle { r: PLAYER_SIZE / 2.0 }
+    }
+}
+
+impl Collider {
+    /// Half-extents of the axis-aligned box that bounds this collider,
+    /// used for `check_collision`'s broad-phase overlap test.
+    fn half_extents(self) -> (f32, f32) {
+        match self {
+            Collider::Aabb { w, h } => (w / 2.0, h / 2.0),
+            Collider::Circle { r } => (r, r),
+            Collider::Capsule { r, half_height } => (r, r + half_height),
+        }
+    }
+}
+
+/// Every layer bit set: the default `layer`/`mask` for a `Player` that
+/// doesn't opt into selective collision, so it keeps colliding with
+/// everything. Used as the `#[serde(default)]` for both fields, so a
+/// `Packet::GameState` payload serialized before they existed still
+/// deserializes, into a player that collides exactly as it used to.
+fn all_layers() -> u32 {
+    u32::MAX
+}
+
+/// Player entity with position, velocity, and state
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Player {
     pub id: u32,
     pub x: f32,
@@ -52,6 +360,32 @@ pub struct Player {
     pub vel_x: f32,
     pub vel_y: f32,
     pub on_ground: bool,
+    /// Bit(s) this player occupies for collision-mask filtering. See
+    /// `check_collision`.
+    #[serde(default = "all_layers")]
+    pub layer: u32,
+    /// Which layers this player collides with. Lets e.g. a ghost/spectator
+    /// player, a projectile, or a team-only entity opt out of colliding
+    /// with everything without forking the collision code. See
+    /// `check_collision`.
+    #[serde(default = "all_layers")]
+    pub mask: u32,
+    /// This player's gameplay mode. See `Gamemode`.
+    #[serde(default)]
+    pub gamemode: Gamemode,
+    /// Whether this player currently ignores gravity. Only meaningful (and
+    /// only settable by the server) while `gamemode` isn't `Survival`; see
+    /// `Packet::ToggleFly`/`Packet::SetGamemode`.
+    #[serde(default)]
+    pub flying: bool,
+    /// This player's collision shape. See `Collider`.
+    #[serde(default)]
+    pub collider: Collider,
+    /// Display name shown above this player's cube (see
+    /// `Renderer::draw_player_id`). Empty for a player nobody has named yet,
+    /// in which case the renderer falls back to a generated label.
+    #[serde(default)]
+    pub username: String,
 }
 
 impl Player {
@@ -63,9 +397,22 @@ impl Player {
             vel_x: 0.0,
             vel_y: 0.0,
             on_ground: true,
+            layer: all_layers(),
+            mask: all_layers(),
+            gamemode: Gamemode::default(),
+            flying: false,
+            collider: Collider::default(),
+            username: String::new(),
         }
     }
 
+    /// Sets this player's display name. Chainable so callers can write
+    /// `Player::new(id, x, y).with_username(name)`.
+    pub fn with_username(mut self, username: impl Into<String>) -> Self {
+        self.username = username.into();
+        self
+    }
+
     /// Returns (left, top, right, bottom) coordinates
     pub fn get_bounds(&self) -> (f32, f32, f32, f32) {
         (self.x, self.y, self.x + PLAYER_SIZE, self.y + PLAYER_SIZE)
@@ -77,13 +424,166 @@ impl Player {
     }
 }
 
-/// AABB collision detection between two players
+/// AABB collision detection between two players, using each player's
+/// `Collider` to size its broad-phase bounding box (a `Circle { r }` bounds
+/// to the same `2r`-wide square a `Player` always used to, so the default
+/// collider keeps this behaving exactly as it did before `Collider` existed).
 pub fn check_collision(player1: &Player, player2: &Player) -> bool {
-    let (x1, y1, x2, y2) = player1.get_bounds();
-    let (x3, y3, x4, y4) = player2.get_bounds();
+    if player1.mask & player2.layer == 0 {
+        return false;
+    }
+
+    let (cx1, cy1) = player1.center();
+    let (cx2, cy2) = player2.center();
+    let (hw1, hh1) = player1.collider.half_extents();
+    let (hw2, hh2) = player2.collider.half_extents();
 
     // No collision if any edge of one box is beyond the corresponding edge of the other
-    !(x2 <= x3 || x4 <= x1 || y2 <= y3 || y4 <= y1)
+    !(cx1 + hw1 <= cx2 - hw2 || cx2 + hw2 <= cx1 - hw1 || cy1 + hh1 <= cy2 - hh2 || cy2 + hh2 <= cy1 - hh1)
+}
+
+/// Nearest point on an axis-aligned box (given by its center and half
+/// extents) to `point`. If `point` is inside the box, clamping is a no-op
+/// and the "nearest point" comes back as `point` itself; callers that need
+/// a meaningful push-out in that case handle it separately (see
+/// `circle_vs_aabb_contact`).
+fn closest_point_on_aabb(point: (f32, f32), center: (f32, f32), half_w: f32, half_h: f32) -> (f32, f32) {
+    (
+        center.0 + (point.0 - center.0).clamp(-half_w, half_w),
+        center.1 + (point.1 - center.1).clamp(-half_h, half_h),
+    )
+}
+
+/// Circle-vs-box contact via the closest-point technique: clamp the
+/// box-relative circle center into the box's half extents to get the
+/// nearest point on the box, then separate along the direction from that
+/// point to the circle center. Returns the normal pointing from the box
+/// towards the circle, and the penetration depth, or `None` if they don't
+/// overlap.
+fn circle_vs_aabb_contact(
+    circle_center: (f32, f32),
+    radius: f32,
+    box_center: (f32, f32),
+    half_w: f32,
+    half_h: f32,
+) -> Option<(f32, f32, f32)> {
+    let closest = closest_point_on_aabb(circle_center, box_center, half_w, half_h);
+    let dx = circle_center.0 - closest.0;
+    let dy = circle_center.1 - closest.1;
+    let distance_sq = dx * dx + dy * dy;
+
+    if distance_sq > 1e-6 {
+        let distance = distance_sq.sqrt();
+        if distance >= radius {
+            return None;
+        }
+        return Some((dx / distance, dy / distance, radius - distance));
+    }
+
+    // The circle's center is inside the box, so there's no closest-point
+    // direction to separate along; push out along whichever axis has the
+    // least penetration to the nearest edge instead.
+    let local_x = circle_center.0 - box_center.0;
+    let local_y = circle_center.1 - box_center.1;
+    let penetration_x = half_w - local_x.abs();
+    let penetration_y = half_h - local_y.abs();
+
+    if penetration_x < penetration_y {
+        Some((local_x.signum(), 0.0, penetration_x + radius))
+    } else {
+        Some((0.0, local_y.signum(), penetration_y + radius))
+    }
+}
+
+/// Box-vs-box contact: the usual overlap-on-each-axis test, resolved along
+/// whichever axis has the shallower penetration. Normal points from `a`
+/// towards `b`.
+fn aabb_vs_aabb_contact(
+    a_center: (f32, f32),
+    a_half: (f32, f32),
+    b_center: (f32, f32),
+    b_half: (f32, f32),
+) -> Option<(f32, f32, f32)> {
+    let dx = b_center.0 - a_center.0;
+    let dy = b_center.1 - a_center.1;
+    let overlap_x = a_half.0 + b_half.0 - dx.abs();
+    let overlap_y = a_half.1 + b_half.1 - dy.abs();
+
+    if overlap_x <= 0.0 || overlap_y <= 0.0 {
+        return None;
+    }
+
+    if overlap_x < overlap_y {
+        Some((dx.signum(), 0.0, overlap_x))
+    } else {
+        Some((0.0, dy.signum(), overlap_y))
+    }
+}
+
+/// Circle-vs-circle contact, matching the push-apart math `resolve_collision`
+/// has always used: normal along the line between centers, penetration is
+/// however much the combined radii exceed the distance between them. Falls
+/// back to an arbitrary horizontal normal when the centers coincide, since
+/// there's no direction to separate along otherwise.
+fn circle_vs_circle_contact(
+    a_center: (f32, f32),
+    a_r: f32,
+    b_center: (f32, f32),
+    b_r: f32,
+) -> Option<(f32, f32, f32)> {
+    let dx = b_center.0 - a_center.0;
+    let dy = b_center.1 - a_center.1;
+    let distance = (dx * dx + dy * dy).sqrt();
+    let combined = a_r + b_r;
+
+    if distance < 0.001 {
+        return Some((1.0, 0.0, combined));
+    }
+    if distance >= combined {
+        return None;
+    }
+    Some((dx / distance, dy / distance, combined - distance))
+}
+
+/// Recenters a capsule on the point of its central vertical segment closest
+/// to `target`, so it can be treated as a plain circle there. Lets every
+/// capsule pairing in `collider_contact` fall through to one of the base
+/// shape cases instead of needing a bespoke routine per pairing.
+fn capsule_closest_circle(capsule_center: (f32, f32), half_height: f32, target: (f32, f32)) -> (f32, f32) {
+    (capsule_center.0, capsule_center.1 + (target.1 - capsule_center.1).clamp(-half_height, half_height))
+}
+
+/// Resolves two colliders' closest-approach contact, dispatching on their
+/// concrete shapes. Returns the separating normal (pointing from `a`
+/// towards `b`) and penetration depth, or `None` if they don't overlap.
+pub fn collider_contact(
+    a_center: (f32, f32),
+    a: Collider,
+    b_center: (f32, f32),
+    b: Collider,
+) -> Option<(f32, f32, f32)> {
+    match (a, b) {
+        (Collider::Aabb { w: aw, h: ah }, Collider::Aabb { w: bw, h: bh }) => {
+            aabb_vs_aabb_contact(a_center, (aw / 2.0, ah / 2.0), b_center, (bw / 2.0, bh / 2.0))
+        }
+        (Collider::Circle { r: ar }, Collider::Circle { r: br }) => {
+            circle_vs_circle_contact(a_center, ar, b_center, br)
+        }
+        (Collider::Circle { r }, Collider::Aabb { w, h }) => {
+            circle_vs_aabb_contact(a_center, r, b_center, w / 2.0, h / 2.0)
+        }
+        (Collider::Aabb { w, h }, Collider::Circle { r }) => {
+            circle_vs_aabb_contact(b_center, r, a_center, w / 2.0, h / 2.0).map(|(nx, ny, pen)| (-nx, -ny, pen))
+        }
+        (Collider::Capsule { r, half_height }, other) => {
+            let recentered = capsule_closest_circle(a_center, half_height, b_center);
+            collider_contact(recentered, Collider::Circle { r }, b_center, other)
+        }
+        (other, Collider::Capsule { r, half_height }) => {
+            let recentered = capsule_closest_circle(b_center, half_height, a_center);
+            collider_contact(a_center, other, recentered, Collider::Circle { r })
+        }
+    }
 }
 
 /// Resolves collision between two players using physics-based separation and momentum exchange
@@ -92,53 +592,302 @@ pub fn resolve_collision(player1: &mut Player, player2: &mut Player) {
         return;
     }
 
-    let (cx1, cy1) = player1.center();
-    let (cx2, cy2) = player2.center();
+    let Some((nx, ny, penetration)) = collider_contact(
+        player1.center(),
+        player1.collider,
+        player2.center(),
+        player2.collider,
+    ) else {
+        return;
+    };
+
+    let separation = penetration / 2.0;
+    player1.x -= nx * separation;
+    player1.y -= ny * separation;
+    player2.x += nx * separation;
+    player2.y += ny * separation;
+
+    // Clamp positions to world boundaries
+    player1.x = player1.x.clamp(0.0, WORLD_WIDTH - PLAYER_SIZE);
+    player1.y = player1.y.clamp(0.0, FLOOR_Y - PLAYER_SIZE);
+    player2.x = player2.x.clamp(0.0, WORLD_WIDTH - PLAYER_SIZE);
+    player2.y = player2.y.clamp(0.0, FLOOR_Y - PLAYER_SIZE);
+
+    // Exchange velocities with damping for realistic collision response
+    let temp_vx = player1.vel_x;
+    let temp_vy = player1.vel_y;
+    player1.vel_x = player2.vel_x * 0.8;
+    player1.vel_y = player2.vel_y * 0.8;
+    player2.vel_x = temp_vx * 0.8;
+    player2.vel_y = temp_vy * 0.8;
+}
 
-    // Calculate direction vector from player1 to player2
-    let dx = cx2 - cx1;
-    let dy = cy2 - cy1;
-    let distance = (dx * dx + dy * dy).sqrt();
+/// Default cell size for `SpatialGrid`, roughly 2x the largest collidable
+/// radius (`PLAYER_SIZE / 2`) so a typical entity overlaps only a handful of
+/// cells.
+pub const DEFAULT_GRID_CELL_SIZE: f32 = PLAYER_SIZE;
+
+/// Uniform-grid broad-phase for many-entity collision. Resolving N entities
+/// with `check_collision`/`resolve_collision` alone is O(N²); this narrows
+/// the field to candidate pairs that could plausibly overlap, by hashing
+/// each entity's AABB onto the integer cell coordinates it covers.
+#[derive(Debug, Clone)]
+pub struct SpatialGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<u32>>,
+}
 
-    // Handle edge case where players are at exactly the same position
-    if distance < 0.001 {
-        player1.x -= PLAYER_SIZE / 2.0;
-        player2.x += PLAYER_SIZE / 2.0;
-        return;
+impl SpatialGrid {
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell_coord(&self, value: f32) -> i32 {
+        (value / self.cell_size).floor() as i32
+    }
+
+    /// Maps `player`'s AABB (`get_bounds`) onto every cell it overlaps,
+    /// storing its id in each.
+    pub fn insert(&mut self, player: &Player) {
+        let (left, top, right, bottom) = player.get_bounds();
+        let (min_cx, min_cy) = (self.cell_coord(left), self.cell_coord(top));
+        let (max_cx, max_cy) = (self.cell_coord(right), self.cell_coord(bottom));
+
+        for cx in min_cx..=max_cx {
+            for cy in min_cy..=max_cy {
+                self.cells.entry((cx, cy)).or_default().push(player.id);
+            }
+        }
+    }
+
+    /// Clears the grid and re-inserts every entity in `players`.
+    pub fn rebuild(&mut self, players: &[Player]) {
+        self.clear();
+        for player in players {
+            self.insert(player);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Offsets of the forward neighbor cells (E, SE, S, SW) checked alongside
+    /// an entity's own cell, so each pair of adjacent cells is visited from
+    /// exactly one side.
+    const FORWARD_NEIGHBORS: [(i32, i32); 4] = [(1, 0), (1, 1), (0, 1), (-1, 1)];
+
+    /// Candidate collision pairs `(id_a, id_b)` with `id_a < id_b`, for the
+    /// narrow phase (`check_collision`/`resolve_collision`) to confirm. An
+    /// entity whose AABB spans more than one cell can otherwise surface the
+    /// same pair from more than one cell, so the result is sorted and
+    /// deduplicated before returning.
+    pub fn candidate_pairs(&self) -> Vec<(u32, u32)> {
+        let mut pairs = Vec::new();
+
+        for (&(cx, cy), ids) in &self.cells {
+            for i in 0..ids.len() {
+                for &other in &ids[i + 1..] {
+                    pairs.push(Self::ordered(ids[i], other));
+                }
+            }
+
+            for (dx, dy) in Self::FORWARD_NEIGHBORS {
+                let Some(neighbor_ids) = self.cells.get(&(cx + dx, cy + dy)) else {
+                    continue;
+                };
+                for &a in ids {
+                    for &b in neighbor_ids {
+                        pairs.push(Self::ordered(a, b));
+                    }
+                }
+            }
+        }
+
+        pairs.sort_unstable();
+        pairs.dedup();
+        pairs
     }
 
-    // Normalize direction vector
-    let nx = dx / distance;
-    let ny = dy / distance;
+    fn ordered(a: u32, b: u32) -> (u32, u32) {
+        if a < b {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
 
-    // Calculate overlap and separate players
-    let overlap = PLAYER_SIZE - distance;
+    /// Partitions this grid's cells into 12 color classes via `(cx mod 4, cy
+    /// mod 3)`, the coloring `step_all_parallel` uses to resolve collisions
+    /// in multiple cells at once. Cells sharing a color are always at least
+    /// 4 cells apart in x or 3 cells apart in y, which leaves at least one
+    /// cell between their batches' cells (each owner plus its
+    /// `FORWARD_NEIGHBORS`). A player's AABB covers at most two adjacent
+    /// cells per axis at `DEFAULT_GRID_CELL_SIZE`, so it can't reach into
+    /// two batches of one color — the batches within one color class can
+    /// resolve concurrently without ever touching the same entity twice.
+    /// Each inner `Vec<u32>` is one owner cell's batch: its
+    /// own ids plus its forward neighbors' ids, the same membership
+    /// `candidate_pairs` considers for that cell.
+    pub fn color_classes(&self) -> Vec<Vec<Vec<u32>>> {
+        let mut classes: Vec<Vec<Vec<u32>>> = vec![Vec::new(); 12];
+
+        for (&(cx, cy), ids) in &self.cells {
+            let color = (cx.rem_euclid(4) * 3 + cy.rem_euclid(3)) as usize;
+
+            let mut batch = ids.clone();
+            for (dx, dy) in Self::FORWARD_NEIGHBORS {
+                if let Some(neighbor_ids) = self.cells.get(&(cx + dx, cy + dy)) {
+                    batch.extend_from_slice(neighbor_ids);
+                }
+            }
+            classes[color].push(batch);
+        }
 
-    if overlap > 0.0 {
-        let separation = overlap / 2.0;
-        player1.x -= nx * separation;
-        player1.y -= ny * separation;
-        player2.x += nx * separation;
-        player2.y += ny * separation;
+        classes
+    }
+}
 
-        // Clamp positions to world boundaries
-        player1.x = player1.x.clamp(0.0, WORLD_WIDTH - PLAYER_SIZE);
-        player1.y = player1.y.clamp(0.0, FLOOR_Y - PLAYER_SIZE);
-        player2.x = player2.x.clamp(0.0, WORLD_WIDTH - PLAYER_SIZE);
-        player2.y = player2.y.clamp(0.0, FLOOR_Y - PLAYER_SIZE);
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        Self::new(DEFAULT_GRID_CELL_SIZE)
+    }
+}
+
+/// Below this many entities, `step_all` takes the plain serial path; the
+/// rayon thread-pool dispatch and grid-coloring overhead only pay for
+/// themselves once there's enough work to spread across cores.
+pub const PARALLEL_STEP_THRESHOLD: usize = 500;
+
+/// Integrates gravity/position and resolves all pairwise collisions for one
+/// physics tick. Delegates to `step_all_serial` or `step_all_parallel`
+/// depending on `players.len()` versus `PARALLEL_STEP_THRESHOLD`.
+pub fn step_all(players: &mut [Player], dt: f32) {
+    if players.len() < PARALLEL_STEP_THRESHOLD {
+        step_all_serial(players, dt);
+    } else {
+        step_all_parallel(players, dt);
+    }
+}
+
+fn integrate(player: &mut Player, dt: f32) {
+    if !player.on_ground && !player.flying {
+        player.vel_y += GRAVITY * dt;
+    }
+    player.x += player.vel_x * dt;
+    player.y += player.vel_y * dt;
+}
+
+/// Resolves every pair `SpatialGrid::candidate_pairs` confirms as colliding,
+/// using `split_at_mut` to borrow both participants mutably from the same
+/// slice without cloning.
+fn resolve_all_collisions(players: &mut [Player]) {
+    let mut grid = SpatialGrid::default();
+    grid.rebuild(players);
+
+    let index_of: HashMap<u32, usize> = players.iter().enumerate().map(|(i, p)| (p.id, i)).collect();
+
+    for (id_a, id_b) in grid.candidate_pairs() {
+        let (Some(&i), Some(&j)) = (index_of.get(&id_a), index_of.get(&id_b)) else {
+            continue;
+        };
+        if players[i].gamemode == Gamemode::Spectator || players[j].gamemode == Gamemode::Spectator {
+            continue;
+        }
+        let (lo, hi) = if i < j { (i, j) } else { (j, i) };
+        let (left, right) = players.split_at_mut(hi);
+        resolve_collision(&mut left[lo], &mut right[0]);
+    }
+}
 
-        // Exchange velocities with damping for realistic collision response
-        let temp_vx = player1.vel_x;
-        let temp_vy = player1.vel_y;
-        player1.vel_x = player2.vel_x * 0.8;
-        player1.vel_y = player2.vel_y * 0.8;
-        player2.vel_x = temp_vx * 0.8;
-        player2.vel_y = temp_vy * 0.8;
+/// Serial integration + collision resolution. Used directly below
+/// `PARALLEL_STEP_THRESHOLD`, and as the per-batch fallback inside
+/// `step_all_parallel` once entities have been partitioned into disjoint
+/// cell batches.
+pub fn step_all_serial(players: &mut [Player], dt: f32) {
+    for player in players.iter_mut() {
+        integrate(player, dt);
     }
+    resolve_all_collisions(players);
+}
+
+/// Parallel integration plus grid-colored parallel collision resolution.
+/// Integration has no shared mutable state between entities, so it simply
+/// runs across all cores via `par_iter_mut`. Collision resolution instead
+/// goes through `SpatialGrid::color_classes`: each color class's batches are
+/// resolved concurrently (cloning each batch's entities out, running the
+/// ordinary serial `resolve_all_collisions` fallback on the clone, then
+/// writing the results back by id), and color classes themselves run one
+/// after another since a cell's neighbors usually land in a different class.
+pub fn step_all_parallel(players: &mut [Player], dt: f32) {
+    players.par_iter_mut().for_each(|player| integrate(player, dt));
+
+    let mut grid = SpatialGrid::default();
+    grid.rebuild(players);
+
+    let index_of: HashMap<u32, usize> = players.iter().enumerate().map(|(i, p)| (p.id, i)).collect();
+
+    for color in grid.color_classes() {
+        let updates: Vec<Player> = color
+            .par_iter()
+            .flat_map(|ids| {
+                let mut batch: Vec<Player> = ids
+                    .iter()
+                    .filter_map(|id| index_of.get(id).map(|&i| players[i].clone()))
+                    .collect();
+                resolve_all_collisions(&mut batch);
+                batch
+            })
+            .collect();
+
+        for updated in updates {
+            if let Some(&i) = index_of.get(&updated.id) {
+                players[i] = updated;
+            }
+        }
+    }
+}
+
+/// Deterministic checksum over a tick's player states, for verifying that
+/// client prediction and server authority ran the identical simulation.
+/// Order-independent (players are sorted by id before hashing) and
+/// quantizes each float to a fixed-point integer first, so two runs that
+/// agree mathematically but differ in the last bit of float rounding don't
+/// spuriously disagree.
+pub fn compute_checksum(players: &[Player]) -> u32 {
+    fn quantize(value: f32) -> i32 {
+        (value * 1000.0) as i32
+    }
+
+    fn fold(hash: &mut u32, bytes: &[u8]) {
+        const FNV_PRIME: u32 = 0x0100_0193;
+        for byte in bytes {
+            *hash ^= *byte as u32;
+            *hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    let mut sorted: Vec<&Player> = players.iter().collect();
+    sorted.sort_by_key(|player| player.id);
+
+    const FNV_OFFSET_BASIS: u32 = 0x811c_9dc5;
+    let mut hash = FNV_OFFSET_BASIS;
+    for player in sorted {
+        fold(&mut hash, &player.id.to_le_bytes());
+        fold(&mut hash, &quantize(player.x).to_le_bytes());
+        fold(&mut hash, &quantize(player.y).to_le_bytes());
+        fold(&mut hash, &quantize(player.vel_x).to_le_bytes());
+        fold(&mut hash, &quantize(player.vel_y).to_le_bytes());
+        fold(&mut hash, &[player.on_ground as u8]);
+    }
+    hash
 }
 
 /// Input state for deterministic networked gameplay
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct InputState {
     pub sequence: u32,  // For reliable ordering
     pub timestamp: u64, // For lag compensation
@@ -147,6 +896,264 @@ pub struct InputState {
     pub jump: bool,
 }
 
+impl InputState {
+    fn to_netencode_value(&self) -> netencode::Value {
+        netencode::Value::Record(vec![
+            ("sequence".to_string(), netencode::Value::Uint(self.sequence as u64)),
+            ("timestamp".to_string(), netencode::Value::Uint(self.timestamp)),
+            ("left".to_string(), netencode::Value::Uint(self.left as u64)),
+            ("right".to_string(), netencode::Value::Uint(self.right as u64)),
+            ("jump".to_string(), netencode::Value::Uint(self.jump as u64)),
+        ])
+    }
+
+    fn from_netencode_value(value: &netencode::Value) -> Option<Self> {
+        let uint_field = |name: &str| match value.field(name) {
+            Some(netencode::Value::Uint(n)) => Some(*n),
+            _ => None,
+        };
+        Some(InputState {
+            sequence: uint_field("sequence")? as u32,
+            timestamp: uint_field("timestamp")?,
+            left: uint_field("left")? != 0,
+            right: uint_field("right")? != 0,
+            jump: uint_field("jump")? != 0,
+        })
+    }
+}
+
+/// Encodes `inputs` via `netencode` for `Packet::Input::redundant`, so a
+/// future field added to `InputState` can be read by an older decoder
+/// without a wire break -- see the field's doc comment.
+pub fn encode_redundant_inputs(inputs: &[InputState]) -> Vec<u8> {
+    let value = netencode::Value::List(inputs.iter().map(InputState::to_netencode_value).collect());
+    netencode::encode(&value)
+}
+
+/// The `encode_redundant_inputs` counterpart. Returns an empty `Vec` for
+/// anything that doesn't decode as a well-formed list of input records,
+/// same as an empty `redundant` bundle would have meant before this existed.
+pub fn decode_redundant_inputs(bytes: &[u8]) -> Vec<InputState> {
+    let Some(netencode::Value::List(items)) = netencode::decode(bytes) else {
+        return Vec::new();
+    };
+    items.iter().filter_map(InputState::from_netencode_value).collect()
+}
+
+/// Deterministically steps one fixed-dt tick: applies each player's
+/// recorded input (if any — a player with no entry in `inputs` just
+/// integrates under its current velocity), integrates gravity/position,
+/// enforces world/floor bounds, and resolves collisions. Players are
+/// processed in ascending-id order so two independently-run instances fed
+/// the same `players`/`inputs` always agree bit-for-bit, regardless of
+/// `HashMap` iteration order — required for `World::rollback_to`'s replay
+/// and the server/client prediction comparison it corrects against.
+pub fn simulate_tick(players: &mut [Player], inputs: &HashMap<u32, InputState>, dt: f32) {
+    players.sort_by_key(|player| player.id);
+
+    for player in players.iter_mut() {
+        if let Some(input) = inputs.get(&player.id) {
+            player.vel_x = 0.0;
+            if input.left {
+                player.vel_x -= PLAYER_SPEED;
+            }
+            if input.right {
+                player.vel_x += PLAYER_SPEED;
+            }
+            if input.jump && player.on_ground {
+                player.vel_y = JUMP_VELOCITY;
+                player.on_ground = false;
+            }
+        }
+
+        integrate(player, dt);
+
+        player.x = player.x.clamp(0.0, WORLD_WIDTH - PLAYER_SIZE);
+        if !player.flying {
+            if player.y + PLAYER_SIZE >= FLOOR_Y {
+                player.y = FLOOR_Y - PLAYER_SIZE;
+                player.vel_y = 0.0;
+                player.on_ground = true;
+            }
+            if player.y <= 0.0 {
+                player.y = 0.0;
+                player.vel_y = 0.0;
+            }
+        }
+    }
+
+    resolve_all_collisions(players);
+}
+
+/// How many past ticks `World` keeps confirmed snapshots for. A
+/// misprediction discovered for a tick older than this can't be
+/// resimulated — see `World::rollback_to`, which stalls rather than roll
+/// back onto state it no longer has.
+pub const PREDICTION_WINDOW: u32 = 8;
+
+/// One `World::history` entry: the full player set as of `tick`, plus the
+/// per-client input that was applied to reach it. Kept so `rollback_to`
+/// can restore to this point and resimulate forward with corrected input.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub tick: u32,
+    pub players: Vec<Player>,
+    pub inputs: HashMap<u32, InputState>,
+}
+
+/// GGRS-style client-side rollback world. `advance` steps one fixed-dt
+/// tick using whatever input has been `record_input`-ed so far — a local
+/// player's actual input, or a remote player's last known input repeated
+/// as a prediction — and keeps a fixed-size ring buffer of the last
+/// `PREDICTION_WINDOW` confirmed snapshots. When an authoritative input or
+/// `GameState` reveals that a past tick's prediction was wrong,
+/// `rollback_to` restores the nearest snapshot at or before it and
+/// resimulates forward with the correction applied.
+#[derive(Debug, Clone)]
+pub struct World {
+    pub tick: u32,
+    pub players: Vec<Player>,
+    /// Confirmed snapshots, oldest at the front, capped at
+    /// `PREDICTION_WINDOW` entries.
+    history: VecDeque<Snapshot>,
+    /// Every input applied since the oldest snapshot still in `history`,
+    /// replayed by `rollback_to`. Pruned in lockstep with `history`.
+    inputs_by_tick: HashMap<u32, HashMap<u32, InputState>>,
+    /// Last known input per client, repeated as the prediction for a
+    /// remote player on a tick nothing has arrived for yet.
+    last_known_input: HashMap<u32, InputState>,
+}
+
+impl World {
+    /// Starts a new world at tick 0, with `players` as the initial state
+    /// already confirmed (so tick 0 itself can be rolled back to).
+    pub fn new(players: Vec<Player>) -> Self {
+        let mut history = VecDeque::with_capacity(PREDICTION_WINDOW as usize + 1);
+        history.push_back(Snapshot {
+            tick: 0,
+            players: players.clone(),
+            inputs: HashMap::new(),
+        });
+
+        Self {
+            tick: 0,
+            players,
+            history,
+            inputs_by_tick: HashMap::new(),
+            last_known_input: HashMap::new(),
+        }
+    }
+
+    /// Records `client_id`'s input as applied for `tick` (local actual
+    /// input, or a remote prediction), and remembers it as that client's
+    /// last known input for predicting any further tick it hasn't sent one
+    /// for yet.
+    pub fn record_input(&mut self, tick: u32, client_id: u32, input: InputState) {
+        self.last_known_input.insert(client_id, input.clone());
+        self.inputs_by_tick
+            .entry(tick)
+            .or_default()
+            .insert(client_id, input);
+    }
+
+    /// The input to predict for `client_id` on a tick nothing has arrived
+    /// for yet: its last known input repeated, or a neutral/idle input if
+    /// none has ever been seen from it.
+    pub fn predicted_input(&self, client_id: u32) -> InputState {
+        self.last_known_input
+            .get(&client_id)
+            .cloned()
+            .unwrap_or(InputState {
+                sequence: 0,
+                timestamp: 0,
+                left: false,
+                right: false,
+                jump: false,
+            })
+    }
+
+    /// Snapshots the current players at `tick`, paired with whatever input
+    /// was recorded for that tick.
+    pub fn save_state(&self, tick: u32) -> Snapshot {
+        Snapshot {
+            tick,
+            players: self.players.clone(),
+            inputs: self.inputs_by_tick.get(&tick).cloned().unwrap_or_default(),
+        }
+    }
+
+    /// Restores `tick` and `players` from a previously-saved snapshot.
+    pub fn load_state(&mut self, snapshot: &Snapshot) {
+        self.tick = snapshot.tick;
+        self.players = snapshot.players.clone();
+    }
+
+    /// Advances the world by one fixed-dt tick using the input recorded
+    /// (via `record_input`) for `self.tick`, then pushes the result onto
+    /// `history`, evicting the oldest snapshot once there are more than
+    /// `PREDICTION_WINDOW`.
+    pub fn advance(&mut self, dt: f32) {
+        let inputs = self.inputs_by_tick.entry(self.tick).or_default().clone();
+        simulate_tick(&mut self.players, &inputs, dt);
+        self.tick += 1;
+
+        self.push_history(self.save_state(self.tick));
+    }
+
+    fn push_history(&mut self, snapshot: Snapshot) {
+        if let Some(pos) = self.history.iter().position(|s| s.tick == snapshot.tick) {
+            self.history[pos] = snapshot;
+        } else {
+            self.history.push_back(snapshot);
+        }
+        while self.history.len() > PREDICTION_WINDOW as usize {
+            if let Some(evicted) = self.history.pop_front() {
+                self.inputs_by_tick.remove(&evicted.tick);
+            }
+        }
+    }
+
+    /// Corrects a misprediction discovered for `corrected_tick`: restores
+    /// the nearest confirmed snapshot at or before it, substitutes
+    /// `corrected_input` for that tick, and resimulates every tick up to
+    /// (not including) `replay_through`, re-applying whatever was recorded
+    /// at each — local prediction, remote repeat, or an earlier correction.
+    /// Returns `false`, leaving `self` untouched, if no snapshot old enough
+    /// to rebase on is still in `history` — the correction has fallen
+    /// outside `PREDICTION_WINDOW`, so the caller should stall rather than
+    /// roll back onto state it no longer has.
+    pub fn rollback_to(
+        &mut self,
+        corrected_tick: u32,
+        corrected_input: (u32, InputState),
+        replay_through: u32,
+        dt: f32,
+    ) -> bool {
+        let Some(base) = self
+            .history
+            .iter()
+            .rev()
+            .find(|snapshot| snapshot.tick <= corrected_tick)
+            .cloned()
+        else {
+            return false;
+        };
+
+        self.load_state(&base);
+        let (client_id, input) = corrected_input;
+        self.record_input(corrected_tick, client_id, input);
+
+        while self.tick < replay_through {
+            let inputs = self.inputs_by_tick.entry(self.tick).or_default().clone();
+            simulate_tick(&mut self.players, &inputs, dt);
+            self.tick += 1;
+            self.push_history(self.save_state(self.tick));
+        }
+
+        true
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -291,6 +1298,52 @@ mod tests {
         assert_approx_eq!(final_momentum_y, initial_momentum_y * 0.8, 0.01);
     }
 
+    #[test]
+    fn test_collision_resolution_circle_vs_aabb_pushes_out_via_closest_point() {
+        let mut player1 = Player::new(1, 100.0, 100.0);
+        player1.collider = Collider::Aabb { w: PLAYER_SIZE, h: PLAYER_SIZE };
+        // Circle center sits just outside the box's right edge, so the
+        // closest-point-on-box technique (not the degenerate inside-box
+        // case) drives the separation.
+        let mut player2 = Player::new(2, 124.0, 100.0);
+        player2.collider = Collider::Circle { r: PLAYER_SIZE / 2.0 };
+
+        assert!(check_collision(&player1, &player2));
+        resolve_collision(&mut player1, &mut player2);
+
+        let box_right_edge = player1.center().0 + PLAYER_SIZE / 2.0;
+        let (cx2, _) = player2.center();
+        assert!(cx2 - box_right_edge >= PLAYER_SIZE / 2.0 - 0.01, "circle should clear the box's edge by its own radius");
+    }
+
+    #[test]
+    fn test_collision_resolution_circle_vs_aabb_degenerate_center_inside_box() {
+        let mut player1 = Player::new(1, 100.0, 100.0);
+        player1.collider = Collider::Aabb { w: PLAYER_SIZE, h: PLAYER_SIZE };
+        let mut player2 = Player::new(2, 100.0, 100.0);
+        player2.collider = Collider::Circle { r: PLAYER_SIZE / 2.0 };
+
+        resolve_collision(&mut player1, &mut player2);
+
+        assert_ne!((player1.x, player1.y), (player2.x, player2.y), "degenerate overlap should still separate the pair");
+    }
+
+    #[test]
+    fn test_collision_resolution_capsule_vs_circle_separates_along_segment() {
+        let mut player1 = Player::new(1, 100.0, 100.0);
+        player1.collider = Collider::Capsule { r: PLAYER_SIZE / 2.0, half_height: PLAYER_SIZE / 2.0 };
+        let mut player2 = Player::new(2, 100.0, 124.0);
+        player2.collider = Collider::Circle { r: PLAYER_SIZE / 2.0 };
+
+        assert!(check_collision(&player1, &player2));
+        resolve_collision(&mut player1, &mut player2);
+
+        let (cx1, cy1) = player1.center();
+        let (cx2, cy2) = player2.center();
+        let distance = ((cx2 - cx1).powi(2) + (cy2 - cy1).powi(2)).sqrt();
+        assert!(distance >= PLAYER_SIZE - 0.01, "capsule's rounded cap should separate from the circle like two circles would");
+    }
+
     #[test]
     fn test_player_bounds_calculation() {
         let player = Player::new(1, 150.0, 200.0);
@@ -330,11 +1383,19 @@ mod tests {
     #[test]
     fn test_packet_serialization_all_variants() {
         // Test Connect packet
-        let connect = Packet::Connect { client_version: 42 };
+        let connect = Packet::Connect {
+            min_version: 1,
+            max_version: 42,
+            resume_token: None,
+            requested_timeout_secs: 15,
+            encrypt_public_key: None,
+            connect_token: None,
+            spectate: false,
+        };
         let serialized = bincode::serialize(&connect).unwrap();
         let deserialized: Packet = bincode::deserialize(&serialized).unwrap();
         match deserialized {
-            Packet::Connect { client_version } => assert_eq!(client_version, 42),
+            Packet::Connect { max_version, .. } => assert_eq!(max_version, 42),
             _ => panic!("Wrong packet type"),
         }
 
@@ -345,16 +1406,30 @@ mod tests {
             left: true,
             right: false,
             jump: true,
+            mac: Some([7u8; 32]),
+            sealed: None,
+            redundant: encode_redundant_inputs(&[InputState {
+                sequence: u32::MAX - 1,
+                timestamp: u64::MAX - 1,
+                left: false,
+                right: true,
+                jump: false,
+            }]),
+            acked_snapshot_tick: 99,
         };
         let serialized = bincode::serialize(&input).unwrap();
         let deserialized: Packet = bincode::deserialize(&serialized).unwrap();
         match deserialized {
-            Packet::Input { sequence, timestamp, left, right, jump } => {
+            Packet::Input { sequence, timestamp, left, right, jump, mac, redundant, .. } => {
                 assert_eq!(sequence, u32::MAX);
                 assert_eq!(timestamp, u64::MAX);
                 assert!(left);
                 assert!(!right);
                 assert!(jump);
+                assert_eq!(mac, Some([7u8; 32]));
+                let redundant = decode_redundant_inputs(&redundant);
+                assert_eq!(redundant.len(), 1);
+                assert_eq!(redundant[0].sequence, u32::MAX - 1);
             },
             _ => panic!("Wrong packet type"),
         }
@@ -374,18 +1449,25 @@ mod tests {
             last_processed.insert(i, i * 100);
         }
 
+        let mut input_receive_ms = HashMap::new();
+        for i in 0..10 {
+            input_receive_ms.insert(i, 9876543210 - i as u64 * 5);
+        }
+
         let game_state = Packet::GameState {
             tick: 12345,
             timestamp: 9876543210,
             last_processed_input: last_processed,
+            input_receive_ms,
             players: players.clone(),
+            checksum: compute_checksum(&players),
         };
 
         let serialized = bincode::serialize(&game_state).unwrap();
         let deserialized: Packet = bincode::deserialize(&serialized).unwrap();
-        
+
         match deserialized {
-            Packet::GameState { tick, timestamp, last_processed_input, players: deserialized_players } => {
+            Packet::GameState { tick, timestamp, last_processed_input, players: deserialized_players, .. } => {
                 assert_eq!(tick, 12345);
                 assert_eq!(timestamp, 9876543210);
                 assert_eq!(last_processed_input.len(), 10);
@@ -400,6 +1482,82 @@ mod tests {
             },
             _ => panic!("Wrong packet type"),
         }
+
+        // Test ToggleFly and SetGamemode round-trip
+        let toggle_fly = Packet::ToggleFly;
+        let serialized = bincode::serialize(&toggle_fly).unwrap();
+        let deserialized: Packet = bincode::deserialize(&serialized).unwrap();
+        assert!(matches!(deserialized, Packet::ToggleFly));
+
+        let set_gamemode = Packet::SetGamemode {
+            client_id: 7,
+            mode: Gamemode::Spectator,
+            can_fly: true,
+        };
+        let serialized = bincode::serialize(&set_gamemode).unwrap();
+        let deserialized: Packet = bincode::deserialize(&serialized).unwrap();
+        match deserialized {
+            Packet::SetGamemode { client_id, mode, can_fly } => {
+                assert_eq!(client_id, 7);
+                assert_eq!(mode, Gamemode::Spectator);
+                assert!(can_fly);
+            },
+            _ => panic!("Wrong packet type"),
+        }
+    }
+
+    #[test]
+    fn test_wire_size_matches_bincode_serialized_size() {
+        let packet = Packet::Ping { nonce: 42 };
+        assert_eq!(packet.wire_size(), bincode::serialized_size(&packet).unwrap());
+    }
+
+    #[test]
+    fn test_wire_size_grows_with_payload() {
+        let empty = Packet::GameState {
+            tick: 0,
+            timestamp: 0,
+            last_processed_input: HashMap::new(),
+            input_receive_ms: HashMap::new(),
+            players: vec![],
+            checksum: 0,
+        };
+        let with_players = Packet::GameState {
+            tick: 0,
+            timestamp: 0,
+            last_processed_input: HashMap::new(),
+            input_receive_ms: HashMap::new(),
+            players: vec![Player::new(1, 0.0, 0.0); 50],
+            checksum: 0,
+        };
+        assert!(with_players.wire_size() > empty.wire_size());
+    }
+
+    #[test]
+    fn test_redundant_inputs_round_trip_through_netencode() {
+        let inputs = vec![
+            InputState {
+                sequence: 1,
+                timestamp: 1000,
+                left: true,
+                right: false,
+                jump: false,
+            },
+            InputState {
+                sequence: 2,
+                timestamp: 1016,
+                left: false,
+                right: true,
+                jump: true,
+            },
+        ];
+        let decoded = decode_redundant_inputs(&encode_redundant_inputs(&inputs));
+        assert_eq!(decoded, inputs);
+    }
+
+    #[test]
+    fn test_decode_redundant_inputs_on_garbage_returns_empty() {
+        assert_eq!(decode_redundant_inputs(&[0xff, 0x00, 0x01]), Vec::new());
     }
 
     #[test]
@@ -463,4 +1621,290 @@ mod tests {
         assert_eq!(player2.vel_x, original_state2.vel_x);
         assert_eq!(player2.vel_y, original_state2.vel_y);
     }
+
+    #[test]
+    fn test_spatial_grid_finds_nearby_pair_as_a_candidate() {
+        let players = vec![Player::new(1, 0.0, 0.0), Player::new(2, 10.0, 10.0)];
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(&players);
+
+        assert_eq!(grid.candidate_pairs(), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn test_spatial_grid_excludes_a_far_apart_pair() {
+        let players = vec![Player::new(1, 0.0, 0.0), Player::new(2, 1000.0, 1000.0)];
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(&players);
+
+        assert!(grid.candidate_pairs().is_empty());
+    }
+
+    #[test]
+    fn test_spatial_grid_candidate_pairs_have_no_duplicates() {
+        // Positioned so each straddles several cell boundaries, a case that
+        // would double-count a pair without the final dedup.
+        let players = vec![
+            Player::new(1, 15.0, 15.0),
+            Player::new(2, 20.0, 20.0),
+            Player::new(3, 25.0, 25.0),
+        ];
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(&players);
+
+        let pairs = grid.candidate_pairs();
+        let mut deduped = pairs.clone();
+        deduped.sort_unstable();
+        deduped.dedup();
+        assert_eq!(pairs, deduped);
+    }
+
+    #[test]
+    fn test_spatial_grid_candidate_pairs_are_ordered_low_id_first() {
+        let players = vec![Player::new(5, 0.0, 0.0), Player::new(2, 5.0, 5.0)];
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(&players);
+
+        assert_eq!(grid.candidate_pairs(), vec![(2, 5)]);
+    }
+
+    #[test]
+    fn test_spatial_grid_clear_empties_cells() {
+        let players = vec![Player::new(1, 0.0, 0.0), Player::new(2, 10.0, 10.0)];
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(&players);
+        assert!(!grid.candidate_pairs().is_empty());
+
+        grid.clear();
+        assert!(grid.candidate_pairs().is_empty());
+    }
+
+    #[test]
+    fn test_spatial_grid_narrow_phase_confirms_candidates() {
+        // The grid is a broad phase: candidates still need check_collision to
+        // confirm an actual overlap, since diagonal-neighbor entities in
+        // adjacent cells may not actually touch.
+        let players = vec![Player::new(1, 0.0, 0.0), Player::new(2, 10.0, 10.0)];
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(&players);
+
+        let by_id: HashMap<u32, &Player> = players.iter().map(|p| (p.id, p)).collect();
+        for (a, b) in grid.candidate_pairs() {
+            assert!(check_collision(by_id[&a], by_id[&b]));
+        }
+    }
+
+    #[test]
+    fn test_color_classes_cover_every_cell_exactly_once() {
+        let players: Vec<Player> = (0..50)
+            .map(|i| Player::new(i, (i % 10) as f32 * 40.0, (i / 10) as f32 * 40.0))
+            .collect();
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(&players);
+
+        let total_owner_cells: usize = grid.color_classes().iter().map(|c| c.len()).sum();
+        assert_eq!(total_owner_cells, grid.cells.len());
+    }
+
+    #[test]
+    fn test_color_classes_batches_within_one_color_share_no_entities() {
+        let players: Vec<Player> = (0..200)
+            .map(|i| Player::new(i, (i % 20) as f32 * 40.0, (i / 20) as f32 * 40.0))
+            .collect();
+        let mut grid = SpatialGrid::default();
+        grid.rebuild(&players);
+
+        for color in grid.color_classes() {
+            let mut seen = std::collections::HashSet::new();
+            for batch in &color {
+                for &id in batch {
+                    assert!(seen.insert(id), "entity {id} appeared in two batches of the same color");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_step_all_serial_integrates_gravity_and_position() {
+        let mut players = vec![Player {
+            id: 1,
+            x: 0.0,
+            y: 0.0,
+            vel_x: 0.0,
+            vel_y: 0.0,
+            on_ground: false,
+            layer: all_layers(),
+            mask: all_layers(),
+            gamemode: Gamemode::default(),
+            flying: false,
+            collider: Collider::default(),
+            username: String::new(),
+        }];
+        step_all_serial(&mut players, 1.0);
+        assert_eq!(players[0].vel_y, GRAVITY);
+        assert_eq!(players[0].y, GRAVITY);
+    }
+
+    #[test]
+    fn test_step_all_serial_and_parallel_agree_on_non_colliding_entities() {
+        let build = || {
+            (0..20)
+                .map(|i| Player::new(i, i as f32 * 500.0, 0.0))
+                .collect::<Vec<Player>>()
+        };
+        let mut serial = build();
+        let mut parallel = build();
+
+        step_all_serial(&mut serial, 1.0 / 60.0);
+        step_all_parallel(&mut parallel, 1.0 / 60.0);
+
+        for (a, b) in serial.iter().zip(parallel.iter()) {
+            assert_eq!(a.id, b.id);
+            assert!((a.x - b.x).abs() < f32::EPSILON);
+            assert!((a.y - b.y).abs() < f32::EPSILON);
+            assert!((a.vel_y - b.vel_y).abs() < f32::EPSILON);
+        }
+    }
+
+    #[test]
+    fn test_step_all_dispatches_by_threshold() {
+        let mut few = vec![Player::new(1, 0.0, 0.0)];
+        assert!(few.len() < PARALLEL_STEP_THRESHOLD);
+        step_all(&mut few, 1.0 / 60.0);
+        assert_eq!(few[0].vel_y, GRAVITY / 60.0);
+    }
+
+    #[test]
+    fn test_step_all_parallel_resolves_an_overlapping_pair() {
+        let mut players = vec![
+            Player::new(1, 100.0, 100.0),
+            Player::new(2, 105.0, 100.0),
+        ];
+        assert!(check_collision(&players[0], &players[1]));
+        step_all_parallel(&mut players, 1.0 / 60.0);
+        assert!(!check_collision(&players[0], &players[1]));
+    }
+
+    fn idle_input(sequence: u32) -> InputState {
+        InputState {
+            sequence,
+            timestamp: 0,
+            left: false,
+            right: false,
+            jump: false,
+        }
+    }
+
+    #[test]
+    fn test_simulate_tick_applies_input_and_integrates() {
+        let mut players = vec![Player::new(1, 0.0, 0.0)];
+        let mut inputs = HashMap::new();
+        inputs.insert(
+            1,
+            InputState {
+                sequence: 1,
+                timestamp: 0,
+                left: false,
+                right: true,
+                jump: false,
+            },
+        );
+
+        simulate_tick(&mut players, &inputs, 1.0 / 60.0);
+
+        assert_eq!(players[0].vel_x, PLAYER_SPEED);
+        assert!(players[0].x > 0.0);
+    }
+
+    #[test]
+    fn test_simulate_tick_is_order_independent() {
+        let forward = || vec![Player::new(1, 0.0, 100.0), Player::new(2, 200.0, 100.0)];
+        let reversed = || vec![Player::new(2, 200.0, 100.0), Player::new(1, 0.0, 100.0)];
+        let mut a = forward();
+        let mut b = reversed();
+
+        simulate_tick(&mut a, &HashMap::new(), 1.0 / 60.0);
+        simulate_tick(&mut b, &HashMap::new(), 1.0 / 60.0);
+
+        assert_eq!(compute_checksum(&a), compute_checksum(&b));
+    }
+
+    #[test]
+    fn test_world_save_load_round_trip() {
+        let mut world = World::new(vec![Player::new(1, 0.0, 0.0)]);
+        world.record_input(0, 1, idle_input(1));
+        world.advance(1.0 / 60.0);
+
+        let snapshot = world.save_state(world.tick);
+        world.players[0].x = 9999.0;
+
+        world.load_state(&snapshot);
+        assert_eq!(world.players[0].x, snapshot.players[0].x);
+        assert_eq!(world.tick, snapshot.tick);
+    }
+
+    #[test]
+    fn test_world_predicted_input_repeats_last_known() {
+        let mut world = World::new(vec![Player::new(1, 0.0, 0.0)]);
+        assert_eq!(world.predicted_input(1).left, false);
+
+        world.record_input(
+            0,
+            1,
+            InputState {
+                sequence: 1,
+                timestamp: 0,
+                left: true,
+                right: false,
+                jump: false,
+            },
+        );
+        assert!(world.predicted_input(1).left);
+    }
+
+    #[test]
+    fn test_world_rollback_corrects_misprediction() {
+        let mut world = World::new(vec![Player::new(1, 0.0, 100.0)]);
+        let dt = 1.0 / 60.0;
+
+        // Predict 3 ticks of no input.
+        for tick in 0..3 {
+            world.record_input(tick, 1, idle_input(tick));
+            world.advance(dt);
+        }
+        let predicted_x = world.players[0].x;
+
+        // Tick 0 actually had the player moving right; roll back and
+        // replay with the correction applied.
+        let corrected = InputState {
+            sequence: 0,
+            timestamp: 0,
+            left: false,
+            right: true,
+            jump: false,
+        };
+        let ok = world.rollback_to(0, (1, corrected), 3, dt);
+
+        assert!(ok);
+        assert_eq!(world.tick, 3);
+        assert!(world.players[0].x > predicted_x);
+    }
+
+    #[test]
+    fn test_world_rollback_stalls_beyond_prediction_window() {
+        let mut world = World::new(vec![Player::new(1, 0.0, 100.0)]);
+        let dt = 1.0 / 60.0;
+
+        for tick in 0..(PREDICTION_WINDOW + 2) {
+            world.record_input(tick, 1, idle_input(tick));
+            world.advance(dt);
+        }
+        let before = world.players[0].x;
+
+        // Tick 0 has long since fallen out of the ring buffer.
+        let ok = world.rollback_to(0, (1, idle_input(0)), world.tick, dt);
+
+        assert!(!ok);
+        assert_eq!(world.players[0].x, before);
+    }
 }
@@ -0,0 +1,230 @@
+//! Self-describing, tagged-and-length-prefixed binary encoding, in the style
+//! of netencode (a typed extension of netstrings): every value is written as
+//! `<tag><len>:<payload>,`, where `len` counts the bytes of `payload` alone,
+//! so a decoder that doesn't recognize a tag -- or a record field it wasn't
+//! built to know about -- can skip straight past it without parsing the
+//! contents. Unlike `byte_codec`'s fixed, declaration-order layout (a good
+//! fit for a type whose shape never changes), this trades a few bytes of
+//! overhead per value for the ability to add a new record field, list entry,
+//! or message variant later without breaking a decoder built against an
+//! older schema -- handy for the input-history and snapshot packets, which
+//! tend to grow debug/metadata fields over a project's lifetime.
+//!
+//! `Packet::Input::redundant` is the first field actually encoded this way:
+//! `encode_redundant_inputs`/`decode_redundant_inputs` in this crate's root
+//! turn its `Vec<InputState>` into a netencode `Value::List` of `Record`s
+//! instead of riding on `Packet`'s usual `bincode` framing, so a field added
+//! to `InputState` later won't break an older build reading this one. The
+//! rest of `Packet` -- snapshots included -- still goes through `bincode`
+//! wholesale; porting more fields over is future work.
+
+use crate::byte_codec::{ByteDecode, ByteEncode};
+
+/// A self-describing value: one of the five shapes below, nested as deep
+/// as needed. `Record` fields are tagged by name rather than position, so a
+/// decoder can look up the fields it knows and ignore the rest.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Uint(u64),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Record(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Looks up a record field by name. Returns `None` if `self` isn't a
+    /// `Record` or the field isn't present -- the forward-compat path for a
+    /// decoder built against an older schema that doesn't know this field.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Value::Record(fields) => fields.iter().find(|(key, _)| key == name).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+impl ByteEncode for Value {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Value::Int(n) => write_tagged(out, b'i', n.to_string().as_bytes()),
+            Value::Uint(n) => write_tagged(out, b'u', n.to_string().as_bytes()),
+            Value::Bytes(bytes) => write_tagged(out, b'b', bytes),
+            Value::List(items) => {
+                let mut payload = Vec::new();
+                for item in items {
+                    item.encode(&mut payload);
+                }
+                write_tagged(out, b'[', &payload);
+            }
+            Value::Record(fields) => {
+                let mut payload = Vec::new();
+                for (key, value) in fields {
+                    Value::Bytes(key.clone().into_bytes()).encode(&mut payload);
+                    value.encode(&mut payload);
+                }
+                write_tagged(out, b'{', &payload);
+            }
+        }
+    }
+}
+
+impl ByteDecode for Value {
+    fn decode(input: &[u8]) -> Option<(Self, &[u8])> {
+        let (tag, payload, rest) = read_tagged(input)?;
+        let value = match tag {
+            b'i' => Value::Int(std::str::from_utf8(payload).ok()?.parse().ok()?),
+            b'u' => Value::Uint(std::str::from_utf8(payload).ok()?.parse().ok()?),
+            b'b' => Value::Bytes(payload.to_vec()),
+            b'[' => Value::List(decode_all(payload)?),
+            b'{' => {
+                let mut fields = Vec::new();
+                let mut remaining = payload;
+                while !remaining.is_empty() {
+                    let (key, after_key) = Value::decode(remaining)?;
+                    let Value::Bytes(key_bytes) = key else {
+                        return None;
+                    };
+                    let key = String::from_utf8(key_bytes).ok()?;
+                    let (value, after_value) = Value::decode(after_key)?;
+                    fields.push((key, value));
+                    remaining = after_value;
+                }
+                Value::Record(fields)
+            }
+            _ => return None,
+        };
+        Some((value, rest))
+    }
+}
+
+/// Decodes a run of back-to-back self-describing values until the slice is
+/// exhausted, used for a `List`'s payload (each element already carries its
+/// own tag and length, so there's no separate element count to track).
+fn decode_all(mut input: &[u8]) -> Option<Vec<Value>> {
+    let mut items = Vec::new();
+    while !input.is_empty() {
+        let (item, rest) = Value::decode(input)?;
+        items.push(item);
+        input = rest;
+    }
+    Some(items)
+}
+
+/// Appends `tag`, then `payload`'s length and bytes, in the
+/// `<tag><len>:<payload>,` shape shared by every value kind.
+fn write_tagged(out: &mut Vec<u8>, tag: u8, payload: &[u8]) {
+    out.push(tag);
+    out.extend_from_slice(payload.len().to_string().as_bytes());
+    out.push(b':');
+    out.extend_from_slice(payload);
+    out.push(b',');
+}
+
+/// Parses the `<tag><len>:<payload>,` shape off the front of `input`,
+/// returning the tag byte, the payload slice, and whatever follows. A
+/// decoder that doesn't recognize `tag` can still skip past `payload` using
+/// only `len` -- it never needs to understand the bytes inside it.
+fn read_tagged(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let (&tag, after_tag) = input.split_first()?;
+    let colon_pos = after_tag.iter().position(|&b| b == b':')?;
+    let (len_digits, after_len_digits) = after_tag.split_at(colon_pos);
+    let after_colon = after_len_digits.get(1..)?;
+    let len: usize = std::str::from_utf8(len_digits).ok()?.parse().ok()?;
+    if after_colon.len() < len + 1 {
+        return None;
+    }
+    let (payload, after_payload) = after_colon.split_at(len);
+    let (&comma, rest) = after_payload.split_first()?;
+    if comma != b',' {
+        return None;
+    }
+    Some((tag, payload, rest))
+}
+
+/// Convenience wrapper around `ByteEncode::encode` for a fresh buffer.
+pub fn encode(value: &Value) -> Vec<u8> {
+    let mut out = Vec::new();
+    value.encode(&mut out);
+    out
+}
+
+/// Convenience wrapper around `ByteDecode::decode` that discards the unused
+/// remainder, for a caller that expects `input` to hold exactly one value.
+pub fn decode(input: &[u8]) -> Option<Value> {
+    let (value, rest) = Value::decode(input)?;
+    rest.is_empty().then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_a_positive_and_negative_int() {
+        assert_eq!(decode(&encode(&Value::Int(42))), Some(Value::Int(42)));
+        assert_eq!(decode(&encode(&Value::Int(-17))), Some(Value::Int(-17)));
+    }
+
+    #[test]
+    fn round_trips_a_uint() {
+        assert_eq!(decode(&encode(&Value::Uint(u64::MAX))), Some(Value::Uint(u64::MAX)));
+    }
+
+    #[test]
+    fn round_trips_a_byte_blob() {
+        let value = Value::Bytes(vec![0, 1, 2, 255]);
+        assert_eq!(decode(&encode(&value)), Some(value));
+    }
+
+    #[test]
+    fn round_trips_a_list_of_mixed_values() {
+        let value = Value::List(vec![
+            Value::Int(-1),
+            Value::Uint(7),
+            Value::Bytes(b"hi".to_vec()),
+        ]);
+        assert_eq!(decode(&encode(&value)), Some(value));
+    }
+
+    #[test]
+    fn round_trips_a_nested_record() {
+        let value = Value::Record(vec![
+            ("sequence".to_string(), Value::Uint(12)),
+            (
+                "inputs".to_string(),
+                Value::List(vec![Value::Int(1), Value::Int(-2)]),
+            ),
+        ]);
+        assert_eq!(decode(&encode(&value)), Some(value));
+    }
+
+    #[test]
+    fn a_decoder_can_look_up_a_known_field_and_ignore_the_rest() {
+        let value = Value::Record(vec![
+            ("sequence".to_string(), Value::Uint(12)),
+            ("debug_label".to_string(), Value::Bytes(b"extra field an old decoder wouldn't know".to_vec())),
+        ]);
+        let decoded = decode(&encode(&value)).unwrap();
+        assert_eq!(decoded.field("sequence"), Some(&Value::Uint(12)));
+    }
+
+    #[test]
+    fn decode_skips_an_unrecognized_tag_by_length_without_understanding_it() {
+        let mut bytes = Vec::new();
+        write_tagged(&mut bytes, b'x', b"unknown-shape");
+        bytes.extend_from_slice(&encode(&Value::Int(5)));
+
+        // The unrecognized leading value can't itself be decoded...
+        assert_eq!(Value::decode(&bytes), None);
+        // ...but its length still lets a caller skip it to reach what follows.
+        let (_, _, rest) = read_tagged(&bytes).unwrap();
+        assert_eq!(Value::decode(rest), Some((Value::Int(5), &[][..])));
+    }
+
+    #[test]
+    fn decode_rejects_truncated_input() {
+        let full = encode(&Value::Bytes(vec![1, 2, 3, 4]));
+        assert_eq!(Value::decode(&full[..full.len() - 2]), None);
+    }
+}
@@ -0,0 +1,170 @@
+//! Reusable sliding-window replay filter for a stream of monotonically
+//! increasing 32-bit sequence numbers (e.g. `InputState::sequence`).
+//!
+//! Tracks the highest sequence seen plus a 256-bit bitfield covering the
+//! window below it. A sequence more than `WINDOW_SIZE` behind the high-water
+//! mark is rejected outright as too old to tell apart from a replay; one
+//! still inside the window is rejected if its bit is already set (a
+//! duplicate); otherwise it's accepted, its bit is set, and if it's the new
+//! highest the window slides forward, clearing the bits that scroll out.
+//! Unlike `server::netcode_handshake::SequenceReplayWindow` (64-bit, 64-bit
+//! sequences, used for the still-standalone AEAD handshake), this lives in
+//! `shared` precisely so both the client's and server's live ingest paths
+//! can drop replays before they ever reach game state.
+
+/// Bits of history kept below the highest sequence seen.
+const WINDOW_SIZE: u32 = 256;
+
+/// Words in the bitfield (`WINDOW_SIZE` / 64 bits per word).
+const WORDS: usize = (WINDOW_SIZE / 64) as usize;
+
+/// Sliding-bitfield replay filter over 32-bit sequence numbers. See the
+/// module docs for the accept/reject rules.
+pub struct ReplayWindow {
+    highest_seen: Option<u32>,
+    seen_mask: [u64; WORDS],
+}
+
+impl ReplayWindow {
+    pub fn new() -> Self {
+        Self {
+            highest_seen: None,
+            seen_mask: [0; WORDS],
+        }
+    }
+
+    /// Returns `true` and records `sequence` as seen if it's new and inside
+    /// the window; `false` for a duplicate or a sequence too far behind the
+    /// high-water mark to tell.
+    pub fn accept(&mut self, sequence: u32) -> bool {
+        let Some(highest) = self.highest_seen else {
+            self.highest_seen = Some(sequence);
+            self.set_bit(0);
+            return true;
+        };
+
+        if sequence > highest {
+            let shift = sequence - highest;
+            self.shift_mask(shift);
+            self.highest_seen = Some(sequence);
+            self.set_bit(0);
+            return true;
+        }
+
+        let age = highest - sequence;
+        if age >= WINDOW_SIZE {
+            return false;
+        }
+        if self.bit_set(age) {
+            false
+        } else {
+            self.set_bit(age);
+            true
+        }
+    }
+
+    fn word_and_bit(age: u32) -> (usize, u32) {
+        (age as usize / 64, age % 64)
+    }
+
+    fn bit_set(&self, age: u32) -> bool {
+        let (word, bit) = Self::word_and_bit(age);
+        self.seen_mask[word] & (1u64 << bit) != 0
+    }
+
+    fn set_bit(&mut self, age: u32) {
+        let (word, bit) = Self::word_and_bit(age);
+        self.seen_mask[word] |= 1u64 << bit;
+    }
+
+    /// Shifts every bit `shift` positions older (toward the high end of each
+    /// word and across word boundaries), clearing bits that scroll out past
+    /// `WINDOW_SIZE`.
+    fn shift_mask(&mut self, shift: u32) {
+        if shift >= WINDOW_SIZE {
+            self.seen_mask = [0; WORDS];
+            return;
+        }
+
+        let word_shift = (shift / 64) as usize;
+        let bit_shift = shift % 64;
+
+        let mut shifted = [0u64; WORDS];
+        for i in (0..WORDS).rev() {
+            if i < word_shift {
+                continue;
+            }
+            let src = i - word_shift;
+            shifted[i] = self.seen_mask[src] << bit_shift;
+            if bit_shift > 0 && src > 0 {
+                shifted[i] |= self.seen_mask[src - 1] >> (64 - bit_shift);
+            }
+        }
+        self.seen_mask = shifted;
+    }
+}
+
+impl Default for ReplayWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_the_first_sequence_seen() {
+        let mut window = ReplayWindow::new();
+        assert!(window.accept(10));
+    }
+
+    #[test]
+    fn rejects_an_exact_duplicate() {
+        let mut window = ReplayWindow::new();
+        assert!(window.accept(10));
+        assert!(!window.accept(10));
+    }
+
+    #[test]
+    fn accepts_out_of_order_sequences_within_the_window() {
+        let mut window = ReplayWindow::new();
+        assert!(window.accept(10));
+        assert!(window.accept(12));
+        assert!(window.accept(11));
+        assert!(!window.accept(11));
+    }
+
+    #[test]
+    fn rejects_a_sequence_too_far_behind_the_high_water_mark() {
+        let mut window = ReplayWindow::new();
+        assert!(window.accept(1000));
+        assert!(!window.accept(1000 - WINDOW_SIZE));
+    }
+
+    #[test]
+    fn accepts_the_oldest_sequence_still_inside_the_window() {
+        let mut window = ReplayWindow::new();
+        assert!(window.accept(1000));
+        assert!(window.accept(1000 - (WINDOW_SIZE - 1)));
+    }
+
+    #[test]
+    fn slides_the_window_forward_as_new_highs_arrive() {
+        let mut window = ReplayWindow::new();
+        assert!(window.accept(0));
+        assert!(window.accept(300)); // slides past the first window entirely
+        assert!(window.accept(300 - (WINDOW_SIZE - 1))); // still in range of the new high
+        assert!(!window.accept(0)); // long since scrolled out
+    }
+
+    #[test]
+    fn handles_large_shifts_spanning_multiple_words_without_panicking() {
+        let mut window = ReplayWindow::new();
+        assert!(window.accept(5));
+        assert!(window.accept(5 + 70)); // shift > 64, crosses a word boundary
+        assert!(window.accept(5 + 70 - 1));
+        assert!(!window.accept(5 + 70 - 1));
+    }
+}
@@ -1,6 +1,9 @@
 //! Performance benchmarks for critical game systems
 
-use shared::{check_collision, resolve_collision, InputState, Player, GRAVITY, PLAYER_SPEED};
+use shared::{
+    check_collision, resolve_collision, step_all_parallel, step_all_serial, InputState, Player,
+    SpatialGrid, GRAVITY, PLAYER_SPEED,
+};
 use std::time::Instant;
 
 /// Benchmarks collision detection performance
@@ -99,6 +102,81 @@ fn benchmark_physics_simulation() {
 }
 
 /// Benchmarks network packet serialization performance
+/// Benchmarks the spatial-hash broad phase against many entities, showing it
+/// scales far better than the naive O(N²) pairwise scan `check_collision`
+/// alone would require.
+#[test]
+fn benchmark_spatial_grid_broad_phase() {
+    let columns = 100;
+    let players: Vec<Player> = (0..2000)
+        .map(|i| {
+            let (col, row) = (i % columns, i / columns);
+            Player::new(i as u32, col as f32 * 40.0, row as f32 * 40.0)
+        })
+        .collect();
+
+    let start = Instant::now();
+    let mut grid = SpatialGrid::default();
+    grid.rebuild(&players);
+    let pairs = grid.candidate_pairs();
+    let duration = start.elapsed();
+
+    let naive_pair_count = players.len() * (players.len() - 1) / 2;
+    println!(
+        "Spatial grid broad phase: {} entities -> {} candidate pairs (naive scan: {}) in {:?}",
+        players.len(),
+        pairs.len(),
+        naive_pair_count,
+        duration
+    );
+
+    // Entities are spread out, so the grid should discard the vast majority
+    // of pairs the naive scan would have to visit.
+    assert!(pairs.len() < naive_pair_count / 10);
+    assert!(duration.as_millis() < 200);
+}
+
+/// Compares `step_all_serial` against `step_all_parallel` (rayon-backed
+/// integration plus grid-colored parallel collision resolution) at a few
+/// entity counts, to show the parallel path scaling with entity count on
+/// multi-core machines rather than asserting a specific speedup (CI
+/// machines vary too much in core count for a hard ratio threshold).
+#[test]
+fn benchmark_parallel_world_step_scaling() {
+    fn grid_of_players(count: u32) -> Vec<Player> {
+        let columns = 100;
+        (0..count)
+            .map(|i| {
+                let (col, row) = (i % columns, i / columns);
+                Player::new(i, col as f32 * 40.0, row as f32 * 40.0)
+            })
+            .collect()
+    }
+
+    for &count in &[100u32, 1_000, 10_000] {
+        let dt = 1.0 / 60.0;
+        let mut serial_players = grid_of_players(count);
+        let start = Instant::now();
+        step_all_serial(&mut serial_players, dt);
+        let serial_duration = start.elapsed();
+
+        let mut parallel_players = grid_of_players(count);
+        let start = Instant::now();
+        step_all_parallel(&mut parallel_players, dt);
+        let parallel_duration = start.elapsed();
+
+        println!(
+            "World step at {} entities: serial {:?}, parallel {:?}",
+            count, serial_duration, parallel_duration
+        );
+
+        // Both paths should still complete a single tick's worth of work
+        // in under a second even at the largest entity count.
+        assert!(serial_duration.as_millis() < 1000);
+        assert!(parallel_duration.as_millis() < 1000);
+    }
+}
+
 #[test]
 fn benchmark_packet_serialization() {
     use bincode::{deserialize, serialize};
@@ -114,11 +192,14 @@ fn benchmark_packet_serialization() {
         .map(|i| Player::new(i, (i as f32) * 10.0, 100.0))
         .collect();
 
+    let checksum = shared::compute_checksum(&players);
     let packet = Packet::GameState {
         tick: 12345,
         timestamp: 1234567890,
         last_processed_input,
+        input_receive_ms: HashMap::new(),
         players,
+        checksum,
     };
 
     let iterations = 10_000;
@@ -299,11 +380,14 @@ fn benchmark_large_packet_processing() {
         last_processed.insert(i, i * 10);
     }
 
+    let checksum = shared::compute_checksum(&players);
     let packet = Packet::GameState {
         tick: 12345,
         timestamp: 1234567890,
         last_processed_input: last_processed,
+        input_receive_ms: HashMap::new(),
         players,
+        checksum,
     };
 
     let iterations = 1_000;
@@ -349,13 +433,14 @@ fn benchmark_reconciliation_performance() {
             right: i % 3 == 0,
             jump: i % 7 == 0,
         };
-        client_state.input_history.push(input);
+        client_state.input_queue.add(input);
     }
 
     let config = ServerStateConfig {
         client_id: Some(1),
         reconciliation_enabled: true,
         interpolation_enabled: false,
+        extrapolation_enabled: true,
     };
 
     let iterations = 100;
@@ -366,7 +451,7 @@ fn benchmark_reconciliation_performance() {
         let mut last_processed = HashMap::new();
         last_processed.insert(1u32, 50u32); // Half the inputs processed
 
-        client_state.apply_server_state(100, 12345, players, last_processed, config.clone());
+        client_state.apply_server_state(100, 12345, players, last_processed, 0, config.clone());
     }
 
     let duration = start.elapsed();
@@ -10,12 +10,12 @@ use tokio::time::sleep;
 /// Tests packet serialization round-trip for network protocol validation
 #[tokio::test]
 async fn test_packet_serialization_roundtrip() {
-    let connect_packet = Packet::Connect { client_version: 1 };
+    let connect_packet = Packet::Connect { min_version: 1, max_version: 1, resume_token: None, requested_timeout_secs: 15, encrypt_public_key: None, connect_token: None, spectate: false };
     let serialized = serialize(&connect_packet).unwrap();
     let deserialized: Packet = deserialize(&serialized).unwrap();
 
     match deserialized {
-        Packet::Connect { client_version } => assert_eq!(client_version, 1),
+        Packet::Connect { max_version, .. } => assert_eq!(max_version, 1),
         _ => panic!("Wrong packet type"),
     }
 
@@ -25,6 +25,10 @@ async fn test_packet_serialization_roundtrip() {
         left: true,
         right: false,
         jump: true,
+        mac: None,
+        sealed: None,
+        redundant: Vec::new(),
+        acked_snapshot_tick: 0,
     };
     let serialized = serialize(&input_packet).unwrap();
     let deserialized: Packet = deserialize(&serialized).unwrap();
@@ -36,6 +40,7 @@ async fn test_packet_serialization_roundtrip() {
             left,
             right,
             jump,
+            ..
         } => {
             assert_eq!(sequence, 42);
             assert_eq!(timestamp, 123456789);
@@ -69,7 +74,7 @@ async fn test_udp_socket_communication() {
         .set_read_timeout(Some(Duration::from_millis(100)))
         .unwrap();
 
-    let test_packet = Packet::Connect { client_version: 1 };
+    let test_packet = Packet::Connect { min_version: 1, max_version: 1, resume_token: None, requested_timeout_secs: 15, encrypt_public_key: None, connect_token: None, spectate: false };
     let serialized = serialize(&test_packet).unwrap();
 
     client_socket.send_to(&serialized, server_addr).unwrap();
@@ -79,7 +84,7 @@ async fn test_udp_socket_communication() {
     let received_packet: Packet = deserialize(&buf[..size]).unwrap();
 
     match received_packet {
-        Packet::Connect { client_version } => assert_eq!(client_version, 1),
+        Packet::Connect { max_version, .. } => assert_eq!(max_version, 1),
         _ => panic!("Wrong packet type received"),
     }
 }
@@ -512,7 +517,7 @@ mod error_handling_tests {
 
     #[test]
     fn test_malformed_packet_handling() {
-        let valid_packet = Packet::Connect { client_version: 1 };
+        let valid_packet = Packet::Connect { min_version: 1, max_version: 1, resume_token: None, requested_timeout_secs: 15, encrypt_public_key: None, connect_token: None, spectate: false };
         let valid_data = serialize(&valid_packet).unwrap();
 
         // Test truncated packet
@@ -653,7 +658,13 @@ mod concurrency_tests {
             thread::spawn(move || {
                 for j in 0..packets_per_producer {
                     let packet = Packet::Connect {
-                        client_version: (i * packets_per_producer + j) as u32
+                        min_version: 1,
+                        max_version: (i * packets_per_producer + j) as u32,
+                        resume_token: None,
+                        requested_timeout_secs: 15,
+                        encrypt_public_key: None,
+                        connect_token: None,
+                        spectate: false,
                     };
                     queue.lock().unwrap().push_back(packet);
                 }